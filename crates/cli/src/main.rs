@@ -1,32 +1,181 @@
-use clap::{Arg, Command};
+use clap::{Arg, ArgAction, Command};
 use colored::*;
-use runner::TaskRunner;
+use env_parser::{EnvFileEditor, secret};
+use std::io::{IsTerminal, Read};
+use std::path::{Path, PathBuf};
+#[cfg(unix)]
+use taskfile_exec::daemon;
+use taskfile_exec::shell::{self, ShellKind};
+use taskfile_exec::user_config::{ColorChoice, Output, UserConfig};
+use taskfile_exec::{
+    GraphSlice, OutputMode, RunOptions, TaskRunner, crash, duration, self_check, self_update,
+    session, stats, update_check,
+};
+use taskfile_test::{Harness, Outcome};
+
+/// Set to anything to turn off the daily check for a newer release.
+const NO_UPDATE_CHECK_VAR: &str = "TASKFILE_NO_UPDATE_CHECK";
 
 #[tokio::main]
 async fn main() {
-    let matches = Command::new("taskfile")
-        .version("1.0")
-        .about("A simple task runner")
-        .arg(
-            Arg::new("command")
-                .help("The command to run (list, version, update, init, or task name)")
-                .value_name("COMMAND")
-                .index(1),
-        )
-        .get_matches();
+    let mut argv: Vec<String> = std::env::args().collect();
+    let cli = cli();
+    let value_options = value_options(&cli);
+    let value_options: Vec<&str> = value_options.iter().map(String::as_str).collect();
+    crash::install(env!("CARGO_PKG_VERSION"), &argv, &value_options);
+    let user_config = match UserConfig::load() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{} {}", "✗".red(), e);
+            std::process::exit(1);
+        }
+    };
+    match user_config.expand(&argv[1..], &value_options) {
+        Ok(args) => {
+            argv.truncate(1);
+            argv.extend(args);
+        }
+        Err(e) => {
+            eprintln!("{} {}", "✗".red(), e);
+            std::process::exit(1);
+        }
+    }
+    let defaults = &user_config.defaults;
+    match defaults.color {
+        Some(ColorChoice::Always) => colored::control::set_override(true),
+        Some(ColorChoice::Never) => colored::control::set_override(false),
+        Some(ColorChoice::Auto) | None => {}
+    }
 
-    let taskfile_name = "Taskfile.toml";
+    // clap drops `--`, so split off what follows it ourselves; the runner
+    // passes it through to the task's command.
+    let passthrough = match argv.iter().position(|a| a == "--") {
+        Some(i) => argv.split_off(i),
+        None => Vec::new(),
+    };
+
+    let matches = cli.clone().get_matches_from(argv);
+    init_logging(matches.get_count("verbose"), matches.get_flag("quiet"));
+
+    let pinned = matches.get_one::<String>("version").cloned();
+    if matches.contains_id("version") && pinned.is_none() {
+        print!("{}", cli.render_version());
+        return;
+    }
+    let updating = matches.get_one::<String>("command").map(String::as_str) == Some("update");
+    if !updating && (pinned.is_some() || matches.contains_id("channel")) {
+        eprintln!(
+            "{} --version <VERSION> and --channel only apply to `task update`",
+            "✗".red()
+        );
+        std::process::exit(1);
+    }
+
+    let offline = matches.get_flag("offline");
+    let bell = match matches
+        .get_one::<String>("bell")
+        .map(|b| duration::parse_duration(b))
+    {
+        Some(Ok(threshold)) => Some(threshold),
+        Some(Err(e)) => {
+            eprintln!("{} --bell: {}", "✗".red(), e);
+            std::process::exit(1);
+        }
+        None => None,
+    };
+    let options = RunOptions {
+        auto_retry_transient: matches.get_flag("auto-retry-transient"),
+        critical_path: matches.get_flag("critical-path"),
+        strict: matches.get_flag("strict"),
+        bell,
+        lang: matches.get_one::<String>("lang").cloned(),
+        yes: matches.get_flag("yes"),
+        approve: matches.get_flag("approve"),
+        offline,
+        record_history: true,
+        jobs: matches.get_one::<u64>("jobs").map(|&jobs| jobs as usize),
+        ..RunOptions::default()
+    };
+    let record = matches.get_one::<String>("record").map(PathBuf::from);
+    let replay = match matches.get_one::<String>("replay") {
+        Some(path) => match session::load(Path::new(path)) {
+            Ok(session) => Some(session),
+            Err(e) => {
+                eprintln!("{} {}", "✗".red(), e);
+                std::process::exit(1);
+            }
+        },
+        None => None,
+    };
+    let slice = GraphSlice {
+        from: matches.get_one::<String>("from").cloned(),
+        until: matches.get_one::<String>("until").cloned(),
+        skip: matches
+            .get_many::<String>("skip")
+            .map(|s| s.cloned().collect())
+            .unwrap_or_default(),
+    };
+    let tags: Vec<String> = matches
+        .get_many::<String>("tag")
+        .map(|t| t.cloned().collect())
+        .unwrap_or_default();
+
+    let mut env_overrides = Vec::new();
+    for pair in matches.get_many::<String>("env").into_iter().flatten() {
+        match pair.split_once('=') {
+            Some((key, value)) if !key.is_empty() => {
+                env_overrides.push((key.to_string(), value.to_string()))
+            }
+            _ => {
+                eprintln!("{} Expected KEY=VAL for -e, got '{}'", "✗".red(), pair);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    let mut stdin_env = Vec::new();
+    if matches.get_flag("env-stdin") {
+        let mut input = String::new();
+        if let Err(e) = std::io::stdin().read_to_string(&mut input) {
+            eprintln!("{} Failed to read env from stdin: {}", "✗".red(), e);
+            std::process::exit(1);
+        }
+        stdin_env = env_parser::parse_dotenv(&input);
+    }
+
+    let taskfile_name = find_taskfile(&defaults.taskfiles);
+    crash::set_taskfile(std::path::Path::new(taskfile_name));
+
+    if !matches!(
+        matches.get_one::<String>("command").map(String::as_str),
+        Some("update" | "self")
+    ) {
+        update_check(offline);
+    }
 
     match matches.get_one::<String>("command") {
         Some(cmd) if cmd == "version" => {
             println!("taskfile-runner v{}", env!("CARGO_PKG_VERSION"));
             println!("A simple task runner written in Rust");
+            match shell::default_shell() {
+                Some(shell) => println!("Default shell: {}", shell),
+                None => println!("Default shell: none detected"),
+            }
             return;
         }
         Some(cmd) if cmd == "update" => {
-            println!("Updating task runner...");
-            match update_task_runner().await {
-                Ok(_) => println!("✓ Update completed successfully!"),
+            let channel = matches
+                .get_one::<String>("channel")
+                .map_or("stable", String::as_str);
+            if offline {
+                eprintln!(
+                    "{} task update downloads a release, which --offline forbids",
+                    "✗".red()
+                );
+                std::process::exit(1);
+            }
+            match update_task_runner(channel, pinned.as_deref()).await {
+                Ok(_) => {}
                 Err(e) => {
                     eprintln!("{} Update failed: {}", "✗".red(), e);
                     std::process::exit(1);
@@ -34,6 +183,51 @@ async fn main() {
             }
             return;
         }
+        Some(cmd) if cmd == "self" => {
+            let sub = matches
+                .get_many::<String>("args")
+                .and_then(|mut a| a.next());
+            match sub.map(String::as_str) {
+                Some("check") => {
+                    let exe = match std::env::current_exe() {
+                        Ok(exe) => exe,
+                        Err(e) => {
+                            eprintln!("{} Cannot locate the running binary: {}", "✗".red(), e);
+                            std::process::exit(1);
+                        }
+                    };
+                    if !self_check::run(&exe, env!("CARGO_PKG_VERSION"), offline).await {
+                        std::process::exit(1);
+                    }
+                }
+                Some("update-check") if offline => {
+                    eprintln!("{} The update check needs the network", "✗".red());
+                    std::process::exit(1);
+                }
+                Some("update-check") => {
+                    if let Err(e) = refresh_update_check().await {
+                        eprintln!("{} {}", "✗".red(), e);
+                        std::process::exit(1);
+                    }
+                }
+                _ => {
+                    eprintln!("{} Usage: task self check|update-check", "✗".red());
+                    std::process::exit(1);
+                }
+            }
+            return;
+        }
+        Some(cmd) if cmd == "daemon" => {
+            let args: Vec<String> = matches
+                .get_many::<String>("args")
+                .map(|a| a.cloned().collect())
+                .unwrap_or_default();
+            if let Err(e) = daemon_command(Path::new(taskfile_name), &args).await {
+                eprintln!("{} {}", "✗".red(), e);
+                std::process::exit(1);
+            }
+            return;
+        }
         Some(cmd) if cmd == "init" => {
             match init_taskfile().await {
                 Ok(_) => println!("✓ Taskfile.toml created successfully!"),
@@ -47,6 +241,27 @@ async fn main() {
         _ => {}
     }
 
+    if matches.get_flag("warm") {
+        let mut args: Vec<String> = matches
+            .get_many::<String>("args")
+            .map(|a| a.cloned().collect())
+            .unwrap_or_default();
+        let task_name = match matches.get_one::<String>("command") {
+            Some(cmd) if cmd == "run" && !args.is_empty() => Some(args.remove(0)),
+            Some(cmd) if !BUILTINS.contains(&cmd.as_str()) => Some(cmd.clone()),
+            _ => None,
+        };
+        let Some(task_name) = task_name else {
+            eprintln!("{} --warm only applies to running a task", "✗".red());
+            std::process::exit(1);
+        };
+        args.extend(passthrough.iter().cloned());
+        let verbose = matches.get_count("verbose") > 0;
+        if let Some(code) = run_warm(Path::new(taskfile_name), &task_name, &args, verbose).await {
+            std::process::exit(code);
+        }
+    }
+
     if !tokio::fs::try_exists(taskfile_name).await.unwrap_or(false) {
         println!("No Taskfile.toml found. Creating a default one...");
         match init_taskfile().await {
@@ -58,20 +273,277 @@ async fn main() {
         }
     }
 
-    match TaskRunner::from_file(taskfile_name).await {
+    match TaskRunner::from_file(taskfile_name).await.map(|runner| {
+        // `--plain`, then the Taskfile's `[settings]`, then the user's
+        // `[defaults]`.
+        let output = match runner.settings().output.or(defaults.output) {
+            _ if matches.get_flag("plain") => OutputMode::Plain,
+            Some(Output::Plain) => OutputMode::Plain,
+            Some(Output::Spinner) => OutputMode::Spinner,
+            None => OutputMode::detect(),
+        };
+        let runner = runner
+            .with_options(RunOptions { output, ..options })
+            .with_env_overrides(&env_overrides)
+            .with_stdin_env(&stdin_env)
+            .with_defaults(defaults);
+        match replay {
+            Some(session) => runner.with_replay(session),
+            None if record.is_some() => runner.with_recording(),
+            None => runner,
+        }
+    }) {
         Ok(runner) => match matches.get_one::<String>("command") {
             Some(cmd) if cmd == "list" => {
-                runner.list_tasks();
+                runner.list_tasks(&tags);
+            }
+            Some(cmd) if cmd == "tools" => {
+                if !runner.print_tools() {
+                    std::process::exit(1);
+                }
             }
-            Some(task_name) => {
-                if let Err(e) = runner.run_task(task_name).await {
-                    eprintln!("{} Error running task '{}': {}", "✗".red(), task_name, e);
+            Some(cmd) if cmd == "validate" => {
+                if !runner.validate() {
+                    std::process::exit(1);
+                }
+            }
+            Some(cmd) if cmd == "bootstrap" => {
+                if !runner.bootstrap().await {
+                    std::process::exit(1);
+                }
+            }
+            Some(cmd) if cmd == "stats" => {
+                let args: Vec<String> = matches
+                    .get_many::<String>("args")
+                    .map(|a| a.cloned().collect())
+                    .unwrap_or_default();
+                let query = stats::Query::parse(&args).unwrap_or_else(|e| {
+                    eprintln!("{} {}", "✗".red(), e);
+                    std::process::exit(1);
+                });
+                if let Err(e) = runner.print_stats(&query) {
+                    eprintln!("{} {}", "✗".red(), e);
+                    std::process::exit(1);
+                }
+            }
+            Some(cmd) if cmd == "env" => {
+                let args: Vec<String> = matches
+                    .get_many::<String>("args")
+                    .map(|a| a.cloned().collect())
+                    .unwrap_or_default();
+                if let Err(e) = env_command(&runner, args) {
+                    eprintln!("{} {}", "✗".red(), e);
+                    std::process::exit(1);
+                }
+            }
+            Some(cmd) if cmd == "watch" => {
+                let mut args: Vec<String> = matches
+                    .get_many::<String>("args")
+                    .map(|a| a.cloned().collect())
+                    .unwrap_or_default();
+                if args.is_empty() {
+                    eprintln!(
+                        "{} Usage: task watch <task_name> [flags] [-- args]",
+                        "✗".red()
+                    );
+                    std::process::exit(1);
+                }
+                if record.is_some() || matches.contains_id("replay") {
+                    eprintln!("{} --record and --replay do not apply to watch", "✗".red());
+                    std::process::exit(1);
+                }
+                let task_name = args.remove(0);
+                args.extend(passthrough);
+                let lock = match runner.lock_project().await {
+                    Ok(lock) => lock,
+                    Err(e) => {
+                        eprintln!("{} {}", "✗".red(), e);
+                        std::process::exit(1);
+                    }
+                };
+                let result = runner.watch(&task_name, &args).await;
+                drop(lock);
+                if let Err(e) = result {
+                    eprintln!("{} {}", "✗".red(), e);
+                    std::process::exit(1);
+                }
+            }
+            Some(cmd) if cmd == "pipeline" => {
+                let args: Vec<String> = matches
+                    .get_many::<String>("args")
+                    .map(|a| a.cloned().collect())
+                    .unwrap_or_default();
+                let name = match args.as_slice() {
+                    [] if passthrough.is_empty() => {
+                        runner.list_pipelines();
+                        return;
+                    }
+                    [name] if passthrough.is_empty() => name,
+                    _ => {
+                        eprintln!("{} Usage: task pipeline [<name>]", "✗".red());
+                        std::process::exit(1);
+                    }
+                };
+                runner.warn_shadowed_env();
+                if let Err(e) = runner.check_env_files_in_git() {
+                    eprintln!("{} {}", "✗".red(), e);
+                    std::process::exit(1);
+                }
+                let lock = match runner.lock_project().await {
+                    Ok(lock) => lock,
+                    Err(e) => {
+                        eprintln!("{} {}", "✗".red(), e);
+                        std::process::exit(1);
+                    }
+                };
+                let started = std::time::Instant::now();
+                let result = runner.run_pipeline(name).await;
+                drop(lock);
+                save_recording(&runner, record.as_deref());
+                runner.ring_bell(started.elapsed());
+                if let Err(e) = result {
+                    eprintln!(
+                        "{} Error running pipeline '{}' (run {}): {}",
+                        "✗".red(),
+                        name,
+                        runner.run_id(),
+                        e
+                    );
+                    std::process::exit(1);
+                }
+            }
+            Some(cmd) if cmd == "export-script" => {
+                let mut args: Vec<String> = matches
+                    .get_many::<String>("args")
+                    .map(|a| a.cloned().collect())
+                    .unwrap_or_default();
+                if args.is_empty() {
+                    eprintln!(
+                        "{} Usage: task export-script <task_name> [flags] [-- args] > run.sh",
+                        "✗".red()
+                    );
+                    std::process::exit(1);
+                }
+                let task_name = args.remove(0);
+                args.extend(passthrough);
+                match runner.export_script(&task_name, &args).await {
+                    Ok(script) => print!("{}", script),
+                    Err(e) => {
+                        eprintln!("{} {}", "✗".red(), e);
+                        std::process::exit(1);
+                    }
+                }
+            }
+            Some(cmd) if cmd == "test-tasks" => {
+                let args: Vec<String> = matches
+                    .get_many::<String>("args")
+                    .map(|a| a.cloned().collect())
+                    .unwrap_or_default();
+                if let Err(e) = test_tasks_command(&runner, taskfile_name, &args) {
+                    eprintln!("{} {}", "✗".red(), e);
+                    std::process::exit(1);
+                }
+            }
+            Some(cmd) if cmd == "describe" || cmd == "help" => {
+                let Some(task_name) = matches
+                    .get_many::<String>("args")
+                    .and_then(|mut a| a.next())
+                else {
+                    eprintln!("{} Usage: task {} <task_name>", "✗".red(), cmd);
+                    std::process::exit(1);
+                };
+                let result = if cmd == "help" {
+                    runner.print_task_help(task_name)
+                } else {
+                    runner.describe_task(task_name)
+                };
+                if let Err(e) = result {
+                    eprintln!("{} {}", "✗".red(), e);
+                    std::process::exit(1);
+                }
+            }
+            Some(cmd) => {
+                let mut args: Vec<String> = matches
+                    .get_many::<String>("args")
+                    .map(|a| a.cloned().collect())
+                    .unwrap_or_default();
+                let task_name = if cmd == "run" {
+                    if args.is_empty() && tags.is_empty() {
+                        eprintln!(
+                            "{} Usage: task run <task_name> [flags] [-- args] | task run --tag <tags>",
+                            "✗".red()
+                        );
+                        std::process::exit(1);
+                    }
+                    if !tags.is_empty()
+                        && (!args.is_empty() || !passthrough.is_empty() || !slice_is_empty(&slice))
+                    {
+                        eprintln!(
+                            "{} `task run --tag` takes no task name, flags, `--` arguments, --from, --until or --skip",
+                            "✗".red()
+                        );
+                        std::process::exit(1);
+                    }
+                    (!args.is_empty()).then(|| args.remove(0))
+                } else {
+                    if !tags.is_empty() {
+                        eprintln!("{} --tag only applies to `list` and `run`", "✗".red());
+                        std::process::exit(1);
+                    }
+                    Some(cmd.clone())
+                };
+                // `task test lint`: when every argument names a task, run them
+                // all in one invocation instead of parsing them as flags.
+                let several = task_name.is_some()
+                    && !args.is_empty()
+                    && passthrough.is_empty()
+                    && slice_is_empty(&slice)
+                    && args.iter().all(|arg| runner.has_task(arg));
+                args.extend(passthrough);
+
+                runner.warn_shadowed_env();
+                if let Err(e) = runner.check_env_files_in_git() {
+                    eprintln!("{} {}", "✗".red(), e);
+                    std::process::exit(1);
+                }
+                let lock = match runner.lock_project().await {
+                    Ok(lock) => lock,
+                    Err(e) => {
+                        eprintln!("{} {}", "✗".red(), e);
+                        std::process::exit(1);
+                    }
+                };
+                let started = std::time::Instant::now();
+                let names: Vec<&str> = task_name.iter().chain(&args).map(String::as_str).collect();
+                let result = match &task_name {
+                    Some(_) if several => runner.run_tasks(&names).await,
+                    Some(task_name) => runner.run_task_slice(task_name, &args, &slice).await,
+                    None => runner.run_tagged(&tags).await,
+                };
+                drop(lock);
+                save_recording(&runner, record.as_deref());
+                runner.ring_bell(started.elapsed());
+                if let Err(e) = result {
+                    let target = match &task_name {
+                        Some(_) if several => format!("tasks {}", names.join(", ")),
+                        Some(task_name) => format!("task '{}'", task_name),
+                        None => format!("tasks tagged {}", tags.join(", ")),
+                    };
+                    eprintln!(
+                        "{} Error running {} (run {}): {}",
+                        "✗".red(),
+                        target,
+                        runner.run_id(),
+                        e
+                    );
                     std::process::exit(1);
                 }
             }
             None => {
                 println!("Please specify a task to run or use 'list' to see available tasks.");
-                println!("Usage: task <task_name> | list | version | update | init");
+                println!(
+                    "Usage: task <task_name> | run <task_name> | watch <task_name> | pipeline <name> | export-script <task_name> | test-tasks | list | tools | bootstrap | validate | stats | describe <task_name> | help <task_name> | daemon | version | update | init"
+                );
                 std::process::exit(1);
             }
         },
@@ -82,7 +554,575 @@ async fn main() {
     }
 }
 
-async fn update_task_runner() -> Result<(), Box<dyn std::error::Error>> {
+/// The command line interface, without the task-specific flags that
+/// `args` carries.
+fn cli() -> Command {
+    Command::new("taskfile")
+        .version("1.0")
+        .disable_version_flag(true)
+        .about("A simple task runner")
+        .arg(
+            Arg::new("command")
+                .help("The command to run (list, describe, help, run, watch, pipeline, export-script, test-tasks, env, tools, bootstrap, validate, stats, daemon, version, update, self, init, or task name)")
+                .value_name("COMMAND")
+                .index(1),
+        )
+        .arg(
+            Arg::new("args")
+                .help("Arguments for the command, e.g. the task name for describe or a task's flags")
+                .value_name("ARGS")
+                .index(2)
+                .num_args(0..)
+                .trailing_var_arg(true)
+                .allow_hyphen_values(true),
+        )
+        .arg(
+            Arg::new("plain")
+                .long("plain")
+                .help("Plain line output without a spinner (default under CI or when not a TTY)")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("verbose")
+                .short('v')
+                .long("verbose")
+                .help("Log more detail to stderr: -v for debug, -vv for trace")
+                .action(ArgAction::Count),
+        )
+        .arg(
+            Arg::new("quiet")
+                .short('q')
+                .long("quiet")
+                .help("Only log errors to stderr")
+                .conflicts_with("verbose")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("auto-retry-transient")
+                .long("auto-retry-transient")
+                .help("Retry tasks marked idempotent when they fail with a transient error")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("critical-path")
+                .long("critical-path")
+                .help("After the run, print the chain of tasks that determined its duration")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("env")
+                .short('e')
+                .long("env")
+                .value_name("KEY=VAL")
+                .action(ArgAction::Append)
+                .help("Set an environment variable for this run, overriding the Taskfile"),
+        )
+        .arg(
+            Arg::new("strict")
+                .long("strict")
+                .help("Fail instead of warning about env files not ignored by git or paths outside the project")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("env-stdin")
+                .long("env-stdin")
+                .help("Read KEY=VALUE lines from stdin, overriding every other source")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("from")
+                .long("from")
+                .value_name("TASK")
+                .help("Start at this dependency, treating the tasks before it as done"),
+        )
+        .arg(
+            Arg::new("until")
+                .long("until")
+                .value_name("TASK")
+                .help("Stop once this dependency has run"),
+        )
+        .arg(
+            Arg::new("skip")
+                .long("skip")
+                .value_name("TASKS")
+                .value_delimiter(',')
+                .action(ArgAction::Append)
+                .help("Comma-separated dependencies to treat as already satisfied"),
+        )
+        .arg(
+            Arg::new("jobs")
+                .short('j')
+                .long("jobs")
+                .value_name("N")
+                .value_parser(clap::value_parser!(u64).range(1..))
+                .help("Run at most N tasks at the same time"),
+        )
+        .arg(
+            Arg::new("bell")
+                .long("bell")
+                .value_name("AFTER")
+                .num_args(0..=1)
+                .require_equals(true)
+                .default_missing_value("10s")
+                .help("Ring the terminal bell when a run of at least AFTER (default 10s) finishes"),
+        )
+        .arg(
+            Arg::new("yes")
+                .short('y')
+                .long("yes")
+                .help("Answer yes to every task's confirm question")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("approve")
+                .long("approve")
+                .help("Run pipeline stages marked approval = true without asking")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("lang")
+                .long("lang")
+                .value_name("LANG")
+                .help("Language for task descriptions, e.g. de (default: $TASKFILE_LANG)"),
+        )
+        .arg(
+            Arg::new("tag")
+                .long("tag")
+                .value_name("TAGS")
+                .value_delimiter(',')
+                .action(ArgAction::Append)
+                .help("Comma-separated tags: `list` shows, and `run` runs, the tasks carrying any of them"),
+        )
+        .arg(
+            Arg::new("offline")
+                .long("offline")
+                .help("Never touch the network: tasks and commands that need it fail before they start")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("record")
+                .long("record")
+                .value_name("FILE")
+                .conflicts_with("replay")
+                .help("Write every command the run starts, with its output and timing, to FILE"),
+        )
+        .arg(
+            Arg::new("replay")
+                .long("replay")
+                .value_name("FILE")
+                .help("Print a run recorded with --record again, without executing anything"),
+        )
+        .arg(
+            Arg::new("warm")
+                .long("warm")
+                .conflicts_with_all([
+                    "record", "replay", "tag", "from", "until", "skip", "env", "env-stdin",
+                ])
+                .help("Run the task on a warm worker of `task daemon`, if one is serving this Taskfile")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("version")
+                .short('V')
+                .long("version")
+                .value_name("VERSION")
+                .num_args(0..=1)
+                .help("Print the version; with `update`, the release to install, e.g. 1.3.2"),
+        )
+        .arg(
+            Arg::new("channel")
+                .long("channel")
+                .value_name("CHANNEL")
+                .value_parser(["stable", "nightly"])
+                .conflicts_with("version")
+                .help("Release channel for `update`: stable (default), or nightly to include prereleases"),
+        )
+}
+
+/// The options whose value can be a separate argument (`-e KEY=VAL`,
+/// `--from build`), which alias expansion and crash reports must step
+/// over. `--bell` only takes `--bell=AFTER`, so it is not one.
+fn value_options(cli: &Command) -> Vec<String> {
+    cli.get_arguments()
+        .filter(|arg| {
+            !arg.is_positional() && arg.get_action().takes_values() && !arg.is_require_equals_set()
+        })
+        .flat_map(|arg| {
+            let short = arg.get_short().map(|short| format!("-{}", short));
+            let long = arg.get_long().map(|long| format!("--{}", long));
+            short.into_iter().chain(long)
+        })
+        .collect()
+}
+
+/// Writes what `--record` kept, whether or not the run succeeded.
+fn save_recording(runner: &TaskRunner, path: Option<&Path>) {
+    let Some(path) = path else {
+        return;
+    };
+    match session::save(path, &runner.recorded()) {
+        Ok(()) => eprintln!("{} Recorded the run to {}", "✓".green(), path.display()),
+        Err(e) => eprintln!("{} Failed to write {}: {}", "✗".red(), path.display(), e),
+    }
+}
+
+/// The first of `names` present in the current directory. With none
+/// present this is `Taskfile.toml`, which gets created.
+fn find_taskfile(names: &[String]) -> &str {
+    names
+        .iter()
+        .map(String::as_str)
+        .find(|name| std::path::Path::new(name).is_file())
+        .unwrap_or("Taskfile.toml")
+}
+
+/// The commands that are not task names.
+const BUILTINS: &[&str] = &[
+    "list",
+    "describe",
+    "help",
+    "run",
+    "watch",
+    "pipeline",
+    "export-script",
+    "test-tasks",
+    "env",
+    "tools",
+    "bootstrap",
+    "validate",
+    "stats",
+    "daemon",
+    "version",
+    "update",
+    "self",
+    "init",
+];
+
+/// `task daemon [--workers N] [--idle DURATION] [--max-jobs N]`, `task
+/// daemon status` and `task daemon stop`.
+#[cfg(unix)]
+async fn daemon_command(
+    taskfile: &Path,
+    args: &[String],
+) -> Result<(), Box<dyn std::error::Error>> {
+    let socket = daemon::socket_path(taskfile);
+    let request = match args.first().map(String::as_str) {
+        Some("status") if args.len() == 1 => "status",
+        Some("stop") if args.len() == 1 => "stop",
+        _ => {
+            let config = daemon::Config::parse(args)?;
+            println!(
+                "{} Serving {} on {} (Ctrl-C to stop)",
+                "✓".green(),
+                taskfile.display(),
+                socket.display()
+            );
+            return daemon::serve(taskfile, config).await;
+        }
+    };
+    let reply = daemon::request(&socket, &[request], &mut |_, _| {})
+        .await
+        .map_err(|e| format!("No daemon is listening on {}: {}", socket.display(), e))?;
+    match reply {
+        daemon::Reply::Status(status) => print!("{}", status),
+        daemon::Reply::Stopped => println!("{} Daemon stopped", "✓".green()),
+        daemon::Reply::Failed(e) => return Err(e.into()),
+        reply => return Err(format!("Unexpected reply {:?}", reply).into()),
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+async fn daemon_command(_: &Path, _: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    Err("task daemon needs Unix sockets, which this platform does not have".into())
+}
+
+/// Runs `task_name` on the daemon's warm workers, returning its exit code;
+/// `None` when no daemon is listening or it declined the run, which then
+/// goes ahead as usual.
+#[cfg(unix)]
+async fn run_warm(taskfile: &Path, task_name: &str, args: &[String], verbose: bool) -> Option<i32> {
+    use std::io::Write;
+
+    let request: Vec<&str> = ["run", task_name]
+        .into_iter()
+        .chain(args.iter().map(String::as_str))
+        .collect();
+    let mut output = |stream, bytes: &[u8]| {
+        let _ = match stream {
+            daemon::Stream::Stdout => std::io::stdout().write_all(bytes),
+            daemon::Stream::Stderr => std::io::stderr().write_all(bytes),
+        };
+    };
+    let reply = daemon::request(&daemon::socket_path(taskfile), &request, &mut output).await;
+    let _ = std::io::stdout().flush();
+    match reply {
+        Ok(daemon::Reply::Exited(code)) => Some(code),
+        Ok(daemon::Reply::Failed(e)) => {
+            eprintln!("{} {}", "✗".red(), e);
+            Some(1)
+        }
+        Ok(daemon::Reply::Declined(reason)) => {
+            if verbose {
+                eprintln!("{} Not running warm: {}", "ℹ".cyan(), reason);
+            }
+            None
+        }
+        Ok(reply) => {
+            eprintln!("{} Unexpected reply {:?} from the daemon", "✗".red(), reply);
+            Some(1)
+        }
+        Err(e) => {
+            if verbose {
+                eprintln!(
+                    "{} No daemon to run on ({}); running normally",
+                    "ℹ".cyan(),
+                    e
+                );
+            }
+            None
+        }
+    }
+}
+
+#[cfg(not(unix))]
+async fn run_warm(_: &Path, _: &str, _: &[String], _: bool) -> Option<i32> {
+    None
+}
+
+fn slice_is_empty(slice: &GraphSlice) -> bool {
+    slice.from.is_none() && slice.until.is_none() && slice.skip.is_empty()
+}
+
+/// `task test-tasks [--update] [<test>...]`: runs the Taskfile's `[tests]`,
+/// or the named ones, with this binary.
+fn test_tasks_command(
+    runner: &TaskRunner,
+    taskfile: &str,
+    args: &[String],
+) -> Result<(), Box<dyn std::error::Error>> {
+    const USAGE: &str = "Usage: task test-tasks [--update] [<test>...]";
+    let mut update = false;
+    let mut names = Vec::new();
+    for arg in args {
+        match arg.as_str() {
+            "--update" => update = true,
+            flag if flag.starts_with('-') => return Err(USAGE.into()),
+            name => names.push(name),
+        }
+    }
+    if runner.tests().next().is_none() {
+        return Err(format!("{} has no [tests]", taskfile).into());
+    }
+    if let Some(name) = names
+        .iter()
+        .find(|name| !runner.tests().any(|(test, _)| test == *name))
+    {
+        return Err(format!("No test named '{}'", name).into());
+    }
+
+    let harness = Harness::new(std::env::current_exe()?, taskfile).update(update);
+    let (mut passed, mut written, mut failed) = (0, 0, 0);
+    for (name, test) in runner
+        .tests()
+        .filter(|(name, _)| names.is_empty() || names.contains(&name.as_str()))
+    {
+        match harness.run(name, test) {
+            Ok(Outcome::Passed) => {
+                println!("{} {}", "✓".green(), name);
+                passed += 1;
+            }
+            Ok(Outcome::Written(path)) => {
+                println!("{} {} (wrote {})", "✓".green(), name, path.display());
+                written += 1;
+            }
+            Ok(Outcome::Failed(reason)) | Err(reason) => {
+                println!("{} {}: {}", "✗".red(), name, reason);
+                failed += 1;
+            }
+        }
+    }
+    let mut summary = format!("{} passed", passed);
+    match written {
+        0 => {}
+        1 => summary.push_str(", 1 snapshot written"),
+        n => summary.push_str(&format!(", {} snapshots written", n)),
+    }
+    if failed > 0 {
+        return Err(format!("{}, {} failed", summary, failed).into());
+    }
+    println!("{}", summary);
+    Ok(())
+}
+
+/// `task env [--sources]` and `task env set|unset|get` for dotenv files. The
+/// edited file is the Taskfile's first `[env]` file unless `--file` says
+/// otherwise.
+fn env_command(
+    runner: &TaskRunner,
+    mut args: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let file = match args.iter().position(|a| a == "--file") {
+        Some(i) if i + 1 < args.len() => {
+            let path = args.remove(i + 1);
+            args.remove(i);
+            Some(std::path::PathBuf::from(path))
+        }
+        Some(_) => return Err("--file expects a path".into()),
+        None => None,
+    };
+    if args.first().is_some_and(|a| a == "example") {
+        let file = file.unwrap_or_else(|| std::path::PathBuf::from(".env.example"));
+        return write_env_example(runner, &file);
+    }
+    let file = file.unwrap_or_else(|| runner.primary_env_file());
+
+    match args.first().map(String::as_str) {
+        None | Some("--sources") => {
+            runner.print_env(args.iter().any(|a| a == "--sources"));
+        }
+        Some("set") => {
+            if args.len() < 2 {
+                return Err("Usage: task env set KEY=VAL [KEY=VAL...] [--file PATH]".into());
+            }
+            let mut editor = EnvFileEditor::open(&file)?;
+            for pair in &args[1..] {
+                let (key, value) = pair
+                    .split_once('=')
+                    .filter(|(key, _)| !key.is_empty())
+                    .ok_or_else(|| format!("Expected KEY=VAL, got '{}'", pair))?;
+                editor.set(key, value);
+            }
+            editor.save()?;
+            println!("✓ Updated {}", file.display());
+        }
+        Some("unset") => {
+            if args.len() < 2 {
+                return Err("Usage: task env unset KEY [KEY...] [--file PATH]".into());
+            }
+            let mut editor = EnvFileEditor::open(&file)?;
+            for key in &args[1..] {
+                if !editor.unset(key) {
+                    eprintln!("Warning: '{}' is not set in {}", key, file.display());
+                }
+            }
+            editor.save()?;
+            println!("✓ Updated {}", file.display());
+        }
+        Some("encrypt") => {
+            let [_, value] = args.as_slice() else {
+                return Err("Usage: task env encrypt VALUE".into());
+            };
+            let identity = secret::load_identity(runner.base_dir())?.ok_or_else(|| {
+                format!(
+                    "No age key found; run `task env keygen` or set {}",
+                    secret::KEY_VAR
+                )
+            })?;
+            println!(
+                "{}",
+                secret::encrypt(value, &secret::recipient_of(&identity))?
+            );
+        }
+        Some("keygen") => {
+            let path = secret::default_key_file(runner.base_dir());
+            if path.exists() {
+                return Err(format!("{} already exists", path.display()).into());
+            }
+            std::fs::create_dir_all(path.parent().unwrap())?;
+            let mut options = std::fs::OpenOptions::new();
+            options.write(true).create_new(true);
+            #[cfg(unix)]
+            std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+            std::io::Write::write_all(
+                &mut options.open(&path)?,
+                secret::generate_key_file().as_bytes(),
+            )?;
+            println!(
+                "✓ Wrote {}; keep it out of version control and share it securely",
+                path.display()
+            );
+        }
+        Some("get") => {
+            let [_, key] = args.as_slice() else {
+                return Err("Usage: task env get KEY [--file PATH]".into());
+            };
+            match EnvFileEditor::open(&file)?.get(key) {
+                Some(value) => println!("{}", value),
+                None => return Err(format!("'{}' is not set in {}", key, file.display()).into()),
+            }
+        }
+        Some(other) => {
+            return Err(format!(
+                "Unknown env command '{}' (expected set, unset, get, example, encrypt, keygen or --sources)",
+                other
+            )
+            .into());
+        }
+    }
+    Ok(())
+}
+
+/// Adds every variable the tasks use to `file` with an empty placeholder,
+/// keeping existing entries, values and comments as they are.
+fn write_env_example(
+    runner: &TaskRunner,
+    file: &std::path::Path,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let used = runner.referenced_env_vars();
+    let mut editor = EnvFileEditor::open(file)?;
+    let existing: Vec<String> = editor.keys().into_iter().map(String::from).collect();
+
+    let mut added = 0;
+    for (var, tasks) in &used {
+        if existing.contains(var) {
+            continue;
+        }
+        editor.push_comment(&format!("Used by: {}", tasks.join(", ")));
+        editor.set(var, "");
+        added += 1;
+    }
+    editor.save()?;
+
+    println!("✓ Added {} variable(s) to {}", added, file.display());
+    let stale: Vec<&String> = existing.iter().filter(|k| !used.contains_key(*k)).collect();
+    if !stale.is_empty() {
+        let names: Vec<&str> = stale.iter().map(|k| k.as_str()).collect();
+        println!(
+            "{} No task references {}; remove them if they are obsolete",
+            "⚠".yellow(),
+            names.join(", ")
+        );
+    }
+    Ok(())
+}
+
+/// Sends log events to stderr. `RUST_LOG` takes precedence over `-v`/`-q`.
+fn init_logging(verbose: u8, quiet: bool) {
+    use tracing_subscriber::EnvFilter;
+
+    let level = match (quiet, verbose) {
+        (true, _) => "error",
+        (false, 0) => "info",
+        (false, 1) => "debug",
+        (false, _) => "trace",
+    };
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(level));
+    tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(std::io::stderr)
+        .with_ansi(std::io::stderr().is_terminal())
+        .with_target(false)
+        .without_time()
+        .init();
+}
+
+/// Installs the release `self_update::select_release` picks over the
+/// running binary, through the project's install script.
+async fn update_task_runner(
+    channel: &str,
+    version: Option<&str>,
+) -> Result<(), Box<dyn std::error::Error>> {
     use std::env;
     use std::process::Stdio;
     use tokio::process::Command;
@@ -90,10 +1130,17 @@ async fn update_task_runner() -> Result<(), Box<dyn std::error::Error>> {
     let current_exe = env::current_exe()?;
     let install_dir = current_exe.parent().unwrap();
 
-    println!("Downloading latest version...");
+    let releases = self_update::fetch_releases().await?;
+    let tag = self_update::select_release(&releases, channel, version)?;
+    if tag.trim_start_matches('v') == env!("CARGO_PKG_VERSION") {
+        println!("✓ Already on {}", tag);
+        return Ok(());
+    }
+
+    println!("Installing {}...", tag);
 
     let output = Command::new("curl")
-        .args(&[
+        .args([
             "-sSL",
             "https://raw.githubusercontent.com/lassejlv/taskfile/main/install.sh",
         ])
@@ -110,10 +1157,11 @@ async fn update_task_runner() -> Result<(), Box<dyn std::error::Error>> {
 
     let output = Command::new("bash")
         .arg("-c")
-        .arg(&format!(
-            "echo '{}' | bash -s -- --install-dir '{}'",
+        .arg(format!(
+            "echo '{}' | bash -s -- --install-dir '{}' --version '{}'",
             install_script.replace("'", "'\"'\"'"),
-            install_dir.display()
+            install_dir.display(),
+            tag.replace("'", "'\"'\"'")
         ))
         .stdout(Stdio::piped())
         .stderr(Stdio::piped())
@@ -123,6 +1171,7 @@ async fn update_task_runner() -> Result<(), Box<dyn std::error::Error>> {
     if output.status.success() {
         println!("Installation output:");
         print!("{}", String::from_utf8_lossy(&output.stdout));
+        println!("✓ Updated to {}", tag);
         Ok(())
     } else {
         eprintln!("Error output:");
@@ -131,6 +1180,75 @@ async fn update_task_runner() -> Result<(), Box<dyn std::error::Error>> {
     }
 }
 
+/// Prints a hint when the last check saw a newer stable release, then starts
+/// the next check in a detached `task self update-check`, at most once a
+/// day. Nothing here waits on the network. Skipped under `--offline`, with
+/// `TASKFILE_NO_UPDATE_CHECK` set, or when stderr is not a terminal.
+fn update_check(offline: bool) {
+    if offline
+        || std::env::var_os(NO_UPDATE_CHECK_VAR).is_some()
+        || !std::io::stderr().is_terminal()
+    {
+        return;
+    }
+    let Some(path) = update_check::State::path() else {
+        return;
+    };
+    let mut state = update_check::State::read(&path);
+    let now = unix_now();
+    if !state.is_due(now) {
+        return;
+    }
+
+    let current = env!("CARGO_PKG_VERSION");
+    if let Some(latest) = state.newer_than(current) {
+        eprintln!(
+            "{} taskfile {} is available (you have v{}); run `task update`, or set {} to stop these hints",
+            "ℹ".cyan(),
+            latest,
+            current,
+            NO_UPDATE_CHECK_VAR
+        );
+    }
+    // Recorded up front so runs starting in the meantime do not check too.
+    state.checked = now;
+    if state.write(&path).is_err() {
+        return;
+    }
+    if let Ok(exe) = std::env::current_exe() {
+        let _ = std::process::Command::new(exe)
+            .args(["self", "update-check"])
+            .stdin(std::process::Stdio::null())
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .spawn();
+    }
+}
+
+/// `task self update-check`: records the newest stable release for the
+/// next `update_check`.
+async fn refresh_update_check() -> Result<(), String> {
+    let path =
+        update_check::State::path().ok_or("No config directory to keep the update check in")?;
+    let releases = self_update::fetch_releases().await?;
+    let latest = self_update::select_release(&releases, "stable", None)?;
+    let state = update_check::State {
+        checked: unix_now(),
+        latest: Some(latest.clone()),
+    };
+    state
+        .write(&path)
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    println!("Latest stable release: {}", latest);
+    Ok(())
+}
+
+fn unix_now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
 async fn init_taskfile() -> Result<(), Box<dyn std::error::Error>> {
     let taskfile_name = "Taskfile.toml";
 
@@ -138,28 +1256,52 @@ async fn init_taskfile() -> Result<(), Box<dyn std::error::Error>> {
         return Err("Taskfile.toml already exists".into());
     }
 
-    let default_content = r#"[tasks.hello]
-cmd = "echo 'Hello, World!'"
+    let shell = shell::default_shell().map(|s| s.kind);
+    if let Some(kind) = shell {
+        println!("Generating example tasks for {}", kind);
+    }
+
+    tokio::fs::write(taskfile_name, default_taskfile_content(shell)).await?;
+    Ok(())
+}
+
+/// Builds the `task init` template so its example commands run on the
+/// detected shell; Windows shells have no standalone `echo` binary.
+fn default_taskfile_content(shell: Option<ShellKind>) -> String {
+    let echo = |message: &str| match shell {
+        Some(kind) if kind.is_powershell() => {
+            format!("{} -NoProfile -Command Write-Output '{}'", kind, message)
+        }
+        Some(ShellKind::Cmd) => format!("cmd /C echo {}", message),
+        _ => format!("echo '{}'", message),
+    };
+
+    format!(
+        r#"[tasks.hello]
+cmd = "{}"
 desc = "Print hello world message"
 
 [tasks.build]
-cmd = "echo 'Building project...'"
+cmd = "{}"
 desc = "Build the project"
 
 [tasks.test]
-cmd = "echo 'Running tests...'"
+cmd = "{}"
 desc = "Run tests"
 
 [tasks.clean]
-cmd = "echo 'Cleaning build artifacts...'"
+cmd = "{}"
 desc = "Clean build artifacts"
 
 [tasks.dev]
-cmd = "echo 'Starting development server...'"
+cmd = "{}"
 desc = "Start development server"
 depends_on = ["build"]
-"#;
-
-    tokio::fs::write(taskfile_name, default_content).await?;
-    Ok(())
+"#,
+        echo("Hello, World!"),
+        echo("Building project..."),
+        echo("Running tests..."),
+        echo("Cleaning build artifacts..."),
+        echo("Starting development server..."),
+    )
 }
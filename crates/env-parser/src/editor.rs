@@ -0,0 +1,149 @@
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::path::{Path, PathBuf};
+
+/// Edits a dotenv file in place. Comments, blank lines and the order of
+/// untouched keys are preserved; new keys are appended at the end.
+#[derive(Debug, Clone)]
+pub struct EnvFileEditor {
+    path: PathBuf,
+    lines: Vec<String>,
+}
+
+impl EnvFileEditor {
+    /// Reads `path`, starting from an empty file when it does not exist yet.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let lines = match fs::read_to_string(&path) {
+            Ok(contents) => contents.lines().map(String::from).collect(),
+            Err(e) if e.kind() == ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e),
+        };
+        Ok(Self { path, lines })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The value of `key`, unquoted the same way the parser reads it.
+    pub fn get(&self, key: &str) -> Option<String> {
+        self.lines
+            .iter()
+            .rev()
+            .find_map(|line| parse_line(line).filter(|(k, _)| *k == key))
+            .map(|(_, value)| unquote(value).to_string())
+    }
+
+    /// Updates the last definition of `key`, or appends one.
+    pub fn set(&mut self, key: &str, value: &str) {
+        let line = format!("{}={}", key, quote(value));
+        match self.position(key) {
+            Some(index) => self.lines[index] = line,
+            None => self.lines.push(line),
+        }
+    }
+
+    /// Keys defined in the file, in order of first definition.
+    pub fn keys(&self) -> Vec<&str> {
+        let mut keys: Vec<&str> = Vec::new();
+        for (key, _) in self.lines.iter().filter_map(|line| parse_line(line)) {
+            if !keys.contains(&key) {
+                keys.push(key);
+            }
+        }
+        keys
+    }
+
+    /// Appends a `# comment` line.
+    pub fn push_comment(&mut self, text: &str) {
+        self.lines.push(format!("# {}", text));
+    }
+
+    /// Removes every definition of `key`; returns whether there was one.
+    pub fn unset(&mut self, key: &str) -> bool {
+        let before = self.lines.len();
+        self.lines
+            .retain(|line| parse_line(line).is_none_or(|(k, _)| k != key));
+        self.lines.len() != before
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let mut contents = self.lines.join("\n");
+        if !contents.is_empty() {
+            contents.push('\n');
+        }
+        fs::write(&self.path, contents)
+    }
+
+    fn position(&self, key: &str) -> Option<usize> {
+        self.lines
+            .iter()
+            .rposition(|line| parse_line(line).is_some_and(|(k, _)| k == key))
+    }
+}
+
+fn parse_line(line: &str) -> Option<(&str, &str)> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return None;
+    }
+    let (key, value) = line.split_once('=')?;
+    Some((key.trim(), value.trim()))
+}
+
+fn unquote(value: &str) -> &str {
+    if value.len() >= 2
+        && ((value.starts_with('"') && value.ends_with('"'))
+            || (value.starts_with('\'') && value.ends_with('\'')))
+    {
+        &value[1..value.len() - 1]
+    } else {
+        value
+    }
+}
+
+/// Quotes values the parser would otherwise trim or misread. The parser does
+/// not unescape, so a value containing `"` is single-quoted instead.
+fn quote(value: &str) -> String {
+    let plain = value.trim() == value && !value.contains(['#', '"', '\'', ' ']);
+    if plain {
+        value.to_string()
+    } else if value.contains('"') {
+        format!("'{}'", value)
+    } else {
+        format!("\"{}\"", value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_edit_preserves_comments_and_order() {
+        let path = std::env::temp_dir().join(format!("taskfile-editor-{}.env", std::process::id()));
+        fs::write(
+            &path,
+            "# database\nDB_HOST=localhost\n\nDB_PORT=5432\nDEBUG=1\n",
+        )
+        .unwrap();
+
+        let mut editor = EnvFileEditor::open(&path).unwrap();
+        editor.set("DB_PORT", "6543");
+        editor.set("GREETING", "hello world");
+        assert!(editor.unset("DEBUG"));
+        assert!(!editor.unset("MISSING"));
+        editor.save().unwrap();
+
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "# database\nDB_HOST=localhost\n\nDB_PORT=6543\nGREETING=\"hello world\"\n"
+        );
+        let editor = EnvFileEditor::open(&path).unwrap();
+        assert_eq!(editor.get("GREETING"), Some("hello world".to_string()));
+        assert_eq!(editor.get("DEBUG"), None);
+
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -1,139 +1,496 @@
+pub mod editor;
+pub mod schema;
+pub mod secret;
+
+pub use editor::EnvFileEditor;
+use schema::VarSchema;
 use serde::Deserialize;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::env;
 use std::fs::File;
 use std::io::{BufRead, BufReader};
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Clone, Default)]
 pub struct EnvConfig {
+    #[serde(default)]
     pub files: Vec<String>,
+    #[serde(default)]
+    pub substitution: Substitution,
+    /// Commands whose stdout (`KEY=VALUE` lines) is loaded after `files`,
+    /// e.g. credential helpers.
+    #[serde(default)]
+    pub from_cmd: Vec<String>,
+    /// Inline variables, applied after (and overriding) `files`.
+    #[serde(default)]
+    pub vars: HashMap<String, String>,
+    /// Keys an included Taskfile makes visible to the file that includes it.
+    #[serde(default)]
+    pub export: Vec<String>,
+    /// Print a warning for every key defined by more than one source.
+    #[serde(default)]
+    pub warn_shadowed: bool,
+    /// Types and constraints checked once variables are loaded.
+    #[serde(default)]
+    pub schema: HashMap<String, VarSchema>,
+}
+
+/// Which placeholder syntax `substitute_env_vars` rewrites.
+///
+/// `braces` leaves `$VAR` untouched so commands can rely on runtime shell
+/// variables, e.g. `for f in *; do echo $f; done`.
+#[derive(Debug, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Substitution {
+    #[default]
+    Dollar,
+    Braces,
+    Both,
 }
 
-#[derive(Debug)]
+impl Substitution {
+    fn dollar(self) -> bool {
+        matches!(self, Substitution::Dollar | Substitution::Both)
+    }
+
+    fn braces(self) -> bool {
+        matches!(self, Substitution::Braces | Substitution::Both)
+    }
+}
+
+/// Loads dotenv files and inline vars into its own variable map, which
+/// takes precedence over the process environment for substitution and is
+/// what callers pass to spawned commands. The process environment itself is
+/// never modified.
+#[derive(Debug, Clone)]
 pub struct EnvParser {
     config: Option<EnvConfig>,
+    vars: HashMap<String, String>,
+    /// Where each key's current value came from, e.g. `.env.local`.
+    sources: HashMap<String, String>,
+    shadowed: Vec<Shadowed>,
+    /// Keys whose values were decrypted and must not be printed.
+    secrets: HashSet<String>,
+    /// Env files that were found and read, in load order.
+    loaded_files: Vec<std::path::PathBuf>,
+}
+
+/// A value that lost to a later definition of the same key.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Shadowed {
+    pub key: String,
+    pub value: String,
+    pub source: String,
+    /// Source of the definition replacing it.
+    pub by: String,
 }
 
+/// Source recorded for `[env] vars`.
+pub const INLINE_VARS_SOURCE: &str = "[env.vars]";
+
 impl EnvParser {
     pub fn new() -> Self {
-        Self { config: None }
+        Self {
+            config: None,
+            vars: HashMap::new(),
+            sources: HashMap::new(),
+            shadowed: Vec::new(),
+            secrets: HashSet::new(),
+            loaded_files: Vec::new(),
+        }
     }
 
     pub fn with_config(config: EnvConfig) -> Self {
         Self {
             config: Some(config),
+            ..Self::new()
+        }
+    }
+
+    pub fn config(&self) -> Option<&EnvConfig> {
+        self.config.as_ref()
+    }
+
+    /// A parser for a nested scope: `child`'s variables layered over this
+    /// one's, using `child`'s config when it has one.
+    pub fn layered(&self, child: &EnvParser) -> EnvParser {
+        let mut vars = self.vars.clone();
+        vars.extend(child.vars.clone());
+        let mut sources = self.sources.clone();
+        sources.extend(child.sources.clone());
+        let mut shadowed = self.shadowed.clone();
+        shadowed.extend(child.shadowed.iter().cloned());
+        let mut secrets = self.secrets.clone();
+        secrets.extend(child.secrets.iter().cloned());
+        let mut loaded_files = self.loaded_files.clone();
+        loaded_files.extend(child.loaded_files.iter().cloned());
+        EnvParser {
+            config: child.config.clone().or_else(|| self.config.clone()),
+            vars,
+            sources,
+            shadowed,
+            secrets,
+            loaded_files,
         }
     }
 
-    pub fn load_env_files(&self) -> Result<(), Box<dyn std::error::Error>> {
+    /// Variables named in `export` that this parser defines.
+    pub fn exported_vars(&self) -> Vec<(String, String)> {
+        self.config
+            .iter()
+            .flat_map(|c| &c.export)
+            .filter_map(|key| self.vars.get(key).map(|v| (key.clone(), v.clone())))
+            .collect()
+    }
+
+    pub fn load_env_files(&mut self) -> Result<(), Box<dyn std::error::Error>> {
         self.load_env_files_with_base_path(None)
     }
 
     pub fn load_env_files_with_base_path(
-        &self,
+        &mut self,
         base_path: Option<&std::path::Path>,
     ) -> Result<(), Box<dyn std::error::Error>> {
-        if let Some(env_config) = &self.config {
-            for file_path in &env_config.files {
-                let full_path = if let Some(base) = base_path {
-                    base.join(file_path)
-                } else {
-                    std::path::PathBuf::from(file_path)
-                };
-
-                if full_path.exists() {
-                    let path_str = full_path.to_string_lossy();
-                    match self.load_env_file(&path_str) {
-                        Ok(count) => {
-                            println!("Loaded {} environment variables from: {}", count, path_str)
-                        }
-                        Err(e) => eprintln!("Warning: Failed to load {}: {}", path_str, e),
+        let Some(env_config) = self.config.clone() else {
+            return Ok(());
+        };
+
+        #[cfg(not(feature = "dotenv"))]
+        if !env_config.files.is_empty() {
+            return Err(format!(
+                "Cannot load {}: this build does not include the dotenv feature",
+                env_config.files.join(", ")
+            )
+            .into());
+        }
+
+        #[cfg(feature = "dotenv")]
+        for file_path in &env_config.files {
+            let full_path = if let Some(base) = base_path {
+                base.join(file_path)
+            } else {
+                std::path::PathBuf::from(file_path)
+            };
+
+            if full_path.exists() {
+                let path_str = full_path.to_string_lossy();
+                match self.load_env_file(&path_str) {
+                    Ok(count) => {
+                        tracing::info!("Loaded {} environment variables from: {}", count, path_str);
+                        self.loaded_files.push(full_path.clone());
+                    }
+                    Err(e) => {
+                        tracing::warn!("Failed to load {}: {}", path_str, e)
                     }
                 }
             }
         }
+
+        for cmd in &env_config.from_cmd {
+            let dir = base_path.unwrap_or(std::path::Path::new("."));
+            let count = self.load_command_output(cmd, dir)?;
+            tracing::info!("Loaded {} environment variables from: {}", count, cmd);
+        }
+
+        let mut identity = None;
+        for (key, value) in &env_config.vars {
+            if !secret::is_encrypted(value) {
+                self.define(key, value, INLINE_VARS_SOURCE);
+                continue;
+            }
+
+            if identity.is_none() {
+                let dir = base_path.unwrap_or(std::path::Path::new("."));
+                identity = Some(secret::load_identity(dir)?.ok_or_else(|| {
+                    format!(
+                        "{} is encrypted but no key is available; set {} or {}, or create {}",
+                        key,
+                        secret::KEY_VAR,
+                        secret::KEY_FILE_VAR,
+                        secret::default_key_file(dir).display()
+                    )
+                })?);
+            }
+            let plaintext = secret::decrypt(value, identity.as_ref().unwrap())
+                .map_err(|e| format!("Failed to decrypt {}: {}", key, e))?;
+            self.define(key, &plaintext, INLINE_VARS_SOURCE);
+            self.secrets.insert(key.clone());
+        }
         Ok(())
     }
 
-    fn load_env_file(&self, file_path: &str) -> Result<usize, Box<dyn std::error::Error>> {
+    pub fn loaded_files(&self) -> &[std::path::PathBuf] {
+        &self.loaded_files
+    }
+
+    /// Masks `key`'s value in `redact` output from now on.
+    pub fn mark_secret(&mut self, key: &str) {
+        self.secrets.insert(key.to_string());
+    }
+
+    pub fn is_secret(&self, key: &str) -> bool {
+        self.secrets.contains(key)
+    }
+
+    /// `text` with the values of decrypted variables masked, for echoing
+    /// commands and listing variables.
+    pub fn redact(&self, text: &str) -> String {
+        let mut result = text.to_string();
+        for key in &self.secrets {
+            if let Some(value) = self.vars.get(key).filter(|v| !v.is_empty()) {
+                result = result.replace(value.as_str(), "********");
+            }
+        }
+        result
+    }
+
+    #[cfg_attr(not(feature = "dotenv"), allow(dead_code))]
+    fn load_env_file(&mut self, file_path: &str) -> Result<usize, Box<dyn std::error::Error>> {
         let file = File::open(file_path)?;
         let reader = BufReader::new(file);
         let mut count = 0;
 
         for line in reader.lines() {
             let line = line?;
-            let line = line.trim();
-
-            if line.is_empty() || line.starts_with('#') {
-                continue;
+            if let Some((key, value)) = parse_line(&line) {
+                self.define(key, value, file_path);
+                count += 1;
             }
+        }
 
-            if let Some(eq_pos) = line.find('=') {
-                let key = line[..eq_pos].trim();
-                let value = line[eq_pos + 1..].trim();
+        Ok(count)
+    }
 
-                let value = if (value.starts_with('"') && value.ends_with('"'))
-                    || (value.starts_with('\'') && value.ends_with('\''))
-                {
-                    &value[1..value.len() - 1]
-                } else {
-                    value
-                };
+    /// Runs `cmd` in `dir` and loads the `KEY=VALUE` lines it prints. The
+    /// values usually are credentials, so they are masked like decrypted ones.
+    fn load_command_output(
+        &mut self,
+        cmd: &str,
+        dir: &std::path::Path,
+    ) -> Result<usize, Box<dyn std::error::Error>> {
+        let parts: Vec<&str> = cmd.split_whitespace().collect();
+        let Some((program, args)) = parts.split_first() else {
+            return Ok(0);
+        };
 
-                unsafe {
-                    env::set_var(key, value);
-                }
-                count += 1;
-            }
+        let output = std::process::Command::new(program)
+            .args(args)
+            .current_dir(dir)
+            .stderr(std::process::Stdio::inherit())
+            .output()
+            .map_err(|e| format!("Failed to run env command '{}': {}", cmd, e))?;
+        if !output.status.success() {
+            return Err(format!("Env command '{}' failed with {}", cmd, output.status).into());
         }
 
-        Ok(count)
+        let source = format!("from_cmd: {}", cmd);
+        let vars = parse_dotenv(&String::from_utf8_lossy(&output.stdout));
+        for (key, value) in &vars {
+            self.define(key, value, &source);
+            self.mark_secret(key);
+        }
+        Ok(vars.len())
+    }
+
+    pub fn substitution(&self) -> Substitution {
+        self.config
+            .as_ref()
+            .map(|c| c.substitution)
+            .unwrap_or_default()
     }
 
     pub fn substitute_env_vars(&self, command: &str) -> String {
-        let mut result = command.to_string();
-
-        let mut start = 0;
-        while let Some(dollar_pos) = result[start..].find('$') {
-            let dollar_pos = start + dollar_pos;
-            let var_start = dollar_pos + 1;
-
-            let var_end = result[var_start..]
-                .find(|c: char| !c.is_alphanumeric() && c != '_')
-                .map(|pos| var_start + pos)
-                .unwrap_or(result.len());
-
-            if var_end > var_start {
-                let var_name = &result[var_start..var_end];
-
-                if let Ok(env_value) = env::var(var_name) {
-                    result.replace_range(dollar_pos..var_end, &env_value);
-                    start = dollar_pos + env_value.len();
-                } else {
-                    eprintln!("Warning: Environment variable '{}' not found", var_name);
-                    start = var_end;
+        self.substitute_env_vars_with(command, |value| value)
+    }
+
+    /// Like `substitute_env_vars`, inserting `splice(value)` for each value
+    /// found instead of the value itself.
+    pub fn substitute_env_vars_with(
+        &self,
+        command: &str,
+        mut splice: impl FnMut(String) -> String,
+    ) -> String {
+        let mut result = String::with_capacity(command.len());
+        let mut last = 0;
+
+        for placeholder in placeholders(command, self.substitution()) {
+            result.push_str(&command[last..placeholder.start]);
+            match self.get_env_var(placeholder.name) {
+                Some(value) => result.push_str(&splice(value)),
+                None => {
+                    tracing::warn!("Environment variable '{}' not found", placeholder.name);
+                    result.push_str(&command[placeholder.start..placeholder.end]);
                 }
-            } else {
-                start = dollar_pos + 1;
             }
+            last = placeholder.end;
         }
 
+        result.push_str(&command[last..]);
         result
     }
 
+    /// Names of the variables `command` references under this parser's
+    /// substitution mode, in order of first use.
+    pub fn referenced_vars(&self, command: &str) -> Vec<String> {
+        let mut names: Vec<String> = Vec::new();
+        for placeholder in placeholders(command, self.substitution()) {
+            if !names.iter().any(|n| n == placeholder.name) {
+                names.push(placeholder.name.to_string());
+            }
+        }
+        names
+    }
+
+    /// Problems with the loaded values according to `[env.schema]`.
+    pub fn schema_errors(&self) -> Vec<String> {
+        match &self.config {
+            Some(config) => schema::validate(&config.schema, |key| self.get_env_var(key)),
+            None => Vec::new(),
+        }
+    }
+
     pub fn get_env_var(&self, key: &str) -> Option<String> {
-        env::var(key).ok()
+        self.vars.get(key).cloned().or_else(|| env::var(key).ok())
+    }
+
+    pub fn set_env_var(&mut self, key: &str, value: &str) {
+        self.define(key, value, "runner");
     }
 
-    pub fn set_env_var(&self, key: &str, value: &str) {
-        unsafe {
-            env::set_var(key, value);
+    /// Sets `key`, recording `source` and any different value it replaces.
+    pub fn define(&mut self, key: &str, value: &str, source: &str) {
+        if let Some(previous) = self.vars.get(key) {
+            if previous != value {
+                self.shadowed.push(Shadowed {
+                    key: key.to_string(),
+                    value: previous.clone(),
+                    source: self.sources.get(key).cloned().unwrap_or_default(),
+                    by: source.to_string(),
+                });
+            }
         }
+        self.vars.insert(key.to_string(), value.to_string());
+        self.sources.insert(key.to_string(), source.to_string());
+    }
+
+    pub fn source(&self, key: &str) -> Option<&str> {
+        self.sources.get(key).map(String::as_str)
+    }
+
+    /// Values replaced by a later definition, in the order that happened.
+    pub fn shadowed(&self) -> &[Shadowed] {
+        &self.shadowed
+    }
+
+    /// Variables loaded by this parser, without the inherited process env.
+    pub fn vars(&self) -> &HashMap<String, String> {
+        &self.vars
     }
 
     pub fn list_env_vars(&self) -> HashMap<String, String> {
-        env::vars().collect()
+        let mut all: HashMap<String, String> = env::vars().collect();
+        all.extend(self.vars.clone());
+        all
+    }
+}
+
+/// Parses dotenv-formatted text, e.g. piped from a secrets manager, into
+/// pairs in order of appearance.
+pub fn parse_dotenv(text: &str) -> Vec<(String, String)> {
+    text.lines()
+        .filter_map(parse_line)
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect()
+}
+
+/// Parses one dotenv line. A leading `export ` is accepted so shell-style
+/// output such as `aws configure export-credentials --format env` loads as-is.
+fn parse_line(line: &str) -> Option<(&str, &str)> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return None;
+    }
+    let line = line.strip_prefix("export ").unwrap_or(line);
+    let (key, value) = line.split_once('=')?;
+    let value = value.trim();
+
+    let value = if value.len() >= 2
+        && ((value.starts_with('"') && value.ends_with('"'))
+            || (value.starts_with('\'') && value.ends_with('\'')))
+    {
+        &value[1..value.len() - 1]
+    } else {
+        value
+    };
+    Some((key.trim(), value))
+}
+
+/// A POSIX variable name: ASCII letters, digits and `_`, not starting with
+/// a digit.
+fn is_var_name(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// One `$VAR`, `${VAR}` or `{{ VAR }}` occurrence; `start..end` spans the whole token.
+struct Placeholder<'a> {
+    start: usize,
+    end: usize,
+    name: &'a str,
+}
+
+fn placeholders(text: &str, mode: Substitution) -> Vec<Placeholder<'_>> {
+    let mut found = Vec::new();
+    let mut start = 0;
+
+    while let Some(pos) = text[start..].find(['$', '{']) {
+        let pos = start + pos;
+
+        if text[pos..].starts_with("{{") {
+            if !mode.braces() {
+                start = pos + 2;
+                continue;
+            }
+            let Some(close) = text[pos + 2..].find("}}") else {
+                break;
+            };
+            let inner_end = pos + 2 + close;
+            let name = text[pos + 2..inner_end].trim();
+            if !name.is_empty() && name.chars().all(|c| c.is_alphanumeric() || c == '_') {
+                found.push(Placeholder {
+                    start: pos,
+                    end: inner_end + 2,
+                    name,
+                });
+            }
+            start = inner_end + 2;
+        } else if text[pos..].starts_with('$') && mode.dollar() {
+            // `${VAR}` or `$VAR`; anything else after `$`, such as `$1`,
+            // `$$` or an unterminated `${`, is left alone.
+            let braced = text[pos + 1..].starts_with('{');
+            let var_start = pos + 1 + braced as usize;
+            let var_end = text[var_start..]
+                .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                .map(|p| var_start + p)
+                .unwrap_or(text.len());
+            let name = &text[var_start..var_end];
+            let closed = !braced || text[var_end..].starts_with('}');
+            if is_var_name(name) && closed {
+                let end = var_end + braced as usize;
+                found.push(Placeholder {
+                    start: pos,
+                    end,
+                    name,
+                });
+                start = end;
+            } else {
+                start = pos + 1;
+            }
+        } else {
+            start = pos + 1;
+        }
     }
+
+    found
 }
 
 impl Default for EnvParser {
@@ -150,7 +507,7 @@ mod tests {
 
     #[test]
     fn test_substitute_env_vars() {
-        let parser = EnvParser::new();
+        let mut parser = EnvParser::new();
 
         parser.set_env_var("TEST_VAR", "test_value");
 
@@ -166,9 +523,92 @@ mod tests {
         assert_eq!(result, "Hello $MISSING_VAR world");
     }
 
+    #[test]
+    fn test_substitute_edge_cases() {
+        let mut parser = EnvParser::new();
+        parser.set_env_var("NAME", "wörld");
+
+        assert_eq!(parser.substitute_env_vars("${NAME}s"), "wörlds");
+        assert_eq!(parser.substitute_env_vars("日本$NAME語"), "日本wörld語");
+        assert_eq!(
+            parser.substitute_env_vars("$1 $$ $ 5$ ${NAME"),
+            "$1 $$ $ 5$ ${NAME"
+        );
+        assert_eq!(parser.substitute_env_vars("${}$NAMEé"), "${}wörldé");
+        assert_eq!(
+            parser.referenced_vars("$NAME ${OTHER} $9"),
+            vec!["NAME", "OTHER"]
+        );
+    }
+
+    #[test]
+    fn test_substitute_braces_only() {
+        let mut parser = EnvParser::with_config(EnvConfig {
+            substitution: Substitution::Braces,
+            ..Default::default()
+        });
+
+        parser.set_env_var("BRACES_VAR", "value");
+
+        let result = parser.substitute_env_vars("for f in *; do echo $f {{ BRACES_VAR }}; done");
+        assert_eq!(result, "for f in *; do echo $f value; done");
+    }
+
+    #[test]
+    fn test_substitute_both() {
+        let mut parser = EnvParser::with_config(EnvConfig {
+            substitution: Substitution::Both,
+            ..Default::default()
+        });
+
+        parser.set_env_var("BOTH_VAR", "x");
+
+        let result = parser.substitute_env_vars("$BOTH_VAR-{{BOTH_VAR}}-{{not a var}}");
+        assert_eq!(result, "x-x-{{not a var}}");
+    }
+
+    #[test]
+    fn test_referenced_vars() {
+        let parser = EnvParser::with_config(EnvConfig {
+            substitution: Substitution::Both,
+            ..Default::default()
+        });
+        assert_eq!(
+            parser.referenced_vars("deploy $REGION {{ TOKEN }} $REGION {{flags.x}} $"),
+            vec!["REGION".to_string(), "TOKEN".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_from_cmd() {
+        let mut parser = EnvParser::with_config(EnvConfig {
+            from_cmd: vec!["echo export AWS_ACCESS_KEY_ID=AKIA123".to_string()],
+            vars: HashMap::from([("REGION".to_string(), "eu-west-1".to_string())]),
+            ..Default::default()
+        });
+        parser.load_env_files_with_base_path(None).unwrap();
+
+        assert_eq!(
+            parser.get_env_var("AWS_ACCESS_KEY_ID"),
+            Some("AKIA123".to_string())
+        );
+        assert_eq!(
+            parser.source("AWS_ACCESS_KEY_ID"),
+            Some("from_cmd: echo export AWS_ACCESS_KEY_ID=AKIA123")
+        );
+        assert!(parser.is_secret("AWS_ACCESS_KEY_ID"));
+        assert!(!parser.is_secret("REGION"));
+
+        let mut failing = EnvParser::with_config(EnvConfig {
+            from_cmd: vec!["false".to_string()],
+            ..Default::default()
+        });
+        assert!(failing.load_env_files_with_base_path(None).is_err());
+    }
+
     #[test]
     fn test_load_env_file() {
-        let parser = EnvParser::new();
+        let mut parser = EnvParser::new();
 
         let env_content = "TEST_KEY=test_value\n# This is a comment\nANOTHER_KEY=another_value\n";
         let mut file = fs::File::create("test.env").unwrap();
@@ -194,9 +634,62 @@ mod tests {
     fn test_env_config() {
         let config = EnvConfig {
             files: vec![".env".to_string(), ".env.local".to_string()],
+            ..Default::default()
         };
 
         let parser = EnvParser::with_config(config);
         assert!(parser.config.is_some());
     }
+
+    #[test]
+    fn test_shadowed_sources() {
+        let mut parser = EnvParser::with_config(EnvConfig {
+            vars: HashMap::from([("PORT".to_string(), "8080".to_string())]),
+            ..Default::default()
+        });
+        let dir = std::env::temp_dir().join(format!("env-parser-shadow-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("shadow.env");
+        fs::write(&file, "PORT=3000\nHOST=localhost\n").unwrap();
+        parser.load_env_file(file.to_str().unwrap()).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        parser.load_env_files().unwrap();
+        parser.define("HOST", "localhost", "-e");
+
+        assert_eq!(parser.get_env_var("PORT"), Some("8080".to_string()));
+        assert_eq!(parser.source("PORT"), Some(INLINE_VARS_SOURCE));
+        assert_eq!(parser.source("HOST"), Some("-e"));
+        assert_eq!(
+            parser.shadowed(),
+            &[Shadowed {
+                key: "PORT".to_string(),
+                value: "3000".to_string(),
+                source: file.display().to_string(),
+                by: INLINE_VARS_SOURCE.to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_layered_scopes() {
+        let mut parent = EnvParser::new();
+        parent.set_env_var("SHARED", "parent");
+        parent.set_env_var("PARENT_ONLY", "1");
+
+        let mut child = EnvParser::with_config(EnvConfig {
+            vars: HashMap::from([("SHARED".to_string(), "child".to_string())]),
+            export: vec!["SHARED".to_string(), "MISSING".to_string()],
+            ..Default::default()
+        });
+        child.load_env_files().unwrap();
+
+        let scoped = parent.layered(&child);
+        assert_eq!(scoped.get_env_var("SHARED"), Some("child".to_string()));
+        assert_eq!(scoped.get_env_var("PARENT_ONLY"), Some("1".to_string()));
+        assert_eq!(parent.get_env_var("SHARED"), Some("parent".to_string()));
+        assert_eq!(
+            child.exported_vars(),
+            vec![("SHARED".to_string(), "child".to_string())]
+        );
+    }
 }
@@ -0,0 +1,139 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum VarType {
+    #[default]
+    String,
+    Int,
+    Bool,
+}
+
+/// One `[env.schema]` entry, e.g. `PORT = { type = "int", min = 1024 }`.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct VarSchema {
+    #[serde(rename = "type", default)]
+    pub kind: VarType,
+    /// The variable must be set.
+    #[serde(default)]
+    pub required: bool,
+    pub min: Option<i64>,
+    pub max: Option<i64>,
+    /// Allowed values.
+    #[serde(rename = "enum")]
+    pub allowed: Option<Vec<String>>,
+}
+
+impl VarSchema {
+    /// Why `value` does not satisfy this entry, if it does not.
+    pub fn check(&self, value: &str) -> Option<String> {
+        match self.kind {
+            VarType::String => {}
+            VarType::Bool => {
+                if !matches!(value, "true" | "false" | "1" | "0") {
+                    return Some(format!("expected a bool, got '{}'", value));
+                }
+            }
+            VarType::Int => {
+                let Ok(number) = value.parse::<i64>() else {
+                    return Some(format!("expected an int, got '{}'", value));
+                };
+                if let Some(min) = self.min.filter(|min| number < *min) {
+                    return Some(format!("{} is below the minimum of {}", number, min));
+                }
+                if let Some(max) = self.max.filter(|max| number > *max) {
+                    return Some(format!("{} is above the maximum of {}", number, max));
+                }
+            }
+        }
+
+        match &self.allowed {
+            Some(allowed) if !allowed.iter().any(|a| a == value) => {
+                Some(format!("'{}' is not one of {}", value, allowed.join(", ")))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Checks every schema entry against `lookup`, returning one message per
+/// problem, sorted by variable name.
+pub fn validate<F>(schema: &HashMap<String, VarSchema>, lookup: F) -> Vec<String>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    let mut names: Vec<&String> = schema.keys().collect();
+    names.sort();
+
+    names
+        .into_iter()
+        .filter_map(|name| {
+            let entry = &schema[name];
+            match lookup(name) {
+                Some(value) => entry.check(&value).map(|e| format!("{}: {}", name, e)),
+                None if entry.required => Some(format!("{}: is required but not set", name)),
+                None => None,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_aggregates_errors() {
+        let schema: HashMap<String, VarSchema> = schema_of(&[
+            (
+                "PORT",
+                VarSchema {
+                    kind: VarType::Int,
+                    min: Some(1024),
+                    ..Default::default()
+                },
+            ),
+            (
+                "MODE",
+                VarSchema {
+                    allowed: Some(vec!["dev".into(), "prod".into()]),
+                    ..Default::default()
+                },
+            ),
+            (
+                "TOKEN",
+                VarSchema {
+                    required: true,
+                    ..Default::default()
+                },
+            ),
+            (
+                "DEBUG",
+                VarSchema {
+                    kind: VarType::Bool,
+                    ..Default::default()
+                },
+            ),
+        ]);
+        let values = HashMap::from([("PORT", "80"), ("MODE", "staging"), ("DEBUG", "1")]);
+
+        let errors = validate(&schema, |name| values.get(name).map(|v| v.to_string()));
+        assert_eq!(
+            errors,
+            vec![
+                "MODE: 'staging' is not one of dev, prod",
+                "PORT: 80 is below the minimum of 1024",
+                "TOKEN: is required but not set",
+            ]
+        );
+    }
+
+    fn schema_of(entries: &[(&str, VarSchema)]) -> HashMap<String, VarSchema> {
+        entries
+            .iter()
+            .map(|(name, schema)| (name.to_string(), schema.clone()))
+            .collect()
+    }
+}
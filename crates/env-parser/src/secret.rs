@@ -0,0 +1,108 @@
+use age::secrecy::ExposeSecret;
+use age::x25519::{Identity, Recipient};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Marks an `[env.vars]` value as age-encrypted, base64-encoded ciphertext.
+pub const PREFIX: &str = "enc:";
+
+/// Environment variable holding the project's age identity
+/// (`AGE-SECRET-KEY-1...`).
+pub const KEY_VAR: &str = "TASKFILE_AGE_KEY";
+
+/// Environment variable pointing at a keyfile, as written by `age-keygen`.
+pub const KEY_FILE_VAR: &str = "TASKFILE_AGE_KEY_FILE";
+
+pub fn is_encrypted(value: &str) -> bool {
+    value.starts_with(PREFIX)
+}
+
+/// Keyfile used when neither variable is set.
+pub fn default_key_file(project_dir: &Path) -> PathBuf {
+    project_dir.join(".taskfile").join("age.key")
+}
+
+/// Finds the project identity: `$TASKFILE_AGE_KEY`, then the file named by
+/// `$TASKFILE_AGE_KEY_FILE`, then `.taskfile/age.key`.
+pub fn load_identity(project_dir: &Path) -> Result<Option<Identity>, String> {
+    if let Ok(key) = env::var(KEY_VAR) {
+        return parse_identity(&key, KEY_VAR).map(Some);
+    }
+
+    let path = match env::var(KEY_FILE_VAR) {
+        Ok(path) => PathBuf::from(path),
+        Err(_) => {
+            let path = default_key_file(project_dir);
+            if !path.exists() {
+                return Ok(None);
+            }
+            path
+        }
+    };
+    let contents = fs::read_to_string(&path)
+        .map_err(|e| format!("Failed to read age key {}: {}", path.display(), e))?;
+    parse_identity(&contents, &path.display().to_string()).map(Some)
+}
+
+/// Parses the first `AGE-SECRET-KEY-` line of `contents`, skipping the
+/// comments `age-keygen` writes.
+fn parse_identity(contents: &str, origin: &str) -> Result<Identity, String> {
+    contents
+        .lines()
+        .map(str::trim)
+        .find(|line| line.starts_with("AGE-SECRET-KEY-"))
+        .ok_or_else(|| format!("No AGE-SECRET-KEY found in {}", origin))?
+        .parse()
+        .map_err(|e| format!("Invalid age key in {}: {}", origin, e))
+}
+
+pub fn decrypt(value: &str, identity: &Identity) -> Result<String, String> {
+    let encoded = value.strip_prefix(PREFIX).unwrap_or(value);
+    let ciphertext = STANDARD
+        .decode(encoded.trim())
+        .map_err(|e| format!("not valid base64: {}", e))?;
+    let plaintext = age::decrypt(identity, &ciphertext).map_err(|e| e.to_string())?;
+    String::from_utf8(plaintext).map_err(|_| "decrypted value is not UTF-8".to_string())
+}
+
+/// Encrypts `plaintext` to `recipient` as an `enc:` value.
+pub fn encrypt(plaintext: &str, recipient: &Recipient) -> Result<String, String> {
+    let ciphertext = age::encrypt(recipient, plaintext.as_bytes()).map_err(|e| e.to_string())?;
+    Ok(format!("{}{}", PREFIX, STANDARD.encode(ciphertext)))
+}
+
+/// The public half of `identity`, which is all `encrypt` needs.
+pub fn recipient_of(identity: &Identity) -> Recipient {
+    identity.to_public()
+}
+
+/// Generates a new identity in the `age-keygen` file format.
+pub fn generate_key_file() -> String {
+    let identity = Identity::generate();
+    format!(
+        "# public key: {}\n{}\n",
+        identity.to_public(),
+        identity.to_string().expose_secret()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip_through_key_file() {
+        let key_file = generate_key_file();
+        let identity = parse_identity(&key_file, "test").unwrap();
+
+        let value = encrypt("s3cret", &recipient_of(&identity)).unwrap();
+        assert!(is_encrypted(&value));
+        assert_eq!(decrypt(&value, &identity).unwrap(), "s3cret");
+
+        let other = parse_identity(&generate_key_file(), "test").unwrap();
+        assert!(decrypt(&value, &other).is_err());
+    }
+}
@@ -0,0 +1,61 @@
+use crate::output_cache::fnv1a;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// The cache key each task last succeeded with, under `.taskfile/cache/keys`.
+/// A task whose rendered `cache_key` matches the recorded one is up to date.
+#[derive(Debug, Clone)]
+pub struct KeyStore {
+    dir: PathBuf,
+}
+
+impl KeyStore {
+    pub fn new(project_dir: &Path) -> Self {
+        Self {
+            dir: project_dir.join(".taskfile").join("cache").join("keys"),
+        }
+    }
+
+    /// The fingerprint of each task's inputs at its last success, under
+    /// `.taskfile/cache/sources`, for tasks that declare `sources`.
+    pub fn for_sources(project_dir: &Path) -> Self {
+        Self {
+            dir: project_dir.join(".taskfile").join("cache").join("sources"),
+        }
+    }
+
+    pub fn is_current(&self, task_name: &str, key: &str) -> bool {
+        fs::read_to_string(self.path(task_name)).is_ok_and(|recorded| recorded == key)
+    }
+
+    pub fn record(&self, task_name: &str, key: &str) -> io::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        fs::write(self.path(task_name), key)
+    }
+
+    fn path(&self, task_name: &str) -> PathBuf {
+        self.dir
+            .join(format!("{:016x}", fnv1a(task_name.as_bytes())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_store() {
+        let dir = std::env::temp_dir().join(format!("taskfile-keys-{}", std::process::id()));
+        let store = KeyStore::new(&dir);
+
+        assert!(!store.is_current("build", "abc-release"));
+        store.record("build", "abc-release").unwrap();
+        assert!(store.is_current("build", "abc-release"));
+        assert!(!store.is_current("build", "abc-debug"));
+        assert!(!store.is_current("test", "abc-release"));
+        assert!(!KeyStore::for_sources(&dir).is_current("build", "abc-release"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,141 @@
+use crate::output_cache::fnv1a;
+use std::backtrace::Backtrace;
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::panic::{self, PanicHookInfo};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Where the issue tracker is, for the note printed after a crash.
+const ISSUES_URL: &str = "https://github.com/lassejlv/taskfile/issues";
+
+/// Stands in for an argument left out of a report.
+const REDACTED: &str = "<redacted>";
+
+/// The Taskfile in use, so a report can say which one (by hash) was loaded.
+static TASKFILE: OnceLock<PathBuf> = OnceLock::new();
+
+/// Installs a panic hook that, after the usual panic message, writes a crash
+/// report to the temp directory and prints its path. Nothing is sent
+/// anywhere. `value_options` lists the options whose value is a separate
+/// argument, so that value is left out of the report like any other.
+pub fn install(version: &'static str, args: &[String], value_options: &[&str]) {
+    let args = sanitize_args(args, value_options);
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        default_hook(info);
+        let report = render(version, &args, info, &Backtrace::force_capture());
+        match write(&report) {
+            Ok(path) => eprintln!(
+                "\ntaskfile crashed. A report was written to {}\nPlease attach it to an issue at {}. It holds the backtrace, the command line with values removed, and a hash of the Taskfile, but no variables or task output.",
+                path.display(),
+                ISSUES_URL
+            ),
+            Err(e) => eprintln!("\ntaskfile crashed, and writing a crash report failed: {}", e),
+        }
+    }));
+}
+
+/// Records the Taskfile the invocation uses; only the first call counts.
+pub fn set_taskfile(path: &Path) {
+    let _ = TASKFILE.set(path.to_path_buf());
+}
+
+/// The command line with anything that might be private removed: option
+/// values, every positional argument but the first (the command or task
+/// name), and everything after `--`.
+fn sanitize_args(args: &[String], value_options: &[&str]) -> String {
+    let mut words = vec!["task".to_string()];
+    let mut kept_positional = false;
+    let mut rest = args.iter().skip(1);
+    while let Some(arg) = rest.next() {
+        if arg == "--" {
+            words.push(arg.clone());
+            words.extend(rest.by_ref().map(|_| REDACTED.to_string()));
+        } else if let Some((option, _)) = arg.split_once('=').filter(|_| arg.starts_with('-')) {
+            words.push(format!("{}={}", option, REDACTED));
+        } else if arg.starts_with('-') {
+            words.push(arg.clone());
+            if value_options.contains(&arg.as_str()) && rest.next().is_some() {
+                words.push(REDACTED.to_string());
+            }
+        } else if !kept_positional {
+            kept_positional = true;
+            words.push(arg.clone());
+        } else {
+            words.push(REDACTED.to_string());
+        }
+    }
+    words.join(" ")
+}
+
+fn render(version: &str, args: &str, info: &PanicHookInfo<'_>, backtrace: &Backtrace) -> String {
+    let message = info
+        .payload()
+        .downcast_ref::<&str>()
+        .map(|s| s.to_string())
+        .or_else(|| info.payload().downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "(no message)".to_string());
+    let location = info
+        .location()
+        .map(|l| format!("{}:{}:{}", l.file(), l.line(), l.column()))
+        .unwrap_or_else(|| "unknown".to_string());
+    let taskfile = match TASKFILE.get() {
+        Some(path) => match fs::read(path) {
+            Ok(contents) => format!("{:016x}", fnv1a(&contents)),
+            Err(_) => "not readable".to_string(),
+        },
+        None => "not loaded".to_string(),
+    };
+
+    let mut report = String::new();
+    let _ = writeln!(report, "taskfile {} crash report", version);
+    let _ = writeln!(
+        report,
+        "platform: {} {}",
+        std::env::consts::OS,
+        std::env::consts::ARCH
+    );
+    let _ = writeln!(report, "command: {}", args);
+    let _ = writeln!(report, "taskfile hash: {}", taskfile);
+    let _ = writeln!(report, "panic: {}", message);
+    let _ = writeln!(report, "at: {}", location);
+    let _ = writeln!(report, "\nbacktrace:\n{}", backtrace);
+    report
+}
+
+fn write(report: &str) -> io::Result<PathBuf> {
+    let dir = std::env::temp_dir().join("taskfile-crashes");
+    fs::create_dir_all(&dir)?;
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let path = dir.join(format!("crash-{}-{}.txt", secs, std::process::id()));
+    fs::write(&path, report)?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sanitize_args() {
+        let args =
+            |line: &str| -> Vec<String> { line.split_whitespace().map(String::from).collect() };
+        let sanitize = |line: &str| sanitize_args(&args(line), &["-e", "--from"]);
+
+        assert_eq!(sanitize("/home/me/bin/task build"), "task build");
+        assert_eq!(
+            sanitize("task -e TOKEN=abc --plain deploy --target prod"),
+            "task -e <redacted> --plain deploy --target <redacted>"
+        );
+        assert_eq!(
+            sanitize("task --bell=1m run build -- --password hunter2"),
+            "task --bell=<redacted> run <redacted> -- <redacted> <redacted>"
+        );
+    }
+}
@@ -0,0 +1,929 @@
+//! `task daemon`: keeps the Taskfile loaded and a pool of warm `sh` workers
+//! running, so a task invoked over and over, such as a format-on-save hook,
+//! skips loading the Taskfile and its env files and starting a shell.
+//! `task --warm <task>` hands the run to it over a Unix socket in
+//! `.taskfile`. A worker runs the script `export_script` would write for the
+//! run, in a subshell, so one job cannot change the next one's directory or
+//! variables.
+
+use crate::duration::parse_duration;
+use crate::process::{self, Tree};
+use crate::TaskRunner;
+use std::collections::VecDeque;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{UnixListener, UnixStream};
+use tokio::process::{ChildStderr, ChildStdin, ChildStdout, Command};
+use tokio::sync::{mpsc, Notify, RwLock, Semaphore};
+use tokio::time::{Duration, Instant};
+
+/// How long the daemon may take to hand a job to a warm worker, from the
+/// whole request being read to the script reaching the worker's shell.
+/// `status` reports how many recent jobs met it.
+pub const DISPATCH_TARGET: Duration = Duration::from_millis(5);
+
+/// How many of the most recent dispatch times `status` summarizes.
+const LATENCY_WINDOW: usize = 1000;
+
+/// The socket of the daemon serving the Taskfile at `taskfile`, in the
+/// `.taskfile` directory next to it.
+pub fn socket_path(taskfile: &Path) -> PathBuf {
+    crate::base_dir(taskfile.parent())
+        .join(".taskfile")
+        .join("daemon.sock")
+}
+
+/// How many workers the pool keeps, and when it lets one go.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Config {
+    /// At most this many workers, busy or idle; further jobs wait for one.
+    pub workers: usize,
+    /// A worker left idle this long is stopped.
+    pub idle_timeout: Duration,
+    /// A worker is replaced after this many jobs, to bound whatever state
+    /// a job leaves behind outside its subshell.
+    pub max_jobs: u32,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            workers: 4,
+            idle_timeout: Duration::from_secs(5 * 60),
+            max_jobs: 100,
+        }
+    }
+}
+
+impl Config {
+    /// `[--workers N] [--idle DURATION] [--max-jobs N]`, each defaulting to
+    /// `Config::default()`.
+    pub fn parse(args: &[String]) -> Result<Self, String> {
+        let mut config = Self::default();
+        let mut rest = args.iter();
+        while let Some(arg) = rest.next() {
+            let value = rest.next().map(String::as_str);
+            match arg.as_str() {
+                "--workers" => {
+                    config.workers = value
+                        .and_then(|n| n.parse().ok())
+                        .filter(|&n| n > 0)
+                        .ok_or("--workers expects a positive number")?;
+                }
+                "--max-jobs" => {
+                    config.max_jobs = value
+                        .and_then(|n| n.parse().ok())
+                        .filter(|&n| n > 0)
+                        .ok_or("--max-jobs expects a positive number")?;
+                }
+                "--idle" => {
+                    let value = value.ok_or("--idle expects a duration, e.g. 5m")?;
+                    config.idle_timeout = parse_duration(value)?;
+                }
+                _ => {
+                    return Err(
+                        "Usage: task daemon [--workers N] [--idle DURATION] [--max-jobs N] | task daemon status | task daemon stop"
+                            .to_string(),
+                    )
+                }
+            }
+        }
+        Ok(config)
+    }
+}
+
+/// Which of a job's streams output came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stream {
+    Stdout,
+    Stderr,
+}
+
+/// What the pool has done since the daemon started.
+#[derive(Debug, Clone, Default)]
+pub struct Stats {
+    pub jobs: u64,
+    /// Jobs that found a warm worker waiting, rather than starting one.
+    pub warm: u64,
+    /// Workers stopped after sitting idle for `Config::idle_timeout`.
+    pub evicted: u64,
+    /// Workers replaced after `Config::max_jobs` jobs.
+    pub retired: u64,
+    /// The most recent dispatch times, oldest first.
+    dispatch: VecDeque<Duration>,
+}
+
+impl Stats {
+    /// The `percent`th percentile of the recent dispatch times.
+    pub fn dispatch_percentile(&self, percent: usize) -> Option<Duration> {
+        let mut sorted: Vec<Duration> = self.dispatch.iter().copied().collect();
+        sorted.sort();
+        let last = sorted.len().checked_sub(1)?;
+        Some(sorted[last * percent.min(100) / 100])
+    }
+
+    /// How many of the recent jobs were dispatched within
+    /// `DISPATCH_TARGET`, and how many there were.
+    pub fn within_target(&self) -> (usize, usize) {
+        let met = self
+            .dispatch
+            .iter()
+            .filter(|&&d| d <= DISPATCH_TARGET)
+            .count();
+        (met, self.dispatch.len())
+    }
+
+    fn record(&mut self, warm: bool, dispatch: Duration) {
+        self.jobs += 1;
+        self.warm += u64::from(warm);
+        if self.dispatch.len() == LATENCY_WINDOW {
+            self.dispatch.pop_front();
+        }
+        self.dispatch.push_back(dispatch);
+    }
+}
+
+/// A finished job.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Job {
+    pub code: i32,
+    /// Whether it ran on a worker that was already waiting.
+    pub warm: bool,
+    /// From the request being read to the script reaching the worker.
+    pub dispatch: Duration,
+}
+
+/// Warm workers, at most `Config::workers` of them, each running one job at
+/// a time.
+pub struct Pool {
+    config: Config,
+    slots: Semaphore,
+    /// The most recently used last, which is the one handed out next, so
+    /// the others age towards eviction when there is little to do.
+    idle: Mutex<Vec<Worker>>,
+    next_id: AtomicU64,
+    stats: Mutex<Stats>,
+}
+
+impl Pool {
+    pub fn new(config: Config) -> Self {
+        Self {
+            slots: Semaphore::new(config.workers),
+            config,
+            idle: Mutex::new(Vec::new()),
+            next_id: AtomicU64::new(0),
+            stats: Mutex::new(Stats::default()),
+        }
+    }
+
+    /// Runs `script` on an idle worker, or on a new one while the pool has
+    /// room, waiting for a worker otherwise. `received` is when the request
+    /// arrived, for the dispatch time. A worker whose job is cancelled, by
+    /// dropping the future, is stopped along with everything it started.
+    pub async fn run(
+        &self,
+        script: &str,
+        received: Instant,
+        output: &mut (dyn FnMut(Stream, &[u8]) + Send),
+    ) -> io::Result<Job> {
+        let _slot = self.slots.acquire().await.map_err(io::Error::other)?;
+        let idle = self.idle.lock().unwrap().pop();
+        let warm = idle.is_some();
+        let mut worker = match idle {
+            Some(worker) => worker,
+            None => Worker::spawn(self.next_id.fetch_add(1, Ordering::Relaxed))?,
+        };
+        worker.send(script).await?;
+        let dispatch = received.elapsed();
+        self.stats.lock().unwrap().record(warm, dispatch);
+        if dispatch > DISPATCH_TARGET {
+            tracing::debug!(
+                "Dispatch took {:?}, over the {:?} target",
+                dispatch,
+                DISPATCH_TARGET
+            );
+        }
+
+        let finished = worker.finish(output).await?;
+        worker.jobs += 1;
+        match finished {
+            Finished::Exited(code) if worker.jobs < self.config.max_jobs => {
+                worker.idle_since = Instant::now();
+                self.idle.lock().unwrap().push(worker);
+                Ok(Job {
+                    code,
+                    warm,
+                    dispatch,
+                })
+            }
+            Finished::Exited(code) => {
+                self.stats.lock().unwrap().retired += 1;
+                Ok(Job {
+                    code,
+                    warm,
+                    dispatch,
+                })
+            }
+            // The shell itself exited, as it does on a syntax error.
+            Finished::Lost(code) => Ok(Job {
+                code,
+                warm,
+                dispatch,
+            }),
+        }
+    }
+
+    /// Stops the workers idle for `Config::idle_timeout` or longer.
+    pub fn evict_idle(&self) {
+        let mut idle = self.idle.lock().unwrap();
+        let before = idle.len();
+        idle.retain(|worker| worker.idle_since.elapsed() < self.config.idle_timeout);
+        self.stats.lock().unwrap().evicted += (before - idle.len()) as u64;
+    }
+
+    /// Stops every idle worker.
+    pub fn clear(&self) {
+        self.idle.lock().unwrap().clear();
+    }
+
+    pub fn idle_workers(&self) -> usize {
+        self.idle.lock().unwrap().len()
+    }
+
+    pub fn stats(&self) -> Stats {
+        self.stats.lock().unwrap().clone()
+    }
+}
+
+enum Finished {
+    Exited(i32),
+    /// The worker's shell exited during the job, with this code.
+    Lost(i32),
+}
+
+/// A `sh` reading jobs from its stdin, one after another, in its own
+/// process group. Dropping it kills the group.
+struct Worker {
+    tree: Tree,
+    stdin: ChildStdin,
+    stdout: ChildStdout,
+    stderr: ChildStderr,
+    /// Unique to the worker; printed after each job to mark the end of its
+    /// output.
+    token: String,
+    jobs: u32,
+    idle_since: Instant,
+}
+
+impl Worker {
+    fn spawn(id: u64) -> io::Result<Self> {
+        let mut cmd = Command::new("sh");
+        cmd.arg("-s")
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped())
+            .kill_on_drop(true);
+        let mut tree = process::spawn(&mut cmd)?;
+        let child = &mut tree.child;
+        let (Some(stdin), Some(stdout), Some(stderr)) =
+            (child.stdin.take(), child.stdout.take(), child.stderr.take())
+        else {
+            return Err(io::Error::other("the worker's pipes are missing"));
+        };
+        Ok(Self {
+            tree,
+            stdin,
+            stdout,
+            stderr,
+            token: format!("taskfile-{}-{}", std::process::id(), id),
+            jobs: 0,
+            idle_since: Instant::now(),
+        })
+    }
+
+    /// Hands `script` to the shell: in a subshell without stdin, followed
+    /// by the end markers, the one on stdout with the exit code.
+    async fn send(&mut self, script: &str) -> io::Result<()> {
+        let job = format!(
+            "(\n{}\n) </dev/null\nprintf '\\036%s %d\\n' {token} \"$?\"\nprintf '\\036%s\\n' {token} >&2\n",
+            script,
+            token = self.token
+        );
+        self.stdin.write_all(job.as_bytes()).await?;
+        self.stdin.flush().await
+    }
+
+    /// Passes the job's output on as it arrives, until both end markers.
+    async fn finish(
+        &mut self,
+        output: &mut (dyn FnMut(Stream, &[u8]) + Send),
+    ) -> io::Result<Finished> {
+        let mut stdout = Split::new(format!("\x1e{} ", self.token));
+        let mut stderr = Split::new(format!("\x1e{}", self.token));
+        let (mut out, mut err) = ([0u8; 8192], [0u8; 8192]);
+        let mut code: Option<i32> = None;
+        let mut stderr_done = false;
+        while code.is_none() || !stderr_done {
+            tokio::select! {
+                read = self.stdout.read(&mut out), if code.is_none() => {
+                    let n = read?;
+                    if n == 0 {
+                        return self.lost(output).await;
+                    }
+                    if let Some(rest) = stdout.push(&out[..n], &mut |b| output(Stream::Stdout, b)) {
+                        code = Some(rest.trim().parse().map_err(io::Error::other)?);
+                    }
+                }
+                read = self.stderr.read(&mut err), if !stderr_done => {
+                    let n = read?;
+                    if n == 0 {
+                        return self.lost(output).await;
+                    }
+                    stderr_done = stderr.push(&err[..n], &mut |b| output(Stream::Stderr, b)).is_some();
+                }
+            }
+        }
+        Ok(Finished::Exited(code.unwrap_or_default()))
+    }
+
+    /// What is left of the output of a shell that exited, and its code.
+    async fn lost(
+        &mut self,
+        output: &mut (dyn FnMut(Stream, &[u8]) + Send),
+    ) -> io::Result<Finished> {
+        let mut rest = Vec::new();
+        self.stderr.read_to_end(&mut rest).await?;
+        output(Stream::Stderr, &rest);
+        let status = self.tree.child.wait().await?;
+        Ok(Finished::Lost(status.code().unwrap_or(1)))
+    }
+}
+
+impl Drop for Worker {
+    fn drop(&mut self) {
+        if let Some(pid) = self.tree.child.id() {
+            // SAFETY: signals the worker's own process group.
+            unsafe {
+                libc::kill(-(pid as libc::pid_t), libc::SIGKILL);
+            }
+        }
+    }
+}
+
+/// Splits a worker's stream at the marker printed after a job.
+struct Split {
+    marker: Vec<u8>,
+    /// Output that could be the start of the marker.
+    pending: Vec<u8>,
+    /// What followed the marker, once it was seen.
+    after: Option<Vec<u8>>,
+}
+
+impl Split {
+    fn new(marker: String) -> Self {
+        Self {
+            marker: marker.into_bytes(),
+            pending: Vec::new(),
+            after: None,
+        }
+    }
+
+    /// Takes the next `bytes` of the stream, passing the job's output to
+    /// `output`. Returns the rest of the marker's line once it is complete.
+    fn push(&mut self, bytes: &[u8], output: &mut dyn FnMut(&[u8])) -> Option<String> {
+        match &mut self.after {
+            Some(after) => after.extend_from_slice(bytes),
+            None => {
+                self.pending.extend_from_slice(bytes);
+                let found = self
+                    .pending
+                    .windows(self.marker.len())
+                    .position(|window| window == self.marker);
+                match found {
+                    Some(at) => {
+                        if at > 0 {
+                            output(&self.pending[..at]);
+                        }
+                        self.after = Some(self.pending[at + self.marker.len()..].to_vec());
+                        self.pending.clear();
+                    }
+                    None => {
+                        let tail = self.pending.len().saturating_sub(self.marker.len() - 1);
+                        let keep = (tail..self.pending.len())
+                            .find(|&at| self.marker.starts_with(&self.pending[at..]))
+                            .unwrap_or(self.pending.len());
+                        if keep > 0 {
+                            output(&self.pending[..keep]);
+                            self.pending.drain(..keep);
+                        }
+                    }
+                }
+            }
+        }
+        let after = self.after.as_ref()?;
+        let end = after.iter().position(|&b| b == b'\n')?;
+        Some(String::from_utf8_lossy(&after[..end]).into_owned())
+    }
+}
+
+/// What the daemon answered.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Reply {
+    /// The task ran on a worker and exited with this code.
+    Exited(i32),
+    /// The task cannot run on a worker, for this reason; run it normally.
+    Declined(String),
+    /// The request failed, e.g. for a task the Taskfile does not have.
+    Failed(String),
+    /// `status`'s report.
+    Status(String),
+    /// The daemon is shutting down.
+    Stopped,
+}
+
+const STDOUT: u8 = b'o';
+const STDERR: u8 = b'e';
+const EXITED: u8 = b'x';
+const DECLINED: u8 = b'd';
+const FAILED: u8 = b'f';
+const STATUS: u8 = b's';
+const STOPPED: u8 = b'q';
+
+/// A reply frame: its tag, the payload's length as a big-endian `u32`, and
+/// the payload.
+fn frame(tag: u8, payload: &[u8]) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(payload.len() + 5);
+    frame.push(tag);
+    frame.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+    frame.extend_from_slice(payload);
+    frame
+}
+
+/// Sends `request` (`run <task> <args>...`, `status` or `stop`) to the
+/// daemon listening on `socket`, passing the output of a run to `output`
+/// as it arrives. Fails when no daemon is listening.
+pub async fn request(
+    socket: &Path,
+    request: &[&str],
+    output: &mut dyn FnMut(Stream, &[u8]),
+) -> io::Result<Reply> {
+    let mut stream = UnixStream::connect(socket).await?;
+    stream.write_all(request.join("\0").as_bytes()).await?;
+    stream.shutdown().await?;
+    loop {
+        let tag = match stream.read_u8().await {
+            Ok(tag) => tag,
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => {
+                return Err(io::Error::other("the daemon closed the connection"))
+            }
+            Err(e) => return Err(e),
+        };
+        let len = stream.read_u32().await? as usize;
+        let mut payload = vec![0; len];
+        stream.read_exact(&mut payload).await?;
+        let text = || String::from_utf8_lossy(&payload).into_owned();
+        match tag {
+            STDOUT => output(Stream::Stdout, &payload),
+            STDERR => output(Stream::Stderr, &payload),
+            EXITED => {
+                let code = payload
+                    .try_into()
+                    .map_err(|_| io::Error::other("bad exit code"))?;
+                return Ok(Reply::Exited(i32::from_be_bytes(code)));
+            }
+            DECLINED => return Ok(Reply::Declined(text())),
+            FAILED => return Ok(Reply::Failed(text())),
+            STATUS => return Ok(Reply::Status(text())),
+            STOPPED => return Ok(Reply::Stopped),
+            _ => return Err(io::Error::other(format!("unknown reply {:?}", tag as char))),
+        }
+    }
+}
+
+/// The Taskfile as last loaded, and when it was last modified then.
+struct Loaded {
+    runner: TaskRunner,
+    modified: Option<SystemTime>,
+}
+
+struct Daemon {
+    taskfile: PathBuf,
+    loaded: RwLock<Loaded>,
+    pool: Pool,
+    started: Instant,
+    stop: Notify,
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Serves `task --warm` runs of the Taskfile at `taskfile` until Ctrl-C or
+/// `task daemon stop`, loading it again whenever it changes. Fails if
+/// another daemon is already listening for it.
+pub async fn serve(taskfile: &Path, config: Config) -> Result<(), Box<dyn std::error::Error>> {
+    let socket = socket_path(taskfile);
+    if UnixStream::connect(&socket).await.is_ok() {
+        return Err(format!("A daemon is already listening on {}", socket.display()).into());
+    }
+    let loaded = Loaded {
+        runner: load(taskfile).await?,
+        modified: modified(taskfile),
+    };
+    if let Some(dir) = socket.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    // Left behind by a daemon that did not shut down cleanly.
+    let _ = std::fs::remove_file(&socket);
+    let listener = UnixListener::bind(&socket)
+        .map_err(|e| format!("Cannot listen on {}: {}", socket.display(), e))?;
+
+    let sweep =
+        (config.idle_timeout / 4).clamp(Duration::from_millis(100), Duration::from_secs(30));
+    let daemon = Arc::new(Daemon {
+        taskfile: taskfile.to_path_buf(),
+        loaded: RwLock::new(loaded),
+        pool: Pool::new(config),
+        started: Instant::now(),
+        stop: Notify::new(),
+    });
+    let mut evict = tokio::time::interval(sweep);
+    loop {
+        tokio::select! {
+            accepted = listener.accept() => {
+                let (stream, _) = accepted?;
+                let daemon = daemon.clone();
+                tokio::spawn(async move { daemon.handle(stream).await });
+            }
+            _ = evict.tick() => daemon.pool.evict_idle(),
+            _ = daemon.stop.notified() => break,
+            _ = tokio::signal::ctrl_c() => break,
+        }
+    }
+    drop(listener);
+    let _ = std::fs::remove_file(&socket);
+    daemon.pool.clear();
+    Ok(())
+}
+
+async fn load(taskfile: &Path) -> Result<TaskRunner, Box<dyn std::error::Error>> {
+    let path = taskfile
+        .to_str()
+        .ok_or("The Taskfile's path is not UTF-8")?;
+    TaskRunner::from_file(path).await
+}
+
+impl Daemon {
+    async fn handle(&self, mut stream: UnixStream) {
+        let mut request = Vec::new();
+        if stream.read_to_end(&mut request).await.is_err() {
+            return;
+        }
+        let received = Instant::now();
+        let request = String::from_utf8_lossy(&request);
+        let fields: Vec<&str> = request.split('\0').collect();
+        let reply = match fields.as_slice() {
+            ["run", task, args @ ..] => {
+                let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+                return self.run(stream, task, &args, received).await;
+            }
+            ["status"] => frame(STATUS, self.status().await.as_bytes()),
+            ["stop"] => {
+                self.stop.notify_one();
+                frame(STOPPED, b"")
+            }
+            _ => frame(FAILED, format!("Unknown request {:?}", request).as_bytes()),
+        };
+        let _ = stream.write_all(&reply).await;
+    }
+
+    /// Runs `task` with `args` on a worker, streaming its output back.
+    /// Stops the job when the client goes away.
+    async fn run(&self, mut stream: UnixStream, task: &str, args: &[String], received: Instant) {
+        let script = match self.script(task, args).await {
+            Ok(script) => script,
+            Err(reply) => {
+                let _ = stream.write_all(&reply).await;
+                return;
+            }
+        };
+        let (sender, mut frames) = mpsc::unbounded_channel();
+        let mut send = |stream: Stream, bytes: &[u8]| {
+            let tag = match stream {
+                Stream::Stdout => STDOUT,
+                Stream::Stderr => STDERR,
+            };
+            let _ = sender.send(frame(tag, bytes));
+        };
+        let job = self.pool.run(&script, received, &mut send);
+        tokio::pin!(job);
+        let result = loop {
+            tokio::select! {
+                result = &mut job => break result,
+                Some(frame) = frames.recv() => {
+                    if stream.write_all(&frame).await.is_err() {
+                        return;
+                    }
+                }
+            }
+        };
+        while let Ok(frame) = frames.try_recv() {
+            if stream.write_all(&frame).await.is_err() {
+                return;
+            }
+        }
+        let reply = match result {
+            Ok(job) => frame(EXITED, &job.code.to_be_bytes()),
+            Err(e) => frame(FAILED, format!("The worker failed: {}", e).as_bytes()),
+        };
+        let _ = stream.write_all(&reply).await;
+    }
+
+    /// The script for the run, from a Taskfile loaded again if it changed,
+    /// or the reply frame when the run cannot go to a worker.
+    async fn script(&self, task: &str, args: &[String]) -> Result<String, Vec<u8>> {
+        let modified = modified(&self.taskfile);
+        if self.loaded.read().await.modified != modified {
+            let mut loaded = self.loaded.write().await;
+            match load(&self.taskfile).await {
+                Ok(runner) => *loaded = Loaded { runner, modified },
+                Err(e) => {
+                    return Err(frame(
+                        FAILED,
+                        format!("Error loading taskfile: {}", e).as_bytes(),
+                    ))
+                }
+            }
+        }
+        let loaded = self.loaded.read().await;
+        let runner = &loaded.runner;
+        // `task test lint` runs both tasks, which one script does not do.
+        if !args.is_empty() && args.iter().all(|arg| runner.has_task(arg)) {
+            return Err(frame(DECLINED, b"it names several tasks"));
+        }
+        match runner.warm_script(task, args).await {
+            Ok(Some(script)) => Ok(script),
+            Ok(None) => Err(frame(
+                DECLINED,
+                format!("task '{}' needs more than a shell script", task).as_bytes(),
+            )),
+            Err(e) => Err(frame(FAILED, e.to_string().as_bytes())),
+        }
+    }
+
+    async fn status(&self) -> String {
+        let stats = self.pool.stats();
+        let config = &self.pool.config;
+        let ms = |d: Option<Duration>| match d {
+            Some(d) => format!("{:.1}ms", d.as_secs_f64() * 1000.0),
+            None => "-".to_string(),
+        };
+        let (met, recent) = stats.within_target();
+        format!(
+            "Serving {} for {}s\nWorkers: {} idle of at most {}, each replaced after {} jobs or {} idle\nJobs: {} ({} on a warm worker); {} workers evicted idle, {} retired\nDispatch: p50 {}, p95 {}; {} of {} recent jobs within the {} target\n",
+            self.taskfile.display(),
+            self.started.elapsed().as_secs(),
+            self.pool.idle_workers(),
+            config.workers,
+            config.max_jobs,
+            crate::format_duration(config.idle_timeout),
+            stats.jobs,
+            stats.warm,
+            stats.evicted,
+            stats.retired,
+            ms(stats.dispatch_percentile(50)),
+            ms(stats.dispatch_percentile(95)),
+            met,
+            recent,
+            ms(Some(DISPATCH_TARGET)),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn collect(output: &mut Vec<(Stream, Vec<u8>)>) -> impl FnMut(Stream, &[u8]) + Send + '_ {
+        |stream, bytes| match output.last_mut() {
+            Some((last, text)) if *last == stream => text.extend_from_slice(bytes),
+            _ => output.push((stream, bytes.to_vec())),
+        }
+    }
+
+    fn text(output: &[(Stream, Vec<u8>)], stream: Stream) -> String {
+        output
+            .iter()
+            .filter(|(s, _)| *s == stream)
+            .map(|(_, bytes)| String::from_utf8_lossy(bytes))
+            .collect()
+    }
+
+    #[test]
+    fn test_split_finds_the_marker_across_reads() {
+        let mut split = Split::new("\x1etok ".to_string());
+        let mut seen = Vec::new();
+        let mut output = |bytes: &[u8]| seen.extend_from_slice(bytes);
+        assert_eq!(split.push(b"hello\n\x1et", &mut output), None);
+        assert_eq!(split.push(b"o", &mut output), None);
+        assert_eq!(split.push(b"k 4", &mut output), None);
+        assert_eq!(split.push(b"2\n", &mut output), Some("42".to_string()));
+        assert_eq!(seen, b"hello\n");
+
+        // Output that only looks like the start of the marker is passed on.
+        let mut split = Split::new("\x1etok ".to_string());
+        let mut seen = Vec::new();
+        let mut output = |bytes: &[u8]| seen.extend_from_slice(bytes);
+        assert_eq!(split.push(b"a\x1eto", &mut output), None);
+        assert_eq!(
+            split.push(b"p b\x1etok 0\n", &mut output),
+            Some("0".to_string())
+        );
+        assert_eq!(seen, b"a\x1etop b");
+    }
+
+    #[test]
+    fn test_parse_config() {
+        let args = |args: &[&str]| args.iter().map(|a| a.to_string()).collect::<Vec<_>>();
+        assert_eq!(Config::parse(&[]).unwrap(), Config::default());
+        let config = Config::parse(&args(&[
+            "--workers",
+            "2",
+            "--idle",
+            "30s",
+            "--max-jobs",
+            "5",
+        ]))
+        .unwrap();
+        assert_eq!(
+            config,
+            Config {
+                workers: 2,
+                idle_timeout: Duration::from_secs(30),
+                max_jobs: 5,
+            }
+        );
+        assert!(Config::parse(&args(&["--workers", "0"])).is_err());
+        assert!(Config::parse(&args(&["--idle"])).is_err());
+        assert!(Config::parse(&args(&["status"]))
+            .unwrap_err()
+            .starts_with("Usage:"));
+    }
+
+    #[tokio::test]
+    async fn test_pool_reuses_and_retires_workers() {
+        let pool = Pool::new(Config {
+            max_jobs: 2,
+            ..Default::default()
+        });
+        let run = async |script: &str| {
+            let mut output = Vec::new();
+            let job = pool
+                .run(script, Instant::now(), &mut collect(&mut output))
+                .await
+                .unwrap();
+            (job, output)
+        };
+
+        let (first, output) = run("echo $$; echo oops >&2; printf partial").await;
+        assert_eq!((first.code, first.warm), (0, false));
+        let pid = text(&output, Stream::Stdout);
+        assert!(pid.ends_with("\npartial"), "{:?}", pid);
+        assert_eq!(text(&output, Stream::Stderr), "oops\n");
+
+        let (second, output) = run("echo $$; exit 3").await;
+        assert_eq!((second.code, second.warm), (3, true));
+        assert!(pid.starts_with(&text(&output, Stream::Stdout)));
+        // The worker's two jobs are up; the next one starts a new worker.
+        let (third, output) = run("echo $$").await;
+        assert!(!third.warm);
+        assert!(!pid.starts_with(&text(&output, Stream::Stdout)));
+
+        // A syntax error takes the shell down with it, and only it.
+        let (broken, output) = run("if then").await;
+        assert_ne!(broken.code, 0);
+        assert!(!text(&output, Stream::Stderr).is_empty());
+        let (after, _) = run("true").await;
+        assert_eq!((after.code, after.warm), (0, false));
+
+        let stats = pool.stats();
+        assert_eq!((stats.jobs, stats.warm, stats.retired), (5, 2, 1));
+        assert!(
+            second.dispatch < Duration::from_millis(100),
+            "{:?}",
+            second.dispatch
+        );
+        assert_eq!(stats.within_target().1, 5);
+        assert!(stats.dispatch_percentile(95) >= stats.dispatch_percentile(50));
+    }
+
+    #[tokio::test]
+    async fn test_idle_workers_are_evicted() {
+        let pool = Pool::new(Config {
+            idle_timeout: Duration::from_millis(50),
+            ..Default::default()
+        });
+        let mut output = Vec::new();
+        pool.run(
+            "sleep 100 & echo $!",
+            Instant::now(),
+            &mut collect(&mut output),
+        )
+        .await
+        .unwrap();
+        let sleep: libc::pid_t = text(&output, Stream::Stdout).trim().parse().unwrap();
+        pool.evict_idle();
+        assert_eq!(pool.idle_workers(), 1);
+
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        pool.evict_idle();
+        assert_eq!(pool.idle_workers(), 0);
+        assert_eq!(pool.stats().evicted, 1);
+        // What the job left running went with the worker.
+        let gone = Instant::now() + Duration::from_secs(5);
+        // SAFETY: signal 0 only checks that the process exists.
+        while unsafe { libc::kill(sleep, 0) } == 0 && Instant::now() < gone {
+            let stat = fs::read_to_string(format!("/proc/{}/stat", sleep)).unwrap_or_default();
+            if stat
+                .rsplit_once(") ")
+                .is_some_and(|(_, rest)| rest.starts_with('Z'))
+            {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert!(Instant::now() < gone, "pid {} outlived its worker", sleep);
+    }
+
+    #[tokio::test]
+    async fn test_serve_runs_tasks_over_the_socket() {
+        let dir = std::env::temp_dir().join(format!("taskfile-daemon-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let taskfile = dir.join("Taskfile.toml");
+        fs::write(
+            &taskfile,
+            "[tasks.greet]\ncmd = \"echo hello\"\n\n[tasks.slow]\ncmd = \"true\"\ntimeout = \"1m\"\n",
+        )
+        .unwrap();
+        let socket = socket_path(&taskfile);
+        let server = tokio::spawn({
+            let taskfile = taskfile.clone();
+            async move { serve(&taskfile, Config::default()).await.unwrap() }
+        });
+        let ready = Instant::now() + Duration::from_secs(5);
+        while UnixStream::connect(&socket).await.is_err() && Instant::now() < ready {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        let send = async |fields: &[&str]| {
+            let mut output = Vec::new();
+            let reply = request(&socket, fields, &mut collect(&mut output))
+                .await
+                .unwrap();
+            (reply, text(&output, Stream::Stdout))
+        };
+
+        assert_eq!(
+            send(&["run", "greet"]).await,
+            (Reply::Exited(0), "hello\n".to_string())
+        );
+        assert_eq!(
+            send(&["run", "greet", "--", "a b"]).await,
+            (Reply::Exited(0), "hello a b\n".to_string())
+        );
+        assert!(matches!(send(&["run", "slow"]).await.0, Reply::Declined(_)));
+        assert!(matches!(
+            send(&["run", "greet", "slow"]).await.0,
+            Reply::Declined(_)
+        ));
+        assert!(
+            matches!(send(&["run", "missing"]).await.0, Reply::Failed(e) if e.contains("missing"))
+        );
+
+        // An edited Taskfile is picked up by the next run.
+        fs::write(&taskfile, "[tasks.greet]\ncmd = \"echo hi\"\n").unwrap();
+        let file = fs::File::options().write(true).open(&taskfile).unwrap();
+        file.set_modified(SystemTime::now() + std::time::Duration::from_secs(5))
+            .unwrap();
+        assert_eq!(send(&["run", "greet"]).await.1, "hi\n");
+
+        let Reply::Status(status) = send(&["status"]).await.0 else {
+            panic!("expected a status");
+        };
+        assert!(
+            status.contains("Jobs: 3 (2 on a warm worker)"),
+            "{}",
+            status
+        );
+        assert!(status.contains("within the 5.0ms target"), "{}", status);
+
+        assert_eq!(send(&["stop"]).await.0, Reply::Stopped);
+        server.await.unwrap();
+        assert!(!socket.exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,147 @@
+/// Whether the runner already has the privileges `elevate` asks for, so
+/// commands can run as they are.
+pub fn is_elevated() -> bool {
+    #[cfg(unix)]
+    {
+        // SAFETY: geteuid(2) cannot fail and touches no memory.
+        unsafe { libc::geteuid() == 0 }
+    }
+    #[cfg(windows)]
+    {
+        token_is_elevated().unwrap_or(false)
+    }
+    #[cfg(not(any(unix, windows)))]
+    {
+        false
+    }
+}
+
+/// Whether the process token is elevated: run as administrator, or with
+/// UAC off for an administrator account.
+#[cfg(windows)]
+fn token_is_elevated() -> Option<bool> {
+    use windows_sys::Win32::Foundation::{CloseHandle, HANDLE};
+    use windows_sys::Win32::Security::{
+        GetTokenInformation, TokenElevation, TOKEN_ELEVATION, TOKEN_QUERY,
+    };
+    use windows_sys::Win32::System::Threading::{GetCurrentProcess, OpenProcessToken};
+
+    let mut token: HANDLE = std::ptr::null_mut();
+    // SAFETY: the current process pseudo-handle needs no closing, the token
+    // handle is closed below, and `elevation` is the size we pass.
+    unsafe {
+        if OpenProcessToken(GetCurrentProcess(), TOKEN_QUERY, &mut token) == 0 {
+            return None;
+        }
+        let mut elevation = TOKEN_ELEVATION { TokenIsElevated: 0 };
+        let mut written = 0;
+        let ok = GetTokenInformation(
+            token,
+            TokenElevation,
+            &mut elevation as *mut TOKEN_ELEVATION as *mut _,
+            std::mem::size_of::<TOKEN_ELEVATION>() as u32,
+            &mut written,
+        );
+        CloseHandle(token);
+        (ok != 0).then_some(elevation.TokenIsElevated != 0)
+    }
+}
+
+/// The invocation that runs `program` with `args` elevated. On Unix that is
+/// `sudo`, kept from prompting again by `authenticate` and passing the
+/// task's environment on; on Windows a UAC prompt through PowerShell, which
+/// waits for the program and exits with its code.
+pub fn wrap(program: &str, args: &[String]) -> (String, Vec<String>) {
+    if cfg!(windows) {
+        let mut start = format!("Start-Process -FilePath {}", ps_quote(program));
+        if !args.is_empty() {
+            let args: Vec<String> = args.iter().map(|arg| ps_quote(arg)).collect();
+            start.push_str(&format!(" -ArgumentList {}", args.join(",")));
+        }
+        let command = format!(
+            "$p = {} -Verb RunAs -Wait -PassThru; exit $p.ExitCode",
+            start
+        );
+        return (
+            "powershell".to_string(),
+            vec!["-NoProfile".to_string(), "-Command".to_string(), command],
+        );
+    }
+    let mut wrapped: Vec<String> = ["-n", "--preserve-env", "--", program]
+        .iter()
+        .map(|word| word.to_string())
+        .collect();
+    wrapped.extend_from_slice(args);
+    ("sudo".to_string(), wrapped)
+}
+
+/// `sudo -v`, asking for the password, if it needs one, with a prompt that
+/// names the task. Returns whether it succeeded, after which `wrap`'s
+/// `sudo -n` runs without asking. On Windows UAC asks for each command
+/// instead, so there is nothing to do.
+pub async fn authenticate(task_name: &str) -> std::io::Result<bool> {
+    if cfg!(windows) {
+        return Ok(true);
+    }
+    let prompt = format!(
+        "[sudo] password for %p to run task '{}' as root: ",
+        task_name
+    );
+    let status = tokio::process::Command::new("sudo")
+        .args(["-v", "-p", &prompt])
+        .status()
+        .await?;
+    Ok(status.success())
+}
+
+/// `sudo -n -v`: extends the cached credentials without asking. Returns
+/// whether they were still valid, so the caller knows to `authenticate`
+/// again.
+pub async fn refresh() -> bool {
+    if cfg!(windows) {
+        return true;
+    }
+    tokio::process::Command::new("sudo")
+        .args(["-n", "-v"])
+        .stdin(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .status()
+        .await
+        .is_ok_and(|status| status.success())
+}
+
+/// A PowerShell string literal.
+fn ps_quote(word: &str) -> String {
+    format!("'{}'", word.replace('\'', "''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wrap() {
+        let (program, args) = wrap("systemctl", &["restart".to_string(), "nginx".to_string()]);
+        if cfg!(windows) {
+            assert_eq!(program, "powershell");
+            assert_eq!(
+                args[2],
+                "$p = Start-Process -FilePath 'systemctl' -ArgumentList 'restart','nginx' -Verb RunAs -Wait -PassThru; exit $p.ExitCode"
+            );
+        } else {
+            assert_eq!(program, "sudo");
+            assert_eq!(
+                args,
+                [
+                    "-n",
+                    "--preserve-env",
+                    "--",
+                    "systemctl",
+                    "restart",
+                    "nginx"
+                ]
+            );
+        }
+        assert_eq!(ps_quote("it's"), "'it''s'");
+    }
+}
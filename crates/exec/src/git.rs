@@ -0,0 +1,304 @@
+use crate::workdir::TempWorkdir;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// How a file could end up in a commit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Exposure {
+    /// Already committed or staged.
+    Tracked,
+    /// Untracked, but no `.gitignore` rule keeps it out of `git add .`.
+    NotIgnored,
+}
+
+impl Exposure {
+    pub fn describe(self) -> &'static str {
+        match self {
+            Exposure::Tracked => "is tracked by git",
+            Exposure::NotIgnored => "is not ignored by git",
+        }
+    }
+}
+
+/// Whether `path` is at risk of being committed. `None` when it is safely
+/// ignored, or when it is not inside a git work tree (or git is missing).
+pub fn exposure(path: &Path) -> Option<Exposure> {
+    let dir = path.parent().filter(|d| !d.as_os_str().is_empty());
+    let name = path.file_name()?;
+
+    // Both commands exit with 1 for "no" and 128 outside a repository.
+    let tracked = git(dir, &["ls-files", "--error-unmatch", "--"], name)?;
+    if tracked {
+        return Some(Exposure::Tracked);
+    }
+    let ignored = git(dir, &["check-ignore", "-q", "--"], name)?;
+    (!ignored).then_some(Exposure::NotIgnored)
+}
+
+fn git(dir: Option<&Path>, args: &[&str], name: &std::ffi::OsStr) -> Option<bool> {
+    let mut command = Command::new("git");
+    if let Some(dir) = dir {
+        command.current_dir(dir);
+    }
+    let status = command
+        .args(args)
+        .arg(name)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .ok()?;
+    match status.code() {
+        Some(0) => Some(true),
+        Some(1) => Some(false),
+        _ => None,
+    }
+}
+
+/// The commit and branch `HEAD` points at.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Head {
+    pub sha: String,
+    /// `None` for a detached `HEAD`.
+    pub branch: Option<String>,
+}
+
+/// Reads `HEAD` of the repository containing `dir`. `None` outside a
+/// repository or before the first commit.
+pub fn head(dir: &Path) -> Option<Head> {
+    let repo = open(dir)?;
+    let head = repo.head().ok()?;
+    let sha = head.target()?.to_string();
+    let branch = match head.name()? {
+        "HEAD" => None,
+        name => Some(name.strip_prefix("refs/heads/").unwrap_or(name).to_string()),
+    };
+    Some(Head { sha, branch })
+}
+
+/// Whether tracked files have uncommitted changes (untracked files do not
+/// count). `None` outside a repository.
+pub fn is_dirty(dir: &Path) -> Option<bool> {
+    let repo = open(dir)?;
+    let mut options = git2::StatusOptions::new();
+    options.include_untracked(false).include_ignored(false);
+    let statuses = repo.statuses(Some(&mut options)).ok()?;
+    Some(!statuses.is_empty())
+}
+
+pub fn tag_exists(dir: &Path, name: &str) -> bool {
+    let tag = format!("refs/tags/{}", name);
+    capture(dir, &["rev-parse", "--quiet", "--verify", &tag]).is_ok()
+}
+
+/// Commits the current contents of `paths`, leaving anything else that is
+/// staged out of the commit.
+pub fn commit_paths(dir: &Path, message: &str, paths: &[PathBuf]) -> Result<(), String> {
+    let mut args = vec!["commit", "--quiet", "-m", message, "--"];
+    args.extend(paths.iter().filter_map(|path| path.to_str()));
+    capture(dir, &args).map(drop)
+}
+
+/// Creates an annotated tag on `HEAD`.
+pub fn annotated_tag(dir: &Path, name: &str, message: &str) -> Result<(), String> {
+    capture(dir, &["tag", "--annotate", name, "-m", message]).map(drop)
+}
+
+/// `git.sha`, `git.short_sha`, `git.branch` and, when `with_dirty` is set,
+/// `git.dirty`, for `{{ }}` templates. Empty outside a repository.
+pub fn template_vars(dir: &Path, with_dirty: bool) -> HashMap<String, String> {
+    let mut vars = HashMap::new();
+    if let Some(head) = head(dir) {
+        vars.insert(
+            "git.short_sha".to_string(),
+            head.sha.chars().take(7).collect(),
+        );
+        vars.insert("git.sha".to_string(), head.sha);
+        vars.insert("git.branch".to_string(), head.branch.unwrap_or_default());
+    }
+    if let Some(dirty) = with_dirty.then(|| is_dirty(dir)).flatten() {
+        vars.insert("git.dirty".to_string(), dirty.to_string());
+    }
+    vars
+}
+
+/// The repository containing `dir`, found the way git finds it: `$GIT_DIR`
+/// (with `$GIT_WORK_TREE`) when set, else the nearest `.git` above `dir`,
+/// be it a directory or the `gitdir:` file of a linked worktree or
+/// submodule.
+fn open(dir: &Path) -> Option<git2::Repository> {
+    let repo = if std::env::var_os("GIT_DIR").is_some() {
+        git2::Repository::open_from_env()
+    } else {
+        git2::Repository::discover(dir)
+    };
+    repo.ok()
+}
+
+/// A detached checkout of a ref in a temporary directory, removed (with
+/// `git worktree remove`) on drop.
+#[derive(Debug)]
+pub struct Worktree {
+    repo: PathBuf,
+    dir: TempWorkdir,
+    /// `project_dir` relative to the repository root, so the task runs in
+    /// the same subdirectory of the checkout.
+    prefix: PathBuf,
+}
+
+impl Worktree {
+    pub fn add(project_dir: &Path, git_ref: &str, label: &str) -> Result<Self, String> {
+        let prefix = capture(project_dir, &["rev-parse", "--show-prefix"])?;
+        let dir = TempWorkdir::create(label).map_err(|e| e.to_string())?;
+        let path = dir.path().to_string_lossy().into_owned();
+        capture(
+            project_dir,
+            &["worktree", "add", "--detach", "--quiet", &path, git_ref],
+        )?;
+        Ok(Self {
+            repo: project_dir.to_path_buf(),
+            dir,
+            prefix: PathBuf::from(prefix.trim()),
+        })
+    }
+
+    /// Where the task runs: the checkout's copy of the project directory.
+    pub fn path(&self) -> PathBuf {
+        self.dir.path().join(&self.prefix)
+    }
+}
+
+impl Drop for Worktree {
+    fn drop(&mut self) {
+        let _ = Command::new("git")
+            .arg("worktree")
+            .arg("remove")
+            .arg("--force")
+            .arg(self.dir.path())
+            .current_dir(&self.repo)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status();
+    }
+}
+
+/// Runs git in `dir`, returning its stdout or its stderr as the error.
+fn capture(dir: &Path, args: &[&str]) -> Result<String, String> {
+    let output = Command::new("git")
+        .args(args)
+        .current_dir(dir)
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|e| format!("failed to run git: {}", e))?;
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_exposure() {
+        let dir = std::env::temp_dir().join(format!("taskfile-git-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        assert_eq!(exposure(&dir.join(".env")), None);
+
+        let initialized = Command::new("git")
+            .args(["init", "-q"])
+            .current_dir(&dir)
+            .status()
+            .is_ok_and(|s| s.success());
+        if initialized {
+            fs::write(dir.join(".gitignore"), ".env\n").unwrap();
+            fs::write(dir.join(".env"), "A=1\n").unwrap();
+            fs::write(dir.join(".env.local"), "A=2\n").unwrap();
+            fs::write(dir.join(".env.shared"), "A=3\n").unwrap();
+            Command::new("git")
+                .args(["add", ".env.shared"])
+                .current_dir(&dir)
+                .status()
+                .unwrap();
+
+            assert_eq!(exposure(&dir.join(".env")), None);
+            assert_eq!(
+                exposure(&dir.join(".env.local")),
+                Some(Exposure::NotIgnored)
+            );
+            assert_eq!(exposure(&dir.join(".env.shared")), Some(Exposure::Tracked));
+        }
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_head_dirty_and_worktree() {
+        let repo = TempWorkdir::create("repo").unwrap();
+        let git = |args: &[&str]| {
+            Command::new("git")
+                .args(args)
+                .current_dir(repo.path())
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .status()
+                .is_ok_and(|s| s.success())
+        };
+        // Fails rather than passing vacuously on a machine without git.
+        assert!(git(&["init", "-q"]), "this test needs git on PATH");
+        assert_eq!(head(repo.path()), None);
+        git(&["symbolic-ref", "HEAD", "refs/heads/trunk"]);
+        fs::write(repo.path().join("VERSION"), "1").unwrap();
+        git(&["add", "VERSION"]);
+        git(&[
+            "-c",
+            "user.name=t",
+            "-c",
+            "user.email=t@t",
+            "commit",
+            "-qm",
+            "one",
+        ]);
+        git(&["tag", "v1"]);
+        let committed = head(repo.path()).unwrap();
+        assert_eq!(committed.sha.len(), 40);
+        assert_eq!(committed.branch.as_deref(), Some("trunk"));
+        // Branches that only live in `packed-refs` still resolve.
+        git(&["pack-refs", "--all"]);
+        assert!(!repo.path().join(".git/refs/heads/trunk").exists());
+        assert_eq!(head(repo.path()), Some(committed.clone()));
+        fs::create_dir_all(repo.path().join("src")).unwrap();
+        assert_eq!(head(&repo.path().join("src")), Some(committed.clone()));
+        assert_eq!(is_dirty(repo.path()), Some(false));
+        fs::write(repo.path().join("VERSION"), "2").unwrap();
+        assert_eq!(is_dirty(repo.path()), Some(true));
+
+        let worktree = Worktree::add(repo.path(), "v1", "release").unwrap();
+        let checkout = worktree.path();
+        assert_eq!(fs::read_to_string(checkout.join("VERSION")).unwrap(), "1");
+        let detached = head(&checkout).unwrap();
+        assert_eq!(detached.sha, committed.sha);
+        assert_eq!(detached.branch, None);
+        drop(worktree);
+        assert!(!checkout.exists());
+
+        // A linked worktree on a branch, whose `commondir` is relative.
+        let linked = TempWorkdir::create("linked").unwrap();
+        let path = linked.path().join("feature");
+        assert!(git(&[
+            "worktree",
+            "add",
+            "-q",
+            "-b",
+            "feature",
+            path.to_str().unwrap()
+        ]));
+        let feature = head(&path).unwrap();
+        assert_eq!(feature.sha, committed.sha);
+        assert_eq!(feature.branch.as_deref(), Some("feature"));
+        git(&["worktree", "remove", "--force", path.to_str().unwrap()]);
+        assert!(Worktree::add(repo.path(), "v9", "release").is_err());
+    }
+}
@@ -0,0 +1,131 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Whether the `/`-separated relative `path` matches `pattern`. `*` and `?`
+/// match within one path segment, `**` matches any number of segments.
+pub fn matches(pattern: &str, path: &str) -> bool {
+    let pattern: Vec<&str> = pattern.split('/').filter(|s| !s.is_empty()).collect();
+    let path: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+    match_segments(&pattern, &path)
+}
+
+fn match_segments(pattern: &[&str], path: &[&str]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((&"**", rest)) => (0..=path.len()).any(|skip| match_segments(rest, &path[skip..])),
+        Some((segment, rest)) => match path.split_first() {
+            Some((name, names)) => match_segment(segment, name) && match_segments(rest, names),
+            None => false,
+        },
+    }
+}
+
+fn match_segment(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+
+    // Iterative wildcard matching, backtracking to the last `*`.
+    let (mut p, mut n) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while n < name.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == name[n]) {
+            p += 1;
+            n += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, n));
+            p += 1;
+        } else if let Some((star_p, star_n)) = star {
+            p = star_p + 1;
+            n = star_n + 1;
+            star = Some((star_p, star_n + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+/// Files under `base` matching any of `patterns`, relative to `base` and
+/// sorted. A pattern naming a directory covers everything inside it.
+/// `.git` and `.taskfile` are never descended into.
+pub fn expand(base: &Path, patterns: &[String]) -> io::Result<Vec<PathBuf>> {
+    let mut found = Vec::new();
+    walk(base, Path::new(""), patterns, &mut found)?;
+    found.sort();
+    Ok(found)
+}
+
+fn walk(base: &Path, rel: &Path, patterns: &[String], found: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(base.join(rel))? {
+        let entry = entry?;
+        let name = entry.file_name();
+        let path = rel.join(&name);
+        if entry.file_type()?.is_dir() {
+            if name != ".git" && name != ".taskfile" {
+                walk(base, &path, patterns, found)?;
+            }
+        } else if covered(patterns, &path) {
+            found.push(path);
+        }
+    }
+    Ok(())
+}
+
+fn covered(patterns: &[String], path: &Path) -> bool {
+    let unix = path
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/");
+    patterns.iter().any(|pattern| {
+        let pattern = pattern.trim_start_matches("./");
+        matches(pattern, &unix) || matches(&format!("{}/**", pattern.trim_end_matches('/')), &unix)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matches() {
+        assert!(matches("*.rs", "main.rs"));
+        assert!(!matches("*.rs", "src/main.rs"));
+        assert!(matches("src/**/*.rs", "src/main.rs"));
+        assert!(matches("src/**/*.rs", "src/a/b/lib.rs"));
+        assert!(matches("**", "a/b"));
+        assert!(matches("file?.txt", "file1.txt"));
+        assert!(!matches("file?.txt", "file10.txt"));
+        assert!(matches("a*b*c", "aXbYc"));
+        assert!(!matches("a*b*c", "aXbY"));
+    }
+
+    #[test]
+    fn test_expand_covers_directories() {
+        let dir = std::env::temp_dir().join(format!("taskfile-glob-{}", std::process::id()));
+        fs::create_dir_all(dir.join("src/nested")).unwrap();
+        fs::create_dir_all(dir.join(".git")).unwrap();
+        for file in [
+            "Cargo.toml",
+            "README.md",
+            "src/lib.rs",
+            "src/nested/mod.rs",
+            ".git/HEAD",
+        ] {
+            fs::write(dir.join(file), "").unwrap();
+        }
+
+        let found = expand(&dir, &["src".to_string(), "*.toml".to_string()]).unwrap();
+        assert_eq!(
+            found,
+            vec![
+                PathBuf::from("Cargo.toml"),
+                PathBuf::from("src/lib.rs"),
+                PathBuf::from("src/nested/mod.rs"),
+            ]
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
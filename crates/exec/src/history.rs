@@ -0,0 +1,288 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// One finished run of a task, a line of `.taskfile/history.jsonl`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Entry {
+    pub task: String,
+    pub run_id: String,
+    /// Unix time the task started, in seconds.
+    pub started_at: u64,
+    pub duration_ms: u64,
+    pub success: bool,
+    /// Hash of what the outcome should depend on, so runs with the same
+    /// inputs can be compared; empty in entries from before it was recorded.
+    #[serde(default)]
+    pub fingerprint: String,
+}
+
+/// What `task stats` shows for one task.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TaskStats {
+    pub task: String,
+    pub runs: usize,
+    pub failures: usize,
+    pub total: Duration,
+    pub slowest: Duration,
+}
+
+impl TaskStats {
+    pub fn mean(&self) -> Duration {
+        self.total / self.runs.max(1) as u32
+    }
+
+    /// Share of runs that failed, from 0 to 1.
+    pub fn failure_rate(&self) -> f64 {
+        self.failures as f64 / self.runs.max(1) as f64
+    }
+}
+
+/// What `task stats --flaky` shows for a task that both passed and failed
+/// with the same fingerprint.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Flaky {
+    pub task: String,
+    /// Runs sharing a fingerprint with a run of the other outcome.
+    pub runs: usize,
+    pub failures: usize,
+    /// How often the outcome changed from one such run to the next.
+    pub flips: usize,
+    /// Unix time of the latest of those runs, in seconds.
+    pub last_seen: u64,
+}
+
+/// How large the history may grow. Past that, `append` drops the oldest
+/// runs until half of it is left.
+pub const MAX_BYTES: u64 = 2 * 1024 * 1024;
+
+pub fn path(project_dir: &Path) -> PathBuf {
+    project_dir.join(".taskfile").join("history.jsonl")
+}
+
+pub fn append(project_dir: &Path, entry: &Entry) -> io::Result<()> {
+    append_capped(project_dir, entry, MAX_BYTES)
+}
+
+fn append_capped(project_dir: &Path, entry: &Entry, max_bytes: u64) -> io::Result<()> {
+    let path = path(project_dir);
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let line = crate::json::to_string(entry)?;
+    let mut file = OpenOptions::new().create(true).append(true).open(&path)?;
+    file.write_all(format!("{}\n", line).as_bytes())?;
+    if file.metadata()?.len() > max_bytes {
+        truncate_oldest(&path, max_bytes / 2)?;
+    }
+    Ok(())
+}
+
+/// Rewrites the history at `path` with only the newest whole lines that
+/// fit in `keep` bytes, replacing it in one rename.
+fn truncate_oldest(path: &Path, keep: u64) -> io::Result<()> {
+    let contents = fs::read_to_string(path)?;
+    let lines: Vec<&str> = contents.lines().collect();
+    let mut size = 0;
+    let newest = lines
+        .iter()
+        .rev()
+        .take_while(|line| {
+            size += line.len() as u64 + 1;
+            size <= keep
+        })
+        .count();
+    let mut kept = String::new();
+    for line in &lines[lines.len() - newest..] {
+        kept.push_str(line);
+        kept.push('\n');
+    }
+    let tmp = path.with_extension("jsonl.tmp");
+    fs::write(&tmp, kept)?;
+    fs::rename(&tmp, path)
+}
+
+/// Every recorded run, oldest first. A missing file is an empty history,
+/// and lines that do not parse (say, cut short by a crash) are skipped.
+pub fn load(project_dir: &Path) -> io::Result<Vec<Entry>> {
+    match fs::read_to_string(path(project_dir)) {
+        Ok(contents) => Ok(contents
+            .lines()
+            .filter_map(|line| crate::json::from_str(line).ok())
+            .collect()),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(e),
+    }
+}
+
+/// Per-task totals for the runs that started at or after `since` (Unix
+/// seconds), most-run first.
+pub fn summarize(entries: &[Entry], since: u64) -> Vec<TaskStats> {
+    let mut by_task: HashMap<&str, TaskStats> = HashMap::new();
+    for entry in entries.iter().filter(|entry| entry.started_at >= since) {
+        let stats = by_task.entry(&entry.task).or_insert_with(|| TaskStats {
+            task: entry.task.clone(),
+            runs: 0,
+            failures: 0,
+            total: Duration::ZERO,
+            slowest: Duration::ZERO,
+        });
+        let duration = Duration::from_millis(entry.duration_ms);
+        stats.runs += 1;
+        stats.failures += usize::from(!entry.success);
+        stats.total += duration;
+        stats.slowest = stats.slowest.max(duration);
+    }
+    let mut stats: Vec<TaskStats> = by_task.into_values().collect();
+    stats.sort_by(|a, b| b.runs.cmp(&a.runs).then_with(|| a.task.cmp(&b.task)));
+    stats
+}
+
+/// Tasks whose outcome changed between runs with identical fingerprints
+/// since `since` (Unix seconds), most flips first. Runs whose fingerprint
+/// differs from every other run say nothing about flakiness and are left
+/// out, as are entries without one.
+pub fn flaky(entries: &[Entry], since: u64) -> Vec<Flaky> {
+    let mut groups: HashMap<(&str, &str), Vec<&Entry>> = HashMap::new();
+    for entry in entries
+        .iter()
+        .filter(|entry| entry.started_at >= since && !entry.fingerprint.is_empty())
+    {
+        groups
+            .entry((&entry.task, &entry.fingerprint))
+            .or_default()
+            .push(entry);
+    }
+
+    let mut by_task: HashMap<&str, Flaky> = HashMap::new();
+    for ((task, _), runs) in groups {
+        let flips = runs
+            .windows(2)
+            .filter(|pair| pair[0].success != pair[1].success)
+            .count();
+        if flips == 0 {
+            continue;
+        }
+        let flaky = by_task.entry(task).or_insert_with(|| Flaky {
+            task: task.to_string(),
+            runs: 0,
+            failures: 0,
+            flips: 0,
+            last_seen: 0,
+        });
+        flaky.runs += runs.len();
+        flaky.failures += runs.iter().filter(|entry| !entry.success).count();
+        flaky.flips += flips;
+        flaky.last_seen = runs
+            .iter()
+            .map(|entry| entry.started_at)
+            .fold(flaky.last_seen, u64::max);
+    }
+    let mut flaky: Vec<Flaky> = by_task.into_values().collect();
+    flaky.sort_by(|a, b| b.flips.cmp(&a.flips).then_with(|| a.task.cmp(&b.task)));
+    flaky
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "records")]
+    #[test]
+    fn test_append_drops_the_oldest_runs_past_the_cap() {
+        let dir = std::env::temp_dir().join(format!("taskfile-history-cap-{}", std::process::id()));
+        let entry = |started_at| Entry {
+            task: "build".to_string(),
+            run_id: "run".to_string(),
+            started_at,
+            duration_ms: 10,
+            success: true,
+            fingerprint: String::new(),
+        };
+        let line = serde_json::to_string(&entry(100)).unwrap().len() as u64 + 1;
+        // Room for four runs; the fifth halves the file to the newest two.
+        for started_at in 100..105 {
+            append_capped(&dir, &entry(started_at), 4 * line).unwrap();
+        }
+        let started: Vec<u64> = load(&dir).unwrap().iter().map(|e| e.started_at).collect();
+        assert_eq!(started, [103, 104]);
+        append_capped(&dir, &entry(105), 4 * line).unwrap();
+        let started: Vec<u64> = load(&dir).unwrap().iter().map(|e| e.started_at).collect();
+        assert_eq!(started, [103, 104, 105]);
+        assert!(!path(&dir).with_extension("jsonl.tmp").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_summarize() {
+        let entry = |task: &str, started_at, duration_ms, success| Entry {
+            task: task.to_string(),
+            run_id: "run".to_string(),
+            started_at,
+            duration_ms,
+            success,
+            fingerprint: String::new(),
+        };
+        let entries = [
+            entry("build", 100, 2000, true),
+            entry("test", 100, 500, false),
+            entry("test", 200, 1500, true),
+            entry("build", 50, 9000, true),
+            entry("lint", 300, 100, true),
+            entry("test", 300, 1000, true),
+        ];
+
+        let stats = summarize(&entries, 100);
+        let names: Vec<&str> = stats.iter().map(|s| s.task.as_str()).collect();
+        assert_eq!(names, ["test", "build", "lint"]);
+        let test = &stats[0];
+        assert_eq!((test.runs, test.failures), (3, 1));
+        assert_eq!(test.total, Duration::from_secs(3));
+        assert_eq!(test.slowest, Duration::from_millis(1500));
+        assert_eq!(test.mean(), Duration::from_secs(1));
+        assert!((test.failure_rate() - 1.0 / 3.0).abs() < 1e-9);
+        assert_eq!(stats[1].slowest, Duration::from_secs(2));
+    }
+
+    #[test]
+    fn test_flaky() {
+        let entry = |task: &str, started_at, success, fingerprint: &str| Entry {
+            task: task.to_string(),
+            run_id: "run".to_string(),
+            started_at,
+            duration_ms: 10,
+            success,
+            fingerprint: fingerprint.to_string(),
+        };
+        let entries = [
+            // Same inputs, pass, fail, pass: two flips.
+            entry("test", 100, true, "a"),
+            entry("test", 110, false, "a"),
+            entry("test", 120, true, "a"),
+            // Failed after its inputs changed, then was fixed: not flaky.
+            entry("build", 100, true, "a"),
+            entry("build", 110, false, "b"),
+            entry("build", 120, true, "c"),
+            // Flipped, but before the window or without a fingerprint.
+            entry("lint", 10, true, "a"),
+            entry("lint", 20, false, "a"),
+            entry("e2e", 100, true, ""),
+            entry("e2e", 110, false, ""),
+        ];
+
+        let flaky = flaky(&entries, 50);
+        assert_eq!(
+            flaky,
+            [Flaky {
+                task: "test".to_string(),
+                runs: 3,
+                failures: 1,
+                flips: 2,
+                last_seen: 120,
+            }]
+        );
+    }
+}
@@ -0,0 +1,44 @@
+//! JSON for the run records kept under `.taskfile`: the history, receipts
+//! and recorded sessions. They need the `records` feature; without it,
+//! writing or reading one fails instead.
+
+use serde::{de::DeserializeOwned, Serialize};
+use std::io;
+
+#[cfg(feature = "records")]
+pub fn to_string<T: Serialize>(value: &T) -> io::Result<String> {
+    serde_json::to_string(value).map_err(io::Error::other)
+}
+
+#[cfg(feature = "records")]
+pub fn to_string_pretty<T: Serialize>(value: &T) -> io::Result<String> {
+    serde_json::to_string_pretty(value).map_err(io::Error::other)
+}
+
+#[cfg(feature = "records")]
+pub fn from_str<T: DeserializeOwned>(json: &str) -> io::Result<T> {
+    serde_json::from_str(json).map_err(io::Error::other)
+}
+
+#[cfg(not(feature = "records"))]
+pub fn to_string<T: Serialize>(_: &T) -> io::Result<String> {
+    Err(unsupported())
+}
+
+#[cfg(not(feature = "records"))]
+pub fn to_string_pretty<T: Serialize>(_: &T) -> io::Result<String> {
+    Err(unsupported())
+}
+
+#[cfg(not(feature = "records"))]
+pub fn from_str<T: DeserializeOwned>(_: &str) -> io::Result<T> {
+    Err(unsupported())
+}
+
+#[cfg(not(feature = "records"))]
+fn unsupported() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Unsupported,
+        "this build does not include the records feature",
+    )
+}
@@ -0,0 +1,7587 @@
+pub mod cache_key;
+pub mod crash;
+#[cfg(unix)]
+pub mod daemon;
+pub mod elevate;
+pub mod git;
+pub mod glob;
+pub mod history;
+mod json;
+pub mod lock;
+pub mod output_cache;
+pub mod process;
+pub mod receipt;
+pub mod release;
+pub mod run_id;
+pub mod script;
+pub mod script_file;
+pub mod self_check;
+#[cfg(feature = "http")]
+pub mod self_update;
+pub mod session;
+pub mod shell;
+pub mod stats;
+pub mod template;
+pub mod update_check;
+pub mod workdir;
+
+pub use taskfile_model::{
+    cache, changelog, command, compose, critical_path, duration, flags, include, kubernetes,
+    locale, mode, pipeline, platform, remote, sandbox, schedule, settings, task_test, ui,
+    user_config,
+};
+pub use taskfile_model::{Dependency, ExitOutcome, Hooks, RunPolicy, Task, TaskFile, TaskKind};
+
+use cache::CacheMethod;
+use cache_key::KeyStore;
+use changelog::ChangelogConfig;
+use colored::*;
+use command::SplitError;
+use compose::{ComposeAction, ComposeConfig};
+use env_parser::EnvParser;
+use flags::{FlagSpec, ParamSpec};
+use include::{EnvScope, Resolved};
+#[cfg(feature = "spinner")]
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use kubernetes::KubernetesConfig;
+use locale::Desc;
+use lock::ProjectLock;
+use output_cache::{CachedOutput, OutputCache};
+use pipeline::Pipeline;
+use script_file::ScriptFile;
+use session::{Replay, Session};
+use settings::Dotenv;
+use shell::{Requirement, Shell, ShellKind, ShellSetting};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::env;
+use std::io::IsTerminal;
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+
+use std::process::Stdio;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Instant, SystemTime};
+use tokio::process::Command;
+use tokio::sync::{OnceCell, Semaphore};
+use tokio::time::{sleep, Duration};
+use tracing::Instrument;
+use ui::Theme;
+use workdir::Workdir;
+
+type TaskFuture<'a> = std::pin::Pin<
+    Box<dyn std::future::Future<Output = Result<(), Box<dyn std::error::Error>>> + 'a>,
+>;
+
+/// How the runner reports progress while a task runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputMode {
+    /// Animated spinner; task output is printed once the task finishes.
+    Spinner,
+    /// Line-oriented output for CI logs: output is streamed as it arrives and
+    /// long-silent tasks print periodic heartbeat lines.
+    Plain,
+}
+
+impl OutputMode {
+    /// Plain when running under CI or when stdout is not a terminal.
+    pub fn detect() -> Self {
+        let ci = env::var("CI").is_ok_and(|v| !v.is_empty() && v != "false" && v != "0");
+        if ci || !std::io::stdout().is_terminal() {
+            OutputMode::Plain
+        } else {
+            OutputMode::Spinner
+        }
+    }
+}
+
+impl Default for OutputMode {
+    fn default() -> Self {
+        Self::detect()
+    }
+}
+
+/// Invocation-level settings, typically populated from CLI flags.
+#[derive(Debug, Clone, Default)]
+pub struct RunOptions {
+    pub output: OutputMode,
+    /// Retry tasks marked `idempotent` when they fail transiently.
+    pub auto_retry_transient: bool,
+    /// Print the chain of tasks that determined the run's duration.
+    pub critical_path: bool,
+    /// Turn guardrail warnings, such as env files tracked by git, into errors.
+    pub strict: bool,
+    /// Ring the bell after runs at least this long, replacing the
+    /// Taskfile's `bell`.
+    pub bell: Option<Duration>,
+    /// Language for task descriptions, replacing `TASKFILE_LANG`.
+    pub lang: Option<String>,
+    /// Answer every `confirm` question with yes.
+    pub yes: bool,
+    /// Go on with every pipeline stage that needs approval without asking.
+    pub approve: bool,
+    /// Refuse tasks that need the network, and never let `compose` pull
+    /// images.
+    pub offline: bool,
+    /// Record each task run in `.taskfile/history.jsonl` for `task stats`,
+    /// unless the Taskfile sets `history = false` or `NO_HISTORY_VAR` is set.
+    pub record_history: bool,
+    /// How many tasks may run their commands at the same time; unlimited
+    /// when unset.
+    pub jobs: Option<usize>,
+}
+
+/// The part of a task's dependency graph one run covers.
+#[derive(Debug, Clone, Default)]
+pub struct GraphSlice {
+    /// Treat this task's dependencies as done and start the run at it.
+    pub from: Option<String>,
+    /// Stop the run once this task has completed.
+    pub until: Option<String>,
+    /// Dependencies treated as already satisfied for this run.
+    pub skip: Vec<String>,
+}
+
+/// Extra attempts granted to idempotent tasks under `auto_retry_transient`.
+const TRANSIENT_RETRIES: u32 = 2;
+
+/// How often `watch` checks the sources for changes.
+const WATCH_INTERVAL: Duration = Duration::from_millis(500);
+
+/// How long a task may stay silent in plain mode before a heartbeat line.
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(60);
+
+pub struct TaskRunner {
+    taskfile: TaskFile,
+    /// One layered environment per include scope; index 0 is the root.
+    env_scopes: Vec<EnvParser>,
+    task_scopes: HashMap<String, usize>,
+    /// Task names by tag, each list in declaration order.
+    tags: BTreeMap<String, Vec<String>>,
+    enhanced_path: Option<String>,
+    shell: Option<Shell>,
+    options: RunOptions,
+    base_dir: PathBuf,
+    run_id: String,
+    theme: Theme,
+    /// The user config's `[defaults]`, below flags and the Taskfile.
+    defaults: user_config::Defaults,
+    /// `--record`: every command run so far, as the session to write.
+    recording: Option<Mutex<Vec<session::Step>>>,
+    /// `--replay`: the session commands are taken from instead of run.
+    replay: Option<Replay>,
+    /// Whether `sudo` has been given the password during this run. Held
+    /// while asking, so concurrent elevated tasks do not prompt at once.
+    sudo: tokio::sync::Mutex<bool>,
+    /// Holds the spinner of every task running at the moment, so concurrent
+    /// tasks each get a line instead of drawing over one another.
+    #[cfg(feature = "spinner")]
+    progress: MultiProgress,
+}
+
+/// Environment variable carrying the invocation's run ID to every task.
+pub const RUN_ID_VAR: &str = "TASKFILE_RUN_ID";
+
+/// Arguments given after `--`. Only the task that was invoked sees them, as
+/// `$ARGS` in its command: each one word, however it was quoted.
+pub const ARGS_VAR: &str = "ARGS";
+
+/// Environment variable passing `ARGS_VAR` to the invoked task's commands,
+/// namespaced so it does not clobber an `ARGS` of the user's own. Words
+/// with spaces or quotes are quoted, so `eval set -- "$TASKFILE_ARGS"` gets
+/// them back.
+pub const ARGS_ENV_VAR: &str = "TASKFILE_ARGS";
+
+/// Set to anything to keep runs out of the history, whatever the Taskfile
+/// says.
+pub const NO_HISTORY_VAR: &str = "TASKFILE_NO_HISTORY";
+
+/// Environment variable naming the task `[hooks]` `before_each` and
+/// `after_each` commands run for.
+pub const TASK_VAR: &str = "TASKFILE_TASK";
+
+/// Environment variable giving `[hooks]` `after_each` and `after_all` the
+/// outcome, `success` or `failed`.
+pub const STATUS_VAR: &str = "TASKFILE_STATUS";
+
+/// Environment variable giving `[hooks]` `after_each` and `after_all` how
+/// long the task or the run took, in milliseconds.
+pub const DURATION_VAR: &str = "TASKFILE_DURATION_MS";
+
+/// Prefix marking the template values that hold `capture`d output, which
+/// are also exported to the task's environment.
+const CAPTURE_PREFIX: &str = "capture.";
+
+/// Environment variable telling a task's `post` hooks the exit code of its
+/// commands, `-1` when they were killed by a signal.
+pub const EXIT_CODE_VAR: &str = "TASKFILE_EXIT_CODE";
+
+impl TaskRunner {
+    pub async fn from_file(taskfile_path: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let (taskfile, resolved) = TaskFile::load(Path::new(taskfile_path))?;
+        for shadowed in &resolved.overridden {
+            tracing::debug!(
+                "Task '{}' at {} overrides the one included from {}",
+                shadowed.task,
+                shadowed.kept,
+                shadowed.ignored
+            );
+        }
+        let env_scopes = Self::load_env_scopes(&resolved.scopes, taskfile.settings.dotenv)?;
+
+        Ok(Self::assemble(
+            taskfile,
+            env_scopes,
+            resolved.task_scopes,
+            Path::new(taskfile_path).parent(),
+        ))
+    }
+
+    pub fn new(taskfile: TaskFile) -> Self {
+        Self::new_with_base_path(taskfile, None)
+    }
+
+    pub fn new_with_base_path(taskfile: TaskFile, base_path: Option<&Path>) -> Self {
+        let resolved = Resolved::root(&taskfile, base_path.unwrap_or(Path::new("")));
+        let env_scopes = Self::load_env_scopes(&resolved.scopes, taskfile.settings.dotenv)
+            .unwrap_or_else(|e| {
+                tracing::error!("Error loading environment files: {}", e);
+                vec![EnvParser::new()]
+            });
+
+        Self::assemble(taskfile, env_scopes, resolved.task_scopes, base_path)
+    }
+
+    fn assemble(
+        mut taskfile: TaskFile,
+        mut env_scopes: Vec<EnvParser>,
+        task_scopes: HashMap<String, usize>,
+        base_path: Option<&Path>,
+    ) -> Self {
+        // Generate this invocation's run ID and export it so commands can
+        // reference `$TASKFILE_RUN_ID`.
+        let run_id = run_id::generate();
+        for env in &mut env_scopes {
+            env.set_env_var(RUN_ID_VAR, &run_id);
+        }
+
+        for task in taskfile.tasks.values_mut() {
+            if task.kind == TaskKind::Release {
+                task.flags
+                    .entry("bump".to_string())
+                    .or_insert_with(release::bump_flag);
+            }
+        }
+
+        let mut tags: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        for (name, task) in &taskfile.tasks {
+            for tag in &task.tags {
+                let tagged = tags.entry(tag.clone()).or_default();
+                if !tagged.contains(name) {
+                    tagged.push(name.clone());
+                }
+            }
+        }
+
+        let theme = Theme::resolve(taskfile.ui.as_ref()).unwrap_or_else(|e| {
+            tracing::warn!("{}; using the default theme", e);
+            Theme::default()
+        });
+
+        Self {
+            taskfile,
+            env_scopes,
+            task_scopes,
+            tags,
+            theme,
+            enhanced_path: Self::setup_enhanced_path(),
+            shell: shell::default_shell(),
+            options: RunOptions::default(),
+            base_dir: base_dir(base_path),
+            run_id,
+            defaults: user_config::Defaults::default(),
+            recording: None,
+            replay: None,
+            sudo: tokio::sync::Mutex::new(false),
+            #[cfg(feature = "spinner")]
+            progress: MultiProgress::new(),
+        }
+    }
+
+    /// Loads each include scope's `[env]` and layers it over its parent's, so
+    /// an included file's variables only reach its own tasks unless it lists
+    /// them in `export`. Under `dotenv = "required"` a listed env file that
+    /// does not exist is an error.
+    fn load_env_scopes(
+        scopes: &[EnvScope],
+        dotenv: Dotenv,
+    ) -> Result<Vec<EnvParser>, Box<dyn std::error::Error>> {
+        let mut own = Vec::with_capacity(scopes.len());
+        for scope in scopes {
+            if dotenv == Dotenv::Required {
+                let files = scope.env.iter().flat_map(|env| &env.files);
+                if let Some(missing) = files.map(|f| scope.dir.join(f)).find(|f| !f.exists()) {
+                    return Err(format!(
+                        "Env file {} listed in {} does not exist ([settings] dotenv = \"required\")",
+                        missing.display(),
+                        scope.file.display()
+                    )
+                    .into());
+                }
+            }
+            let mut parser = match &scope.env {
+                Some(env_config) => EnvParser::with_config(env_config.clone()),
+                None => EnvParser::new(),
+            };
+            parser.load_env_files_with_base_path(Some(&scope.dir))?;
+            for (key, value) in &scope.overrides {
+                parser.define(key, value, "include vars");
+            }
+            own.push(parser);
+        }
+
+        // Scopes are numbered parent-first, so walking backwards lets exports
+        // bubble up through several levels. The parent's own values win.
+        for i in (1..own.len()).rev() {
+            if let Some(parent) = scopes[i].parent {
+                for (key, value) in own[i].exported_vars() {
+                    if !own[parent].vars().contains_key(&key) {
+                        let source = format!("{} (exported)", own[i].source(&key).unwrap_or(""));
+                        own[parent].define(&key, &value, &source);
+                    }
+                }
+            }
+        }
+
+        let mut layered: Vec<EnvParser> = Vec::with_capacity(own.len());
+        for (scope, parser) in scopes.iter().zip(&own) {
+            let env = match scope.parent {
+                Some(parent) => layered[parent].layered(parser),
+                None => parser.clone(),
+            };
+            layered.push(env);
+        }
+        Ok(layered)
+    }
+
+    /// The environment a task runs with: its include scope's variables.
+    fn env_for(&self, task_name: &str) -> &EnvParser {
+        let scope = self.task_scopes.get(task_name).copied().unwrap_or(0);
+        &self.env_scopes[scope]
+    }
+
+    /// Applies `-e KEY=VAL` style overrides on top of every scope's env.
+    pub fn with_env_overrides(mut self, overrides: &[(String, String)]) -> Self {
+        for env in &mut self.env_scopes {
+            for (key, value) in overrides {
+                env.define(key, value, "-e");
+            }
+        }
+        self
+    }
+
+    /// Applies variables read from `--env-stdin` above everything else. They
+    /// are masked like decrypted values since they are usually secrets.
+    pub fn with_stdin_env(mut self, vars: &[(String, String)]) -> Self {
+        for env in &mut self.env_scopes {
+            for (key, value) in vars {
+                env.define(key, value, "stdin");
+                env.mark_secret(key);
+            }
+        }
+        self
+    }
+
+    /// `--strict`, or `[settings] strict_env`.
+    fn strict(&self) -> bool {
+        self.options.strict || self.taskfile.settings.strict_env
+    }
+
+    /// Warns about loaded env files that git tracks or does not ignore, since
+    /// they usually hold secrets. Fails instead under `strict`.
+    pub fn check_env_files_in_git(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let mut seen = HashSet::new();
+        let mut exposed = Vec::new();
+        for path in self.env_scopes.iter().flat_map(|env| env.loaded_files()) {
+            if !seen.insert(path) {
+                continue;
+            }
+            if let Some(exposure) = git::exposure(path) {
+                tracing::warn!(
+                    "Env file {} {}; it may leak secrets into the repository",
+                    path.display(),
+                    exposure.describe()
+                );
+                exposed.push(path.display().to_string());
+            }
+        }
+
+        if self.strict() && !exposed.is_empty() {
+            return Err(format!(
+                "Refusing to run with env files exposed to git (strict): {}",
+                exposed.join(", ")
+            )
+            .into());
+        }
+        Ok(())
+    }
+
+    /// Warns about keys defined by more than one source when any scope's
+    /// `[env]` sets `warn_shadowed`.
+    pub fn warn_shadowed_env(&self) {
+        let mut seen = HashSet::new();
+        for env in &self.env_scopes {
+            if !env.config().is_some_and(|c| c.warn_shadowed) {
+                continue;
+            }
+            for shadowed in env.shadowed() {
+                if !seen.insert(shadowed) {
+                    continue;
+                }
+                tracing::warn!(
+                    "{}={} from {} is shadowed by {}",
+                    shadowed.key,
+                    shadowed.value,
+                    shadowed.source,
+                    shadowed.by
+                );
+            }
+        }
+    }
+
+    pub fn base_dir(&self) -> &Path {
+        &self.base_dir
+    }
+
+    /// The dotenv file `task env set` edits: the first of the root `[env]
+    /// files, or `.env` next to the Taskfile.
+    pub fn primary_env_file(&self) -> PathBuf {
+        let first = self.env_scopes[0]
+            .config()
+            .and_then(|c| c.files.first())
+            .map(String::as_str)
+            .unwrap_or(".env");
+        self.base_dir.join(first)
+    }
+
+    /// Every environment variable tasks reference in their commands or list
+    /// in `requires_env` or `env_docs`, with the tasks using it. Variables the runner
+    /// provides and common system ones are left out.
+    pub fn referenced_env_vars(&self) -> BTreeMap<String, Vec<String>> {
+        const SYSTEM_VARS: &[&str] = &["PATH", "HOME", "USER", "SHELL", "PWD", "TMPDIR"];
+
+        let mut names: Vec<&String> = self.taskfile.tasks.keys().collect();
+        names.sort();
+
+        let mut used: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        for name in names {
+            let task = &self.taskfile.tasks[name];
+            let env = self.env_for(name);
+            let mut vars = Vec::new();
+            for cmd in task.steps().into_iter().flatten() {
+                vars.extend(env.referenced_vars(cmd));
+            }
+            vars.extend(task.requires_env.iter().cloned());
+            vars.extend(task.env_docs.keys().cloned());
+
+            for var in vars {
+                if var == RUN_ID_VAR || SYSTEM_VARS.contains(&var.as_str()) {
+                    continue;
+                }
+                let tasks = used.entry(var).or_default();
+                if !tasks.contains(name) {
+                    tasks.push(name.clone());
+                }
+            }
+        }
+        used
+    }
+
+    /// Every external program the tasks run or list in `requires`, with the
+    /// tasks using it. Commands starting with a placeholder and names that
+    /// are `package.json` scripts are left out.
+    pub fn referenced_tools(&self) -> BTreeMap<String, Vec<String>> {
+        let scripts = npm_scripts();
+        let mut names: Vec<&String> = self.taskfile.tasks.keys().collect();
+        names.sort();
+
+        let mut used: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        for name in names {
+            let task = &self.taskfile.tasks[name];
+            let programs = task
+                .steps()
+                .into_iter()
+                .flatten()
+                .filter_map(|cmd| command::program(cmd))
+                .filter(|program| !program.contains('$') && !program.contains("{{"))
+                .filter(|program| !scripts.contains(program))
+                .chain(task.requires.iter().map(|r| r.bin().to_string()))
+                .chain(
+                    task.script
+                        .as_ref()
+                        .and_then(|_| task.interpreter(self.taskfile.default_shell()).ok())
+                        .and_then(|words| words.into_iter().next()),
+                )
+                .chain(task.compose.as_ref().map(|_| "docker".to_string()))
+                .chain(task.kubernetes.as_ref().map(|_| "kubectl".to_string()));
+
+            for program in programs {
+                let tasks = used.entry(program).or_default();
+                if !tasks.contains(name) {
+                    tasks.push(name.clone());
+                }
+            }
+        }
+        used
+    }
+
+    /// Prints each referenced tool, whether it is on the enhanced `PATH` and
+    /// its version. Returns whether all of them were found.
+    pub fn print_tools(&self) -> bool {
+        let tools = self.referenced_tools();
+        if tools.is_empty() {
+            println!("No tools referenced by any task.");
+            return true;
+        }
+
+        let rows: Vec<(&String, bool, String, String)> = tools
+            .iter()
+            .map(|(tool, tasks)| {
+                let path = self.enhanced_path.as_deref();
+                let found = shell::find_executable(tool, path).is_some();
+                let version = if found {
+                    shell::tool_version(tool, path, &self.base_dir)
+                } else {
+                    "not found".to_string()
+                };
+                (tool, found, version, tasks.join(", "))
+            })
+            .collect();
+        let tool_width = rows.iter().map(|r| r.0.len()).max().unwrap_or(0).max(4);
+        let version_width = rows.iter().map(|r| r.2.len()).max().unwrap_or(0).max(7);
+
+        println!(
+            "{:tool_width$}     {:version_width$}  {}",
+            "Tool".bold(),
+            "Version".bold(),
+            "Used by".bold()
+        );
+        for (tool, found, version, tasks) in &rows {
+            let marker = if *found {
+                self.theme.success()
+            } else {
+                self.theme.failure()
+            };
+            println!(
+                "{:tool_width$}  {}  {:version_width$}  {}",
+                tool,
+                marker,
+                version,
+                tasks.dimmed()
+            );
+        }
+        rows.iter().all(|r| r.1)
+    }
+
+    /// Prints what `query` asks of `.taskfile/history.jsonl`; see
+    /// `stats::report`.
+    pub fn print_stats(&self, query: &stats::Query) -> Result<(), Box<dyn std::error::Error>> {
+        let entries = history::load(&self.base_dir).map_err(|e| {
+            format!(
+                "Failed to read {}: {}",
+                history::path(&self.base_dir).display(),
+                e
+            )
+        })?;
+        print!(
+            "{}",
+            stats::report(&entries, query, unix_secs(SystemTime::now()))
+        );
+        Ok(())
+    }
+
+    /// Installs every program in the tasks' `requires` that is missing from
+    /// `PATH`, running their `install` commands concurrently through the
+    /// platform shell. Returns whether all of them are available afterwards.
+    pub async fn bootstrap(&self) -> bool {
+        let mut names: Vec<&String> = self.taskfile.tasks.keys().collect();
+        names.sort();
+        let mut recipes: BTreeMap<&str, Option<&str>> = BTreeMap::new();
+        for name in names {
+            for requirement in &self.taskfile.tasks[name].requires {
+                let recipe = recipes.entry(requirement.bin()).or_default();
+                if recipe.is_none() {
+                    *recipe = requirement.install();
+                }
+            }
+        }
+
+        let path = self.enhanced_path.as_deref();
+        let missing: Vec<(&str, Option<&str>)> = recipes
+            .into_iter()
+            .filter(|(bin, _)| shell::find_executable(bin, path).is_none())
+            .collect();
+        if missing.is_empty() {
+            println!("{} All required tools are installed", self.theme.success());
+            return true;
+        }
+
+        let mut ok = true;
+        let mut installs = Vec::new();
+        for (bin, recipe) in missing {
+            let Some(recipe) = recipe else {
+                println!(
+                    "{} {} is missing and has no install command; install it yourself",
+                    self.theme.failure(),
+                    bin
+                );
+                ok = false;
+                continue;
+            };
+            println!(
+                "{} Installing {}: {}",
+                self.theme.glyphs.running, bin, recipe
+            );
+
+            let kind = ShellKind::platform();
+            let mut command = Command::new(kind.binary());
+            command
+                .args([kind.command_flag(), recipe])
+                .stdin(Stdio::null())
+                .kill_on_drop(true);
+            if let Some(path) = path {
+                command.env("PATH", path);
+            }
+            let started = Instant::now();
+            installs.push((
+                bin,
+                tokio::spawn(async move { (command.output().await, started.elapsed()) }),
+            ));
+        }
+
+        for (bin, install) in installs {
+            let (output, elapsed) = match install.await {
+                Ok(result) => result,
+                Err(e) => (Err(std::io::Error::other(e)), Duration::ZERO),
+            };
+            let problem = match &output {
+                Err(e) => Some(format!("could not run the install command: {}", e)),
+                Ok(output) if !output.status.success() => {
+                    Some(format!("install command failed ({})", output.status))
+                }
+                Ok(_) if shell::find_executable(bin, path).is_none() => {
+                    Some("installed, but still not found on PATH".to_string())
+                }
+                Ok(_) => None,
+            };
+            match problem {
+                None => println!(
+                    "{} Installed {} in {}",
+                    self.theme.success(),
+                    bin,
+                    self.theme.good(&format_duration(elapsed))
+                ),
+                Some(problem) => {
+                    ok = false;
+                    println!("{} {}: {}", self.theme.failure(), bin, problem);
+                    if let Ok(output) = &output {
+                        for line in String::from_utf8_lossy(&output.stderr).lines() {
+                            println!("    {}", line.dimmed());
+                        }
+                    }
+                }
+            }
+        }
+        ok
+    }
+
+    /// Prints the root Taskfile's variables; with `sources`, also where each
+    /// value came from and which definitions it shadows.
+    pub fn print_env(&self, sources: bool) {
+        let env = &self.env_scopes[0];
+        let mut keys: Vec<&String> = env.vars().keys().collect();
+        keys.sort();
+
+        for key in keys {
+            let value = if env.is_secret(key) {
+                "********"
+            } else {
+                env.vars()[key].as_str()
+            };
+            if !sources {
+                println!("{}={}", key, value);
+                continue;
+            }
+            println!(
+                "{}={}  {}",
+                key.bold(),
+                value,
+                format!("({})", env.source(key).unwrap_or("unknown")).dimmed()
+            );
+            for shadowed in env.shadowed().iter().filter(|s| &s.key == key) {
+                println!(
+                    "  {} {}={} from {}",
+                    "shadows".dimmed(),
+                    key,
+                    shadowed.value,
+                    shadowed.source
+                );
+            }
+        }
+    }
+
+    /// The Taskfile's `[settings]`, for the choices the caller makes, such
+    /// as the output mode.
+    pub fn settings(&self) -> &settings::Settings {
+        &self.taskfile.settings
+    }
+
+    /// The Taskfile's `[tests]`, in order, for `task test-tasks`.
+    pub fn tests(&self) -> impl Iterator<Item = (&String, &task_test::TaskTest)> {
+        self.taskfile.tests.iter()
+    }
+
+    pub fn with_options(mut self, options: RunOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Keeps the outcome, output and duration of every command the run
+    /// starts, for `recorded`.
+    pub fn with_recording(mut self) -> Self {
+        self.recording = Some(Mutex::new(Vec::new()));
+        self
+    }
+
+    /// Plays `session` back: each command's outcome and output is taken
+    /// from it instead of running the command, and the run reports the
+    /// recorded durations. Nothing the run would start is executed.
+    pub fn with_replay(mut self, session: Session) -> Self {
+        if !session.run_id.is_empty() {
+            self.run_id = session.run_id.clone();
+            for env in &mut self.env_scopes {
+                env.set_env_var(RUN_ID_VAR, &self.run_id);
+            }
+        }
+        self.replay = Some(Replay::new(session));
+        self
+    }
+
+    /// What was recorded since `with_recording`.
+    pub fn recorded(&self) -> Session {
+        Session {
+            run_id: self.run_id.clone(),
+            steps: self
+                .recording
+                .as_ref()
+                .map(|steps| steps.lock().unwrap().clone())
+                .unwrap_or_default(),
+        }
+    }
+
+    /// `--jobs`, else `[settings] jobs`, else the user's default: how many
+    /// tasks may run at once.
+    fn jobs(&self) -> Option<usize> {
+        self.options.jobs.or(self
+            .taskfile
+            .settings
+            .jobs
+            .or(self.defaults.jobs)
+            .map(NonZeroUsize::get))
+    }
+
+    fn invocation(
+        &self,
+        task: String,
+        flag_values: Values,
+        git_vars: HashMap<String, String>,
+        skipped: HashMap<String, String>,
+    ) -> Invocation {
+        Invocation {
+            task,
+            flag_values,
+            git_vars,
+            skipped,
+            slots: self.jobs().map(Semaphore::new),
+            estimates: OnceLock::new(),
+            durations: Mutex::new(HashMap::new()),
+            teardowns: Mutex::new(Vec::new()),
+            claimed: Mutex::new(HashMap::new()),
+            background: Mutex::new(Vec::new()),
+            captured: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// How long `task_name` is expected to take: its `estimated_duration`,
+    /// else the mean of its recorded runs.
+    fn estimate(&self, task_name: &str, invocation: &Invocation) -> Option<Duration> {
+        if let Some(estimate) = self.get_task(task_name)?.estimated_duration {
+            return Some(estimate);
+        }
+        invocation
+            .estimates
+            .get_or_init(|| {
+                let entries = history::load(&self.base_dir).unwrap_or_default();
+                history::summarize(&entries, 0)
+                    .into_iter()
+                    .map(|stats| (stats.task.clone(), stats.mean()))
+                    .collect()
+            })
+            .get(task_name)
+            .copied()
+    }
+
+    /// Whether the run is recorded or replayed. Either way every task runs
+    /// as though nothing were cached, so a replay sees the same commands as
+    /// the recording.
+    fn in_session(&self) -> bool {
+        self.recording.is_some() || self.replay.is_some()
+    }
+
+    /// The time, which during a replay is moved on by the recorded commands.
+    fn now(&self) -> Instant {
+        match &self.replay {
+            Some(replay) => replay.now(),
+            None => Instant::now(),
+        }
+    }
+
+    fn elapsed(&self, since: Instant) -> Duration {
+        self.now().saturating_duration_since(since)
+    }
+
+    fn record_step(
+        &self,
+        task_name: &str,
+        step: &str,
+        cmd: &str,
+        elapsed: Duration,
+        output: &process::Completed,
+    ) {
+        let Some(recording) = &self.recording else {
+            return;
+        };
+        let env = self.env_for(task_name);
+        recording.lock().unwrap().push(session::Step {
+            task: task_name.to_string(),
+            step: step.to_string(),
+            cmd: cmd.to_string(),
+            duration_ms: receipt::duration_ms(elapsed),
+            outcome: session::Outcome::of(&output.termination),
+            stdout: env.redact(&String::from_utf8_lossy(&output.stdout)),
+            stderr: env.redact(&String::from_utf8_lossy(&output.stderr)),
+        });
+    }
+
+    /// Applies the user config's `[defaults]` that the runner owns: the
+    /// spinner, unless the Taskfile or `TASKFILE_THEME` picks one, and the
+    /// bell threshold. Output mode, color and the Taskfile name are the
+    /// caller's to apply.
+    pub fn with_defaults(mut self, defaults: &user_config::Defaults) -> Self {
+        let themed = env::var_os(ui::THEME_VAR).is_some()
+            || self
+                .taskfile
+                .ui
+                .as_ref()
+                .is_some_and(|ui| ui.spinner.is_some());
+        if let (Some(spinner), false) = (defaults.spinner, themed) {
+            self.theme.spinner = spinner;
+        }
+        self.defaults = defaults.clone();
+        self
+    }
+
+    #[cfg(feature = "node")]
+    async fn check_npm_script(script_name: &str) -> Option<String> {
+        if let Ok(contents) = tokio::fs::read_to_string("package.json").await {
+            if let Ok(package_json) = serde_json::from_str::<serde_json::Value>(&contents) {
+                if let Some(scripts) = package_json.get("scripts") {
+                    if let Some(script) = scripts.get(script_name) {
+                        if let Some(script_cmd) = script.as_str() {
+                            return Some(script_cmd.to_string());
+                        }
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    fn setup_enhanced_path() -> Option<String> {
+        if cfg!(feature = "node") && std::path::Path::new("package.json").exists() {
+            let mut enhanced_path = String::new();
+
+            let node_modules_bin = std::path::Path::new("node_modules/.bin");
+            if node_modules_bin.exists() {
+                if let Ok(canonical) = node_modules_bin.canonicalize() {
+                    enhanced_path.push_str(&canonical.to_string_lossy());
+                }
+            }
+
+            if let Ok(current_path) = env::var("PATH") {
+                if !enhanced_path.is_empty() {
+                    enhanced_path.push(':');
+                }
+                enhanced_path.push_str(&current_path);
+            }
+
+            if !enhanced_path.is_empty() {
+                return Some(enhanced_path);
+            }
+        }
+        None
+    }
+
+    /// The task's `desc`, in the selected language and with `[vars]` filled
+    /// in.
+    fn desc(&self, task: &Task) -> String {
+        match &task.desc {
+            Some(desc) => self.render_desc(desc),
+            None => "No description".to_string(),
+        }
+    }
+
+    /// `desc` in the selected language, with `{{vars}}` filled in.
+    fn render_desc(&self, desc: &Desc) -> String {
+        let lang = locale::selected(self.options.lang.as_deref());
+        template::render(desc.get(lang.as_deref()), &self.taskfile.vars)
+    }
+
+    /// Prints the task table, limited to tasks carrying any of `tags` unless
+    /// it is empty. A Tags column appears once a listed task has tags.
+    pub fn list_tasks(&self, tags: &[String]) {
+        if self.taskfile.tasks.is_empty() {
+            println!("No tasks found in Taskfile.");
+            return;
+        }
+
+        let selected = self.tasks_tagged(tags);
+        let tasks: Vec<(&String, &Task)> = self
+            .taskfile
+            .tasks
+            .iter()
+            .filter(|(name, _)| tags.is_empty() || selected.contains(name))
+            .collect();
+        if tasks.is_empty() {
+            println!("No tasks tagged {}.", tags.join(", "));
+            return;
+        }
+
+        let mut headers = vec!["Task", "Description", "Dependencies"];
+        let show_tags = tasks.iter().any(|(_, task)| !task.tags.is_empty());
+        if show_tags {
+            headers.push("Tags");
+        }
+        let rows: Vec<Vec<String>> = tasks
+            .iter()
+            .map(|(name, task)| {
+                let mut row = vec![
+                    name.to_string(),
+                    self.desc(task),
+                    task.depends_on
+                        .as_deref()
+                        .map(join_dependencies)
+                        .unwrap_or_else(|| "-".to_string()),
+                ];
+                if show_tags {
+                    row.push(if task.tags.is_empty() {
+                        "-".to_string()
+                    } else {
+                        task.tags.join(", ")
+                    });
+                }
+                row
+            })
+            .collect();
+
+        // Cell width plus one space of padding on each side.
+        let widths: Vec<usize> = headers
+            .iter()
+            .enumerate()
+            .map(|(i, header)| {
+                rows.iter()
+                    .map(|row| row[i].chars().count())
+                    .chain([header.len()])
+                    .max()
+                    .unwrap_or(0)
+                    + 2
+            })
+            .collect();
+
+        let border = self.theme.borders.chars();
+        let rule = |[left, mid, right]: [&str; 3]| {
+            let segments: Vec<String> = widths
+                .iter()
+                .map(|w| border.horizontal.repeat(*w))
+                .collect();
+            println!("{}{}{}", left, segments.join(mid), right);
+        };
+        let v = border.vertical;
+        let line = |cells: Vec<String>| {
+            println!("{v} {} {v}", cells.join(&format!(" {v} ")));
+        };
+
+        rule(border.top);
+        line(
+            headers
+                .iter()
+                .zip(&widths)
+                .map(|(header, width)| format!("{:^w$}", header, w = width - 2))
+                .collect(),
+        );
+        rule(border.separator);
+        for row in &rows {
+            line(
+                row.iter()
+                    .zip(&widths)
+                    .map(|(cell, width)| format!("{:w$}", cell, w = width - 2))
+                    .collect(),
+            );
+        }
+        rule(border.bottom);
+    }
+
+    /// Names of the tasks carrying any of `tags`, in declaration order.
+    pub fn tasks_tagged(&self, tags: &[String]) -> Vec<&String> {
+        let tagged: HashSet<&String> = tags
+            .iter()
+            .filter_map(|tag| self.tags.get(tag))
+            .flatten()
+            .collect();
+        self.taskfile
+            .tasks
+            .keys()
+            .filter(|name| tagged.contains(name))
+            .collect()
+    }
+
+    /// Runs every task carrying any of `tags` in one invocation, one after
+    /// another in declaration order, stopping at the first failure.
+    pub async fn run_tagged(&self, tags: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+        let names = self.tasks_tagged(tags);
+        if names.is_empty() {
+            return Err(format!("No tasks tagged {}", tags.join(", ")).into());
+        }
+        let names: Vec<&str> = names.into_iter().map(String::as_str).collect();
+        self.run_tasks(&names).await
+    }
+
+    /// Rings the terminal bell if a run that took `elapsed` reached the
+    /// `--bell`, `bell` or user default threshold, whether it succeeded or
+    /// failed.
+    pub fn ring_bell(&self, elapsed: Duration) {
+        let threshold = self
+            .options
+            .bell
+            .or(self.taskfile.bell)
+            .or(self.defaults.bell);
+        let Some(threshold) = threshold else {
+            return;
+        };
+        if elapsed >= threshold && std::io::stderr().is_terminal() {
+            eprint!("\x07");
+        }
+    }
+
+    /// Takes the project lock when the Taskfile opts into one; the returned
+    /// guard releases it on drop.
+    pub async fn lock_project(&self) -> Result<Option<ProjectLock>, Box<dyn std::error::Error>> {
+        match self.taskfile.lock {
+            Some(mode) => Ok(Some(ProjectLock::acquire(&self.base_dir, mode).await?)),
+            None => Ok(None),
+        }
+    }
+
+    pub fn describe_task(&self, task_name: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let task = self
+            .get_task(task_name)
+            .ok_or_else(|| format!("Task '{}' not found in Taskfile", task_name))?;
+
+        println!("{} {}", "Task:".bold(), task_name);
+        println!("{} {}", "Description:".bold(), self.desc(task));
+        if let Some(script) = &task.script {
+            let interpreter = match task.interpreter(self.taskfile.default_shell()) {
+                Ok(words) => words.join(" "),
+                Err(_) => task.interpreter.clone().unwrap_or_default(),
+            };
+            println!("{} run with {}", "Script:".bold(), interpreter);
+            for line in script.lines() {
+                println!("  {}", line);
+            }
+        } else if task.cmds.is_empty() {
+            println!("{} {}", "Command:".bold(), task.cmd);
+        } else {
+            println!("{}", "Commands:".bold());
+            for (index, cmd) in task.cmds.iter().enumerate() {
+                println!("  {}. {}", index + 1, cmd);
+            }
+        }
+        if !task.cmd_fallbacks.is_empty() {
+            println!("{} {}", "Fallbacks:".bold(), task.cmd_fallbacks.join(" | "));
+        }
+        for (heading, cmds) in [
+            ("Pre:", &task.pre),
+            ("Post:", &task.post),
+            ("Finally:", &task.finally),
+        ] {
+            if !cmds.is_empty() {
+                println!("{}", heading.bold());
+                for (index, cmd) in cmds.iter().enumerate() {
+                    println!("  {}. {}", index + 1, cmd);
+                }
+            }
+        }
+        if !task.tags.is_empty() {
+            println!("{} {}", "Tags:".bold(), task.tags.join(", "));
+        }
+        if let Some(key) = &task.cache_key {
+            println!("{} {}", "Cache key:".bold(), key);
+        }
+        if task.background {
+            println!("{} yes, when run as a dependency", "Background:".bold());
+        }
+        if task.elevate {
+            println!("{} yes, as root", "Elevated:".bold());
+        }
+        if let Some(condition) = &task.run_if {
+            println!("{} {}", "Run if:".bold(), condition);
+        }
+        if let Some(condition) = &task.skip_if {
+            println!("{} {}", "Skip if:".bold(), condition);
+        }
+        if !task.status.is_empty() {
+            println!("{} {}", "Up to date if:".bold(), task.status.join(" && "));
+        }
+        if let Some(estimate) = task.estimated_duration {
+            println!(
+                "{} {}",
+                "Estimated duration:".bold(),
+                format_duration(estimate)
+            );
+        }
+        println!(
+            "{} {}",
+            "Dependencies:".bold(),
+            match &task.depends_on {
+                Some(deps)
+                    if deps.len() > 1
+                        && task
+                            .depends_on_parallel
+                            .unwrap_or(self.taskfile.settings.depends_on_parallel) =>
+                {
+                    format!("{} (in parallel)", join_dependencies(deps))
+                }
+                Some(deps) => join_dependencies(deps),
+                None => "-".to_string(),
+            }
+        );
+        println!(
+            "{} {}",
+            "Shell:".bold(),
+            self.shell
+                .as_ref()
+                .map(|s| s.to_string())
+                .unwrap_or_else(|| "none detected".to_string())
+        );
+        let documented = self.documented_env(task_name, task);
+        if !documented.is_empty() {
+            println!("{}", "Environment:".bold());
+            let width = documented
+                .iter()
+                .map(|var| var.key.len())
+                .max()
+                .unwrap_or(0);
+            for DocumentedVar { key, doc, notes } in documented {
+                let mut text = doc.unwrap_or_default().to_string();
+                if !notes.is_empty() {
+                    let notes = format!("({})", notes.join(", ")).dimmed().to_string();
+                    text = if text.is_empty() {
+                        notes
+                    } else {
+                        format!("{} {}", text, notes)
+                    };
+                }
+                println!("  {:width$}  {}", key, text);
+            }
+        }
+        if !task.flags.is_empty() {
+            println!("{}", "Flags:".bold());
+            print_flags(&task.flags);
+        }
+        if !task.params.is_empty() {
+            println!("{}", "Params:".bold());
+            print_params(&task.params);
+        }
+        if let Some(docs) = &task.docs {
+            println!();
+            println!("{}", docs.trim_end());
+        }
+        Ok(())
+    }
+
+    /// Checks the whole Taskfile without running anything: `[env.schema]`
+    /// violations are errors, and variables a task documents in `env_docs`
+    /// or lists in `requires_env` but that are not set are warnings.
+    /// Returns whether there were no errors.
+    pub fn validate(&self) -> bool {
+        let all: HashSet<String> = self.taskfile.tasks.keys().cloned().collect();
+        let valid = match self.validate_env(&all) {
+            Ok(()) => true,
+            Err(e) => {
+                eprintln!("{} {}", self.theme.failure(), e);
+                false
+            }
+        };
+        let mut valid = valid;
+        for (name, pipeline) in &self.taskfile.pipelines {
+            if let Err(e) = self.check_pipeline(name, pipeline) {
+                eprintln!("{} {}", self.theme.failure(), e);
+                valid = false;
+            }
+        }
+        for (name, test) in &self.taskfile.tests {
+            if !all.contains(&test.task) {
+                eprintln!(
+                    "{} Test '{}' runs task '{}', which does not exist",
+                    self.theme.failure(),
+                    name,
+                    test.task
+                );
+                valid = false;
+            }
+        }
+        let mut warnings = 0;
+        for (name, task) in &self.taskfile.tasks {
+            let missing = self.missing_env(name, task);
+            if missing.is_empty() {
+                continue;
+            }
+            warnings += 1;
+            tracing::warn!(
+                "Task '{}' needs variables that are not set: {}",
+                name,
+                missing.join(", ")
+            );
+        }
+        if valid {
+            let note = match warnings {
+                0 => String::new(),
+                1 => " (1 warning)".to_string(),
+                n => format!(" ({} warnings)", n),
+            };
+            println!("{} Taskfile is valid{}", self.theme.success(), note);
+        }
+        valid
+    }
+
+    /// Prints the help text for a task: its summary followed by its docs.
+    pub fn print_task_help(&self, task_name: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let task = self
+            .get_task(task_name)
+            .ok_or_else(|| format!("Task '{}' not found in Taskfile", task_name))?;
+
+        println!("{} - {}", task_name.bold(), self.desc(task));
+        if !task.flags.is_empty() || !task.params.is_empty() {
+            println!();
+            println!(
+                "{} {}",
+                "Usage:".bold(),
+                flags::usage(task_name, &task.flags, &task.params)
+            );
+            print_flags(&task.flags);
+            print_params(&task.params);
+        }
+        match &task.docs {
+            Some(docs) => {
+                println!();
+                println!("{}", docs.trim_end());
+            }
+            None => println!("No documentation for this task; add a `docs` field."),
+        }
+        Ok(())
+    }
+
+    pub async fn run_task(&self, task_name: &str) -> Result<(), Box<dyn std::error::Error>> {
+        self.run_task_with_args(task_name, &[]).await
+    }
+
+    /// Runs a task with command-line arguments, which are parsed against the
+    /// task's `flags` before anything executes. Arguments after `--` are
+    /// passed through to the command as `$ARGS`.
+    pub async fn run_task_with_args(
+        &self,
+        task_name: &str,
+        args: &[String],
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.run_task_slice(task_name, args, &GraphSlice::default())
+            .await
+    }
+
+    /// The task's flags and params parsed from `args`, plus `$ARGS` from
+    /// whatever follows `--`.
+    fn parse_args(
+        task_name: &str,
+        task: &Task,
+        args: &[String],
+    ) -> Result<Values, Box<dyn std::error::Error>> {
+        let (args, extra_args) = match args.iter().position(|arg| arg == "--") {
+            Some(i) => (&args[..i], &args[i + 1..]),
+            None => (args, &[][..]),
+        };
+        let mut flag_values =
+            flags::parse_with_params(&task.flags, &task.params, args).map_err(|e| {
+                format!(
+                    "Invalid arguments for task '{}': {}\nUsage: {}",
+                    task_name,
+                    e,
+                    flags::usage(task_name, &task.flags, &task.params)
+                )
+            })?;
+        flag_values.insert(ARGS_VAR.to_string(), quote_words(extra_args));
+        Ok(Values {
+            vars: flag_values,
+            args: extra_args.to_vec(),
+        })
+    }
+
+    /// Like `run_task_with_args`, limited to the part of the dependency
+    /// graph selected by `slice`.
+    pub async fn run_task_slice(
+        &self,
+        task_name: &str,
+        args: &[String],
+        slice: &GraphSlice,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let task = self
+            .get_task(task_name)
+            .ok_or_else(|| format!("Task '{}' not found in Taskfile", task_name))?;
+        let flag_values = Self::parse_args(task_name, task, args)?;
+
+        let graph = self.taskfile.dependency_closure(task_name);
+        self.validate_env(&graph)?;
+        let root = match &slice.until {
+            Some(until) if !graph.contains(until) => {
+                return Err(format!(
+                    "--until '{}' is not part of the dependency graph of '{}'",
+                    until, task_name
+                )
+                .into());
+            }
+            Some(until) => until.as_str(),
+            None => task_name,
+        };
+
+        let mut skipped = HashMap::new();
+        if let Some(from) = &slice.from {
+            let upstream = self.taskfile.dependency_closure(from);
+            if !self.taskfile.dependency_closure(root).contains(from) {
+                return Err(format!(
+                    "--from '{}' is not part of the dependency graph of '{}'",
+                    from, root
+                )
+                .into());
+            }
+            for name in upstream.into_iter().filter(|name| name != from) {
+                skipped.insert(name, format!("before --from '{}'", from));
+            }
+        }
+
+        for name in &slice.skip {
+            if name == root {
+                return Err(format!("Cannot --skip '{}', the task being run", name).into());
+            }
+            if !graph.contains(name) {
+                return Err(
+                    format!("--skip '{}' is not a dependency of '{}'", name, task_name).into(),
+                );
+            }
+            skipped.insert(name.clone(), "--skip".to_string());
+        }
+        if !slice.skip.is_empty() {
+            eprintln!(
+                "{} Treating {} as already satisfied; they will not run",
+                self.theme.warning(),
+                slice.skip.join(", ")
+            );
+        }
+
+        let will_run = self.taskfile.dependency_closure(root);
+        let selected = |name: &str| will_run.contains(name) && !skipped.contains_key(name);
+        self.check_session(selected)?;
+        self.check_offline(selected)?;
+        self.check_required_env(selected)?;
+        self.confirm(selected)?;
+
+        let invocation = self.invocation(
+            task_name.to_string(),
+            flag_values,
+            self.git_vars(&graph),
+            skipped,
+        );
+        self.run_invocation(&[vec![root]], None, invocation).await
+    }
+
+    /// Runs `task_name`, then runs it again each time the `sources` of a
+    /// task in its graph change, until Ctrl-C. A rerun only covers the
+    /// tasks whose sources changed and the ones depending on them; the rest
+    /// of the graph is treated as satisfied. Sources are polled, and a
+    /// change counts only when their contents differ, not just their
+    /// modification times. A failed run is reported and watching goes on.
+    pub async fn watch(
+        &self,
+        task_name: &str,
+        args: &[String],
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        if !self.has_task(task_name) {
+            return Err(format!("Task '{}' not found in Taskfile", task_name).into());
+        }
+        let graph = self.taskfile.dependency_closure(task_name);
+        let mut watched: Vec<(&String, &Task)> = self
+            .taskfile
+            .tasks
+            .iter()
+            .filter(|(name, task)| graph.contains(*name) && !task.sources.is_empty())
+            .collect();
+        watched.sort_by_key(|(name, _)| *name);
+        if watched.is_empty() {
+            return Err(format!(
+                "Nothing to watch: no task in the graph of '{}' declares sources",
+                task_name
+            )
+            .into());
+        }
+        let names: Vec<&str> = watched.iter().map(|(name, _)| name.as_str()).collect();
+
+        let stop = Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let listener = stop.clone();
+        tokio::spawn(async move {
+            if tokio::signal::ctrl_c().await.is_ok() {
+                listener.store(true, std::sync::atomic::Ordering::SeqCst);
+            }
+        });
+        let stopped = || stop.load(std::sync::atomic::Ordering::SeqCst);
+
+        let mut slice = GraphSlice::default();
+        loop {
+            if let Err(e) = self.run_task_slice(task_name, args, &slice).await {
+                eprintln!("{} {}", self.theme.failure(), e);
+            }
+            if stopped() {
+                return Ok(());
+            }
+            // Taken after the run, so files a task writes into its own
+            // sources do not set off another run.
+            let mut hashes: Vec<(u64, u64)> = watched
+                .iter()
+                .map(|(_, task)| {
+                    (
+                        self.sources_hash(task, false),
+                        self.sources_hash(task, true),
+                    )
+                })
+                .collect();
+            println!(
+                "{} Watching the sources of {} (Ctrl-C to stop)",
+                self.theme.glyphs.running,
+                names.join(", ")
+            );
+
+            let changed: HashSet<String> = loop {
+                sleep(WATCH_INTERVAL).await;
+                if stopped() {
+                    return Ok(());
+                }
+                let mut changed = HashSet::new();
+                for ((name, task), (stamp, contents)) in watched.iter().zip(&mut hashes) {
+                    let new_stamp = self.sources_hash(task, false);
+                    if new_stamp == *stamp {
+                        continue;
+                    }
+                    *stamp = new_stamp;
+                    let new_contents = self.sources_hash(task, true);
+                    if new_contents != *contents {
+                        *contents = new_contents;
+                        changed.insert(name.to_string());
+                    }
+                }
+                if !changed.is_empty() {
+                    break changed;
+                }
+            };
+
+            let affected = self.taskfile.affected(task_name, &changed);
+            let mut changed: Vec<String> = changed.into_iter().collect();
+            changed.sort();
+            let mut rerun: Vec<&String> = affected.iter().collect();
+            rerun.sort();
+            println!(
+                "{} Sources of {} changed; running {} again",
+                self.theme.retry(),
+                changed.join(", "),
+                rerun
+                    .iter()
+                    .map(|name| name.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+            slice.skip = graph
+                .iter()
+                .filter(|name| !affected.contains(*name) && *name != task_name)
+                .cloned()
+                .collect();
+            slice.skip.sort();
+        }
+    }
+
+    /// Runs several tasks in one invocation, one after another, so a
+    /// dependency they share runs only once. The tasks get their flag
+    /// defaults, as dependencies do.
+    pub async fn run_tasks(&self, task_names: &[&str]) -> Result<(), Box<dyn std::error::Error>> {
+        let mut graph = HashSet::new();
+        for name in task_names {
+            if !self.has_task(name) {
+                return Err(format!("Task '{}' not found in Taskfile", name).into());
+            }
+            graph.extend(self.taskfile.dependency_closure(name));
+        }
+        self.validate_env(&graph)?;
+        self.check_session(|name| graph.contains(name))?;
+        self.check_offline(|name| graph.contains(name))?;
+        self.check_required_env(|name| graph.contains(name))?;
+        self.confirm(|name| graph.contains(name))?;
+
+        let invocation = self.invocation(
+            String::new(),
+            Values::default(),
+            self.git_vars(&graph),
+            HashMap::new(),
+        );
+        let stages: Vec<Vec<&str>> = task_names.iter().map(|name| vec![*name]).collect();
+        self.run_invocation(&stages, None, invocation).await
+    }
+
+    /// Runs the pipeline `name` in one invocation: its stages one after
+    /// another, the tasks of a stage concurrently. A dependency shared by
+    /// several tasks still runs once, and the tasks get their flag defaults,
+    /// as with `run_tasks`.
+    pub async fn run_pipeline(&self, name: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let pipeline = self
+            .taskfile
+            .pipelines
+            .get(name)
+            .ok_or_else(|| format!("Pipeline '{}' not found in Taskfile", name))?;
+        let graph = self.check_pipeline(name, pipeline)?;
+        if let Some(i) = pipeline.stages.iter().position(|stage| stage.approval) {
+            if !self.options.approve && self.replay.is_none() && !std::io::stdin().is_terminal() {
+                return Err(format!(
+                    "Stage {} of pipeline '{}' needs approval, but there is no terminal to ask on; pass --approve to run it",
+                    i + 1,
+                    name
+                )
+                .into());
+            }
+        }
+        self.validate_env(&graph)?;
+        self.check_session(|name| graph.contains(name))?;
+        self.check_offline(|name| graph.contains(name))?;
+        self.check_required_env(|name| graph.contains(name))?;
+        self.confirm(|name| graph.contains(name))?;
+
+        let invocation = self.invocation(
+            String::new(),
+            Values::default(),
+            self.git_vars(&graph),
+            HashMap::new(),
+        );
+        let stages: Vec<Vec<&str>> = pipeline
+            .stages
+            .iter()
+            .map(|stage| stage.tasks.iter().map(String::as_str).collect())
+            .collect();
+        self.run_invocation(&stages, Some((name, pipeline)), invocation)
+            .await
+    }
+
+    /// The tasks a pipeline runs, dependencies included, after checking that
+    /// every stage names tasks that exist and that no dependency cycle could
+    /// leave concurrent tasks waiting on each other.
+    fn check_pipeline(&self, name: &str, pipeline: &Pipeline) -> Result<HashSet<String>, String> {
+        if pipeline.stages.is_empty() || pipeline.stages.iter().any(|stage| stage.tasks.is_empty())
+        {
+            return Err(format!("Pipeline '{}' has an empty stage", name));
+        }
+        let mut graph = HashSet::new();
+        for task in pipeline.tasks() {
+            if !self.has_task(task) {
+                return Err(format!(
+                    "Pipeline '{}' names task '{}', which is not in the Taskfile",
+                    name, task
+                ));
+            }
+            if let Some(cycle) = self.taskfile.dependency_cycle(task) {
+                return Err(format!(
+                    "Circular dependency detected: {}",
+                    cycle.join(" -> ")
+                ));
+            }
+            graph.extend(self.taskfile.dependency_closure(task));
+        }
+        Ok(graph)
+    }
+
+    /// Prints each pipeline with its description and stages.
+    pub fn list_pipelines(&self) {
+        if self.taskfile.pipelines.is_empty() {
+            println!("No pipelines found in Taskfile.");
+            return;
+        }
+        for (name, pipeline) in &self.taskfile.pipelines {
+            match &pipeline.desc {
+                Some(desc) => println!("{}  {}", name.bold(), self.render_desc(desc)),
+                None => println!("{}", name.bold()),
+            }
+            let stages: Vec<String> = pipeline
+                .stages
+                .iter()
+                .map(|stage| {
+                    if stage.approval {
+                        format!("{} (approval)", stage.tasks.join(", "))
+                    } else {
+                        stage.tasks.join(", ")
+                    }
+                })
+                .collect();
+            println!("  {}", stages.join(" -> ").dimmed());
+        }
+    }
+
+    /// A standalone POSIX shell script doing what `task <task_name> <args>`
+    /// would, one task at a time: the dependencies in the order they would
+    /// run, then the task, each in a subshell with its environment and
+    /// directory. Commands are resolved as the runner would resolve them, with
+    /// `{{vars}}`, flags and `$VARS` filled in. Secret values are not written
+    /// out; the script expects them in its own environment. What a script
+    /// cannot do, such as timeouts or caching, is noted on the task.
+    pub async fn export_script(
+        &self,
+        task_name: &str,
+        args: &[String],
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        let script = self.render_script(task_name, args, false).await?;
+        Ok(script.unwrap_or_default())
+    }
+
+    /// The script a warm worker of `task daemon` runs for `task <task_name>
+    /// <args>`: `export_script`'s, with secret values set in it rather than
+    /// expected from the environment, as the script never leaves the
+    /// daemon. `None` when the run would do something the script leaves
+    /// out (`[hooks]`, or a task's `timeout`, `cache_key` and the like), or
+    /// needs a terminal or `elevate`, so it has to run normally.
+    pub async fn warm_script(
+        &self,
+        task_name: &str,
+        args: &[String],
+    ) -> Result<Option<String>, Box<dyn std::error::Error>> {
+        self.render_script(task_name, args, true).await
+    }
+
+    async fn render_script(
+        &self,
+        task_name: &str,
+        args: &[String],
+        warm: bool,
+    ) -> Result<Option<String>, Box<dyn std::error::Error>> {
+        if warm && !self.taskfile.hooks.is_empty() {
+            return Ok(None);
+        }
+        let task = self
+            .get_task(task_name)
+            .ok_or_else(|| format!("Task '{}' not found in Taskfile", task_name))?;
+        let flag_values = Self::parse_args(task_name, task, args)?;
+        if let Some(cycle) = self.taskfile.dependency_cycle(task_name) {
+            return Err(format!("Circular dependency detected: {}", cycle.join(" -> ")).into());
+        }
+        let mut plan = Vec::new();
+        self.plan(task_name, false, &mut HashSet::new(), &mut plan)?;
+        let graph: HashSet<String> = plan.iter().map(|(name, _)| name.to_string()).collect();
+        let git_vars = self.git_vars(&graph);
+
+        let mut secrets: Vec<(String, String)> = Vec::new();
+        for (name, _) in &plan {
+            let env = self.env_for(name);
+            for (key, value) in env.vars() {
+                if env.is_secret(key) && !secrets.iter().any(|(k, _)| k == key) {
+                    secrets.push((key.clone(), value.clone()));
+                }
+            }
+        }
+        secrets.sort();
+        let masked = if warm { &[][..] } else { &secrets[..] };
+        let quote = |word: &str| script::quote(word, masked);
+
+        let mut lines = vec![
+            "#!/bin/sh".to_string(),
+            format!(
+                "# Written by `task export-script {}`: runs it and its dependencies",
+                task_name
+            ),
+            "# one at a time, as task would. [hooks] are not included.".to_string(),
+            "set -e".to_string(),
+        ];
+        let base_dir = self
+            .base_dir
+            .canonicalize()
+            .unwrap_or_else(|_| self.base_dir.clone());
+        lines.push(format!("cd {}", quote(&base_dir.to_string_lossy())));
+        if self.enhanced_path.is_some() {
+            if let Ok(bin) = Path::new("node_modules/.bin").canonicalize() {
+                lines.push(format!(
+                    "export PATH={}:\"$PATH\"",
+                    quote(&bin.to_string_lossy())
+                ));
+            }
+        }
+        for (key, value) in &secrets {
+            if warm {
+                lines.push(format!("export {}={}", key, quote(value)));
+                continue;
+            }
+            lines.push(format!(
+                ": \"${{{key}:?{key} is a secret; set it before running this script}}\""
+            ));
+            lines.push(format!("export {}", key));
+        }
+
+        for (name, optional) in plan {
+            let task = &self.taskfile.tasks[name];
+            lines.push(String::new());
+            match &task.desc {
+                Some(desc) => lines.push(format!("# {}: {}", name, self.render_desc(desc))),
+                None => lines.push(format!("# {}", name)),
+            }
+            if let Some(reason) = task.not_here() {
+                lines.push(format!("# Skipped: {}", reason));
+                continue;
+            }
+            let notes = Self::not_exported(task);
+            if warm && (!notes.is_empty() || task.interactive || task.elevate) {
+                return Ok(None);
+            }
+            if !notes.is_empty() {
+                lines.push(format!("# Not reproduced: {}", notes.join(", ")));
+            }
+            let invoked = (name == task_name).then_some(&flag_values);
+            let values = self.template_values(name, task, invoked, &git_vars)?;
+            let body = self
+                .script_body(name, task, &values, &quote)
+                .await
+                .map_err(|failure| failure.message)?;
+
+            let tolerant = optional || task.ignore_errors;
+            let success = task.success_codes.clone().unwrap_or_else(|| vec![0]);
+            if !tolerant && success == [0] && task.skip_codes.is_empty() {
+                lines.push("(".to_string());
+                lines.extend(body.into_iter().map(|line| format!("  {}", line)));
+                lines.push(")".to_string());
+                continue;
+            }
+            lines.push("set +e".to_string());
+            lines.push("(".to_string());
+            lines.push("  set -e".to_string());
+            lines.extend(body.into_iter().map(|line| format!("  {}", line)));
+            lines.push(")".to_string());
+            lines.push("status=$?".to_string());
+            lines.push("set -e".to_string());
+            lines.push("case $status in".to_string());
+            let codes = |codes: &[i32]| {
+                let codes: Vec<String> = codes.iter().map(i32::to_string).collect();
+                codes.join("|")
+            };
+            lines.push(format!("  {}) ;;", codes(&success)));
+            if !task.skip_codes.is_empty() {
+                lines.push(format!(
+                    "  {}) echo {} ;;",
+                    codes(&task.skip_codes),
+                    quote(&format!("Task '{}' skipped", name))
+                ));
+            }
+            if tolerant {
+                lines.push(format!(
+                    "  *) echo {}\"$status\" >&2 ;;",
+                    quote(&format!("Ignoring failure of task '{}': exit code ", name))
+                ));
+            } else {
+                lines.push("  *) exit \"$status\" ;;".to_string());
+            }
+            lines.push("esac".to_string());
+        }
+        lines.push(String::new());
+        Ok(Some(lines.join("\n")))
+    }
+
+    /// The tasks `export_script` writes out, in the order a run would reach
+    /// them, each with whether its failure is tolerated as an optional
+    /// dependency.
+    fn plan<'a>(
+        &'a self,
+        task_name: &'a str,
+        optional: bool,
+        seen: &mut HashSet<&'a str>,
+        plan: &mut Vec<(&'a str, bool)>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let Some(task) = self.get_task(task_name) else {
+            return Err(format!("Task '{}' not found in Taskfile", task_name).into());
+        };
+        if !seen.insert(task_name) && task.run != RunPolicy::Always {
+            return Ok(());
+        }
+        if task.runs_here() {
+            for dep in task.depends_on.iter().flatten() {
+                if !self.has_task(&dep.task) {
+                    return Err(format!(
+                        "Dependency '{}' not found for task '{}'",
+                        dep.task, task_name
+                    )
+                    .into());
+                }
+                self.plan(&dep.task, dep.optional, seen, plan)?;
+            }
+        }
+        plan.push((task_name, optional));
+        Ok(())
+    }
+
+    /// What a task sets that `export_script` leaves out.
+    fn not_exported(task: &Task) -> Vec<&'static str> {
+        [
+            (task.kind == TaskKind::Release, "type = \"release\""),
+            (task.timeout.is_some(), "timeout"),
+            (task.no_output_timeout.is_some(), "no_output_timeout"),
+            (task.cache_key.is_some(), "cache_key"),
+            (task.capture.is_some(), "capture"),
+            (task.background, "background"),
+            (task.confirm.is_some(), "confirm"),
+            (!task.inherits_env(), "env_inherit = false"),
+            (task.git_ref.is_some(), "git_ref"),
+            (task.isolate_workdir, "isolate_workdir"),
+            (task.compose.is_some(), "compose"),
+            (task.kubernetes.is_some(), "kubernetes"),
+            (task.remote_build, "remote_build"),
+            (task.output_mode.is_some(), "output_mode"),
+            (task.elevate && cfg!(windows), "elevate"),
+        ]
+        .into_iter()
+        .filter_map(|(set, name)| set.then_some(name))
+        .collect()
+    }
+
+    /// The lines that go inside a task's subshell in `export_script`.
+    async fn script_body(
+        &self,
+        task_name: &str,
+        task: &Task,
+        values: &Values,
+        quote: &(dyn Fn(&str) -> String + Sync),
+    ) -> Result<Vec<String>, Failure> {
+        let env = self.env_for(task_name);
+        let mut lines = Vec::new();
+        let mut vars: Vec<(&String, &String)> = env
+            .vars()
+            .iter()
+            .filter(|(key, _)| *key != RUN_ID_VAR && !env.is_secret(key))
+            .collect();
+        vars.sort();
+        for (key, value) in vars {
+            lines.push(format!("export {}={}", key, quote(value)));
+        }
+        if let Some(args) = values.get(ARGS_VAR).filter(|args| !args.is_empty()) {
+            lines.push(format!("export {}={}", ARGS_ENV_VAR, quote(args)));
+        }
+        if let Some(dir) = self.task_dir(task_name, task, None, values)? {
+            lines.push(format!("cd {}", quote(&dir.to_string_lossy())));
+        }
+        if let Some(mask) = task.umask {
+            lines.push(format!("umask {:03o}", mask));
+        }
+        for requirement in &task.requires {
+            let bin = requirement.bin();
+            lines.push(format!(
+                "command -v {} >/dev/null 2>&1 || {{ echo {} >&2; exit 127; }}",
+                quote(bin),
+                quote(&format!(
+                    "Task '{}' requires {}, which is not installed",
+                    task_name, bin
+                ))
+            ));
+        }
+        for key in &task.requires_env {
+            lines.push(format!(
+                ": \"${{{}:?task '{}' requires {}}}\"",
+                key, task_name, key
+            ));
+        }
+
+        let shell = task.shell_kind(self.taskfile.default_shell());
+        if !task.finally.is_empty() {
+            lines.push("taskfile_finally() {".to_string());
+            for cmd in &task.finally {
+                let line = self
+                    .script_command(task_name, &[cmd], shell, values, quote)
+                    .await?;
+                lines.push(format!("  {} || true", line));
+            }
+            lines.push("}".to_string());
+            lines.push("trap taskfile_finally EXIT".to_string());
+        }
+        let kind = shell.unwrap_or_else(ShellKind::platform);
+        for (condition, wanted) in [(&task.run_if, true), (&task.skip_if, false)] {
+            let Some(condition) = condition else {
+                continue;
+            };
+            let condition = env.substitute_env_vars(&template::render(condition, values));
+            lines.push(format!(
+                "if {}{} {} {} >/dev/null 2>&1; then echo {}; exit 0; fi",
+                if wanted { "! " } else { "" },
+                kind.binary(),
+                kind.command_flag(),
+                quote(&condition),
+                quote(&format!(
+                    "Skipping task '{}' (condition not met)",
+                    task_name
+                ))
+            ));
+        }
+        if !task.status.is_empty() {
+            let checks: Vec<String> = task
+                .status
+                .iter()
+                .map(|cmd| {
+                    let cmd = env.substitute_env_vars(&template::render(cmd, values));
+                    format!(
+                        "{} {} {} >/dev/null 2>&1",
+                        kind.binary(),
+                        kind.command_flag(),
+                        quote(&cmd)
+                    )
+                })
+                .collect();
+            lines.push(format!(
+                "if {}; then echo {}; exit 0; fi",
+                checks.join(" && "),
+                quote(&format!("Task '{}' is up to date", task_name))
+            ));
+        }
+
+        let mut commands = Vec::new();
+        for cmd in &task.pre {
+            commands.push(
+                self.script_command(task_name, &[cmd], shell, values, quote)
+                    .await?,
+            );
+        }
+        match &task.script {
+            Some(script) => {
+                commands.extend(self.script_heredoc(task_name, task, script, values, quote)?)
+            }
+            None => {
+                for candidates in task.steps() {
+                    commands.push(
+                        self.script_command(task_name, &candidates, shell, values, quote)
+                            .await?,
+                    );
+                }
+            }
+        }
+        if task.post.is_empty() {
+            lines.extend(commands);
+            return Ok(lines);
+        }
+        // Post hooks run whatever the commands did, and see their exit code.
+        lines.push("set +e".to_string());
+        lines.push("(".to_string());
+        lines.push("  set -e".to_string());
+        lines.extend(commands.into_iter().map(|line| format!("  {}", line)));
+        lines.push(")".to_string());
+        lines.push(format!("{}=$?", EXIT_CODE_VAR));
+        lines.push(format!("export {}", EXIT_CODE_VAR));
+        lines.push("set -e".to_string());
+        for cmd in &task.post {
+            lines.push(
+                self.script_command(task_name, &[cmd], shell, values, quote)
+                    .await?,
+            );
+        }
+        lines.push(format!("exit \"${}\"", EXIT_CODE_VAR));
+        Ok(lines)
+    }
+
+    /// The task's `script` for `export_script`: written to a temporary file
+    /// from a here-document, run, and removed again. The document is one
+    /// line with its end marker, so it is not indented with the others.
+    fn script_heredoc(
+        &self,
+        task_name: &str,
+        task: &Task,
+        script: &str,
+        values: &Values,
+        quote: &(dyn Fn(&str) -> String + Sync),
+    ) -> Result<Vec<String>, Failure> {
+        let interpreter = self.script_interpreter(task_name, task)?;
+        let extra = &values.args;
+        let body = template::render(script, values);
+        let mut delimiter = "TASKFILE_SCRIPT".to_string();
+        while body.lines().any(|line| line == delimiter) {
+            delimiter.push('_');
+        }
+        let run = task
+            .elevate
+            .then(|| "sudo --preserve-env --".to_string())
+            .into_iter()
+            .chain(interpreter.iter().map(|word| quote(word)))
+            .chain(["\"$taskfile_script\"".to_string()])
+            .chain(extra.iter().map(|word| quote(word)))
+            .collect::<Vec<_>>()
+            .join(" ");
+        let newline = if body.is_empty() || body.ends_with('\n') {
+            ""
+        } else {
+            "\n"
+        };
+        Ok(vec![
+            "taskfile_script=$(mktemp)".to_string(),
+            format!(
+                "cat > \"$taskfile_script\" <<'{}'\n{}{}{}",
+                delimiter, body, newline, delimiter
+            ),
+            "taskfile_status=0".to_string(),
+            format!("{} || taskfile_status=$?", run),
+            "rm -f \"$taskfile_script\"".to_string(),
+            "[ \"$taskfile_status\" -eq 0 ] || exit \"$taskfile_status\"".to_string(),
+        ])
+    }
+
+    /// A step of the task as a line of `export_script`, resolved the way
+    /// `run_step` would resolve it.
+    async fn script_command(
+        &self,
+        task_name: &str,
+        candidates: &[&String],
+        shell: Option<ShellKind>,
+        values: &Values,
+        quote: &(dyn Fn(&str) -> String + Sync),
+    ) -> Result<String, Failure> {
+        let (_, program, args) = self
+            .select_command(task_name, candidates, shell, values)
+            .await?;
+        let sudo = self
+            .get_task(task_name)
+            .is_some_and(|task| task.elevate)
+            .then(|| "sudo --preserve-env --".to_string());
+        Ok(sudo
+            .into_iter()
+            .chain(
+                std::iter::once(program)
+                    .chain(args)
+                    .map(|word| quote(&word)),
+            )
+            .collect::<Vec<_>>()
+            .join(" "))
+    }
+
+    /// Runs the tasks named in `stages` with their dependencies, a stage at a
+    /// time and the tasks of a stage concurrently, stopping after the first
+    /// stage with a failure. `pipeline` is the pipeline the stages come from,
+    /// with its name, to announce each stage and ask before the ones that
+    /// need approval.
+    async fn run_invocation(
+        &self,
+        stages: &[Vec<&str>],
+        pipeline: Option<(&str, &Pipeline)>,
+        invocation: Invocation,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        if self.options.output == OutputMode::Plain {
+            println!("Run ID: {}", self.run_id);
+        }
+        let hooks = &self.taskfile.hooks;
+        let started = Instant::now();
+        self.run_hooks("before_all", &hooks.before_all, &self.env_scopes[0], &[])
+            .await?;
+        let mut result = Ok(());
+        for (i, stage) in stages.iter().enumerate() {
+            if let Some((name, pipeline)) = pipeline {
+                let label = format!("Pipeline '{}' stage {}/{}", name, i + 1, stages.len());
+                println!(
+                    "{} {}: {}",
+                    self.theme.glyphs.running,
+                    label.bold(),
+                    stage.join(", ")
+                );
+                if pipeline.stages[i].approval {
+                    result = self.approve(&label);
+                    if result.is_err() {
+                        break;
+                    }
+                }
+            }
+            result = self.run_concurrently(stage, &[], &[], &invocation).await;
+            if result.is_err() {
+                break;
+            }
+        }
+        self.stop_background(invocation.background.into_inner().unwrap())
+            .await;
+        self.run_teardowns(invocation.teardowns.into_inner().unwrap())
+            .await;
+        let vars = [
+            (STATUS_VAR, hook_status(&result).to_string()),
+            (
+                DURATION_VAR,
+                receipt::duration_ms(started.elapsed()).to_string(),
+            ),
+        ];
+        let after = self
+            .run_hooks("after_all", &hooks.after_all, &self.env_scopes[0], &vars)
+            .await;
+        self.merge_hook_result(&mut result, after);
+        result?;
+
+        if self.options.critical_path {
+            let durations = invocation.durations.into_inner().unwrap();
+            for root in stages.iter().flatten() {
+                self.print_critical_path(root, &durations);
+            }
+        }
+        Ok(())
+    }
+
+    /// Runs `tasks` with their dependencies concurrently, letting all of
+    /// them finish even when one fails, and reports every failure except
+    /// those of the `optional` ones, which only get a warning. `visited` is
+    /// the chain of tasks that led here.
+    async fn run_concurrently(
+        &self,
+        tasks: &[&str],
+        optional: &[&str],
+        visited: &[String],
+        invocation: &Invocation,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let dependent = visited.last().map(String::as_str).unwrap_or_default();
+        // Started in this order, so under `--jobs` the long ones go first.
+        let mut tasks = tasks.to_vec();
+        schedule::longest_first(&mut tasks, |name| self.estimate(name, invocation));
+        let tasks = &tasks;
+        let mut branches = vec![visited.to_vec(); tasks.len()];
+        let results = futures::future::join_all(
+            tasks
+                .iter()
+                .zip(branches.iter_mut())
+                .map(|(name, visited)| self.run_task_with_deps(name, visited, invocation)),
+        )
+        .await;
+        let mut errors: Vec<Box<dyn std::error::Error>> = Vec::new();
+        for (name, result) in tasks.iter().zip(results) {
+            match result {
+                Err(e) if optional.contains(name) => {
+                    self.warn_optional_failed(name, dependent, &*e)
+                }
+                Err(e) => errors.push(e),
+                Ok(()) => {}
+            }
+        }
+        if errors.len() <= 1 {
+            return errors.pop().map_or(Ok(()), Err);
+        }
+        let errors: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+        Err(format!("{} tasks failed:\n{}", errors.len(), errors.join("\n")).into())
+    }
+
+    fn warn_optional_failed(&self, dep: &str, dependent: &str, e: &dyn std::error::Error) {
+        eprintln!(
+            "{} Optional dependency '{}' of task '{}' failed, continuing: {}",
+            self.theme.warning(),
+            dep,
+            dependent,
+            e
+        );
+    }
+
+    fn print_critical_path(&self, root: &str, durations: &HashMap<String, Duration>) {
+        let path = critical_path::critical_path(root, durations, |name| {
+            self.get_task(name)
+                .map(|t| t.dependency_names().cloned().collect())
+                .unwrap_or_default()
+        });
+        let on_path: Duration = path.iter().map(|(_, d)| *d).sum();
+        let total: Duration = durations.values().sum();
+
+        println!();
+        println!(
+            "{} {} of {} total task time",
+            "Critical path:".bold(),
+            format_duration(on_path),
+            format_duration(total)
+        );
+        let width = path.iter().map(|(name, _)| name.len()).max().unwrap_or(0);
+        for (name, duration) in &path {
+            println!(
+                "  {:<width$}  {}",
+                name,
+                format_duration(*duration),
+                width = width
+            );
+        }
+    }
+
+    /// Asks each selected task's `confirm` question, in declaration order,
+    /// and fails the run at the first one not answered with yes.
+    fn confirm(&self, selected: impl Fn(&str) -> bool) -> Result<(), Box<dyn std::error::Error>> {
+        for (name, task) in &self.taskfile.tasks {
+            let Some(question) = &task.confirm else {
+                continue;
+            };
+            if !selected(name) || self.options.yes || self.replay.is_some() {
+                continue;
+            }
+            if !std::io::stdin().is_terminal() {
+                return Err(format!(
+                    "Task '{}' needs confirmation, but there is no terminal to ask on; pass --yes to run it",
+                    name
+                )
+                .into());
+            }
+            if !ask(&template::render(question, &self.taskfile.vars))? {
+                return Err(format!("Task '{}' was not confirmed", name).into());
+            }
+        }
+        Ok(())
+    }
+
+    /// Asks whether to go on with the pipeline stage `label`, unless
+    /// `--approve` already said so. `run_pipeline` has made sure there is a
+    /// terminal to ask on.
+    fn approve(&self, label: &str) -> Result<(), Box<dyn std::error::Error>> {
+        if self.options.approve || self.replay.is_some() {
+            let by = if self.options.approve {
+                "--approve"
+            } else {
+                "--replay"
+            };
+            println!("{} {} approved with {}", self.theme.success(), label, by);
+            return Ok(());
+        }
+        if !ask(&format!("{} needs approval. Continue?", label))? {
+            return Err(format!("{} was not approved", label).into());
+        }
+        Ok(())
+    }
+
+    /// Refuses to record or replay a run that would do what a session
+    /// cannot capture: global hooks, a release, or a task that runs
+    /// elsewhere or in the background.
+    fn check_session(
+        &self,
+        selected: impl Fn(&str) -> bool,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        if !self.in_session() {
+            return Ok(());
+        }
+        let hooks = &self.taskfile.hooks;
+        if [
+            &hooks.before_all,
+            &hooks.after_all,
+            &hooks.before_each,
+            &hooks.after_each,
+        ]
+        .iter()
+        .any(|cmds| !cmds.is_empty())
+        {
+            return Err("--record and --replay do not support [hooks]".into());
+        }
+        for (name, task) in &self.taskfile.tasks {
+            if !selected(name) {
+                continue;
+            }
+            let uses = [
+                (task.kind == TaskKind::Release, "type = \"release\""),
+                (task.compose.is_some(), "compose"),
+                (task.kubernetes.is_some(), "kubernetes"),
+                (task.remote_build, "remote_build"),
+                (task.background, "background"),
+                (task.git_ref.is_some(), "git_ref"),
+            ];
+            if let Some((_, field)) = uses.iter().find(|(set, _)| *set) {
+                return Err(format!(
+                    "Task '{}' sets {}, which --record and --replay do not support",
+                    name, field
+                )
+                .into());
+            }
+        }
+        Ok(())
+    }
+
+    /// Under `--offline`, fails before anything runs if a selected task
+    /// needs the network: a remote build or a Kubernetes Job.
+    fn check_offline(
+        &self,
+        selected: impl Fn(&str) -> bool,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        if !self.options.offline {
+            return Ok(());
+        }
+        for (name, task) in &self.taskfile.tasks {
+            if !selected(name) {
+                continue;
+            }
+            let needs = if task.remote_build {
+                "builds on the remote builder over ssh"
+            } else if task.kubernetes.is_some() {
+                "runs as a Kubernetes Job"
+            } else {
+                continue;
+            };
+            return Err(format!(
+                "Task '{}' {}, which needs the network; drop --offline or --skip it",
+                name, needs
+            )
+            .into());
+        }
+        Ok(())
+    }
+
+    /// Fails before anything runs if a selected task's `requires_env` names
+    /// variables that are unset or empty, listing all of them per task.
+    /// A task with `env_inherit = false` only sees the runner's environment
+    /// through `env_import`.
+    fn check_required_env(
+        &self,
+        selected: impl Fn(&str) -> bool,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        if self.replay.is_some() {
+            return Ok(());
+        }
+        let mut problems = Vec::new();
+        for (name, task) in &self.taskfile.tasks {
+            if !selected(name) || !task.runs_here() {
+                continue;
+            }
+            let missing: Vec<&str> = task
+                .requires_env
+                .iter()
+                .filter(|key| !self.env_var_set(name, task, key))
+                .map(String::as_str)
+                .collect();
+            if !missing.is_empty() {
+                problems.push(format!("{}: {}", name, missing.join(", ")));
+            }
+        }
+        if problems.is_empty() {
+            return Ok(());
+        }
+        Err(format!(
+            "Required environment variables are not set (export them or add them to an env file):\n  {}",
+            problems.join("\n  ")
+        )
+        .into())
+    }
+
+    /// The variables a task documents in `env_docs` or lists in
+    /// `requires_env`, as `describe` shows them.
+    fn documented_env<'a>(&self, task_name: &str, task: &'a Task) -> Vec<DocumentedVar<'a>> {
+        task.documented_env()
+            .map(|key| {
+                let mut notes = Vec::new();
+                if task.requires_env.contains(key) {
+                    notes.push("required");
+                }
+                if !self.env_var_set(task_name, task, key) {
+                    notes.push("not set");
+                }
+                DocumentedVar {
+                    key,
+                    doc: task.env_docs.get(key).map(String::as_str),
+                    notes,
+                }
+            })
+            .collect()
+    }
+
+    /// The documented variables that are not set, as `validate` warns about
+    /// them: `AWS_PROFILE (the profile to deploy with)`.
+    fn missing_env(&self, task_name: &str, task: &Task) -> Vec<String> {
+        self.documented_env(task_name, task)
+            .into_iter()
+            .filter(|var| var.notes.contains(&"not set"))
+            .map(|var| match var.doc {
+                Some(doc) => format!("{} ({})", var.key, doc),
+                None => var.key.to_string(),
+            })
+            .collect()
+    }
+
+    /// Whether `key` reaches the task's command with a non-empty value.
+    fn env_var_set(&self, task_name: &str, task: &Task, key: &str) -> bool {
+        let value = self
+            .env_for(task_name)
+            .vars()
+            .get(key)
+            .cloned()
+            .or_else(|| {
+                (task.inherits_env() || task.env_import.iter().any(|k| k == key))
+                    .then(|| env::var(key).ok())
+                    .flatten()
+            });
+        value.is_some_and(|value| !value.is_empty())
+    }
+
+    /// Checks `[env.schema]` for the scopes the given tasks run in, reporting
+    /// every problem at once.
+    fn validate_env(&self, tasks: &HashSet<String>) -> Result<(), Box<dyn std::error::Error>> {
+        let mut scopes: Vec<usize> = tasks
+            .iter()
+            .map(|name| self.task_scopes.get(name).copied().unwrap_or(0))
+            .collect();
+        scopes.sort();
+        scopes.dedup();
+
+        let mut errors: Vec<String> = Vec::new();
+        for scope in scopes {
+            for error in self.env_scopes[scope].schema_errors() {
+                if !errors.contains(&error) {
+                    errors.push(error);
+                }
+            }
+        }
+        if errors.is_empty() {
+            return Ok(());
+        }
+        Err(format!("Invalid environment:\n  {}", errors.join("\n  ")).into())
+    }
+
+    /// What `{{name}}` templates in the task see, apart from captured
+    /// output: the task's flags and params, the Taskfile's `vars` and the
+    /// `git.*` values. `invoked` holds the parsed arguments of the task the
+    /// run was started for; dependencies only see their defaults and an
+    /// empty `$ARGS`.
+    fn template_values(
+        &self,
+        task_name: &str,
+        task: &Task,
+        invoked: Option<&Values>,
+        git_vars: &HashMap<String, String>,
+    ) -> Result<Values, Box<dyn std::error::Error>> {
+        let mut values = match invoked {
+            Some(values) => values.clone(),
+            None => {
+                let mut vars = flags::parse_with_params(&task.flags, &task.params, &[])
+                    .map_err(|e| format!("Invalid arguments for task '{}': {}", task_name, e))?;
+                vars.insert(ARGS_VAR.to_string(), String::new());
+                Values {
+                    vars,
+                    args: Vec::new(),
+                }
+            }
+        };
+        for (key, value) in &self.taskfile.vars {
+            values.entry(key.clone()).or_insert_with(|| value.clone());
+        }
+        values.extend(git_vars.clone());
+        Ok(values)
+    }
+
+    fn run_task_with_deps<'a>(
+        &'a self,
+        task_name: &'a str,
+        visited: &'a mut Vec<String>,
+        invocation: &'a Invocation,
+    ) -> TaskFuture<'a> {
+        let span = tracing::info_span!("task", name = task_name);
+        Box::pin(
+            async move {
+                if visited.contains(&task_name.to_string()) {
+                    return Err(
+                        format!("Circular dependency detected for task '{}'", task_name).into(),
+                    );
+                }
+
+                let always = self
+                    .get_task(task_name)
+                    .is_some_and(|task| task.run == RunPolicy::Always);
+                if always {
+                    return self.run_reached(task_name, visited, invocation).await;
+                }
+                // Whoever reaches the task first runs it; anyone reaching it
+                // meanwhile, from a concurrent stage, waits for the outcome.
+                let claim = invocation
+                    .claimed
+                    .lock()
+                    .unwrap()
+                    .entry(task_name.to_string())
+                    .or_default()
+                    .clone();
+                let mut ran = None;
+                let succeeded = *claim
+                    .get_or_init(|| async {
+                        let result = self.run_reached(task_name, visited, invocation).await;
+                        let succeeded = result.is_ok();
+                        ran = Some(result);
+                        succeeded
+                    })
+                    .await;
+                match ran {
+                    Some(result) => result,
+                    None if succeeded => {
+                        tracing::debug!("Already ran in this invocation");
+                        Ok(())
+                    }
+                    None => Err(format!("Task '{}' failed earlier in this run", task_name).into()),
+                }
+            }
+            .instrument(span),
+        )
+    }
+
+    /// Runs `task_name` and its dependencies once `run_task_with_deps` has
+    /// decided it should run in this invocation.
+    fn run_reached<'a>(
+        &'a self,
+        task_name: &'a str,
+        visited: &'a mut Vec<String>,
+        invocation: &'a Invocation,
+    ) -> TaskFuture<'a> {
+        Box::pin(async move {
+            if let Some(reason) = invocation.skipped.get(task_name) {
+                println!(
+                    "{} Skipping task '{}' ({})",
+                    self.theme.skipped(),
+                    task_name,
+                    reason
+                );
+                return Ok(());
+            }
+
+            if let Some(task) = self.taskfile.tasks.get(task_name) {
+                if let Some(reason) = task.not_here() {
+                    println!(
+                        "{} Skipping task '{}' ({})",
+                        self.theme.skipped(),
+                        task_name,
+                        reason
+                    );
+                    return Ok(());
+                }
+                if let Some(deps) = &task.depends_on {
+                    if let Some(dep) = deps.iter().find(|dep| !self.has_task(&dep.task)) {
+                        return Err(format!(
+                            "Dependency '{}' not found for task '{}'",
+                            dep.task, task_name
+                        )
+                        .into());
+                    }
+
+                    let parallel = task
+                        .depends_on_parallel
+                        .unwrap_or(self.taskfile.settings.depends_on_parallel);
+                    visited.push(task_name.to_string());
+                    if parallel && deps.len() > 1 {
+                        // Concurrent branches track their own paths, so a cycle
+                        // between them would wait forever instead of being
+                        // detected on the way down.
+                        if let Some(cycle) = self.taskfile.dependency_cycle(task_name) {
+                            return Err(format!(
+                                "Circular dependency detected: {}",
+                                cycle.join(" -> ")
+                            )
+                            .into());
+                        }
+                        let names: Vec<&str> = deps.iter().map(|dep| dep.task.as_str()).collect();
+                        let optional: Vec<&str> = deps
+                            .iter()
+                            .filter(|dep| dep.optional)
+                            .map(|dep| dep.task.as_str())
+                            .collect();
+                        self.run_concurrently(&names, &optional, visited, invocation)
+                            .await?;
+                    } else {
+                        for dep in deps {
+                            match self
+                                .run_task_with_deps(&dep.task, visited, invocation)
+                                .await
+                            {
+                                Err(e) if dep.optional => {
+                                    self.warn_optional_failed(&dep.task, task_name, &*e)
+                                }
+                                result => result?,
+                            }
+                        }
+                    }
+                    visited.pop();
+                }
+
+                // Dependencies only see their flag and param defaults and an
+                // empty `$ARGS`; arguments belong to the task that was invoked.
+                let invoked = (task_name == invocation.task).then_some(&invocation.flag_values);
+                let mut values =
+                    self.template_values(task_name, task, invoked, &invocation.git_vars)?;
+                for (name, value) in invocation.captured.lock().unwrap().iter() {
+                    values.entry(name.clone()).or_insert_with(|| value.clone());
+                    values.insert(format!("{}{}", CAPTURE_PREFIX, name), value.clone());
+                }
+                if let Some(name) = &task.capture {
+                    if !is_var_name(name) {
+                        return Err(format!(
+                            "Task '{}' has capture = '{}', which is not a valid variable name",
+                            task_name, name
+                        )
+                        .into());
+                    }
+                    if task.interactive {
+                        return Err(format!(
+                                "Task '{}' sets capture, but the output of an interactive task is not captured",
+                                task_name
+                            )
+                            .into());
+                    }
+                }
+                if !self.conditions_met(task_name, task, &values).await? {
+                    println!(
+                        "{} Skipping task '{}' (condition not met)",
+                        self.theme.skipped(),
+                        task_name
+                    );
+                    return Ok(());
+                }
+                let cache_key = task
+                    .cache_key
+                    .as_deref()
+                    .filter(|_| !self.in_session())
+                    .map(|key| self.render_cache_key(task_name, key, &values));
+                if let Some(key) = &cache_key {
+                    if let Some(output) =
+                        self.replay_if_current(task_name, key, task.capture.is_some())
+                    {
+                        self.capture(task, &output, invocation);
+                        return Ok(());
+                    }
+                }
+
+                // A task that declares `sources` is up to date when neither
+                // they nor anything else it depends on changed since it last
+                // succeeded, and its `outputs` are still there.
+                let track_sources = cache_key.is_none()
+                    && !self.in_session()
+                    && !task.sources.is_empty()
+                    && !task.background
+                    && task.capture.is_none();
+                // With `status` as well, both have to agree.
+                let check_status = !task.status.is_empty() && !self.in_session();
+                let sources =
+                    track_sources.then(|| self.sources_fingerprint(task_name, task, &values));
+                if (track_sources || check_status)
+                    && sources.as_ref().is_none_or(|fingerprint| {
+                        self.sources_current(task_name, task, fingerprint)
+                    })
+                    && self.status_met(task_name, task, &values).await?
+                {
+                    let reason = if task.status.is_empty() {
+                        "sources unchanged"
+                    } else {
+                        "status commands passed"
+                    };
+                    println!(
+                        "{} Task '{}' is up to date ({})",
+                        self.theme.success(),
+                        task_name,
+                        reason
+                    );
+                    return Ok(());
+                }
+
+                // Taken once the dependencies are done, so a task waiting on
+                // them never holds a slot they need.
+                let _slot = match &invocation.slots {
+                    Some(slots) => Some(slots.acquire().await?),
+                    None => None,
+                };
+                let fingerprint = self
+                    .records_history()
+                    .then(|| self.fingerprint(task_name, task, &values, CacheMethod::Checksum));
+                let started = self.now();
+                let started_at = SystemTime::now();
+                // Registered up front so services that fail to become healthy
+                // are still stopped.
+                if let Some(config) = &task.compose {
+                    if config.teardown && config.action == ComposeAction::Up {
+                        invocation
+                            .teardowns
+                            .lock()
+                            .unwrap()
+                            .push((task_name.to_string(), config.clone()));
+                    }
+                }
+                let hooks = &self.taskfile.hooks;
+                let env = self.env_for(task_name);
+                let named = |e: Box<dyn std::error::Error>| -> Box<dyn std::error::Error> {
+                    format!("Task '{}': {}", task_name, e).into()
+                };
+                let task_var = (TASK_VAR, task_name.to_string());
+                self.run_hooks(
+                    "before_each",
+                    &hooks.before_each,
+                    env,
+                    std::slice::from_ref(&task_var),
+                )
+                .await
+                .map_err(named)?;
+                // Only a dependency goes to the background; an invoked
+                // task is what the run waits for.
+                let background = task.background && !visited.is_empty();
+                let mut result = if background {
+                    self.start_background(task_name, task, values.clone())
+                        .await
+                        .map(|started| {
+                            invocation.background.lock().unwrap().push(started);
+                            None
+                        })
+                } else {
+                    self.execute(task_name, task, &values).await
+                };
+                let vars = [
+                    task_var,
+                    (STATUS_VAR, hook_status(&result).to_string()),
+                    (
+                        DURATION_VAR,
+                        receipt::duration_ms(self.elapsed(started)).to_string(),
+                    ),
+                ];
+                let after = self
+                    .run_hooks("after_each", &hooks.after_each, env, &vars)
+                    .await
+                    .map_err(named);
+                self.merge_hook_result(&mut result, after);
+                if let Some(fingerprint) = fingerprint.filter(|_| !background) {
+                    self.record_history(
+                        task_name,
+                        fingerprint,
+                        started_at,
+                        self.elapsed(started),
+                        &result,
+                    );
+                }
+                let output = match result {
+                    Ok(output) => output,
+                    Err(e) if task.ignore_errors => {
+                        eprintln!(
+                            "{} Ignoring failure of task '{}' (ignore_errors): {}",
+                            self.theme.warning(),
+                            task_name,
+                            e
+                        );
+                        None
+                    }
+                    Err(e) => return Err(e),
+                };
+                if let Some(output) = output {
+                    self.capture(task, &output, invocation);
+                    if let Some(key) = &cache_key {
+                        self.record_cache(task_name, key, &output);
+                    }
+                    if let Some(fingerprint) = &sources {
+                        self.record_sources(task_name, fingerprint);
+                    }
+                }
+                let elapsed = self.elapsed(started);
+                tracing::debug!(duration_ms = elapsed.as_millis() as u64, "Task finished");
+                invocation
+                    .durations
+                    .lock()
+                    .unwrap()
+                    .insert(task_name.to_string(), elapsed);
+                Ok(())
+            } else {
+                Err(format!("Task '{}' not found in Taskfile", task_name).into())
+            }
+        })
+    }
+
+    /// The program and arguments that run `cmd`, plus the program the
+    /// command itself starts (the first word, when it goes through a shell),
+    /// for `cmd_fallbacks` to check.
+    async fn prepare_command(
+        cmd: &str,
+        shell: Option<ShellKind>,
+    ) -> Result<Option<(String, Vec<String>, String)>, SplitError> {
+        let Some(kind) = shell else {
+            let Some((program, args)) = Self::resolve_command(cmd).await? else {
+                return Ok(None);
+            };
+            return Ok(Some((program.clone(), args, program)));
+        };
+        let Some(first) = command::program(cmd) else {
+            return Ok(None);
+        };
+        let args = vec![kind.command_flag().to_string(), cmd.to_string()];
+        Ok(Some((kind.binary().to_string(), args, first)))
+    }
+
+    /// Resolves `cmd` to a program and its arguments, preferring npm scripts
+    /// and `node_modules/.bin` binaries when they match the first word.
+    async fn resolve_command(cmd: &str) -> Result<Option<(String, Vec<String>)>, SplitError> {
+        let words = command::split(cmd)?;
+        let parts: Vec<&str> = words.iter().map(String::as_str).collect();
+        if parts.is_empty() {
+            return Ok(None);
+        }
+
+        #[cfg(feature = "node")]
+        if let Some((command, args)) = Self::resolve_node_command(&parts).await {
+            return Ok(Some((command, args)));
+        }
+        Ok(Some((
+            parts[0].to_string(),
+            parts[1..].iter().map(|s| s.to_string()).collect(),
+        )))
+    }
+
+    /// Runs npm scripts through the project's package manager and prefers
+    /// `node_modules/.bin` over `PATH`.
+    #[cfg(feature = "node")]
+    async fn resolve_node_command(parts: &[&str]) -> Option<(String, Vec<String>)> {
+        let (command, args): (String, Vec<&str>) =
+            if let Some(_npm_script) = Self::check_npm_script(parts[0]).await {
+                // If the first part is an npm script, run it with npm/yarn
+                let package_manager = if tokio::fs::try_exists("yarn.lock").await.unwrap_or(false) {
+                    "yarn"
+                } else if tokio::fs::try_exists("pnpm-lock.yaml")
+                    .await
+                    .unwrap_or(false)
+                {
+                    "pnpm"
+                } else {
+                    "npm"
+                };
+
+                let mut npm_args = vec!["run", parts[0]];
+                npm_args.extend_from_slice(&parts[1..]);
+                (package_manager.to_string(), npm_args)
+            } else {
+                let node_bin_path = format!("node_modules/.bin/{}", parts[0]);
+                if !tokio::fs::try_exists(&node_bin_path).await.unwrap_or(false) {
+                    return None;
+                }
+                (node_bin_path, parts[1..].to_vec())
+            };
+
+        Some((command, args.into_iter().map(String::from).collect()))
+    }
+
+    /// Renders the task's command and resolves its program. With
+    /// `cmd_fallbacks`, the first candidate whose program is installed wins.
+    async fn select_command(
+        &self,
+        task_name: &str,
+        candidates: &[&String],
+        shell: Option<ShellKind>,
+        values: &Values,
+    ) -> Result<(String, String, Vec<String>), Failure> {
+        let env = self.env_for(task_name);
+        // Pass-through arguments go where the command references them, or
+        // at the end of a `cmd` that does not.
+        let append = self.get_task(task_name).is_some_and(|t| t.cmds.is_empty());
+        let unparsable = |e: SplitError| {
+            Failure::permanent(format!(
+                "Cannot parse the command of task '{}': {} (quote it differently or set shell = true)",
+                task_name, e
+            ))
+        };
+        let render = |cmd: &str| {
+            // A shell does its own word splitting; otherwise the command is
+            // split before the values go in, so each stays within its word.
+            let splice = Splice::default();
+            let split_first = shell.is_none() && !command::is_script(cmd);
+            let mark = |value: &str| {
+                if split_first {
+                    splice.mark(value)
+                } else {
+                    value.to_string()
+                }
+            };
+            // Each pass-through argument is a word of its own, empty ones
+            // too: marked on its own, or quoted for the shell.
+            let args = if split_first {
+                values
+                    .args
+                    .iter()
+                    .map(|arg| {
+                        if arg.is_empty() {
+                            command::quote(arg)
+                        } else {
+                            splice.mark(arg)
+                        }
+                    })
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            } else {
+                quote_words(&values.args)
+            };
+            let mut cmd = match substitute_var(cmd, ARGS_VAR, &args) {
+                Some(cmd) => cmd,
+                None if append && !args.is_empty() => format!("{} {}", cmd, args),
+                None => cmd.to_string(),
+            };
+            for (key, value) in values.iter() {
+                if let Some(name) = key.strip_prefix(CAPTURE_PREFIX) {
+                    cmd = substitute_var(&cmd, name, &mark(value)).unwrap_or(cmd);
+                }
+            }
+            let cmd = template::render_with(&cmd, |key| values.get(key).map(|value| mark(value)));
+            let cmd = env.substitute_env_vars_with(&cmd, |value| mark(&value));
+            if split_first {
+                splice.split(&cmd).map_err(unparsable)
+            } else {
+                Ok(cmd)
+            }
+        };
+
+        let errexit = self.get_task(task_name).is_none_or(Task::errexit);
+        if let [only] = candidates {
+            let cmd = render(only)?;
+            let (shell, script) = Self::as_script(&cmd, shell, errexit);
+            let Some((program, args, _)) = Self::prepare_command(&script, shell)
+                .await
+                .map_err(unparsable)?
+            else {
+                return Err(Failure::permanent(format!(
+                    "Empty command for task '{}'",
+                    task_name
+                )));
+            };
+            return Ok((script_summary(&cmd), program, args));
+        }
+
+        let mut tried = Vec::new();
+        for candidate in candidates {
+            let cmd = render(candidate)?;
+            let (shell, script) = Self::as_script(&cmd, shell, errexit);
+            let Some((program, args, first)) = Self::prepare_command(&script, shell)
+                .await
+                .map_err(unparsable)?
+            else {
+                continue;
+            };
+            // Not the `set -e` in front of a script.
+            let first = if command::is_script(&cmd) {
+                command::program(&cmd).unwrap_or(first)
+            } else {
+                first
+            };
+            if shell::find_executable(&first, self.enhanced_path.as_deref()).is_some() {
+                return Ok((script_summary(&cmd), program, args));
+            }
+            tried.push(first);
+        }
+        Err(Failure::permanent(format!(
+            "No command for task '{}' is installed (tried {})",
+            task_name,
+            tried.join(", ")
+        )))
+    }
+
+    /// A command of several lines is a script rather than words to split:
+    /// it runs as a whole through `shell`, or the platform's, and with
+    /// `errexit` stops at its first failing line. Returns the shell and what
+    /// to give it; other commands come back as they are.
+    fn as_script(
+        cmd: &str,
+        shell: Option<ShellKind>,
+        errexit: bool,
+    ) -> (Option<ShellKind>, String) {
+        if !command::is_script(cmd) {
+            return (shell, cmd.to_string());
+        }
+        let kind = shell.unwrap_or_else(ShellKind::platform);
+        let script = match kind.errexit() {
+            // `cmd /C` only runs the first line, so chain them instead.
+            _ if kind == ShellKind::Cmd => cmd
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty())
+                .collect::<Vec<_>>()
+                .join(if errexit { " && " } else { " & " }),
+            Some(line) if errexit => format!("{}\n{}", line, cmd),
+            _ => cmd.to_string(),
+        };
+        (Some(kind), script)
+    }
+
+    /// Runs `run_if` and `skip_if` through the task's shell, or the platform
+    /// one, and reports whether the task should go ahead. Their output is
+    /// discarded; only the exit status counts.
+    async fn conditions_met(
+        &self,
+        task_name: &str,
+        task: &Task,
+        values: &Values,
+    ) -> Result<bool, Box<dyn std::error::Error>> {
+        for (field, condition, wanted) in [
+            ("run_if", &task.run_if, true),
+            ("skip_if", &task.skip_if, false),
+        ] {
+            let Some(condition) = condition else {
+                continue;
+            };
+            if self
+                .check(task_name, task, field, condition, None, values)
+                .await?
+                != wanted
+            {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    /// Whether every `status` command of the task exits 0, run like
+    /// `run_if` but in the task's directory. True when it has none.
+    async fn status_met(
+        &self,
+        task_name: &str,
+        task: &Task,
+        values: &Values,
+    ) -> Result<bool, Box<dyn std::error::Error>> {
+        let dir = self
+            .task_dir(task_name, task, None, values)
+            .map_err(|failure| failure.message)?;
+        for cmd in &task.status {
+            if !self
+                .check(task_name, task, "status", cmd, dir.as_deref(), values)
+                .await?
+            {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    /// Runs one of the task's `field` commands, e.g. `run_if`, with its
+    /// output discarded, and reports whether it exited 0.
+    async fn check(
+        &self,
+        task_name: &str,
+        task: &Task,
+        field: &str,
+        condition: &str,
+        dir: Option<&Path>,
+        values: &Values,
+    ) -> Result<bool, Box<dyn std::error::Error>> {
+        if let Some(replay) = &self.replay {
+            let recorded = replay.next(task_name, field).ok_or_else(|| {
+                format!(
+                    "The replayed session has no more {} checks of task '{}'",
+                    field, task_name
+                )
+            })?;
+            return Ok(recorded.outcome == session::Outcome::Exited(0));
+        }
+        let env = self.env_for(task_name);
+        let kind = task
+            .shell_kind(self.taskfile.default_shell())
+            .unwrap_or_else(ShellKind::platform);
+        let condition = env.substitute_env_vars(&template::render(condition, values));
+        let started = self.now();
+        let mut command = Command::new(kind.binary());
+        command
+            .args([kind.command_flag(), condition.as_str()])
+            .envs(env.vars())
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .kill_on_drop(true);
+        if let Some(dir) = dir {
+            command.current_dir(dir);
+        }
+        if let Some(path) = &self.enhanced_path {
+            command.env("PATH", path);
+        }
+        let status = command.status().await.map_err(|e| {
+            format!(
+                "Task '{}': could not run {} through {}: {}",
+                task_name, field, kind, e
+            )
+        })?;
+        let output = process::Completed {
+            termination: process::Termination::Exited(status),
+            stdout: Vec::new(),
+            stderr: Vec::new(),
+        };
+        self.record_step(
+            task_name,
+            field,
+            &env.redact(&condition),
+            self.elapsed(started),
+            &output,
+        );
+        Ok(status.success())
+    }
+
+    /// Runs the `[hooks]` commands listed under `point`, e.g. `before_each`,
+    /// through the default shell, or the platform one, with `vars` in their
+    /// environment and as `{{name}}` templates. Stops at the first that
+    /// fails.
+    async fn run_hooks(
+        &self,
+        point: &str,
+        cmds: &[String],
+        env: &EnvParser,
+        vars: &[(&str, String)],
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let kind = self
+            .taskfile
+            .default_shell()
+            .and_then(ShellSetting::kind)
+            .unwrap_or_else(ShellKind::platform);
+        let mut values = self.taskfile.vars.clone();
+        values.extend(
+            vars.iter()
+                .map(|(key, value)| (key.to_string(), value.clone())),
+        );
+        for cmd in cmds {
+            let cmd = env.substitute_env_vars(&template::render(cmd, &values));
+            println!(
+                "{} Running {} hook: {}",
+                self.theme.glyphs.running,
+                point,
+                env.redact(&cmd)
+            );
+            let mut command = Command::new(kind.binary());
+            command
+                .args([kind.command_flag(), cmd.as_str()])
+                .envs(env.vars())
+                .envs(vars.iter().map(|(key, value)| (key, value)))
+                .kill_on_drop(true);
+            if let Some(path) = &self.enhanced_path {
+                command.env("PATH", path);
+            }
+            let status = command
+                .status()
+                .await
+                .map_err(|e| format!("could not run {} hook through {}: {}", point, kind, e))?;
+            if !status.success() {
+                return Err(format!(
+                    "{} hook failed with exit code {}: {}",
+                    point,
+                    status.code().unwrap_or(-1),
+                    env.redact(&cmd)
+                )
+                .into());
+            }
+        }
+        Ok(())
+    }
+
+    /// Lets a failing after hook fail what it followed, unless that had
+    /// already failed; then the hook's error is only printed.
+    fn merge_hook_result<T>(
+        &self,
+        result: &mut Result<T, Box<dyn std::error::Error>>,
+        after: Result<(), Box<dyn std::error::Error>>,
+    ) {
+        if let Err(e) = after {
+            if result.is_ok() {
+                *result = Err(e);
+            } else {
+                tracing::warn!("{}", e);
+            }
+        }
+    }
+
+    /// Renders `cache_key` with the task's flag values and environment;
+    /// `{{VAR}}` works whatever the `[env] substitution` mode.
+    fn render_cache_key(&self, task_name: &str, key: &str, values: &Values) -> String {
+        let env = self.env_for(task_name);
+        let rendered = template::render_with(key, |name| {
+            values.get(name).cloned().or_else(|| env.get_env_var(name))
+        });
+        env.substitute_env_vars(&rendered)
+    }
+
+    fn output_cache(&self) -> OutputCache {
+        let max = self
+            .taskfile
+            .cache
+            .as_ref()
+            .and_then(|c| c.max_output_size)
+            .unwrap_or(cache::DEFAULT_MAX_OUTPUT_SIZE);
+        OutputCache::new(&self.base_dir, max)
+    }
+
+    /// When `key` matches the task's last successful run, reports the task as
+    /// up to date, replays that run's output and returns it (empty when it
+    /// is no longer stored). With `need_output`, a run whose output is gone
+    /// does not count as current, since there is nothing to `capture`.
+    fn replay_if_current(
+        &self,
+        task_name: &str,
+        key: &str,
+        need_output: bool,
+    ) -> Option<CachedOutput> {
+        if !KeyStore::new(&self.base_dir).is_current(task_name, key) {
+            return None;
+        }
+        let stored = self.output_cache().load(&output_entry(task_name, key));
+        if need_output && stored.is_none() {
+            return None;
+        }
+
+        println!(
+            "{} Task '{}' is up to date (cache key '{}')",
+            self.theme.success(),
+            task_name,
+            key
+        );
+        match &stored {
+            Some(output) if output.stdout.is_empty() && output.stderr.is_empty() => {}
+            Some(output) => {
+                let rule = self.theme.glyphs.rule;
+                println!(
+                    "{}",
+                    format!("{} replayed output from the cached run {}", rule, rule).dimmed()
+                );
+                print!("{}", String::from_utf8_lossy(&output.stdout));
+                eprint!("{}", String::from_utf8_lossy(&output.stderr));
+                println!(
+                    "{}",
+                    format!("{} end of replayed output {}", rule, rule).dimmed()
+                );
+            }
+            None => println!(
+                "{}",
+                "(output of the cached run is no longer stored)".dimmed()
+            ),
+        }
+        Some(stored.unwrap_or_default())
+    }
+
+    /// Keeps the task's trimmed stdout for later tasks when it sets
+    /// `capture`.
+    fn capture(&self, task: &Task, output: &CachedOutput, invocation: &Invocation) {
+        if let Some(name) = &task.capture {
+            let value = String::from_utf8_lossy(&output.stdout).trim().to_string();
+            invocation
+                .captured
+                .lock()
+                .unwrap()
+                .insert(name.clone(), value);
+        }
+    }
+
+    fn record_cache(&self, task_name: &str, key: &str, output: &CachedOutput) {
+        let result = KeyStore::new(&self.base_dir)
+            .record(task_name, key)
+            .and_then(|_| {
+                self.output_cache().store(
+                    &output_entry(task_name, key),
+                    &output.stdout,
+                    &output.stderr,
+                )
+            });
+        if let Err(e) = result {
+            tracing::warn!("Failed to cache result of task '{}': {}", task_name, e);
+        }
+    }
+
+    /// Whether `fingerprint` matches the one recorded at the task's last
+    /// success and every `outputs` pattern still matches a file.
+    fn sources_current(&self, task_name: &str, task: &Task, fingerprint: &str) -> bool {
+        let outputs_exist = task.outputs.iter().all(|pattern| {
+            glob::expand(&self.base_dir, std::slice::from_ref(pattern))
+                .is_ok_and(|found| !found.is_empty())
+        });
+        outputs_exist && KeyStore::for_sources(&self.base_dir).is_current(task_name, fingerprint)
+    }
+
+    /// Records the fingerprint taken before the task ran, once it
+    /// succeeded, so the next run can skip it. A source that changed while
+    /// the task ran makes it run again.
+    fn record_sources(&self, task_name: &str, fingerprint: &str) {
+        if let Err(e) = KeyStore::for_sources(&self.base_dir).record(task_name, fingerprint) {
+            tracing::warn!(
+                "Failed to record the sources of task '{}': {}",
+                task_name,
+                e
+            );
+        }
+    }
+
+    /// The fingerprint kept for a task with `sources`, marked with the
+    /// `method` that produced it, so switching methods is a change too.
+    fn sources_fingerprint(&self, task_name: &str, task: &Task, values: &Values) -> String {
+        format!(
+            "{}:{}",
+            task.method.as_str(),
+            self.fingerprint(task_name, task, values, task.method)
+        )
+    }
+
+    /// What the task's outcome should depend on, hashed so `task stats
+    /// --flaky` can compare runs and `sources` tasks can be skipped: its
+    /// commands, the values and variables passed in, and its `sources` as
+    /// `method` compares them, or the checked-out commit when it declares
+    /// none.
+    fn fingerprint(
+        &self,
+        task_name: &str,
+        task: &Task,
+        values: &Values,
+        method: CacheMethod,
+    ) -> String {
+        let mut bytes = Vec::new();
+        for cmd in task
+            .pre
+            .iter()
+            .chain(task.steps().into_iter().flatten())
+            .chain(&task.script)
+            .chain(&task.interpreter)
+            .chain(&task.post)
+        {
+            bytes.extend_from_slice(cmd.as_bytes());
+            bytes.push(0);
+        }
+        bytes.extend_from_slice(receipt::env_hash(values.iter()).as_bytes());
+        bytes.extend_from_slice(
+            receipt::env_hash(receipt::hashed_vars(
+                self.env_for(task_name).vars(),
+                RUN_ID_VAR,
+            ))
+            .as_bytes(),
+        );
+        if task.sources.is_empty() {
+            if let Some(head) = git::head(&self.base_dir) {
+                bytes.extend_from_slice(head.sha.as_bytes());
+            }
+        } else {
+            let contents = method == CacheMethod::Checksum;
+            bytes.extend_from_slice(&self.sources_hash(task, contents).to_le_bytes());
+        }
+        format!("{:016x}", output_cache::fnv1a(&bytes))
+    }
+
+    /// A hash of the task's `sources`: their paths and contents, or with
+    /// `contents` false only their paths, sizes and modification times,
+    /// which is cheap enough to poll.
+    fn sources_hash(&self, task: &Task, contents: bool) -> u64 {
+        let mut hash = output_cache::Fnv1a::default();
+        for path in glob::expand(&self.base_dir, &task.sources).unwrap_or_default() {
+            hash.update(path.to_string_lossy().as_bytes());
+            hash.update(&[0]);
+            let full = self.base_dir.join(&path);
+            if contents {
+                // Streamed, so large sources are not held in memory; the
+                // length keeps one file's end from running into the next.
+                let len = std::fs::File::open(&full)
+                    .and_then(|mut file| std::io::copy(&mut file, &mut hash))
+                    .unwrap_or(0);
+                hash.update(&len.to_le_bytes());
+            } else if let Ok(meta) = std::fs::metadata(&full) {
+                hash.update(&meta.len().to_le_bytes());
+                let modified = meta.modified().map(unix_nanos).unwrap_or(0);
+                hash.update(&modified.to_le_bytes());
+            }
+            hash.update(&[0]);
+        }
+        hash.finish()
+    }
+
+    fn records_history(&self) -> bool {
+        cfg!(feature = "records")
+            && self.options.record_history
+            && self.taskfile.settings.history != Some(false)
+            && env::var_os(NO_HISTORY_VAR).is_none()
+            && self.replay.is_none()
+    }
+
+    /// Adds the run to `.taskfile/history.jsonl` for `task stats`.
+    fn record_history<T, E>(
+        &self,
+        task_name: &str,
+        fingerprint: String,
+        started_at: SystemTime,
+        elapsed: Duration,
+        result: &Result<T, E>,
+    ) {
+        let entry = history::Entry {
+            task: task_name.to_string(),
+            run_id: self.run_id.clone(),
+            started_at: unix_secs(started_at),
+            duration_ms: receipt::duration_ms(elapsed),
+            success: result.is_ok(),
+            fingerprint,
+        };
+        if let Err(e) = history::append(&self.base_dir, &entry) {
+            tracing::warn!("Failed to record run of task '{}': {}", task_name, e);
+        }
+    }
+
+    /// Runs a task's command, retrying transient failures of idempotent tasks
+    /// when `auto_retry_transient` is enabled. Returns the captured output
+    /// when the task succeeded (rather than exiting with a skip code).
+    async fn execute(
+        &self,
+        task_name: &str,
+        task: &Task,
+        values: &Values,
+    ) -> Result<Option<CachedOutput>, Box<dyn std::error::Error>> {
+        if !task.cmds.is_empty() && (!task.cmd.is_empty() || !task.cmd_fallbacks.is_empty()) {
+            return Err(format!(
+                "Task '{}' sets cmds together with cmd or cmd_fallbacks; use one form",
+                task_name
+            )
+            .into());
+        }
+        if task.script.is_some()
+            && (!task.cmd.is_empty() || !task.cmds.is_empty() || !task.cmd_fallbacks.is_empty())
+        {
+            return Err(format!(
+                "Task '{}' sets script together with cmd, cmds or cmd_fallbacks; use one form",
+                task_name
+            )
+            .into());
+        }
+        if task.errexit.is_some()
+            && !task
+                .steps()
+                .iter()
+                .flatten()
+                .any(|cmd| command::is_script(cmd))
+        {
+            return Err(format!(
+                "Task '{}' sets errexit, which only applies to a cmd of several lines",
+                task_name
+            )
+            .into());
+        }
+        if task.interpreter.is_some() && task.script.is_none() {
+            return Err(format!(
+                "Task '{}' sets an interpreter but no script for it to run",
+                task_name
+            )
+            .into());
+        }
+        if task.script.is_some() && (task.remote_build || task.kubernetes.is_some()) {
+            return Err(format!(
+                "Task '{}' has a script, which remote_build and kubernetes cannot run; use cmd",
+                task_name
+            )
+            .into());
+        }
+
+        if task.elevate && (task.remote_build || task.kubernetes.is_some()) {
+            return Err(format!(
+                "Task '{}' sets elevate, which remote_build and kubernetes cannot honour",
+                task_name
+            )
+            .into());
+        }
+
+        if task.output_mode.is_some() && task.outputs.is_empty() {
+            return Err(format!(
+                "Task '{}' sets output_mode but no outputs for it to apply to",
+                task_name
+            )
+            .into());
+        }
+        if cfg!(not(unix)) && (task.umask.is_some() || task.output_mode.is_some()) {
+            tracing::warn!(
+                "Task '{}' sets umask or output_mode, which only apply on Unix",
+                task_name
+            );
+        }
+
+        self.check_sandbox(task_name, task, values)?;
+
+        // A replay runs nothing, so it needs none of what the commands do.
+        let live = self.replay.is_none();
+        if live && task.require_clean_git {
+            match git::is_dirty(&self.base_dir) {
+                Some(false) => {}
+                Some(true) => {
+                    return Err(format!(
+                        "Task '{}' requires a clean git tree; commit or stash your changes first",
+                        task_name
+                    )
+                    .into())
+                }
+                None => {
+                    return Err(format!(
+                        "Task '{}' requires a clean git tree, but {} is not in a git repository",
+                        task_name,
+                        self.base_dir.display()
+                    )
+                    .into())
+                }
+            }
+        }
+
+        if live && task.elevate && !elevate::is_elevated() {
+            self.authenticate(task_name).await?;
+        }
+
+        if let Some(kind) = task
+            .shell_kind(self.taskfile.default_shell())
+            .filter(|_| live)
+        {
+            if shell::find_executable(kind.binary(), self.enhanced_path.as_deref()).is_none() {
+                return Err(format!(
+                    "Task '{}' runs in {}, which is not installed (not found on PATH)",
+                    task_name, kind
+                )
+                .into());
+            }
+        }
+
+        let missing: Vec<&str> = task
+            .requires
+            .iter()
+            .map(Requirement::bin)
+            .filter(|bin| {
+                live && shell::find_executable(bin, self.enhanced_path.as_deref()).is_none()
+            })
+            .collect();
+        if !missing.is_empty() {
+            return Err(format!(
+                "Task '{}' requires {}, which could not be found on PATH",
+                task_name,
+                missing.join(", ")
+            )
+            .into());
+        }
+
+        if let Some(config) = &task.compose {
+            self.compose(task_name, config, config.action).await?;
+            if task.cmd.is_empty() && task.cmds.is_empty() && task.script.is_none() {
+                return Ok(Some(CachedOutput {
+                    stdout: Vec::new(),
+                    stderr: Vec::new(),
+                }));
+            }
+        }
+
+        // The release itself is never retried; only its hook is.
+        let released;
+        let values = if task.kind == TaskKind::Release {
+            released = self.release(task_name, task, values)?;
+            if task.cmd.is_empty() && task.cmds.is_empty() && task.script.is_none() {
+                return Ok(Some(CachedOutput {
+                    stdout: Vec::new(),
+                    stderr: Vec::new(),
+                }));
+            }
+            &released
+        } else {
+            values
+        };
+
+        if task.remote_build {
+            return self.run_remote(task_name, task, values).await;
+        }
+
+        if let Some(config) = &task.kubernetes {
+            return self
+                .run_on_kubernetes(task_name, task, config, values)
+                .await;
+        }
+
+        let attempts = if self.options.auto_retry_transient && task.idempotent {
+            TRANSIENT_RETRIES + 1
+        } else {
+            1
+        };
+
+        let mut attempt = 1;
+        loop {
+            match self.execute_once(task_name, task, values).await {
+                Ok(output) => {
+                    if output.is_some() && self.replay.is_none() {
+                        self.set_output_mode(task_name, task)?;
+                    }
+                    if let (Some(config), Some(_)) = (&task.changelog, &output) {
+                        self.record_changelog(task_name, config, values)?;
+                    }
+                    return Ok(output);
+                }
+                Err(failure) if failure.transient && attempt < attempts => {
+                    let delay = Duration::from_secs(1 << (attempt - 1));
+                    attempt += 1;
+                    eprintln!(
+                        "{} Retrying task '{}' after transient failure in {} (attempt {}/{})",
+                        self.theme.retry(),
+                        task_name,
+                        format_duration(delay),
+                        attempt,
+                        attempts
+                    );
+                    sleep(delay).await;
+                }
+                Err(failure) => return Err(failure.message.into()),
+            }
+        }
+    }
+
+    /// Says that `elevate` is about to ask for privileges, and has `sudo` ask
+    /// for the password now, before the spinner would draw over its prompt.
+    /// Once that has happened in this run, it only refreshes sudo's cached
+    /// credentials, which is also what keeps a long task's later commands
+    /// from finding them expired; it asks again only if they did expire.
+    async fn authenticate(&self, task_name: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let mut authenticated = self.sudo.lock().await;
+        if *authenticated && elevate::refresh().await {
+            return Ok(());
+        }
+        let how = if cfg!(windows) {
+            "confirm the UAC prompt for each of its commands"
+        } else {
+            "sudo may ask for your password"
+        };
+        eprintln!(
+            "{} Task '{}' runs as root; {}",
+            self.theme.warning(),
+            task_name,
+            how
+        );
+        match elevate::authenticate(task_name).await {
+            Ok(true) => {
+                *authenticated = true;
+                Ok(())
+            }
+            Ok(false) => {
+                Err(format!("Task '{}' needs root, but sudo did not grant it", task_name).into())
+            }
+            Err(e) => Err(format!(
+                "Task '{}' needs root, but sudo could not be started: {}",
+                task_name, e
+            )
+            .into()),
+        }
+    }
+
+    /// Warns when the task's commands name absolute paths outside the
+    /// project, or its `cwd` leaves it, as a Taskfile copied from elsewhere
+    /// might. Fails instead under `strict`, unless the task sets
+    /// `allow_outside`.
+    fn check_sandbox(
+        &self,
+        task_name: &str,
+        task: &Task,
+        values: &Values,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        if task.allow_outside {
+            return Ok(());
+        }
+        let root = self
+            .base_dir
+            .canonicalize()
+            .unwrap_or_else(|_| self.base_dir.clone());
+        let mut problems = Vec::new();
+        if let Some(cwd) = &task.cwd {
+            let cwd = self
+                .env_for(task_name)
+                .substitute_env_vars(&template::render(cwd, values));
+            if sandbox::escapes(&root.join(&cwd), &root) {
+                problems.push(format!("runs in {}, outside the project", cwd));
+            }
+        }
+        let mut paths: Vec<String> = Vec::new();
+        for cmd in task
+            .pre
+            .iter()
+            .chain(task.steps().into_iter().flatten())
+            .chain(&task.script)
+            .chain(&task.post)
+            .chain(&task.finally)
+        {
+            for path in sandbox::outside_paths(&template::render(cmd, values), &root) {
+                if !paths.contains(&path) {
+                    paths.push(path);
+                }
+            }
+        }
+        if !paths.is_empty() {
+            problems.push(format!("uses {} outside the project", paths.join(", ")));
+        }
+        if problems.is_empty() {
+            return Ok(());
+        }
+
+        let problem = format!("Task '{}' {}", task_name, problems.join(" and "));
+        if self.strict() {
+            return Err(format!(
+                "Refusing to run: {} (strict); set allow_outside = true if it is meant to",
+                problem
+            )
+            .into());
+        }
+        eprintln!(
+            "{} {}; set allow_outside = true if it is meant to",
+            self.theme.warning(),
+            problem
+        );
+        Ok(())
+    }
+
+    /// Gives the files matching the task's `outputs` its `output_mode`.
+    fn set_output_mode(
+        &self,
+        task_name: &str,
+        task: &Task,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let Some(mode) = task.output_mode else {
+            return Ok(());
+        };
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let files = glob::expand(&self.base_dir, &task.outputs).map_err(|e| {
+                format!("Failed to find the outputs of task '{}': {}", task_name, e)
+            })?;
+            for file in files {
+                std::fs::set_permissions(
+                    self.base_dir.join(&file),
+                    std::fs::Permissions::from_mode(mode),
+                )
+                .map_err(|e| {
+                    format!(
+                        "Failed to set the mode of {} for task '{}': {}",
+                        file.display(),
+                        task_name,
+                        e
+                    )
+                })?;
+            }
+        }
+        #[cfg(not(unix))]
+        let _ = (task_name, mode);
+        Ok(())
+    }
+
+    /// `docker compose` if the plugin is installed, else the standalone
+    /// `docker-compose`.
+    fn compose_program(&self) -> Option<(&'static str, &'static [&'static str])> {
+        let path = self.enhanced_path.as_deref();
+        if shell::find_executable("docker", path).is_some() {
+            Some(("docker", &["compose"]))
+        } else if shell::find_executable("docker-compose", path).is_some() {
+            Some(("docker-compose", &[]))
+        } else {
+            None
+        }
+    }
+
+    /// Runs `action` for a task's `compose` and, for `up`, waits until the
+    /// services are healthy, or running when they have no healthcheck.
+    async fn compose(
+        &self,
+        task_name: &str,
+        config: &ComposeConfig,
+        action: ComposeAction,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let (program, prefix) = self.compose_program().ok_or_else(|| {
+            format!(
+                "Task '{}' uses compose, but neither docker nor docker-compose is on PATH",
+                task_name
+            )
+        })?;
+        let shown = std::iter::once(program)
+            .chain(prefix.iter().copied())
+            .collect::<Vec<_>>()
+            .join(" ");
+        let mut args = config.args(action);
+        if self.options.offline && action == ComposeAction::Up {
+            // Fail on missing images instead of pulling them.
+            args.extend(["--pull".to_string(), "never".to_string()]);
+        }
+        println!(
+            "{} Running task '{}': {} {}",
+            self.theme.glyphs.running,
+            task_name,
+            shown,
+            args.join(" ")
+        );
+        let status = Command::new(program)
+            .args(prefix)
+            .args(&args)
+            .current_dir(&self.base_dir)
+            .stdin(Stdio::null())
+            .status()
+            .await
+            .map_err(|e| format!("Task '{}' failed to run {}: {}", task_name, program, e))?;
+        if !status.success() {
+            return Err(format!("Task '{}': compose failed ({})", task_name, status).into());
+        }
+        if action != ComposeAction::Up {
+            return Ok(());
+        }
+
+        let started = Instant::now();
+        let timeout = config.wait_timeout();
+        let target = config.service.as_deref().unwrap_or("services");
+        loop {
+            let output = Command::new(program)
+                .args(prefix)
+                .args(config.ps_args())
+                .current_dir(&self.base_dir)
+                .stdin(Stdio::null())
+                .output()
+                .await?;
+            match compose::parse_status(&String::from_utf8_lossy(&output.stdout)) {
+                compose::Status::Ready => {
+                    println!(
+                        "{} {} ready after {}",
+                        self.theme.success(),
+                        target,
+                        self.theme.good(&format_duration(started.elapsed()))
+                    );
+                    return Ok(());
+                }
+                compose::Status::Failed(reason) => {
+                    return Err(format!(
+                        "Task '{}': {}; see `{} -f {} logs`",
+                        task_name,
+                        reason,
+                        shown,
+                        config.file()
+                    )
+                    .into());
+                }
+                compose::Status::Starting if started.elapsed() >= timeout => {
+                    return Err(format!(
+                        "Task '{}': {} not healthy after {}",
+                        task_name,
+                        target,
+                        format_duration(timeout)
+                    )
+                    .into());
+                }
+                compose::Status::Starting => sleep(Duration::from_secs(1)).await,
+            }
+        }
+    }
+
+    /// Submits the task's commands as a Job, streams its pod's logs and maps
+    /// the container's exit code through `success_codes` and `skip_codes`
+    /// like a local run. `cmd_fallbacks` do not apply; the image decides
+    /// what is installed.
+    async fn run_on_kubernetes(
+        &self,
+        task_name: &str,
+        task: &Task,
+        config: &KubernetesConfig,
+        values: &Values,
+    ) -> Result<Option<CachedOutput>, Box<dyn std::error::Error>> {
+        if shell::find_executable("kubectl", self.enhanced_path.as_deref()).is_none() {
+            return Err(format!(
+                "Task '{}' runs on Kubernetes, but kubectl is not on PATH",
+                task_name
+            )
+            .into());
+        }
+        let env = self.env_for(task_name);
+        let script = task
+            .steps()
+            .iter()
+            .map(|candidates| env.substitute_env_vars(&template::render(candidates[0], values)))
+            .collect::<Vec<_>>()
+            .join(" && ");
+        let name = kubernetes::job_name(task_name, &self.run_id);
+        let manifest =
+            config.job_manifest(&name, task_name, &script, &[(RUN_ID_VAR, &self.run_id)]);
+        let namespace = config.namespace_args();
+
+        println!(
+            "{} Running task '{}' as Kubernetes Job {}: {}",
+            self.theme.glyphs.running,
+            task_name,
+            name,
+            env.redact(&script)
+        );
+        let started = Instant::now();
+
+        let mut apply = Command::new("kubectl")
+            .args(&namespace)
+            .args(["apply", "-f", "-"])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        if let Some(mut stdin) = apply.stdin.take() {
+            tokio::io::AsyncWriteExt::write_all(&mut stdin, manifest.to_string().as_bytes())
+                .await?;
+        }
+        let applied = apply.wait_with_output().await?;
+        if !applied.status.success() {
+            return Err(format!(
+                "Task '{}': kubectl apply failed: {}",
+                task_name,
+                String::from_utf8_lossy(&applied.stderr).trim()
+            )
+            .into());
+        }
+
+        // Logs go straight to the terminal; a Job's output is not cached.
+        let job = format!("job/{}", name);
+        Command::new("kubectl")
+            .args(&namespace)
+            .args(["logs", "--follow", "--pod-running-timeout=10m", &job])
+            .stdin(Stdio::null())
+            .status()
+            .await?;
+
+        let selector = format!("job-name={}", name);
+        let mut code = None;
+        for _ in 0..30 {
+            let output = Command::new("kubectl")
+                .args(&namespace)
+                .args([
+                    "get",
+                    "pods",
+                    "-l",
+                    &selector,
+                    "-o",
+                    "jsonpath={.items[0].status.containerStatuses[0].state.terminated.exitCode}",
+                ])
+                .stdin(Stdio::null())
+                .output()
+                .await?;
+            code = String::from_utf8_lossy(&output.stdout).trim().parse().ok();
+            if code.is_some() {
+                break;
+            }
+            sleep(Duration::from_secs(2)).await;
+        }
+        let elapsed = started.elapsed();
+        let Some(code) = code else {
+            return Err(format!(
+                "Task '{}': Job {} did not finish; see `kubectl describe {}`",
+                task_name, name, job
+            )
+            .into());
+        };
+
+        self.report_remote_exit(task_name, task, code, elapsed)
+            .map(|ran| ran.then(CachedOutput::default))
+    }
+
+    /// Reports the exit code of a command that ran elsewhere, whose output
+    /// was streamed rather than captured. Returns whether it succeeded
+    /// (rather than exiting with a skip code).
+    fn report_remote_exit(
+        &self,
+        task_name: &str,
+        task: &Task,
+        code: i32,
+        elapsed: Duration,
+    ) -> Result<bool, Box<dyn std::error::Error>> {
+        match task.exit_outcome(Some(code)) {
+            ExitOutcome::Success => {
+                println!(
+                    "{} Task '{}' completed successfully in {}",
+                    self.theme.success(),
+                    task_name,
+                    self.theme.good(&format_duration(elapsed))
+                );
+                Ok(true)
+            }
+            ExitOutcome::Skipped => {
+                println!(
+                    "{} Task '{}' skipped (exit code {}) after {}",
+                    self.theme.skip_code(),
+                    task_name,
+                    code,
+                    format_duration(elapsed)
+                );
+                Ok(false)
+            }
+            ExitOutcome::Failed => {
+                eprintln!(
+                    "{} Task '{}' failed with exit code {} after {}",
+                    self.theme.failure(),
+                    task_name,
+                    code,
+                    self.theme.bad(&format_duration(elapsed))
+                );
+                Err(format!("Task '{}' failed with exit code {}", task_name, code).into())
+            }
+        }
+    }
+
+    /// Runs the task on the `[remote_builder]`: its `sources` are synced
+    /// there with rsync, its commands run over SSH from the synced
+    /// directory, and its `outputs` are synced back if it succeeds.
+    async fn run_remote(
+        &self,
+        task_name: &str,
+        task: &Task,
+        values: &Values,
+    ) -> Result<Option<CachedOutput>, Box<dyn std::error::Error>> {
+        let builder = self.taskfile.remote_builder.as_ref().ok_or_else(|| {
+            format!(
+                "Task '{}' sets remote_build, but the Taskfile has no [remote_builder]",
+                task_name
+            )
+        })?;
+        if task.sources.is_empty() {
+            return Err(format!(
+                "Task '{}' sets remote_build but declares no sources to sync",
+                task_name
+            )
+            .into());
+        }
+        for program in ["ssh", "rsync"] {
+            if shell::find_executable(program, self.enhanced_path.as_deref()).is_none() {
+                return Err(format!(
+                    "Task '{}' builds remotely, but {} is not on PATH",
+                    task_name, program
+                )
+                .into());
+            }
+        }
+
+        let project = self
+            .base_dir
+            .canonicalize()
+            .ok()
+            .and_then(|dir| dir.file_name().map(|n| n.to_string_lossy().into_owned()))
+            .unwrap_or_else(|| "project".to_string());
+        let dir = builder.dir(&project);
+        let files = glob::expand(&self.base_dir, &task.sources)?;
+        println!(
+            "{} Syncing {} {} for task '{}' to {}:{}",
+            self.theme.glyphs.running,
+            files.len(),
+            if files.len() == 1 { "file" } else { "files" },
+            task_name,
+            builder.host,
+            dir
+        );
+
+        let ssh = |script: String| {
+            let mut command = Command::new("ssh");
+            command
+                .args(builder.ssh_options())
+                .arg(&builder.host)
+                .arg(script)
+                .stdin(Stdio::null());
+            command
+        };
+        let status = ssh(format!("mkdir -p {}", remote::shell_quote(&dir)))
+            .status()
+            .await?;
+        if !status.success() {
+            return Err(format!(
+                "Task '{}': could not reach {} ({})",
+                task_name, builder.host, status
+            )
+            .into());
+        }
+
+        let mut sync = Command::new("rsync")
+            .args(["-az", "--relative", "--files-from=-", "-e"])
+            .arg(builder.rsync_shell())
+            .arg(".")
+            .arg(format!("{}:{}/", builder.host, dir))
+            .current_dir(&self.base_dir)
+            .stdin(Stdio::piped())
+            .spawn()?;
+        if let Some(mut stdin) = sync.stdin.take() {
+            let list: String = files
+                .iter()
+                .map(|file| format!("{}\n", file.display()))
+                .collect();
+            tokio::io::AsyncWriteExt::write_all(&mut stdin, list.as_bytes()).await?;
+        }
+        let status = sync.wait().await?;
+        if !status.success() {
+            return Err(format!(
+                "Task '{}': syncing sources to {} failed ({})",
+                task_name, builder.host, status
+            )
+            .into());
+        }
+
+        let env = self.env_for(task_name);
+        let script = task
+            .steps()
+            .iter()
+            .map(|candidates| env.substitute_env_vars(&template::render(candidates[0], values)))
+            .collect::<Vec<_>>()
+            .join(" && ");
+        println!(
+            "{} Running task '{}' on {}: {}",
+            self.theme.glyphs.running,
+            task_name,
+            builder.host,
+            env.redact(&script)
+        );
+        let started = Instant::now();
+        let status = ssh(format!("cd {} && {}", remote::shell_quote(&dir), script))
+            .status()
+            .await?;
+        let elapsed = started.elapsed();
+        // ssh itself exits 255 when the connection fails.
+        let code = match status.code() {
+            Some(255) | None => {
+                return Err(format!(
+                    "Task '{}': lost the connection to {} ({})",
+                    task_name, builder.host, status
+                )
+                .into())
+            }
+            Some(code) => code,
+        };
+        if !self.report_remote_exit(task_name, task, code, elapsed)? {
+            return Ok(None);
+        }
+
+        if !task.outputs.is_empty() {
+            let status = Command::new("rsync")
+                .args(["-az", "-e"])
+                .arg(builder.rsync_shell())
+                .args(remote::output_filters(&task.outputs))
+                .arg(format!("{}:{}/", builder.host, dir))
+                .arg(".")
+                .current_dir(&self.base_dir)
+                .stdin(Stdio::null())
+                .status()
+                .await?;
+            if !status.success() {
+                return Err(format!(
+                    "Failed to sync outputs of task '{}' back from {} ({})",
+                    task_name, builder.host, status
+                )
+                .into());
+            }
+        }
+        Ok(Some(CachedOutput::default()))
+    }
+
+    /// Spawns a `background` task's `cmd` in its own process group, with
+    /// its output going to `.taskfile/background/<task>.log`.
+    async fn start_background(
+        &self,
+        task_name: &str,
+        task: &Task,
+        values: Values,
+    ) -> Result<Background, Box<dyn std::error::Error>> {
+        if task.cmd.is_empty()
+            || !task.cmds.is_empty()
+            || !task.pre.is_empty()
+            || !task.post.is_empty()
+            || task.interactive
+            || task.capture.is_some()
+            || task.remote_build
+            || task.kubernetes.is_some()
+        {
+            return Err(format!(
+                "Task '{}' runs in the background, which needs a local cmd without cmds, pre, post, interactive or capture",
+                task_name
+            )
+            .into());
+        }
+        let dir = self
+            .task_dir(task_name, task, None, &values)
+            .map_err(|failure| failure.message)?;
+        let steps = task.steps();
+        let (substituted_cmd, program, args) = self
+            .select_command(
+                task_name,
+                &steps[0],
+                task.shell_kind(self.taskfile.default_shell()),
+                &values,
+            )
+            .await
+            .map_err(|failure| failure.message)?;
+
+        let log_dir = self.base_dir.join(".taskfile").join("background");
+        let log = log_dir.join(format!("{}.log", task_name.replace([':', '/', '\\'], "_")));
+        let open_log = || -> std::io::Result<std::fs::File> {
+            std::fs::create_dir_all(&log_dir)?;
+            std::fs::File::create(&log)
+        };
+        let stdout =
+            open_log().map_err(|e| format!("Failed to create {}: {}", log.display(), e))?;
+        let stderr = stdout.try_clone()?;
+
+        let mut cmd = self.command(task_name, task, &program, &args, dir.as_deref(), &values);
+        cmd.stdin(Stdio::null())
+            .stdout(stdout)
+            .stderr(stderr)
+            .kill_on_drop(true);
+        let tree = process::spawn(&mut cmd)
+            .map_err(|e| format!("Failed to start task '{}': {}", task_name, e))?;
+        println!(
+            "{} Started task '{}' in the background: {} (output in {})",
+            self.theme.glyphs.running,
+            task_name,
+            self.env_for(task_name).redact(&substituted_cmd),
+            log.display()
+        );
+        Ok(Background {
+            task: task_name.to_string(),
+            tree,
+            dir,
+            values,
+            log,
+        })
+    }
+
+    /// Stops the `background` tasks that were started, latest first, then
+    /// runs their `finally` commands. One that already exited is reported,
+    /// since whatever depended on it probably failed for that reason.
+    async fn stop_background(&self, started: Vec<Background>) {
+        for mut background in started.into_iter().rev() {
+            match background.tree.child.try_wait() {
+                Ok(Some(status)) => eprintln!(
+                    "{} Background task '{}' had already exited ({}); see {}",
+                    self.theme.warning(),
+                    background.task,
+                    status,
+                    background.log.display()
+                ),
+                _ => {
+                    process::kill_tree(&mut background.tree).await;
+                    println!(
+                        "{} Stopped background task '{}'",
+                        self.theme.glyphs.running, background.task
+                    );
+                }
+            }
+            if let Some(task) = self.get_task(&background.task) {
+                self.run_finally(
+                    &background.task,
+                    task,
+                    background.dir.as_deref(),
+                    &background.values,
+                )
+                .await;
+            }
+        }
+    }
+
+    /// Undoes the `compose` tasks with `teardown` that ran, latest first.
+    /// Failures are reported but do not change the run's result.
+    async fn run_teardowns(&self, teardowns: Vec<(String, ComposeConfig)>) {
+        for (task_name, config) in teardowns.into_iter().rev() {
+            if let Err(e) = self
+                .compose(&task_name, &config, config.teardown_action())
+                .await
+            {
+                eprintln!("{} Teardown failed: {}", self.theme.warning(), e);
+            }
+        }
+    }
+
+    /// Bumps the version in the task's manifests, commits them and tags the
+    /// commit, returning `values` with `release.version`, `release.previous`
+    /// and `release.tag` added for the hook.
+    fn release(
+        &self,
+        task_name: &str,
+        task: &Task,
+        values: &Values,
+    ) -> Result<Values, Box<dyn std::error::Error>> {
+        let files: Vec<PathBuf> = if task.version_files.is_empty() {
+            release::DEFAULT_VERSION_FILES
+                .iter()
+                .map(PathBuf::from)
+                .filter(|file| self.base_dir.join(file).exists())
+                .collect()
+        } else {
+            task.version_files.iter().map(PathBuf::from).collect()
+        };
+        let Some(first) = files.first() else {
+            return Err(format!(
+                "Release task '{}' found no Cargo.toml or package.json; set version_files",
+                task_name
+            )
+            .into());
+        };
+
+        let previous = release::read_version(&self.base_dir.join(first))?;
+        let bump = values
+            .get("flags.bump")
+            .map(String::as_str)
+            .unwrap_or("patch");
+        let version = release::bump(&previous, bump)
+            .map_err(|e| format!("Release task '{}': {}", task_name, e))?;
+
+        let mut values = values.clone();
+        values.insert("release.version".to_string(), version.clone());
+        values.insert("release.previous".to_string(), previous.clone());
+        let tag = template::render(task.tag.as_deref().unwrap_or(release::DEFAULT_TAG), &values);
+        values.insert("release.tag".to_string(), tag.clone());
+
+        match git::is_dirty(&self.base_dir) {
+            Some(false) => {}
+            Some(true) => {
+                return Err(format!(
+                "Release task '{}' requires a clean git tree; commit or stash your changes first",
+                task_name
+            )
+                .into())
+            }
+            None => {
+                return Err(format!(
+                    "Release task '{}' needs a git repository to tag, but {} is not in one",
+                    task_name,
+                    self.base_dir.display()
+                )
+                .into())
+            }
+        }
+        if git::tag_exists(&self.base_dir, &tag) {
+            return Err(
+                format!("Release task '{}': tag '{}' already exists", task_name, tag).into(),
+            );
+        }
+
+        let mut files = files;
+        let mut originals = Vec::new();
+        for file in &files {
+            let path = self.base_dir.join(file);
+            let text = std::fs::read_to_string(&path)
+                .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+            originals.push((path, text));
+        }
+        let changelog = self.base_dir.join(changelog::DEFAULT_FILE);
+        let released_changelog = std::fs::read_to_string(&changelog).ok().and_then(|text| {
+            let released = changelog::release(&text, &version, &changelog::today())?;
+            originals.push((changelog.clone(), text));
+            Some(released)
+        });
+        if released_changelog.is_some() {
+            files.push(PathBuf::from(changelog::DEFAULT_FILE));
+        }
+
+        let message = format!("Release {}", tag);
+        let committed = originals
+            .iter()
+            .filter(|(path, _)| *path != changelog)
+            .try_for_each(|(path, _)| release::write_version(path, &version))
+            .and_then(|()| match &released_changelog {
+                Some(text) => std::fs::write(&changelog, text)
+                    .map_err(|e| format!("Failed to write {}: {}", changelog.display(), e)),
+                None => Ok(()),
+            })
+            .and_then(|()| git::commit_paths(&self.base_dir, &message, &files));
+        if let Err(e) = committed {
+            // Leave the tree as it was so the release can simply be re-run.
+            for (path, text) in &originals {
+                let _ = std::fs::write(path, text);
+            }
+            return Err(format!("Release task '{}' failed to commit: {}", task_name, e).into());
+        }
+        git::annotated_tag(&self.base_dir, &tag, &message)
+            .map_err(|e| format!("Release task '{}' failed to tag: {}", task_name, e))?;
+
+        println!(
+            "{} Released {} -> {} (tagged {})",
+            self.theme.success(),
+            previous,
+            version.bold(),
+            tag
+        );
+        Ok(values)
+    }
+
+    /// Adds the entry for a task's `changelog`, asking for the message on
+    /// the terminal unless `message` provides one. Not being able to ask, or
+    /// getting an empty answer, skips the entry rather than failing the task.
+    fn record_changelog(
+        &self,
+        task_name: &str,
+        config: &ChangelogConfig,
+        values: &Values,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let mut message = config
+            .message
+            .as_deref()
+            .map(|message| template::render(message, values).trim().to_string())
+            .unwrap_or_default();
+        if message.is_empty() && std::io::stdin().is_terminal() {
+            print!("{} {}: ", "?".cyan(), config.prompt());
+            std::io::Write::flush(&mut std::io::stdout())?;
+            let mut line = String::new();
+            std::io::stdin().read_line(&mut line)?;
+            message = line.trim().to_string();
+        }
+        if message.is_empty() {
+            println!(
+                "{} No changelog message for task '{}'; nothing added",
+                self.theme.warning(),
+                task_name
+            );
+            return Ok(());
+        }
+
+        let path = self.base_dir.join(config.file());
+        let text = std::fs::read_to_string(&path).unwrap_or_default();
+        std::fs::write(
+            &path,
+            changelog::add_entry(&text, &config.section, &message),
+        )
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+        println!(
+            "{} Added to {} under {}: {}",
+            self.theme.success(),
+            config.file(),
+            config.section,
+            message
+        );
+        Ok(())
+    }
+
+    async fn execute_once(
+        &self,
+        task_name: &str,
+        task: &Task,
+        values: &Values,
+    ) -> Result<Option<CachedOutput>, Failure> {
+        let script = self.write_script(task_name, task, values)?;
+        let steps = match script {
+            // The script is the one command; it has no fallbacks.
+            Some(_) => vec![Vec::new()],
+            None => task.steps(),
+        };
+        let workdir = self.prepare_workdir(task_name, task, values)?;
+        let workdir_path = workdir.as_ref().map(Workdir::path);
+        let dir = self.task_dir(task_name, task, workdir_path.as_deref(), values)?;
+        let start_time = self.now();
+        let started_at = SystemTime::now();
+
+        let mut shown = Vec::new();
+        let mut captured = CachedOutput {
+            stdout: Vec::new(),
+            stderr: Vec::new(),
+        };
+        let pre: Vec<Vec<&String>> = task.pre.iter().map(|cmd| vec![cmd]).collect();
+        let post: Vec<Vec<&String>> = task.post.iter().map(|cmd| vec![cmd]).collect();
+        let mut end = process::Termination::Exited(Default::default());
+        // The hook that exited non-zero, failing a task whose own commands
+        // did not fail.
+        let mut failed_hook = None;
+        let mut main_failed = false;
+        let mut post_values;
+        'phases: for (phase, steps) in [
+            (Phase::Pre, &pre),
+            (Phase::Main, &steps),
+            (Phase::Post, &post),
+        ] {
+            let values = if phase == Phase::Post {
+                // Post hooks only follow commands that exited, and see how.
+                let process::Termination::Exited(status) = &end else {
+                    break;
+                };
+                main_failed = task.exit_outcome(status.code()) != ExitOutcome::Success;
+                post_values = values.clone();
+                post_values.insert(
+                    EXIT_CODE_VAR.to_string(),
+                    status.code().unwrap_or(-1).to_string(),
+                );
+                &post_values
+            } else {
+                values
+            };
+            for (index, candidates) in steps.iter().enumerate() {
+                let step = Step {
+                    candidates,
+                    script: script
+                        .as_ref()
+                        .filter(|_| phase == Phase::Main)
+                        .map(|(words, file)| (words.as_slice(), file.path())),
+                    label: phase.label(index, steps.len()),
+                    timeout: task
+                        .timeout
+                        .map(|limit| limit.saturating_sub(self.elapsed(start_time))),
+                };
+                let (shown_cmd, output) = match self
+                    .run_step(task_name, task, &step, dir.as_deref(), values)
+                    .await
+                {
+                    Ok(ran) => ran,
+                    Err(failure) => {
+                        self.run_finally(task_name, task, dir.as_deref(), values)
+                            .await;
+                        return Err(failure);
+                    }
+                };
+                shown.push(shown_cmd);
+                captured.stdout.extend(output.stdout);
+                captured.stderr.extend(output.stderr);
+
+                let failed = match &output.termination {
+                    process::Termination::Exited(status) if phase == Phase::Main => {
+                        task.exit_outcome(status.code()) != ExitOutcome::Success
+                    }
+                    process::Termination::Exited(status) => !status.success(),
+                    _ => true,
+                };
+                // A post hook cannot hide the failure of the commands it
+                // follows.
+                if !main_failed {
+                    match output.termination {
+                        process::Termination::Exited(status) if phase != Phase::Main => {
+                            if failed {
+                                failed_hook = Some((phase, status.code().unwrap_or(-1)));
+                            }
+                        }
+                        termination => end = termination,
+                    }
+                }
+                if failed && phase == Phase::Main {
+                    break;
+                } else if failed {
+                    break 'phases;
+                }
+            }
+        }
+        let elapsed = self.elapsed(start_time);
+        self.run_finally(task_name, task, dir.as_deref(), values)
+            .await;
+
+        if task.receipt && self.replay.is_none() {
+            let exit_code = match &end {
+                process::Termination::Exited(status) => status.code(),
+                _ => None,
+            };
+            self.write_receipt(
+                task_name,
+                task,
+                &shown.join(" && "),
+                started_at,
+                elapsed,
+                exit_code,
+            );
+        }
+
+        let status = match end {
+            process::Termination::Exited(status) => status,
+            process::Termination::Interrupted => {
+                eprintln!(
+                    "{} Task '{}' interrupted after {}",
+                    self.theme.failure(),
+                    task_name,
+                    self.theme.bad(&format_duration(elapsed))
+                );
+                return Err(Failure::permanent(format!(
+                    "Task '{}' was interrupted",
+                    task_name
+                )));
+            }
+            process::Termination::TimedOut => {
+                let limit = format_duration(task.timeout.unwrap_or_default());
+                eprintln!(
+                    "{} Task '{}' timed out: killed after {}",
+                    self.theme.failure(),
+                    task_name,
+                    self.theme.bad(&limit)
+                );
+                return Err(Failure::permanent(format!(
+                    "Task '{}' timed out after {}",
+                    task_name, limit
+                )));
+            }
+            process::Termination::Stalled(limit) => {
+                eprintln!(
+                    "{} Task '{}' stalled: no output for {}, killed after {}",
+                    self.theme.failure(),
+                    task_name,
+                    format_duration(limit),
+                    self.theme.bad(&format_duration(elapsed))
+                );
+                return Err(Failure {
+                    message: format!(
+                        "Task '{}' stalled (no output for {})",
+                        task_name,
+                        format_duration(limit)
+                    ),
+                    transient: true,
+                });
+            }
+        };
+
+        if let Some((phase, code)) = failed_hook {
+            eprintln!(
+                "{} Task '{}' failed: a {} hook exited with code {} after {}",
+                self.theme.failure(),
+                task_name,
+                phase,
+                code,
+                self.theme.bad(&format_duration(elapsed))
+            );
+            return Err(Failure::permanent(format!(
+                "Task '{}' failed: a {} hook exited with code {}",
+                task_name, phase, code
+            )));
+        }
+
+        match task.exit_outcome(status.code()) {
+            ExitOutcome::Success => {
+                if let Some(path) = &workdir_path {
+                    workdir::copy_matching(path, &self.base_dir, &task.outputs).map_err(|e| {
+                        Failure::permanent(format!(
+                            "Failed to copy outputs of task '{}' back: {}",
+                            task_name, e
+                        ))
+                    })?;
+                }
+                self.suspend_spinners(|| {
+                    println!(
+                        "{} Task '{}' completed successfully in {}",
+                        self.theme.success(),
+                        task_name,
+                        self.theme.good(&format_duration(elapsed))
+                    )
+                });
+                return Ok(Some(captured));
+            }
+            ExitOutcome::Skipped => {
+                self.suspend_spinners(|| {
+                    println!(
+                        "{} Task '{}' skipped (exit code {}) after {}",
+                        self.theme.skip_code(),
+                        task_name,
+                        status.code().unwrap_or(-1),
+                        format_duration(elapsed)
+                    )
+                });
+                return Ok(None);
+            }
+            ExitOutcome::Failed => {}
+        }
+
+        let code = status.code().unwrap_or(-1);
+        self.suspend_spinners(|| {
+            eprintln!(
+                "{} Task '{}' failed with exit code {} after {}",
+                self.theme.failure(),
+                task_name,
+                code,
+                self.theme.bad(&format_duration(elapsed))
+            )
+        });
+        Err(Failure {
+            message: format!("Task '{}' failed with exit code {}", task_name, code),
+            transient: is_transient(&status),
+        })
+    }
+
+    /// Runs the task's `finally` commands, all of them even when one fails.
+    /// Failures are only reported; the task's result is its own commands'.
+    async fn run_finally(&self, task_name: &str, task: &Task, dir: Option<&Path>, values: &Values) {
+        for (index, cmd) in task.finally.iter().enumerate() {
+            let step = Step {
+                candidates: &[cmd],
+                script: None,
+                label: Phase::Finally.label(index, task.finally.len()),
+                timeout: None,
+            };
+            let problem = match self.run_step(task_name, task, &step, dir, values).await {
+                Ok((_, output)) => match output.termination {
+                    process::Termination::Exited(status) if status.success() => continue,
+                    process::Termination::Exited(status) => {
+                        format!("exit code {}", status.code().unwrap_or(-1))
+                    }
+                    process::Termination::Interrupted => "interrupted".to_string(),
+                    process::Termination::TimedOut => "timed out".to_string(),
+                    process::Termination::Stalled(limit) => {
+                        format!("no output for {}", format_duration(limit))
+                    }
+                },
+                Err(failure) => failure.message,
+            };
+            eprintln!(
+                "{} Cleanup command of task '{}' failed ({}): {}",
+                self.theme.warning(),
+                task_name,
+                problem,
+                cmd
+            );
+        }
+    }
+
+    /// Where the task's commands run: its `cwd`, relative to `workdir` or
+    /// the project, or else `workdir` itself (`None` for the project).
+    fn task_dir(
+        &self,
+        task_name: &str,
+        task: &Task,
+        workdir: Option<&Path>,
+        values: &Values,
+    ) -> Result<Option<PathBuf>, Failure> {
+        let Some(cwd) = &task.cwd else {
+            return Ok(workdir.map(Path::to_path_buf));
+        };
+        let cwd = self
+            .env_for(task_name)
+            .substitute_env_vars(&template::render(cwd, values));
+        let dir = workdir.unwrap_or(&self.base_dir).join(cwd);
+        if !dir.is_dir() {
+            return Err(Failure::permanent(format!(
+                "Task '{}' runs in {}, which is not a directory",
+                task_name,
+                dir.display()
+            )));
+        }
+        Ok(Some(dir))
+    }
+
+    /// The directory the task runs in when it sets `git_ref` (a temporary
+    /// worktree) or `isolate_workdir` (a copy of its `sources`).
+    fn prepare_workdir(
+        &self,
+        task_name: &str,
+        task: &Task,
+        values: &Values,
+    ) -> Result<Option<Workdir>, Failure> {
+        if let Some(git_ref) = &task.git_ref {
+            if task.isolate_workdir {
+                return Err(Failure::permanent(format!(
+                    "Task '{}' sets both git_ref and isolate_workdir; use one",
+                    task_name
+                )));
+            }
+            let git_ref = template::render(git_ref, values);
+            let worktree =
+                git::Worktree::add(&self.base_dir, &git_ref, task_name).map_err(|e| {
+                    Failure::permanent(format!(
+                        "Failed to check out '{}' for task '{}': {}",
+                        git_ref, task_name, e
+                    ))
+                })?;
+            println!(
+                "{} Running task '{}' in a worktree of {}",
+                self.theme.glyphs.running, task_name, git_ref
+            );
+            return Ok(Some(Workdir::Worktree(worktree)));
+        }
+
+        if !task.isolate_workdir {
+            return Ok(None);
+        }
+        if task.sources.is_empty() {
+            return Err(Failure::permanent(format!(
+                "Task '{}' sets isolate_workdir but declares no sources to copy",
+                task_name
+            )));
+        }
+        let failed = |e: std::io::Error| {
+            Failure::permanent(format!(
+                "Failed to prepare an isolated workdir for task '{}': {}",
+                task_name, e
+            ))
+        };
+        let workdir = workdir::TempWorkdir::create(task_name).map_err(failed)?;
+        workdir::copy_matching(&self.base_dir, workdir.path(), &task.sources).map_err(failed)?;
+        Ok(Some(Workdir::Copy(workdir)))
+    }
+
+    /// Runs one of the task's commands, picking the first installed one of
+    /// the step's candidates. Returns the command as echoed, with secrets
+    /// masked.
+    async fn run_step(
+        &self,
+        task_name: &str,
+        task: &Task,
+        step: &Step<'_>,
+        workdir: Option<&Path>,
+        values: &Values,
+    ) -> Result<(String, process::Completed), Failure> {
+        let env = self.env_for(task_name);
+        let (substituted_cmd, command, args) = match step.script {
+            Some((interpreter, path)) => Self::script_invocation(interpreter, path, values),
+            None => {
+                self.select_command(
+                    task_name,
+                    step.candidates,
+                    task.shell_kind(self.taskfile.default_shell()),
+                    values,
+                )
+                .await?
+            }
+        };
+        let shown_cmd = env.redact(&substituted_cmd);
+
+        let plain = self.options.output == OutputMode::Plain;
+
+        if task.elevate && !elevate::is_elevated() && self.replay.is_none() {
+            self.authenticate(task_name)
+                .await
+                .map_err(|e| Failure::permanent(e.to_string()))?;
+        }
+        let mut cmd = self.command(task_name, task, &command, &args, workdir, values);
+        if task.interactive {
+            cmd.stdin(Stdio::inherit())
+                .stdout(Stdio::inherit())
+                .stderr(Stdio::inherit());
+        } else {
+            cmd.stdout(Stdio::piped()).stderr(Stdio::piped());
+        }
+
+        let heartbeat = |elapsed: Duration| {
+            println!(
+                "... still running '{}' ({} elapsed)",
+                task_name,
+                format_duration_coarse(elapsed)
+            );
+        };
+        let options = process::Options {
+            no_output_timeout: task.no_output_timeout,
+            timeout: step.timeout,
+            stream: plain,
+            heartbeat: plain.then_some((HEARTBEAT_INTERVAL, &heartbeat as _)),
+            tail: task
+                .max_output_lines
+                .filter(|&lines| !plain && lines > 0)
+                .map(|lines| Arc::new(Mutex::new(process::Tail::new(lines)))),
+            interactive: task.interactive,
+        };
+
+        let started = self.now();
+        let output = if let Some(replay) = &self.replay {
+            let recorded = replay.next(task_name, &step.label).ok_or_else(|| {
+                Failure::permanent(format!(
+                    "The replayed session has no more runs of task '{}'{}",
+                    task_name, step.label
+                ))
+            })?;
+            if recorded.cmd != shown_cmd {
+                tracing::warn!(
+                    "Task '{}'{} ran '{}' when it was recorded",
+                    task_name,
+                    step.label,
+                    recorded.cmd
+                );
+            }
+            if plain || task.interactive {
+                println!(
+                    "{} Running task '{}'{}: {}",
+                    self.theme.glyphs.running, task_name, step.label, shown_cmd
+                );
+                print!("{}", recorded.stdout);
+                eprint!("{}", recorded.stderr);
+            }
+            Ok(recorded.completed())
+        } else if plain || task.interactive {
+            println!(
+                "{} Running task '{}'{}: {}",
+                self.theme.glyphs.running, task_name, step.label, shown_cmd
+            );
+            process::run(cmd, options).await
+        } else {
+            let label = format!("'{}'{}", task_name, step.label);
+            self.run_with_spinner(cmd, options, &label, &shown_cmd)
+                .await
+        };
+        let output = output.map_err(|e| Failure::permanent(e.to_string()))?;
+        self.record_step(
+            task_name,
+            &step.label,
+            &shown_cmd,
+            self.elapsed(started),
+            &output,
+        );
+        let exit_code = match &output.termination {
+            process::Termination::Exited(status) => status.code(),
+            _ => None,
+        };
+        tracing::debug!(
+            cmd = %shown_cmd,
+            exit_code,
+            duration_ms = self.elapsed(started).as_millis() as u64,
+            "Command finished"
+        );
+
+        if !plain {
+            self.suspend_spinners(|| {
+                if !output.stdout.is_empty() {
+                    print!("{}", String::from_utf8_lossy(&output.stdout));
+                }
+
+                if !output.stderr.is_empty() {
+                    eprint!("{}", String::from_utf8_lossy(&output.stderr));
+                }
+            });
+        }
+        Ok((shown_cmd, output))
+    }
+
+    /// Writes the task's `script`, with `{{vars}}` filled in, to a file for
+    /// its interpreter; the file is removed when the result is dropped.
+    fn write_script(
+        &self,
+        task_name: &str,
+        task: &Task,
+        values: &Values,
+    ) -> Result<Option<(Vec<String>, ScriptFile)>, Failure> {
+        let Some(script) = &task.script else {
+            return Ok(None);
+        };
+        let interpreter = self.script_interpreter(task_name, task)?;
+        let file =
+            ScriptFile::write(&template::render(script, values), &interpreter[0]).map_err(|e| {
+                Failure::permanent(format!(
+                    "Failed to write the script of task '{}': {}",
+                    task_name, e
+                ))
+            })?;
+        Ok(Some((interpreter, file)))
+    }
+
+    /// `Task::interpreter`, checked to name a program.
+    fn script_interpreter(&self, task_name: &str, task: &Task) -> Result<Vec<String>, Failure> {
+        let interpreter = task
+            .interpreter(self.taskfile.default_shell())
+            .map_err(|e| {
+                Failure::permanent(format!(
+                    "Cannot parse the interpreter of task '{}': {}",
+                    task_name, e
+                ))
+            })?;
+        if interpreter.is_empty() {
+            return Err(Failure::permanent(format!(
+                "Empty interpreter for task '{}'",
+                task_name
+            )));
+        }
+        Ok(interpreter)
+    }
+
+    /// The interpreter running the script at `path`, with the task's
+    /// arguments after it. The command shown names the script `<script>`,
+    /// since its path changes from run to run.
+    fn script_invocation(
+        interpreter: &[String],
+        path: &Path,
+        values: &Values,
+    ) -> (String, String, Vec<String>) {
+        let shown = interpreter
+            .iter()
+            .map(String::as_str)
+            .chain(["<script>"])
+            .map(String::from)
+            .chain(values.args.iter().map(|arg| command::quote(arg)))
+            .collect::<Vec<_>>()
+            .join(" ");
+        let args = interpreter[1..]
+            .iter()
+            .cloned()
+            .chain([path.to_string_lossy().into_owned()])
+            .chain(values.args.iter().cloned())
+            .collect();
+        (shown, interpreter[0].clone(), args)
+    }
+
+    /// `program` set up to run as part of the task: in `dir`, with the task's
+    /// environment. The caller decides where its output goes.
+    fn command(
+        &self,
+        task_name: &str,
+        task: &Task,
+        program: &str,
+        args: &[String],
+        dir: Option<&Path>,
+        values: &Values,
+    ) -> Command {
+        // After the npm script lookup, which would see `sudo` otherwise, and
+        // by full path, since sudo runs commands with its own PATH.
+        let mut cmd = if task.elevate && !elevate::is_elevated() {
+            let resolved = shell::find_executable(program, self.enhanced_path.as_deref())
+                .map(|path| path.to_string_lossy().into_owned());
+            let (program, args) = elevate::wrap(resolved.as_deref().unwrap_or(program), args);
+            let mut cmd = Command::new(program);
+            cmd.args(args);
+            cmd
+        } else {
+            let mut cmd = Command::new(program);
+            cmd.args(args);
+            cmd
+        };
+        if let Some(dir) = dir {
+            cmd.current_dir(dir);
+        }
+        #[cfg(unix)]
+        if let Some(mask) = task.umask {
+            process::set_umask(&mut cmd, mask);
+        }
+
+        let inherit = task.inherits_env();
+        if !inherit {
+            cmd.env_clear();
+            for key in &task.env_import {
+                if let Ok(value) = env::var(key) {
+                    cmd.env(key, value);
+                }
+            }
+        }
+        cmd.envs(self.env_for(task_name).vars());
+        if let Some(args) = values.get(ARGS_VAR) {
+            cmd.env(ARGS_ENV_VAR, args);
+        }
+        if let Some(code) = values.get(EXIT_CODE_VAR) {
+            cmd.env(EXIT_CODE_VAR, code);
+        }
+        for (key, value) in values.iter() {
+            if let Some(name) = key.strip_prefix(CAPTURE_PREFIX) {
+                cmd.env(name, value);
+            }
+        }
+
+        // A hermetic task only gets a PATH when it imports one.
+        if let Some(enhanced_path) = &self.enhanced_path {
+            if inherit || task.env_import.iter().any(|key| key == "PATH") {
+                cmd.env("PATH", enhanced_path);
+            }
+        }
+        cmd
+    }
+
+    /// Runs `cmd` behind a spinner naming the task and its command.
+    #[cfg(feature = "spinner")]
+    async fn run_with_spinner(
+        &self,
+        cmd: Command,
+        options: process::Options<'_>,
+        label: &str,
+        shown_cmd: &str,
+    ) -> std::io::Result<process::Completed> {
+        let pb = self.progress.add(ProgressBar::new_spinner());
+        pb.set_style(
+            ProgressStyle::default_spinner()
+                .tick_strings(self.theme.spinner.frames())
+                .template("{spinner:.cyan} {msg} [{elapsed_precise}]{prefix}")
+                .unwrap(),
+        );
+        pb.set_message(format!("Running task {}: {}", label, shown_cmd));
+        pb.enable_steady_tick(Duration::from_millis(80));
+
+        let pb_clone = pb.clone();
+        let label = label.to_string();
+        let cmd_clone = shown_cmd.to_string();
+        let tail = options.tail.clone();
+        let spinner_task = tokio::spawn(async move {
+            let start = Instant::now();
+            loop {
+                let elapsed = start.elapsed();
+                pb_clone.set_message(format!(
+                    "Running task {}: {} [{}]",
+                    label,
+                    cmd_clone,
+                    format_duration(elapsed)
+                ));
+                if let Some(tail) = &tail {
+                    pb_clone.set_prefix(tail_preview(&tail.lock().unwrap().lines()));
+                }
+                sleep(Duration::from_millis(100)).await;
+            }
+        });
+
+        // Wait for the process to complete
+        let output = process::run(cmd, options).await;
+
+        spinner_task.abort();
+        pb.finish_and_clear();
+        self.progress.remove(&pb);
+        output
+    }
+
+    /// Runs `print` with the spinners of other running tasks cleared from
+    /// the terminal, so the lines it writes are not drawn over.
+    fn suspend_spinners<R>(&self, print: impl FnOnce() -> R) -> R {
+        #[cfg(feature = "spinner")]
+        return self.progress.suspend(print);
+        #[cfg(not(feature = "spinner"))]
+        print()
+    }
+
+    /// Without the `spinner` feature the command is announced once instead;
+    /// its output still appears when it finishes.
+    #[cfg(not(feature = "spinner"))]
+    async fn run_with_spinner(
+        &self,
+        cmd: Command,
+        options: process::Options<'_>,
+        label: &str,
+        shown_cmd: &str,
+    ) -> std::io::Result<process::Completed> {
+        println!(
+            "{} Running task {}: {}",
+            self.theme.glyphs.running, label, shown_cmd
+        );
+        process::run(cmd, options).await
+    }
+
+    fn write_receipt(
+        &self,
+        task_name: &str,
+        task: &Task,
+        shown_cmd: &str,
+        started_at: SystemTime,
+        elapsed: Duration,
+        exit_code: Option<i32>,
+    ) {
+        let receipt = receipt::Receipt {
+            task: task_name.to_string(),
+            run_id: self.run_id.clone(),
+            command: shown_cmd.to_string(),
+            started_at: unix_secs(started_at),
+            duration_ms: receipt::duration_ms(elapsed),
+            exit_code,
+            env_hash: receipt::env_hash(receipt::hashed_vars(
+                self.env_for(task_name).vars(),
+                RUN_ID_VAR,
+            )),
+            tools: task
+                .requires
+                .iter()
+                .map(|requirement| {
+                    let bin = requirement.bin();
+                    let version =
+                        shell::tool_version(bin, self.enhanced_path.as_deref(), &self.base_dir);
+                    (bin.to_string(), version)
+                })
+                .collect(),
+        };
+        if let Err(e) = receipt.write(&self.base_dir) {
+            tracing::warn!("Failed to write receipt for task '{}': {}", task_name, e);
+        }
+    }
+
+    pub fn has_task(&self, task_name: &str) -> bool {
+        self.taskfile.tasks.contains_key(task_name)
+    }
+
+    pub fn get_task_names(&self) -> Vec<&String> {
+        self.taskfile.tasks.keys().collect()
+    }
+
+    pub fn get_task(&self, task_name: &str) -> Option<&Task> {
+        self.taskfile.tasks.get(task_name)
+    }
+
+    pub fn task_count(&self) -> usize {
+        self.taskfile.tasks.len()
+    }
+
+    pub fn run_id(&self) -> &str {
+        &self.run_id
+    }
+
+    /// `git.*` template values, looked up only when a task in `graph` uses
+    /// them; `git.dirty` scans the work tree, so it is only computed when
+    /// referenced.
+    fn git_vars(&self, graph: &HashSet<String>) -> HashMap<String, String> {
+        let uses = |needle: &str| {
+            graph
+                .iter()
+                .filter_map(|name| self.get_task(name))
+                .any(|task| {
+                    task.steps()
+                        .into_iter()
+                        .flatten()
+                        .chain(&task.script)
+                        .chain(&task.git_ref)
+                        .chain(&task.cache_key)
+                        .any(|text| text.contains(needle))
+                })
+        };
+        if !uses("git.") {
+            return HashMap::new();
+        }
+        git::template_vars(&self.base_dir, uses("git.dirty"))
+    }
+
+    pub fn default_shell(&self) -> Option<&Shell> {
+        self.shell.as_ref()
+    }
+}
+
+/// One of a task's commands, ready to run.
+struct Step<'a> {
+    /// The command and its `cmd_fallbacks`.
+    candidates: &'a [&'a String],
+    /// For the task's `script`, instead of candidates: its interpreter's
+    /// words and the file it was written to.
+    script: Option<(&'a [String], &'a Path)>,
+    /// ` (2/3)` when the task has several commands, otherwise empty.
+    label: String,
+    /// What is left of the task's `timeout`.
+    timeout: Option<Duration>,
+}
+
+/// `$TASKFILE_STATUS` for `[hooks]` after a task or run ended with `result`.
+fn hook_status<T, E>(result: &Result<T, E>) -> &'static str {
+    if result.is_ok() {
+        "success"
+    } else {
+        "failed"
+    }
+}
+
+/// Which of a task's command lists a step comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Phase {
+    Pre,
+    /// `cmd`, its fallbacks, or `cmds`.
+    Main,
+    Post,
+    Finally,
+}
+
+impl Phase {
+    /// ` (2/3)` for the task's own commands when it has several, ` (pre)`
+    /// or ` (post 1/2)` for the others.
+    fn label(self, index: usize, count: usize) -> String {
+        match (self, count > 1) {
+            (Phase::Main, true) => format!(" ({}/{})", index + 1, count),
+            (Phase::Main, false) => String::new(),
+            (_, true) => format!(" ({} {}/{})", self, index + 1, count),
+            (_, false) => format!(" ({})", self),
+        }
+    }
+}
+
+impl std::fmt::Display for Phase {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Phase::Pre => "pre",
+            Phase::Main => "main",
+            Phase::Post => "post",
+            Phase::Finally => "finally",
+        })
+    }
+}
+
+/// The task a `run_task_slice` call was made for, its parsed flags, and the
+/// tasks left out of the run with the reason shown for each, plus timings.
+struct Invocation {
+    task: String,
+    flag_values: Values,
+    /// `git.*` template values shared by every task in the run.
+    git_vars: HashMap<String, String>,
+    skipped: HashMap<String, String>,
+    /// `--jobs`: a permit for each task that may run at once. Waiters are
+    /// served in order, so tasks start in the order they asked.
+    slots: Option<Semaphore>,
+    /// Mean durations from the run history, for ordering concurrent tasks
+    /// without an `estimated_duration`; loaded when first needed.
+    estimates: OnceLock<HashMap<String, Duration>>,
+    /// How long each task that ran took, for the critical-path report.
+    durations: Mutex<HashMap<String, Duration>>,
+    /// `compose` tasks to take down once the run is over.
+    teardowns: Mutex<Vec<(String, ComposeConfig)>>,
+    /// Tasks already reached in this invocation, set to whether they
+    /// succeeded once they finish. `run = "once"` tasks do not run again.
+    claimed: Mutex<HashMap<String, Arc<OnceCell<bool>>>>,
+    /// `background` tasks still running, to stop once the run is over.
+    background: Mutex<Vec<Background>>,
+    /// Output kept by tasks with `capture`, by variable name.
+    captured: Mutex<HashMap<String, String>>,
+}
+
+/// A `background` task that was started and not waited for.
+struct Background {
+    task: String,
+    tree: process::Tree,
+    dir: Option<PathBuf>,
+    values: Values,
+    log: PathBuf,
+}
+
+/// `cmd` with `$NAME`, `${NAME}` and `{{ NAME }}` replaced by `value`, or
+/// `None` when it references none of them.
+fn substitute_var(cmd: &str, name: &str, value: &str) -> Option<String> {
+    let found = std::cell::Cell::new(false);
+    let cmd = template::render_with(cmd, |key| {
+        (key == name).then(|| {
+            found.set(true);
+            value.to_string()
+        })
+    });
+
+    let braced = format!("{{{}}}", name);
+    let mut result = String::with_capacity(cmd.len());
+    let mut rest = cmd.as_str();
+    while let Some(dollar) = rest.find('$') {
+        result.push_str(&rest[..dollar]);
+        let after = &rest[dollar + 1..];
+        let len = if after.starts_with(&braced) {
+            Some(braced.len())
+        } else if after.starts_with(name)
+            && !after[name.len()..].starts_with(|c: char| c.is_ascii_alphanumeric() || c == '_')
+        {
+            Some(name.len())
+        } else {
+            None
+        };
+        match len {
+            Some(len) => {
+                result.push_str(value);
+                found.set(true);
+                rest = &after[len..];
+            }
+            None => {
+                result.push('$');
+                rest = after;
+            }
+        }
+    }
+    result.push_str(rest);
+    found.get().then_some(result)
+}
+
+/// Asks a y/N question on the terminal; only `y` or `yes` is a yes.
+fn ask(question: &str) -> std::io::Result<bool> {
+    print!("{} {} [y/N] ", "?".cyan(), question);
+    std::io::Write::flush(&mut std::io::stdout())?;
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+/// `depends_on` for display, marking optional entries.
+fn join_dependencies(deps: &[Dependency]) -> String {
+    let deps: Vec<String> = deps.iter().map(Dependency::to_string).collect();
+    deps.join(", ")
+}
+
+/// Whether `name` can be used as an environment variable: a letter or `_`,
+/// then letters, digits and `_`.
+fn is_var_name(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Output cache entry for one task and cache key.
+fn output_entry(task_name: &str, key: &str) -> String {
+    format!("{}\0{}", task_name, key)
+}
+
+fn print_flags(specs: &HashMap<String, FlagSpec>) {
+    let mut names: Vec<&String> = specs.keys().collect();
+    names.sort();
+    for name in names {
+        let spec = &specs[name];
+        match &spec.help {
+            Some(help) => println!("  {:<24} {}", spec.usage(name), help),
+            None => println!("  {}", spec.usage(name)),
+        }
+    }
+}
+
+fn print_params(specs: &HashMap<String, ParamSpec>) {
+    let mut names: Vec<&String> = specs.keys().collect();
+    names.sort();
+    for name in names {
+        let spec = &specs[name];
+        match spec.help() {
+            Some(help) => println!("  {:<24} {}", spec.usage(name), help),
+            None => println!("  {}", spec.usage(name)),
+        }
+    }
+}
+
+/// Output lines shown beneath the spinner, one per terminal row: escape
+/// codes are stripped and long lines cut to the width of stderr.
+#[cfg(feature = "spinner")]
+fn tail_preview(lines: &[String]) -> String {
+    let width = console::Term::stderr().size().1 as usize;
+    lines
+        .iter()
+        .map(|line| {
+            let line = console::strip_ansi_codes(line);
+            format!(
+                "\n  {} {}",
+                "│".dimmed(),
+                console::truncate_str(&line, width.saturating_sub(4), "…")
+            )
+        })
+        .collect()
+}
+
+/// `time` in Unix nanoseconds; 0 for anything before the epoch.
+fn unix_nanos(time: SystemTime) -> u128 {
+    time.duration_since(SystemTime::UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or(0)
+}
+
+/// `time` in Unix seconds; 0 for anything before the epoch.
+fn unix_secs(time: SystemTime) -> u64 {
+    time.duration_since(SystemTime::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+fn format_duration(duration: Duration) -> String {
+    let total_secs = duration.as_secs();
+    let millis = duration.subsec_millis();
+
+    if total_secs >= 60 {
+        let mins = total_secs / 60;
+        let secs = total_secs % 60;
+        if millis > 0 {
+            format!("{}m {}s {}ms", mins, secs, millis)
+        } else {
+            format!("{}m {}s", mins, secs)
+        }
+    } else if total_secs > 0 {
+        if millis > 0 {
+            format!("{}.{}s", total_secs, millis / 100)
+        } else {
+            format!("{}s", total_secs)
+        }
+    } else {
+        format!("{}ms", millis)
+    }
+}
+
+/// A variable in `describe`'s Environment section.
+#[derive(Debug, PartialEq, Eq)]
+struct DocumentedVar<'a> {
+    key: &'a str,
+    /// Its `env_docs` entry.
+    doc: Option<&'a str>,
+    /// `required`, `not set`.
+    notes: Vec<&'static str>,
+}
+
+/// What a task's commands are rendered with: the `{{name}}` values, and the
+/// arguments given after `--` as the words they were given as. `vars` holds
+/// those too, quoted, as `ARGS_VAR`.
+#[derive(Debug, Clone, Default)]
+struct Values {
+    vars: HashMap<String, String>,
+    args: Vec<String>,
+}
+
+impl std::ops::Deref for Values {
+    type Target = HashMap<String, String>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.vars
+    }
+}
+
+impl std::ops::DerefMut for Values {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.vars
+    }
+}
+
+/// `words` joined with spaces, each quoted when it needs to be, so
+/// `command::split` or a shell gets the same words back.
+fn quote_words(words: &[String]) -> String {
+    words
+        .iter()
+        .map(|word| command::quote(word))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Values put into a command that runs without a shell. Each stands in as a
+/// marker until the command has been split, so one holding spaces or quotes
+/// stays a single word: `cp $SRC out/` with `SRC="my file"` copies one file.
+#[derive(Default)]
+struct Splice {
+    values: std::cell::RefCell<Vec<String>>,
+}
+
+impl Splice {
+    /// The marker `split` replaces with `value`.
+    fn mark(&self, value: &str) -> String {
+        let mut values = self.values.borrow_mut();
+        values.push(value.to_string());
+        format!("\u{0}{}\u{0}", values.len() - 1)
+    }
+
+    /// Splits `cmd`, puts the values back into its words and quotes them
+    /// again, so a second `command::split` gets the same words. A word that
+    /// was only an empty value goes away, as it would in a shell.
+    fn split(&self, cmd: &str) -> Result<String, SplitError> {
+        let values = self.values.borrow();
+        let words: Vec<String> = command::split(cmd)?
+            .into_iter()
+            .filter_map(|word| {
+                let parts: Vec<&str> = word.split('\0').collect();
+                let filled: String = parts
+                    .iter()
+                    .enumerate()
+                    .map(|(i, part)| match i % 2 {
+                        0 => part,
+                        _ => part
+                            .parse::<usize>()
+                            .ok()
+                            .and_then(|index| values.get(index))
+                            .map_or(*part, String::as_str),
+                    })
+                    .collect();
+                let only_value = parts.len() == 3 && parts[0].is_empty() && parts[2].is_empty();
+                (!(only_value && filled.is_empty())).then_some(filled)
+            })
+            .collect();
+        Ok(words
+            .iter()
+            .map(|word| command::quote(word))
+            .collect::<Vec<_>>()
+            .join(" "))
+    }
+}
+
+/// Script names in `./package.json`, which `resolve_command` runs through
+/// the package manager rather than as programs.
+#[cfg(feature = "node")]
+fn npm_scripts() -> HashSet<String> {
+    std::fs::read_to_string("package.json")
+        .ok()
+        .and_then(|contents| serde_json::from_str::<serde_json::Value>(&contents).ok())
+        .and_then(|package| {
+            package
+                .get("scripts")?
+                .as_object()
+                .map(|s| s.keys().cloned().collect())
+        })
+        .unwrap_or_default()
+}
+
+#[cfg(not(feature = "node"))]
+fn npm_scripts() -> HashSet<String> {
+    HashSet::new()
+}
+
+/// Directory the Taskfile lives in; an empty parent means the current one.
+fn base_dir(taskfile_dir: Option<&Path>) -> PathBuf {
+    match taskfile_dir {
+        Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+        _ => PathBuf::from("."),
+    }
+}
+
+/// Why a single attempt at running a task failed.
+struct Failure {
+    message: String,
+    /// Whether the failure may go away on retry (signals, watchdog kills,
+    /// temporary-failure exit codes).
+    transient: bool,
+}
+
+impl Failure {
+    fn permanent(message: String) -> Self {
+        Self {
+            message,
+            transient: false,
+        }
+    }
+}
+
+/// Exit codes conventionally meaning "try again later": `EX_UNAVAILABLE`,
+/// `EX_TEMPFAIL` and coreutils `timeout`.
+const TRANSIENT_EXIT_CODES: &[i32] = &[69, 75, 124];
+
+/// Signals a program gets for its own bug, which running it again repeats.
+#[cfg(unix)]
+const CRASH_SIGNALS: &[i32] = &[
+    libc::SIGSEGV,
+    libc::SIGBUS,
+    libc::SIGILL,
+    libc::SIGFPE,
+    libc::SIGABRT,
+    libc::SIGSYS,
+    libc::SIGTRAP,
+];
+
+fn is_transient(status: &std::process::ExitStatus) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(signal) = status.signal() {
+            return signal != libc::SIGINT && !CRASH_SIGNALS.contains(&signal);
+        }
+    }
+    status
+        .code()
+        .is_some_and(|code| TRANSIENT_EXIT_CODES.contains(&code))
+}
+
+/// How long ago something happened, to the nearest unit: `5m ago`.
+fn format_age(secs: u64) -> String {
+    match secs {
+        0..60 => "just now".to_string(),
+        60..3600 => format!("{}m ago", secs / 60),
+        3600..86400 => format!("{}h ago", secs / 3600),
+        _ => format!("{}d ago", secs / 86400),
+    }
+}
+
+/// How output shows a command: a script by its first line and length.
+fn script_summary(cmd: &str) -> String {
+    if !command::is_script(cmd) {
+        return cmd.to_string();
+    }
+    let mut lines = cmd.lines().map(str::trim).filter(|line| !line.is_empty());
+    let first = lines.next().unwrap_or_default();
+    format!("{} … ({} lines)", first, lines.count() + 1)
+}
+
+/// Whole-second rendering for heartbeat lines, e.g. `3m10s`.
+fn format_duration_coarse(duration: Duration) -> String {
+    let total_secs = duration.as_secs();
+    if total_secs >= 60 {
+        format!("{}m{}s", total_secs / 60, total_secs % 60)
+    } else {
+        format!("{}s", total_secs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shell::ShellSetting;
+    use indexmap::IndexMap;
+    use std::fs;
+    use std::io::Write;
+
+    #[tokio::test]
+    async fn test_task_runner_creation() {
+        let toml_content = r#"
+[tasks.test]
+cmd = "echo 'test'"
+desc = "Test task"
+"#;
+
+        let mut file = fs::File::create("test_taskfile.toml").unwrap();
+        file.write_all(toml_content.as_bytes()).unwrap();
+
+        let runner = TaskRunner::from_file("test_taskfile.toml").await;
+        assert!(runner.is_ok());
+
+        let runner = runner.unwrap();
+        assert!(runner.has_task("test"));
+        assert_eq!(runner.task_count(), 1);
+
+        fs::remove_file("test_taskfile.toml").unwrap();
+    }
+
+    #[cfg(feature = "dotenv")]
+    #[tokio::test]
+    async fn test_settings_dotenv_required() {
+        let dir = std::env::temp_dir().join(format!("taskfile-settings-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let taskfile = dir.join("Taskfile.toml");
+        let write = |settings: &str| {
+            fs::write(
+                &taskfile,
+                format!(
+                    "{}\n[env]\nfiles = [\".env.local\"]\n\n[tasks.t]\ncmd = \"true\"\n",
+                    settings
+                ),
+            )
+            .unwrap()
+        };
+        let path = taskfile.to_str().unwrap();
+
+        write("");
+        assert!(TaskRunner::from_file(path).await.is_ok());
+        write("[settings]\ndotenv = \"required\"\n");
+        let err = TaskRunner::from_file(path).await.err().unwrap().to_string();
+        assert!(err.contains(".env.local"), "{}", err);
+        fs::write(dir.join(".env.local"), "A=1\n").unwrap();
+        assert!(TaskRunner::from_file(path).await.is_ok());
+
+        write("shell = true\n[settings]\nshell = \"bash\"\n");
+        assert!(TaskRunner::from_file(path).await.is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_cmd_fallbacks_pick_installed_program() {
+        let task = Task {
+            cmd: "taskfile-missing-binary --version".into(),
+            cmd_fallbacks: vec!["also-missing".to_string(), "sh -c true".to_string()],
+            ..Default::default()
+        };
+        let runner = TaskRunner::new(TaskFile::default());
+
+        let (cmd, program, args) = runner
+            .select_command("t", &task.steps()[0], None, &Values::default())
+            .await
+            .ok()
+            .unwrap();
+        assert_eq!(cmd, "sh -c true");
+        assert_eq!(program, "sh");
+        assert_eq!(args, vec!["-c", "true"]);
+
+        let task = Task {
+            cmd_fallbacks: vec!["also-missing".to_string()],
+            ..task
+        };
+        let err = runner
+            .select_command("t", &task.steps()[0], None, &Values::default())
+            .await
+            .err()
+            .unwrap();
+        assert!(err
+            .message
+            .contains("taskfile-missing-binary, also-missing"));
+    }
+
+    #[tokio::test]
+    async fn test_pass_through_args() {
+        assert_eq!(
+            substitute_var("run $ARGS ${ARGS} {{ ARGS }} $ARGSX", ARGS_VAR, "-v"),
+            Some("run -v -v -v $ARGSX".to_string())
+        );
+        assert_eq!(substitute_var("cargo test", ARGS_VAR, "-v"), None);
+
+        let mut tasks = IndexMap::new();
+        tasks.insert(
+            "test".to_string(),
+            Task {
+                cmd: "cargo test".into(),
+                ..Default::default()
+            },
+        );
+        tasks.insert(
+            "bench".to_string(),
+            Task {
+                cmd: "cargo bench $ARGS --quiet".into(),
+                ..Default::default()
+            },
+        );
+        let runner = TaskRunner::new(TaskFile {
+            tasks,
+            ..Default::default()
+        });
+        let values = TaskRunner::parse_args(
+            "test",
+            &Task::default(),
+            &[
+                "--".to_string(),
+                "--".to_string(),
+                "--nocapture".to_string(),
+            ],
+        )
+        .unwrap();
+
+        for (name, expected) in [
+            ("test", "cargo test -- --nocapture"),
+            ("bench", "cargo bench -- --nocapture --quiet"),
+        ] {
+            let task = runner.get_task(name).unwrap();
+            let (cmd, _, _) = runner
+                .select_command(name, &task.steps()[0], None, &values)
+                .await
+                .ok()
+                .unwrap();
+            assert_eq!(cmd, expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_pass_through_args_keep_their_words() {
+        let task = Task {
+            cmd: "echo $ARGS".into(),
+            ..Default::default()
+        };
+        let args = ["--", "hello world", "it's", ""].map(String::from);
+        let values = TaskRunner::parse_args("t", &task, &args).unwrap();
+        assert_eq!(values.args, ["hello world", "it's", ""]);
+        assert_eq!(values[ARGS_VAR], r"'hello world' 'it'\''s' ''");
+
+        let runner = TaskRunner::new(TaskFile::default());
+        let (_, program, words) = runner
+            .select_command("t", &task.steps()[0], None, &values)
+            .await
+            .ok()
+            .unwrap();
+        assert_eq!(program, "echo");
+        assert_eq!(words, ["hello world", "it's", ""]);
+
+        // A shell gets them quoted, to split back into the same words.
+        let (cmd, _, _) = runner
+            .select_command("t", &task.steps()[0], Some(ShellKind::Sh), &values)
+            .await
+            .ok()
+            .unwrap();
+        assert_eq!(cmd, r"echo 'hello world' 'it'\''s' ''");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_cache_key_skips_unchanged_task() {
+        let dir = std::env::temp_dir().join(format!("taskfile-cache-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let marker = dir.join("ran");
+
+        let mut tasks = IndexMap::new();
+        tasks.insert(
+            "build".to_string(),
+            Task {
+                cmd: format!("touch {}", marker.display()).into(),
+                cache_key: Some("{{flags.profile}}".to_string()),
+                flags: toml::from_str(r#"profile = { type = "string", default = "debug" }"#)
+                    .unwrap(),
+                ..Default::default()
+            },
+        );
+        let runner = TaskRunner::new_with_base_path(
+            TaskFile {
+                tasks,
+                ..Default::default()
+            },
+            Some(&dir),
+        );
+
+        runner.run_task("build").await.unwrap();
+        assert!(marker.exists());
+
+        fs::remove_file(&marker).unwrap();
+        runner.run_task("build").await.unwrap();
+        assert!(!marker.exists());
+
+        let args = ["--profile".to_string(), "release".to_string()];
+        runner.run_task_with_args("build", &args).await.unwrap();
+        assert!(marker.exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_args_reach_the_environment_namespaced() {
+        let dir = std::env::temp_dir().join(format!("taskfile-args-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let log = dir.join("env");
+        let runner = TaskRunner::new_with_base_path(
+            TaskFile {
+                tasks: IndexMap::from([(
+                    "env".to_string(),
+                    Task {
+                        cmd: format!("sh -c 'env > {}'", log.display()).into(),
+                        ..Default::default()
+                    },
+                )]),
+                ..Default::default()
+            },
+            Some(&dir),
+        );
+
+        let args = ["--".to_string(), "-v".to_string()];
+        runner.run_task_with_args("env", &args).await.unwrap();
+        let env = fs::read_to_string(&log).unwrap();
+        assert!(
+            env.lines().any(|line| line == "TASKFILE_ARGS=-v"),
+            "{}",
+            env
+        );
+        assert!(
+            !env.lines().any(|line| line.starts_with("ARGS=")),
+            "{}",
+            env
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_sources_skip_up_to_date_task() {
+        let dir = std::env::temp_dir().join(format!("taskfile-sources-{}", std::process::id()));
+        fs::create_dir_all(dir.join("src")).unwrap();
+        fs::write(dir.join("src/main.c"), "v1").unwrap();
+        let task = Task {
+            cmd: format!(
+                "sh -c 'cd {} && echo built >> log && touch app'",
+                dir.display()
+            )
+            .into(),
+            sources: vec!["src".to_string()],
+            outputs: vec!["app".to_string()],
+            ..Default::default()
+        };
+        // Edits a source while it runs, as an editor saving mid-build would.
+        let edits = Task {
+            cmd: format!(
+                "sh -c 'cd {} && echo edited >> edits && echo v3 > src/main.c'",
+                dir.display()
+            )
+            .into(),
+            sources: vec!["src".to_string()],
+            ..Default::default()
+        };
+        let runner = TaskRunner::new_with_base_path(
+            TaskFile {
+                tasks: IndexMap::from([("build".to_string(), task), ("edit".to_string(), edits)]),
+                ..Default::default()
+            },
+            Some(&dir),
+        );
+        let runs = async |name, log| {
+            runner.run_task(name).await.unwrap();
+            fs::read_to_string(dir.join(log)).unwrap().lines().count()
+        };
+
+        assert_eq!(runs("build", "log").await, 1);
+        assert_eq!(runs("build", "log").await, 1);
+        fs::write(dir.join("src/main.c"), "v2").unwrap();
+        assert_eq!(runs("build", "log").await, 2);
+        fs::remove_file(dir.join("app")).unwrap();
+        assert_eq!(runs("build", "log").await, 3);
+        assert_eq!(runs("build", "log").await, 3);
+
+        assert_eq!(runs("edit", "edits").await, 1);
+        assert_eq!(runs("edit", "edits").await, 2);
+        assert_eq!(runs("edit", "edits").await, 2);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_cache_method() {
+        let dir = std::env::temp_dir().join(format!("taskfile-method-{}", std::process::id()));
+        fs::create_dir_all(dir.join("assets")).unwrap();
+        fs::write(dir.join("assets/logo.png"), "png").unwrap();
+        let runner = |method| {
+            let task = Task {
+                cmd: format!("sh -c 'echo copied >> {}'", dir.join("log").display()).into(),
+                sources: vec!["assets".to_string()],
+                method,
+                ..Default::default()
+            };
+            TaskRunner::new_with_base_path(
+                TaskFile {
+                    tasks: IndexMap::from([("assets".to_string(), task)]),
+                    ..Default::default()
+                },
+                Some(&dir),
+            )
+        };
+        let runs = async |method| {
+            runner(method).run_task("assets").await.unwrap();
+            fs::read_to_string(dir.join("log")).unwrap().lines().count()
+        };
+        let touch = || {
+            let file = fs::File::options()
+                .write(true)
+                .open(dir.join("assets/logo.png"))
+                .unwrap();
+            file.set_modified(SystemTime::now() + Duration::from_secs(60))
+                .unwrap();
+        };
+
+        assert_eq!(runs(CacheMethod::Checksum).await, 1);
+        touch();
+        assert_eq!(runs(CacheMethod::Checksum).await, 1);
+        // An entry from the other method does not count.
+        assert_eq!(runs(CacheMethod::Timestamp).await, 2);
+        assert_eq!(runs(CacheMethod::Timestamp).await, 2);
+        touch();
+        assert_eq!(runs(CacheMethod::Timestamp).await, 3);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(feature = "records")]
+    #[tokio::test]
+    async fn test_history_setting() {
+        let dir = std::env::temp_dir().join(format!("taskfile-history-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let run = async |history| {
+            let taskfile = TaskFile {
+                tasks: IndexMap::from([(
+                    "t".to_string(),
+                    Task {
+                        cmd: "true".into(),
+                        ..Default::default()
+                    },
+                )]),
+                settings: settings::Settings {
+                    history,
+                    ..Default::default()
+                },
+                ..Default::default()
+            };
+            TaskRunner::new_with_base_path(taskfile, Some(&dir))
+                .with_options(RunOptions {
+                    record_history: true,
+                    ..Default::default()
+                })
+                .run_task("t")
+                .await
+                .unwrap();
+            history::load(&dir).unwrap().len()
+        };
+
+        assert_eq!(run(Some(false)).await, 0);
+        assert!(!history::path(&dir).exists());
+        assert_eq!(run(None).await, 1);
+        assert_eq!(run(Some(true)).await, 2);
+        assert_eq!(run(Some(false)).await, 2);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_status_skips_up_to_date_task() {
+        let dir = std::env::temp_dir().join(format!("taskfile-status-{}", std::process::id()));
+        fs::create_dir_all(dir.join("dist")).unwrap();
+        let runner = TaskRunner::new(TaskFile {
+            tasks: IndexMap::from([(
+                "bundle".to_string(),
+                Task {
+                    cmd: "sh -c 'echo built >> ../log; touch bundle.js'".into(),
+                    cwd: Some(dir.join("dist").display().to_string()),
+                    status: vec!["test -f bundle.js".to_string(), "true".to_string()],
+                    ..Default::default()
+                },
+            )]),
+            ..Default::default()
+        });
+        let runs = async || {
+            runner.run_task("bundle").await.unwrap();
+            fs::read_to_string(dir.join("log")).unwrap().lines().count()
+        };
+
+        assert_eq!(runs().await, 1);
+        assert_eq!(runs().await, 1);
+        fs::remove_file(dir.join("dist/bundle.js")).unwrap();
+        assert_eq!(runs().await, 2);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_record_and_replay() {
+        let dir = std::env::temp_dir().join(format!("taskfile-session-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let log = dir.join("log");
+        let taskfile = || TaskFile {
+            tasks: IndexMap::from([
+                (
+                    "build".to_string(),
+                    Task {
+                        cmds: vec![
+                            format!("sh -c 'echo built >> {}'", log.display()),
+                            "sh -c 'echo warning >&2; exit 3'".to_string(),
+                        ],
+                        success_codes: Some(vec![0, 3]),
+                        run_if: Some("true".to_string()),
+                        ..Default::default()
+                    },
+                ),
+                (
+                    "serve".to_string(),
+                    Task {
+                        cmd: "true".into(),
+                        background: true,
+                        ..Default::default()
+                    },
+                ),
+            ]),
+            ..Default::default()
+        };
+        let runner = TaskRunner::new(taskfile()).with_recording();
+        runner.run_task("build").await.unwrap();
+        let session = runner.recorded();
+        let steps: Vec<(&str, &str)> = session
+            .steps
+            .iter()
+            .map(|step| (step.step.as_str(), step.stderr.as_str()))
+            .collect();
+        assert_eq!(
+            steps,
+            [("run_if", ""), (" (1/2)", ""), (" (2/2)", "warning\n")]
+        );
+        assert_eq!(session.steps[2].outcome, session::Outcome::Exited(3));
+        assert_eq!(session.run_id, runner.run_id());
+        let err = runner.run_task("serve").await.unwrap_err();
+        assert!(err.to_string().contains("background"));
+
+        let replayed = TaskRunner::new(taskfile()).with_replay(session.clone());
+        assert_eq!(replayed.run_id(), session.run_id);
+        replayed.run_task("build").await.unwrap();
+        assert_eq!(fs::read_to_string(&log).unwrap(), "built\n");
+        let err = replayed.run_task("build").await.unwrap_err();
+        assert!(err.to_string().contains("no more run_if checks"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_env_inherit_false_imports_only_named_vars() {
+        let task = Task {
+            cmd: "env".into(),
+            env_inherit: Some(false),
+            env_import: vec!["PATH".to_string()],
+            ..Default::default()
+        };
+        let runner = TaskRunner::new(TaskFile::default());
+
+        let output = runner
+            .execute("env", &task, &Values::default())
+            .await
+            .unwrap()
+            .unwrap();
+        let vars: Vec<String> = String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .filter_map(|line| line.split_once('=').map(|(key, _)| key.to_string()))
+            .collect();
+        assert!(vars.contains(&"PATH".to_string()));
+        assert!(vars.contains(&RUN_ID_VAR.to_string()));
+        assert!(!vars.contains(&"HOME".to_string()));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_capture_output_for_later_tasks() {
+        let dir = std::env::temp_dir().join(format!("taskfile-capture-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let out = dir.join("out");
+        let taskfile: TaskFile = toml::from_str(&format!(
+            r#"
+[tasks.sha]
+cmd = "echo ' abc123 '"
+capture = "GIT_SHA"
+
+[tasks.tag]
+cmd = "sh -c 'echo v-$GIT_SHA-${{GIT_SHA}}-{{{{GIT_SHA}}}} > {out}; printenv GIT_SHA >> {out}'"
+depends_on = ["sha"]
+
+[tasks.bad]
+cmd = "true"
+capture = "GIT-SHA"
+"#,
+            out = out.display()
+        ))
+        .unwrap();
+        let runner = TaskRunner::new(taskfile);
+
+        runner.run_task("tag").await.unwrap();
+        assert_eq!(
+            fs::read_to_string(&out).unwrap(),
+            "v-abc123-abc123-abc123\nabc123\n"
+        );
+        let err = runner.run_task("bad").await.unwrap_err().to_string();
+        assert!(err.contains("not a valid variable name"), "{}", err);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_cmd_without_a_variant_for_this_os_is_skipped() {
+        let dir = std::env::temp_dir().join(format!("taskfile-variants-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let out = dir.join("out");
+        let taskfile: TaskFile = toml::from_str(&format!(
+            r#"
+[tasks.sign]
+cmd = {{ windows = "signtool sign app.exe" }}
+
+[tasks.clean]
+cmd = {{ default = "false", unix = "sh -c 'echo clean >> {out}'" }}
+
+[tasks.release]
+cmd = "sh -c 'echo release >> {out}'"
+depends_on = ["sign", "clean"]
+"#,
+            out = out.display()
+        ))
+        .unwrap();
+        let sign = &taskfile.tasks["sign"];
+        assert!(!sign.cmd.is_empty());
+        assert_eq!(
+            sign.not_here(),
+            Some(format!("no command for {}", std::env::consts::OS))
+        );
+        let runner = TaskRunner::new(taskfile);
+
+        runner.run_task("release").await.unwrap();
+        assert_eq!(fs::read_to_string(&out).unwrap(), "clean\nrelease\n");
+        runner.run_task("sign").await.unwrap();
+        let script = runner.export_script("release", &[]).await.unwrap();
+        assert!(script.contains("# Skipped: no command for"), "{}", script);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_values_stay_within_their_word() {
+        let dir = std::env::temp_dir().join(format!("taskfile-splice-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let out = dir.join("out");
+        let taskfile: TaskFile = toml::from_str(&format!(
+            r#"
+[env.vars]
+TITLE = "two words 'and quotes'"
+EMPTY = ""
+
+[tasks.words]
+cmd = "sh -c 'printf \"[%s]\" \"$@\" > {out}' sh $TITLE $EMPTY end"
+"#,
+            out = out.display()
+        ))
+        .unwrap();
+        let runner = TaskRunner::new(taskfile);
+
+        runner.run_task("words").await.unwrap();
+        assert_eq!(
+            fs::read_to_string(&out).unwrap(),
+            "[two words 'and quotes'][end]"
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_documented_env_for_describe_and_validate() {
+        let taskfile: TaskFile = toml::from_str(
+            r#"
+[env.vars]
+REGION = "eu-west-1"
+
+[tasks.deploy]
+cmd = "true"
+requires_env = ["REGION", "TASKFILE_TEST_UNSET_TOKEN"]
+env_docs = { REGION = "Where to deploy", TASKFILE_TEST_UNSET_PROFILE = "The AWS profile" }
+"#,
+        )
+        .unwrap();
+        let runner = TaskRunner::new(taskfile);
+        let task = runner.get_task("deploy").unwrap();
+
+        assert_eq!(
+            runner.documented_env("deploy", task),
+            [
+                DocumentedVar {
+                    key: "REGION",
+                    doc: Some("Where to deploy"),
+                    notes: vec!["required"],
+                },
+                DocumentedVar {
+                    key: "TASKFILE_TEST_UNSET_PROFILE",
+                    doc: Some("The AWS profile"),
+                    notes: vec!["not set"],
+                },
+                DocumentedVar {
+                    key: "TASKFILE_TEST_UNSET_TOKEN",
+                    doc: None,
+                    notes: vec!["required", "not set"],
+                },
+            ]
+        );
+        assert_eq!(
+            runner.missing_env("deploy", task),
+            [
+                "TASKFILE_TEST_UNSET_PROFILE (The AWS profile)",
+                "TASKFILE_TEST_UNSET_TOKEN"
+            ]
+        );
+        // Unset variables are warnings, not errors.
+        assert!(runner.validate());
+    }
+
+    #[tokio::test]
+    async fn test_requires_env_fails_before_running() {
+        let dir =
+            std::env::temp_dir().join(format!("taskfile-requires-env-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let marker = dir.join("ran");
+        let taskfile: TaskFile = toml::from_str(&format!(
+            r#"
+[env.vars]
+REGION = "eu-west-1"
+EMPTY = ""
+
+[tasks.setup]
+cmd = "touch {}"
+
+[tasks.deploy]
+cmd = "true"
+depends_on = ["setup"]
+requires_env = ["REGION", "TASKFILE_TEST_UNSET_PROFILE", "EMPTY"]
+
+[tasks.hermetic]
+cmd = "true"
+env_inherit = false
+requires_env = ["PATH"]
+"#,
+            marker.display()
+        ))
+        .unwrap();
+        let runner = TaskRunner::new(taskfile);
+
+        let err = runner.run_task("deploy").await.unwrap_err().to_string();
+        assert!(
+            err.contains("deploy: TASKFILE_TEST_UNSET_PROFILE, EMPTY"),
+            "{}",
+            err
+        );
+        assert!(!marker.exists(), "setup ran before the check");
+        let err = runner.run_task("hermetic").await.unwrap_err().to_string();
+        assert!(err.contains("hermetic: PATH"), "{}", err);
+        runner.run_task("setup").await.unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_referenced_env_vars() {
+        let taskfile: TaskFile = toml::from_str(
+            r#"
+[tasks.deploy]
+cmd = "deploy $REGION $HOME $TASKFILE_RUN_ID"
+requires_env = ["AWS_PROFILE"]
+[tasks.build]
+cmd = "build $REGION"
+"#,
+        )
+        .unwrap();
+        let used = TaskRunner::new(taskfile).referenced_env_vars();
+
+        assert_eq!(
+            used.keys().collect::<Vec<_>>(),
+            vec!["AWS_PROFILE", "REGION"]
+        );
+        assert_eq!(used["REGION"], vec!["build", "deploy"]);
+    }
+
+    #[test]
+    fn test_referenced_tools() {
+        let taskfile: TaskFile = toml::from_str(
+            r#"
+[tasks.deploy]
+cmd = "kubectl apply -f k8s/"
+requires = ["helm", { bin = "kustomize", install = "brew install kustomize" }]
+[tasks.readme]
+cmd = "bat README.md"
+cmd_fallbacks = ["cat README.md", "$PAGER README.md"]
+[tasks.version]
+cmd = "kubectl version"
+"#,
+        )
+        .unwrap();
+        let used = TaskRunner::new(taskfile).referenced_tools();
+
+        assert_eq!(
+            used.keys().collect::<Vec<_>>(),
+            vec!["bat", "cat", "helm", "kubectl", "kustomize"]
+        );
+        assert_eq!(used["kubectl"], vec!["deploy", "version"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_is_transient() {
+        use std::os::unix::process::ExitStatusExt;
+        use std::process::ExitStatus;
+
+        assert!(is_transient(&ExitStatus::from_raw(75 << 8)));
+        assert!(is_transient(&ExitStatus::from_raw(libc::SIGKILL)));
+        assert!(!is_transient(&ExitStatus::from_raw(libc::SIGINT)));
+        assert!(!is_transient(&ExitStatus::from_raw(libc::SIGSEGV)));
+        assert!(!is_transient(&ExitStatus::from_raw(libc::SIGABRT)));
+        assert!(!is_transient(&ExitStatus::from_raw(1 << 8)));
+    }
+
+    #[test]
+    fn test_task_operations() {
+        let mut tasks = IndexMap::new();
+        tasks.insert(
+            "test".to_string(),
+            Task {
+                cmd: "echo 'hello'".into(),
+                desc: Some("Test description".into()),
+                ..Default::default()
+            },
+        );
+
+        let taskfile = TaskFile {
+            tasks,
+            ..Default::default()
+        };
+        let runner = TaskRunner::new(taskfile);
+
+        assert!(runner.has_task("test"));
+        assert!(!runner.has_task("nonexistent"));
+        assert_eq!(runner.task_count(), 1);
+
+        let task = runner.get_task("test");
+        assert!(task.is_some());
+        assert_eq!(task.unwrap().cmd, "echo 'hello'");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_shell_mode() {
+        let out = std::env::temp_dir().join(format!("taskfile-shell-{}", std::process::id()));
+        let cmd = format!("echo 'a  b' | tr a c > {}", out.display());
+        let mut tasks = IndexMap::new();
+        tasks.insert(
+            "pipe".to_string(),
+            Task {
+                cmd: cmd.clone().into(),
+                ..Default::default()
+            },
+        );
+        tasks.insert(
+            "split".to_string(),
+            Task {
+                cmd: cmd.into(),
+                shell: Some(ShellSetting::Enabled(false)),
+                ..Default::default()
+            },
+        );
+        let runner = TaskRunner::new(TaskFile {
+            tasks,
+            shell: Some(ShellSetting::Enabled(true)),
+            ..Default::default()
+        });
+
+        runner.run_task("split").await.unwrap();
+        assert!(!out.exists());
+        runner.run_task("pipe").await.unwrap();
+        assert_eq!(fs::read_to_string(&out).unwrap(), "c  b\n");
+
+        fs::remove_file(&out).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_ignore_errors_keeps_dependents_running() {
+        let out = std::env::temp_dir().join(format!("taskfile-ignore-{}", std::process::id()));
+        let mut tasks = IndexMap::new();
+        tasks.insert(
+            "clean".to_string(),
+            Task {
+                cmd: "sh -c false".into(),
+                ignore_errors: true,
+                ..Default::default()
+            },
+        );
+        tasks.insert(
+            "build".to_string(),
+            Task {
+                cmd: format!("touch {}", out.display()).into(),
+                depends_on: Some(vec!["clean".into()]),
+                ..Default::default()
+            },
+        );
+        let runner = TaskRunner::new(TaskFile {
+            tasks,
+            ..Default::default()
+        });
+
+        runner.run_task("build").await.unwrap();
+        assert!(out.exists());
+        fs::remove_file(&out).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_run_if_skips_task_but_not_dependents() {
+        let out = std::env::temp_dir().join(format!("taskfile-run-if-{}", std::process::id()));
+        let mut tasks = IndexMap::new();
+        tasks.insert(
+            "never".to_string(),
+            Task {
+                cmd: "sh -c false".into(),
+                run_if: Some("test -f /nonexistent/Cargo.lock".to_string()),
+                ..Default::default()
+            },
+        );
+        tasks.insert(
+            "build".to_string(),
+            Task {
+                cmd: format!("mkdir {}", out.display()).into(),
+                depends_on: Some(vec!["never".into()]),
+                skip_if: Some(format!("test -d {}", out.display())),
+                ..Default::default()
+            },
+        );
+        let runner = TaskRunner::new(TaskFile {
+            tasks,
+            ..Default::default()
+        });
+
+        runner.run_task("build").await.unwrap();
+        assert!(out.is_dir());
+        // A second `mkdir` would fail; `skip_if` now holds instead.
+        runner.run_task("build").await.unwrap();
+        fs::remove_dir(&out).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_shared_dependencies_run_once() {
+        let dir = std::env::temp_dir().join(format!("taskfile-once-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let log = dir.join("log");
+        let task = |name: &str, deps: &[&str], run: RunPolicy| Task {
+            cmd: format!("sh -c 'echo {} >> {}'", name, log.display()).into(),
+            depends_on: Some(deps.iter().map(|d| Dependency::from(*d)).collect()),
+            run,
+            ..Default::default()
+        };
+        let tasks = IndexMap::from([
+            ("build".to_string(), task("build", &[], RunPolicy::Once)),
+            ("stamp".to_string(), task("stamp", &[], RunPolicy::Always)),
+            (
+                "test".to_string(),
+                task("test", &["build", "stamp"], RunPolicy::Once),
+            ),
+            (
+                "lint".to_string(),
+                task("lint", &["build", "stamp"], RunPolicy::Once),
+            ),
+        ]);
+        let runner = TaskRunner::new(TaskFile {
+            tasks,
+            ..Default::default()
+        });
+
+        runner.run_tasks(&["test", "lint"]).await.unwrap();
+        assert_eq!(
+            fs::read_to_string(&log).unwrap(),
+            "build\nstamp\ntest\nstamp\nlint\n"
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_pipeline_runs_stage_concurrently() {
+        let dir = std::env::temp_dir().join(format!("taskfile-pipeline-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let log = dir.join("log");
+        let append = |word: &str| format!("echo {} >> {}", word, log.display());
+        // `lint` only finishes once `typecheck` has started, so the stage
+        // fails unless both run at the same time.
+        let wait_for = |word: &str| {
+            format!(
+                "for i in $(seq 100); do grep -q {} {} && break; sleep 0.05; done; grep -q {} {}",
+                word,
+                log.display(),
+                word,
+                log.display()
+            )
+        };
+        let task = |cmd: String, deps: &[&str]| Task {
+            cmd: format!("sh -c '{}'", cmd).into(),
+            depends_on: Some(deps.iter().map(|d| Dependency::from(*d)).collect()),
+            ..Default::default()
+        };
+        let stages = |stages: &[&[&str]]| pipeline::Pipeline {
+            desc: None,
+            stages: stages
+                .iter()
+                .map(|stage| {
+                    let tasks: Vec<String> = stage.iter().map(|name| name.to_string()).collect();
+                    tasks.into()
+                })
+                .collect(),
+        };
+        let runner = TaskRunner::new(TaskFile {
+            tasks: IndexMap::from([
+                ("setup".to_string(), task(append("setup"), &[])),
+                (
+                    "lint".to_string(),
+                    task(
+                        format!("{} && {}", wait_for("typecheck"), append("lint")),
+                        &["setup"],
+                    ),
+                ),
+                (
+                    "typecheck".to_string(),
+                    task(append("typecheck"), &["setup"]),
+                ),
+                ("build".to_string(), task(append("build"), &[])),
+                ("broken".to_string(), task("exit 3".to_string(), &[])),
+                ("missing".to_string(), task("exit 4".to_string(), &[])),
+            ]),
+            pipelines: IndexMap::from([
+                (
+                    "ci".to_string(),
+                    stages(&[&["lint", "typecheck"], &["build"]]),
+                ),
+                (
+                    "fails".to_string(),
+                    stages(&[&["broken", "missing", "setup"], &["build"]]),
+                ),
+                ("unknown".to_string(), stages(&[&["lint", "deploy"]])),
+            ]),
+            ..Default::default()
+        });
+
+        runner.run_pipeline("ci").await.unwrap();
+        assert_eq!(
+            fs::read_to_string(&log).unwrap(),
+            "setup\ntypecheck\nlint\nbuild\n"
+        );
+
+        fs::remove_file(&log).unwrap();
+        let err = runner.run_pipeline("fails").await.unwrap_err().to_string();
+        assert!(
+            err.starts_with("2 tasks failed")
+                && err.contains("exit code 3")
+                && err.contains("exit code 4"),
+            "{}",
+            err
+        );
+        assert_eq!(fs::read_to_string(&log).unwrap(), "setup\n");
+
+        let err = runner
+            .run_pipeline("unknown")
+            .await
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("'deploy'"), "{}", err);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_parallel_dependencies() {
+        let dir = std::env::temp_dir().join(format!("taskfile-parallel-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let log = dir.join("log");
+        let task = |cmd: String, deps: &[&str], parallel: Option<bool>| Task {
+            cmd: format!("sh -c '{}'", cmd).into(),
+            depends_on: Some(deps.iter().map(|d| Dependency::from(*d)).collect()),
+            depends_on_parallel: parallel,
+            ..Default::default()
+        };
+        let append = |word: &str| format!("echo {} >> {}", word, log.display());
+        // `slow` only finishes once `fast` has run, so it fails unless both
+        // run at the same time.
+        let slow = format!(
+            "for i in $(seq 100); do grep -q fast {log} && break; sleep 0.05; done; grep -q fast {log} && echo slow >> {log}",
+            log = log.display()
+        );
+        let taskfile =
+            TaskFile::parse("[settings]\ndepends_on_parallel = true\n[tasks]\n").unwrap();
+        let runner = TaskRunner::new(TaskFile {
+            tasks: IndexMap::from([
+                ("setup".to_string(), task(append("setup"), &[], None)),
+                ("slow".to_string(), task(slow, &["setup"], None)),
+                ("fast".to_string(), task(append("fast"), &["setup"], None)),
+                (
+                    "all".to_string(),
+                    task(append("all"), &["slow", "fast"], None),
+                ),
+                ("one".to_string(), task("exit 3".to_string(), &[], None)),
+                ("two".to_string(), task("exit 4".to_string(), &[], None)),
+                (
+                    "both".to_string(),
+                    task(append("both"), &["one", "two"], None),
+                ),
+                (
+                    "sequential".to_string(),
+                    task(append("sequential"), &["one", "two"], Some(false)),
+                ),
+            ]),
+            ..taskfile
+        });
+
+        runner.run_task("all").await.unwrap();
+        assert_eq!(
+            fs::read_to_string(&log).unwrap(),
+            "setup\nfast\nslow\nall\n"
+        );
+
+        let err = runner.run_task("both").await.unwrap_err().to_string();
+        assert!(
+            err.starts_with("2 tasks failed")
+                && err.contains("exit code 3")
+                && err.contains("exit code 4"),
+            "{}",
+            err
+        );
+        let err = runner.run_task("sequential").await.unwrap_err().to_string();
+        assert!(!err.contains("exit code 4"), "{}", err);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_jobs_limit_starts_longest_first() {
+        let dir = std::env::temp_dir().join(format!("taskfile-jobs-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let log = dir.join("log");
+        let task = |name: &str, estimate: Option<u64>| Task {
+            cmd: format!(
+                "sh -c 'echo start-{name} >> {log}; sleep 0.05; echo end-{name} >> {log}'",
+                name = name,
+                log = log.display()
+            )
+            .into(),
+            estimated_duration: estimate.map(Duration::from_secs),
+            ..Default::default()
+        };
+        let runner = TaskRunner::new_with_base_path(
+            TaskFile {
+                tasks: IndexMap::from([
+                    ("docs".to_string(), task("docs", None)),
+                    ("lint".to_string(), task("lint", Some(10))),
+                    ("test".to_string(), task("test", Some(120))),
+                    (
+                        "ci".to_string(),
+                        Task {
+                            cmd: "true".into(),
+                            depends_on: Some(vec!["docs".into(), "lint".into(), "test".into()]),
+                            depends_on_parallel: Some(true),
+                            ..Default::default()
+                        },
+                    ),
+                ]),
+                ..Default::default()
+            },
+            Some(&dir),
+        )
+        .with_options(RunOptions {
+            jobs: Some(1),
+            ..Default::default()
+        });
+
+        runner.run_task("ci").await.unwrap();
+        assert_eq!(
+            fs::read_to_string(&log).unwrap(),
+            "start-test\nend-test\nstart-lint\nend-lint\nstart-docs\nend-docs\n"
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_optional_dependency_failure_is_a_warning() {
+        let dir = std::env::temp_dir().join(format!("taskfile-optional-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let log = dir.join("log");
+        let runner = TaskRunner::new(
+            TaskFile::parse(&format!(
+                r#"
+[tasks.lint]
+cmd = "sh -c 'exit 3'"
+[tasks.fmt]
+cmd = "sh -c 'echo fmt >> {log}'"
+[tasks.build]
+cmd = "sh -c 'echo build >> {log}'"
+depends_on = [{{ task = "lint", optional = true }}, "fmt"]
+[tasks.release]
+cmd = "sh -c 'echo release >> {log}'"
+depends_on = [{{ task = "lint", optional = true }}, "fmt"]
+depends_on_parallel = true
+[tasks.strict]
+cmd = "sh -c 'echo strict >> {log}'"
+depends_on = ["lint"]
+"#,
+                log = log.display()
+            ))
+            .unwrap(),
+        );
+
+        runner.run_task("build").await.unwrap();
+        runner.run_task("release").await.unwrap();
+        assert_eq!(
+            fs::read_to_string(&log).unwrap(),
+            "fmt\nbuild\nfmt\nrelease\n"
+        );
+        let err = runner.run_task("strict").await.unwrap_err().to_string();
+        assert!(err.contains("exit code 3"), "{}", err);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_export_script() {
+        let task = |cmd: &str, deps: &[&str]| Task {
+            cmd: cmd.into(),
+            depends_on: Some(deps.iter().map(|d| Dependency::from(*d)).collect()),
+            ..Default::default()
+        };
+        let runner = TaskRunner::new(TaskFile {
+            tasks: IndexMap::from([
+                (
+                    "deploy".to_string(),
+                    task("sh -c 'echo \"$GREETING $TOKEN\"'", &["build", "setup"]),
+                ),
+                ("build".to_string(), task("echo {{target}}", &["setup"])),
+                ("setup".to_string(), task("echo \"it's set up\"", &[])),
+            ]),
+            vars: HashMap::from([("target".to_string(), "release".to_string())]),
+            ..Default::default()
+        })
+        .with_env_overrides(&[("GREETING".to_string(), "hello".to_string())])
+        .with_stdin_env(&[("TOKEN".to_string(), "s3cr3t".to_string())]);
+
+        let script = runner.export_script("deploy", &[]).await.unwrap();
+        assert!(!script.contains("s3cr3t"));
+        assert!(script.contains("export GREETING=hello\n"));
+        assert!(script.contains("echo release\n"));
+        let setup = script.find("# setup").unwrap();
+        let build = script.find("# build").unwrap();
+        assert!(setup < build && build < script.find("# deploy").unwrap());
+        assert_eq!(script.matches("# setup").count(), 1);
+
+        let output = std::process::Command::new("sh")
+            .args(["-c", &script])
+            .env("TOKEN", "abc")
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout),
+            "it's set up\nrelease\nhello abc\n"
+        );
+        let output = std::process::Command::new("sh")
+            .args(["-c", &script])
+            .env_remove("TOKEN")
+            .output()
+            .unwrap();
+        assert!(!output.status.success());
+        assert!(String::from_utf8_lossy(&output.stderr).contains("TOKEN is a secret"));
+        assert!(runner.export_script("missing", &[]).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_warm_script() {
+        let runner = TaskRunner::new(TaskFile {
+            tasks: IndexMap::from([
+                (
+                    "fmt".to_string(),
+                    Task {
+                        cmd: "echo \"$TOKEN\"".into(),
+                        ..Default::default()
+                    },
+                ),
+                (
+                    "slow".to_string(),
+                    Task {
+                        cmd: "true".into(),
+                        timeout: Some(Duration::from_secs(60)),
+                        ..Default::default()
+                    },
+                ),
+            ]),
+            ..Default::default()
+        })
+        .with_stdin_env(&[("TOKEN".to_string(), "s3cr3t".to_string())]);
+
+        // The daemon keeps the script, so the secret is set in it.
+        let script = runner.warm_script("fmt", &[]).await.unwrap().unwrap();
+        assert!(script.contains("export TOKEN=s3cr3t\n"));
+        let output = std::process::Command::new("sh")
+            .args(["-c", &script])
+            .env_remove("TOKEN")
+            .output()
+            .unwrap();
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "s3cr3t\n");
+
+        assert_eq!(runner.warm_script("slow", &[]).await.unwrap(), None);
+        assert!(runner.warm_script("missing", &[]).await.is_err());
+        let hooked = TaskRunner::new(TaskFile {
+            tasks: IndexMap::from([("fmt".to_string(), Task::default())]),
+            hooks: Hooks {
+                before_all: vec!["echo start".to_string()],
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+        assert_eq!(hooked.warm_script("fmt", &[]).await.unwrap(), None);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_script_runs_with_interpreter() {
+        let dir = std::env::temp_dir().join(format!("taskfile-script-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let log = dir.join("log");
+        let script = format!(
+            "set -e\necho \"{{{{target}}}} $# $1\" > {}\ncat \"$0\" | wc -l | tr -d ' ' >> {}\n",
+            log.display(),
+            log.display()
+        );
+        let runner = TaskRunner::new(TaskFile {
+            tasks: IndexMap::from([
+                (
+                    "gen".to_string(),
+                    Task {
+                        script: Some(script.clone()),
+                        interpreter: Some("sh -e".to_string()),
+                        ..Default::default()
+                    },
+                ),
+                (
+                    "both".to_string(),
+                    Task {
+                        cmd: "true".into(),
+                        script: Some(script),
+                        ..Default::default()
+                    },
+                ),
+                (
+                    "fails".to_string(),
+                    Task {
+                        script: Some("exit 4\n".to_string()),
+                        ..Default::default()
+                    },
+                ),
+            ]),
+            vars: HashMap::from([("target".to_string(), "release".to_string())]),
+            ..Default::default()
+        });
+
+        let args = ["--".to_string(), "one".to_string(), "two".to_string()];
+        runner.run_task_with_args("gen", &args).await.unwrap();
+        assert_eq!(fs::read_to_string(&log).unwrap(), "release 2 one\n3\n");
+        let quoted = ["--", "it's one", "two"].map(String::from);
+        runner.run_task_with_args("gen", &quoted).await.unwrap();
+        assert_eq!(fs::read_to_string(&log).unwrap(), "release 2 it's one\n3\n");
+        let scripts = |prefix: &str| {
+            fs::read_dir(std::env::temp_dir())
+                .unwrap()
+                .filter_map(Result::ok)
+                .filter(|entry| {
+                    let name = entry.file_name().to_string_lossy().into_owned();
+                    name.starts_with(prefix) && name.ends_with(".sh")
+                })
+                .count()
+        };
+        let prefix = format!("taskfile-script-{}-", std::process::id());
+        assert_eq!(scripts(&prefix), 0);
+
+        let err = runner.run_task("both").await.unwrap_err().to_string();
+        assert!(err.contains("sets script together with cmd"), "{}", err);
+        let err = runner.run_task("fails").await.unwrap_err().to_string();
+        assert!(err.contains("exit code 4"), "{}", err);
+        assert_eq!(scripts(&prefix), 0);
+
+        let exported = runner.export_script("gen", &args).await.unwrap();
+        fs::remove_file(&log).unwrap();
+        let output = std::process::Command::new("sh")
+            .args(["-c", &exported])
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{}", exported);
+        assert_eq!(fs::read_to_string(&log).unwrap(), "release 2 one\n3\n");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_multi_line_cmd_runs_as_script() {
+        let dir = std::env::temp_dir().join(format!("taskfile-multiline-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let log = dir.join("log");
+        let cmd = format!(
+            "\nfor word in one two; do\n  echo $word >> {}\ndone\nfalse\necho three >> {}\n",
+            log.display(),
+            log.display()
+        );
+        let task = |errexit| Task {
+            cmd: cmd.clone().into(),
+            errexit,
+            ..Default::default()
+        };
+        let runner = TaskRunner::new(TaskFile {
+            tasks: IndexMap::from([
+                ("strict".to_string(), task(None)),
+                ("lenient".to_string(), task(Some(false))),
+                (
+                    "one-line".to_string(),
+                    Task {
+                        cmd: "true".into(),
+                        errexit: Some(false),
+                        ..Default::default()
+                    },
+                ),
+            ]),
+            ..Default::default()
+        });
+
+        let err = runner.run_task("strict").await.unwrap_err().to_string();
+        assert!(err.contains("exit code 1"), "{}", err);
+        assert_eq!(fs::read_to_string(&log).unwrap(), "one\ntwo\n");
+
+        fs::remove_file(&log).unwrap();
+        runner.run_task("lenient").await.unwrap();
+        assert_eq!(fs::read_to_string(&log).unwrap(), "one\ntwo\nthree\n");
+        assert_eq!(script_summary(&cmd), "for word in one two; do … (5 lines)");
+        let err = runner.run_task("one-line").await.unwrap_err().to_string();
+        assert!(err.contains("sets errexit"), "{}", err);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_paths_outside_project_fail_under_strict() {
+        let dir = std::env::temp_dir().join(format!("taskfile-sandbox-{}", std::process::id()));
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        let task = |cmd: &str, cwd: Option<&str>, allow_outside| Task {
+            cmd: cmd.into(),
+            cwd: cwd.map(str::to_string),
+            allow_outside,
+            ..Default::default()
+        };
+        let taskfile = || TaskFile {
+            tasks: IndexMap::from([
+                (
+                    "inside".to_string(),
+                    task("/usr/bin/env true", Some("sub"), false),
+                ),
+                ("deploy".to_string(), task("echo /srv/www", None, false)),
+                ("parent".to_string(), task("true", Some(".."), false)),
+                ("install".to_string(), task("echo ~/.local/bin", None, true)),
+            ]),
+            ..Default::default()
+        };
+        let runner = TaskRunner::new_with_base_path(taskfile(), Some(&dir));
+        runner.run_task("deploy").await.unwrap();
+
+        let strict =
+            TaskRunner::new_with_base_path(taskfile(), Some(&dir)).with_options(RunOptions {
+                strict: true,
+                ..Default::default()
+            });
+        strict.run_task("inside").await.unwrap();
+        strict.run_task("install").await.unwrap();
+        let err = strict.run_task("deploy").await.unwrap_err().to_string();
+        assert!(err.contains("uses /srv/www outside the project"), "{}", err);
+        let err = strict.run_task("parent").await.unwrap_err().to_string();
+        assert!(err.contains("runs in .., outside the project"), "{}", err);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_elevate() {
+        let dir = std::env::temp_dir().join(format!("taskfile-elevate-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let log = dir.join("log");
+        let runner = TaskRunner::new_with_base_path(
+            TaskFile {
+                tasks: IndexMap::from([
+                    (
+                        "install".to_string(),
+                        Task {
+                            cmd: format!("sh -c 'id -u > {}'", log.display()).into(),
+                            elevate: true,
+                            ..Default::default()
+                        },
+                    ),
+                    (
+                        "remote".to_string(),
+                        Task {
+                            cmd: "true".into(),
+                            elevate: true,
+                            remote_build: true,
+                            ..Default::default()
+                        },
+                    ),
+                ]),
+                ..Default::default()
+            },
+            Some(&dir),
+        );
+
+        let exported = runner.export_script("install", &[]).await.unwrap();
+        assert!(
+            exported.contains("  sudo --preserve-env -- sh -c "),
+            "{}",
+            exported
+        );
+        let err = runner.run_task("remote").await.unwrap_err().to_string();
+        assert!(err.contains("sets elevate"), "{}", err);
+        // Already root, as in a container, the command runs as it is.
+        if elevate::is_elevated() {
+            runner.run_task("install").await.unwrap();
+            assert_eq!(fs::read_to_string(&log).unwrap(), "0\n");
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_umask_and_output_mode() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir().join(format!("taskfile-umask-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let mode = |name: &str| fs::metadata(dir.join(name)).unwrap().permissions().mode() & 0o7777;
+        let task = |cmd: String, umask, output_mode| Task {
+            cmd: cmd.into(),
+            umask,
+            outputs: vec!["*.key".to_string()],
+            output_mode,
+            ..Default::default()
+        };
+        let write = |name| format!("sh -c 'echo secret > {}/{}'", dir.display(), name);
+        let runner = TaskRunner::new_with_base_path(
+            TaskFile {
+                tasks: IndexMap::from([
+                    (
+                        "strict".to_string(),
+                        task(write("a.conf"), Some(0o077), None),
+                    ),
+                    ("key".to_string(), task(write("b.key"), None, Some(0o400))),
+                ]),
+                ..Default::default()
+            },
+            Some(&dir),
+        );
+
+        runner.run_task("strict").await.unwrap();
+        assert_eq!(mode("a.conf"), 0o600);
+        runner.run_task("key").await.unwrap();
+        assert_eq!(mode("b.key"), 0o400);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_finally_runs_after_failure() {
+        let dir = std::env::temp_dir().join(format!("taskfile-finally-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let log = dir.join("log");
+        let append = |word: &str| format!("sh -c 'echo {} >> {}'", word, log.display());
+        let task = |cmd: String| Task {
+            cmd: cmd.into(),
+            finally: vec![append("down"), "false".to_string(), append("done")],
+            ..Default::default()
+        };
+        let runner = TaskRunner::new(TaskFile {
+            tasks: IndexMap::from([
+                ("fails".to_string(), task("sh -c 'exit 3'".to_string())),
+                ("passes".to_string(), task(append("up"))),
+            ]),
+            ..Default::default()
+        });
+
+        let err = runner.run_task("fails").await.unwrap_err().to_string();
+        assert!(err.contains("exit code 3"), "{}", err);
+        assert_eq!(fs::read_to_string(&log).unwrap(), "down\ndone\n");
+
+        fs::remove_file(&log).unwrap();
+        runner.run_task("passes").await.unwrap();
+        assert_eq!(fs::read_to_string(&log).unwrap(), "up\ndown\ndone\n");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_background_dependency_is_stopped() {
+        let dir = std::env::temp_dir().join(format!("taskfile-background-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let pid = dir.join("pid");
+        let taskfile = TaskFile {
+            tasks: IndexMap::from([
+                (
+                    "db".to_string(),
+                    Task {
+                        cmd: format!(
+                            "sh -c 'echo $$ > {}; echo ready; exec sleep 30'",
+                            pid.display()
+                        )
+                        .into(),
+                        background: true,
+                        ..Default::default()
+                    },
+                ),
+                (
+                    "test".to_string(),
+                    Task {
+                        cmd: format!("sh -c 'sleep 0.3; test -s {}'", pid.display()).into(),
+                        depends_on: Some(vec!["db".into()]),
+                        ..Default::default()
+                    },
+                ),
+            ]),
+            ..Default::default()
+        };
+        let runner = TaskRunner::new_with_base_path(taskfile, Some(&dir));
+
+        runner.run_task("test").await.unwrap();
+        let pid: libc::pid_t = fs::read_to_string(&pid).unwrap().trim().parse().unwrap();
+        assert_eq!(unsafe { libc::kill(pid, 0) }, -1, "db is still running");
+        let log = fs::read_to_string(dir.join(".taskfile/background/db.log")).unwrap();
+        assert_eq!(log, "ready\n");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_pre_and_post_hooks() {
+        let dir = std::env::temp_dir().join(format!("taskfile-hooks-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let log = dir.join("log");
+        let append = |word: &str| format!("sh -c 'echo {} >> {}'", word, log.display());
+        let task = |pre: &str, cmd: &str, post: &str| Task {
+            pre: vec![pre.to_string()],
+            cmd: cmd.into(),
+            post: vec![post.to_string()],
+            ..Default::default()
+        };
+        let post = append("post=$TASKFILE_EXIT_CODE");
+        let runner = TaskRunner::new(TaskFile {
+            tasks: IndexMap::from([
+                (
+                    "ok".to_string(),
+                    task(&append("pre"), &append("main"), &post),
+                ),
+                ("fails".to_string(), task("true", "sh -c 'exit 4'", &post)),
+                (
+                    "pre_fails".to_string(),
+                    task("false", &append("main"), &post),
+                ),
+                ("post_fails".to_string(), task("true", "true", "false")),
+            ]),
+            ..Default::default()
+        });
+        let run = async |name: &str| {
+            let _ = fs::remove_file(&log);
+            let result = runner.run_task(name).await.map_err(|e| e.to_string());
+            (result, fs::read_to_string(&log).unwrap_or_default())
+        };
+
+        assert_eq!(run("ok").await, (Ok(()), "pre\nmain\npost=0\n".to_string()));
+        let (result, log_text) = run("fails").await;
+        assert!(result.unwrap_err().contains("exit code 4"));
+        assert_eq!(log_text, "post=4\n");
+        let (result, log_text) = run("pre_fails").await;
+        assert!(result.unwrap_err().contains("pre hook"));
+        assert_eq!(log_text, "");
+        let (result, _) = run("post_fails").await;
+        assert!(result.unwrap_err().contains("post hook"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_global_hooks() {
+        let dir =
+            std::env::temp_dir().join(format!("taskfile-global-hooks-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let log = dir.join("log");
+        let append = |text: &str| format!("echo {} >> {}", text, log.display());
+        let task = |cmd: &str, deps: &[&str]| Task {
+            cmd: cmd.into(),
+            depends_on: Some(deps.iter().map(|d| Dependency::from(*d)).collect()),
+            ..Default::default()
+        };
+        let runner = TaskRunner::new(TaskFile {
+            tasks: IndexMap::from([
+                ("build".to_string(), task("true", &[])),
+                ("test".to_string(), task("false", &["build"])),
+            ]),
+            hooks: Hooks {
+                before_all: vec![append("start")],
+                before_each: vec![append("before-$TASKFILE_TASK")],
+                after_each: vec![append("after-{{TASKFILE_TASK}}-$TASKFILE_STATUS")],
+                after_all: vec![append("end-$TASKFILE_STATUS"), "false".to_string()],
+            },
+            ..Default::default()
+        });
+
+        // The task's own failure outlives the failing after_all hook.
+        let err = runner.run_task("test").await.unwrap_err().to_string();
+        assert!(err.contains("exit code 1"), "{}", err);
+        assert!(!err.contains("after_all"), "{}", err);
+        assert_eq!(
+            fs::read_to_string(&log).unwrap(),
+            "start\nbefore-build\nafter-build-success\nbefore-test\nafter-test-failed\nend-failed\n"
+        );
+
+        fs::remove_file(&log).unwrap();
+        let err = runner.run_task("build").await.unwrap_err().to_string();
+        assert!(err.starts_with("after_all hook failed"), "{}", err);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_offline_rejects_network_tasks() {
+        let runner = TaskRunner::new(TaskFile {
+            tasks: IndexMap::from([
+                (
+                    "deploy".to_string(),
+                    Task {
+                        cmd: "make".into(),
+                        remote_build: true,
+                        ..Default::default()
+                    },
+                ),
+                (
+                    "ship".to_string(),
+                    Task {
+                        cmd: "true".into(),
+                        depends_on: Some(vec!["deploy".into()]),
+                        ..Default::default()
+                    },
+                ),
+            ]),
+            ..Default::default()
+        })
+        .with_options(RunOptions {
+            offline: true,
+            ..Default::default()
+        });
+
+        let err = runner.run_tasks(&["ship"]).await.unwrap_err().to_string();
+        assert!(
+            err.contains("'deploy'") && err.contains("--offline"),
+            "{}",
+            err
+        );
+        let slice = GraphSlice {
+            skip: vec!["deploy".to_string()],
+            ..Default::default()
+        };
+        runner.run_task_slice("ship", &[], &slice).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_confirm_needs_yes_without_terminal() {
+        let taskfile = || TaskFile {
+            tasks: IndexMap::from([
+                (
+                    "drop".to_string(),
+                    Task {
+                        cmd: "echo dropped".into(),
+                        confirm: Some("Drop the database?".to_string()),
+                        ..Default::default()
+                    },
+                ),
+                (
+                    "reset".to_string(),
+                    Task {
+                        cmd: "echo reset".into(),
+                        depends_on: Some(vec!["drop".into()]),
+                        ..Default::default()
+                    },
+                ),
+            ]),
+            ..Default::default()
+        };
+
+        if !std::io::stdin().is_terminal() {
+            let err = TaskRunner::new(taskfile())
+                .run_task("reset")
+                .await
+                .unwrap_err();
+            assert!(err.to_string().contains("pass --yes"), "{}", err);
+        }
+        let options = RunOptions {
+            yes: true,
+            ..Default::default()
+        };
+        TaskRunner::new(taskfile())
+            .with_options(options)
+            .run_task("reset")
+            .await
+            .unwrap();
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_pipeline_approval() {
+        let dir = std::env::temp_dir().join(format!("taskfile-approval-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let log = dir.join("log");
+        let taskfile = || {
+            TaskFile::parse(&format!(
+                "[pipelines.release]\nstages = [[\"test\"], {{ tasks = [\"deploy\"], approval = true }}]\n[tasks.test]\ncmd = \"sh -c 'echo test >> {log}'\"\n[tasks.deploy]\ncmd = \"sh -c 'echo deploy >> {log}'\"\n",
+                log = log.display()
+            ))
+            .unwrap()
+        };
+
+        if !std::io::stdin().is_terminal() {
+            let err = TaskRunner::new(taskfile())
+                .run_pipeline("release")
+                .await
+                .unwrap_err();
+            assert!(err.to_string().contains("pass --approve"), "{}", err);
+            assert!(!log.exists());
+        }
+        let options = RunOptions {
+            approve: true,
+            ..Default::default()
+        };
+        TaskRunner::new(taskfile())
+            .with_options(options)
+            .run_pipeline("release")
+            .await
+            .unwrap();
+        assert_eq!(fs::read_to_string(&log).unwrap(), "test\ndeploy\n");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_run_tagged() {
+        let dir = std::env::temp_dir().join(format!("taskfile-tags-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let log = dir.join("log");
+        let task = |name: &str, tags: &[&str], deps: &[&str]| Task {
+            cmd: format!("sh -c 'echo {} >> {}'", name, log.display()).into(),
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+            depends_on: Some(deps.iter().map(|d| Dependency::from(*d)).collect()),
+            ..Default::default()
+        };
+        let tasks = IndexMap::from([
+            ("test".to_string(), task("test", &["ci"], &["lint"])),
+            ("migrate".to_string(), task("migrate", &["db"], &[])),
+            ("lint".to_string(), task("lint", &["ci"], &[])),
+            ("docs".to_string(), task("docs", &[], &[])),
+        ]);
+        let runner = TaskRunner::new_with_base_path(
+            TaskFile {
+                tasks,
+                ..Default::default()
+            },
+            Some(&dir),
+        );
+
+        let tags = |tags: &[&str]| tags.iter().map(|t| t.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            runner.tasks_tagged(&tags(&["db", "ci"])),
+            ["test", "migrate", "lint"]
+        );
+        assert!(runner.run_tagged(&tags(&["nope"])).await.is_err());
+
+        // `lint` runs once, as the dependency of `test`.
+        runner.run_tagged(&tags(&["ci"])).await.unwrap();
+        assert_eq!(fs::read_to_string(&log).unwrap(), "lint\ntest\n");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_vars_in_cmd_and_cwd() {
+        let dir = std::env::temp_dir().join(format!("taskfile-vars-{}", std::process::id()));
+        fs::create_dir_all(dir.join("out")).unwrap();
+        let mut tasks = IndexMap::new();
+        tasks.insert(
+            "build".to_string(),
+            Task {
+                cmd: "touch {{bin}}-{{version}}".into(),
+                cwd: Some("{{out_dir}}".to_string()),
+                params: HashMap::from([(
+                    "version".to_string(),
+                    ParamSpec::Default("dev".to_string()),
+                )]),
+                ..Default::default()
+            },
+        );
+        let vars = HashMap::from([
+            ("bin".to_string(), "app".to_string()),
+            ("version".to_string(), "1.0".to_string()),
+            ("out_dir".to_string(), "out".to_string()),
+        ]);
+        let runner = TaskRunner::new_with_base_path(
+            TaskFile {
+                tasks,
+                vars,
+                ..Default::default()
+            },
+            Some(&dir),
+        );
+
+        runner.run_task("build").await.unwrap();
+        // The param's default wins over the var of the same name.
+        assert!(dir.join("out/app-dev").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_cmds_stop_at_first_failure() {
+        let dir = std::env::temp_dir().join(format!("taskfile-cmds-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let marker = |name: &str| dir.join(name).display().to_string();
+
+        let mut tasks = IndexMap::new();
+        tasks.insert(
+            "ci".to_string(),
+            Task {
+                cmds: vec![
+                    format!("touch {}", marker("lint")),
+                    "false".to_string(),
+                    format!("touch {}", marker("test")),
+                ],
+                ..Default::default()
+            },
+        );
+        let runner = TaskRunner::new(TaskFile {
+            tasks,
+            ..Default::default()
+        });
+
+        assert!(runner.run_task("ci").await.is_err());
+        assert!(dir.join("lint").exists());
+        assert!(!dir.join("test").exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_run_task_slice() {
+        let dir = std::env::temp_dir().join(format!("taskfile-slice-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let pipeline = ["fetch", "build", "test", "deploy"];
+        let mut tasks = IndexMap::new();
+        for (i, name) in pipeline.iter().enumerate() {
+            tasks.insert(
+                name.to_string(),
+                Task {
+                    cmd: format!("touch {}", dir.join(name).display()).into(),
+                    depends_on: i.checked_sub(1).map(|prev| vec![pipeline[prev].into()]),
+                    ..Default::default()
+                },
+            );
+        }
+        let runner = TaskRunner::new(TaskFile {
+            tasks,
+            ..Default::default()
+        });
+
+        let ran = || -> Vec<bool> {
+            pipeline
+                .iter()
+                .map(|name| dir.join(name).exists())
+                .collect()
+        };
+
+        let slice = GraphSlice {
+            from: Some("build".to_string()),
+            until: Some("test".to_string()),
+            ..Default::default()
+        };
+        runner.run_task_slice("deploy", &[], &slice).await.unwrap();
+        assert_eq!(ran(), vec![false, true, true, false]);
+
+        let slice = GraphSlice {
+            skip: vec!["test".to_string()],
+            ..Default::default()
+        };
+        fs::remove_file(dir.join("build")).unwrap();
+        fs::remove_file(dir.join("test")).unwrap();
+        runner.run_task_slice("deploy", &[], &slice).await.unwrap();
+        assert_eq!(ran(), vec![false, false, false, true]);
+
+        let slice = GraphSlice {
+            from: Some("deploy".to_string()),
+            until: Some("build".to_string()),
+            ..Default::default()
+        };
+        assert!(runner.run_task_slice("deploy", &[], &slice).await.is_err());
+
+        let slice = GraphSlice {
+            skip: vec!["deploy".to_string()],
+            ..Default::default()
+        };
+        assert!(runner.run_task_slice("deploy", &[], &slice).await.is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
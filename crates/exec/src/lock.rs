@@ -0,0 +1,107 @@
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use tokio::time::{sleep, Duration};
+
+pub use taskfile_model::LockMode;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Exclusive `.taskfile/lock` for one invocation; released on drop.
+#[derive(Debug)]
+pub struct ProjectLock {
+    path: PathBuf,
+}
+
+impl ProjectLock {
+    pub async fn acquire(
+        project_dir: &Path,
+        mode: LockMode,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let dir = project_dir.join(".taskfile");
+        fs::create_dir_all(&dir)?;
+        let path = dir.join("lock");
+        let mut announced = false;
+
+        loop {
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(mut file) => {
+                    write!(file, "{}", std::process::id())?;
+                    return Ok(Self { path });
+                }
+                Err(e) if e.kind() == ErrorKind::AlreadyExists => {}
+                Err(e) => return Err(e.into()),
+            }
+
+            let holder = fs::read_to_string(&path)
+                .ok()
+                .and_then(|pid| pid.trim().parse::<u32>().ok());
+
+            if let Some(pid) = holder {
+                if !process_alive(pid) {
+                    let _ = fs::remove_file(&path);
+                    continue;
+                }
+            }
+
+            let holder = holder
+                .map(|pid| format!("pid {}", pid))
+                .unwrap_or_else(|| "unknown process".to_string());
+
+            match mode {
+                LockMode::Fail => {
+                    return Err(format!(
+                        "Another task run ({}) holds {}; wait for it to finish or set lock = \"wait\"",
+                        holder,
+                        path.display()
+                    )
+                    .into());
+                }
+                LockMode::Wait => {
+                    if !announced {
+                        println!("Waiting for another task run ({}) to finish...", holder);
+                        announced = true;
+                    }
+                    sleep(POLL_INTERVAL).await;
+                }
+            }
+        }
+    }
+}
+
+impl Drop for ProjectLock {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+#[cfg(unix)]
+fn process_alive(pid: u32) -> bool {
+    let rc = unsafe { libc::kill(pid as libc::pid_t, 0) };
+    rc == 0 || std::io::Error::last_os_error().raw_os_error() != Some(libc::ESRCH)
+}
+
+/// Without a cheap liveness probe, assume the holder is still running.
+#[cfg(not(unix))]
+fn process_alive(_pid: u32) -> bool {
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_lock_fails_fast_while_held() {
+        let dir = std::env::temp_dir().join(format!("taskfile-lock-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let lock = ProjectLock::acquire(&dir, LockMode::Fail).await.unwrap();
+        assert!(ProjectLock::acquire(&dir, LockMode::Fail).await.is_err());
+
+        drop(lock);
+        assert!(ProjectLock::acquire(&dir, LockMode::Fail).await.is_ok());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
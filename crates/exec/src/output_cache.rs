@@ -0,0 +1,170 @@
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// Output captured from a successful run, replayed on a cache hit.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CachedOutput {
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+}
+
+/// Captured output of cacheable tasks under `.taskfile/cache/output`, one
+/// file per cache key. Once the entries exceed `max_bytes`, the least
+/// recently used ones are evicted.
+#[derive(Debug, Clone)]
+pub struct OutputCache {
+    dir: PathBuf,
+    max_bytes: u64,
+}
+
+impl OutputCache {
+    pub fn new(project_dir: &Path, max_bytes: u64) -> Self {
+        Self {
+            dir: project_dir.join(".taskfile").join("cache").join("output"),
+            max_bytes,
+        }
+    }
+
+    /// Saves the output for `key`. Output larger than the whole cap is not
+    /// kept at all.
+    pub fn store(&self, key: &str, stdout: &[u8], stderr: &[u8]) -> io::Result<()> {
+        let size = 8 + stdout.len() as u64 + stderr.len() as u64;
+        if size > self.max_bytes {
+            return Ok(());
+        }
+
+        fs::create_dir_all(&self.dir)?;
+        let mut entry = Vec::with_capacity(size as usize);
+        entry.extend_from_slice(&(stdout.len() as u64).to_le_bytes());
+        entry.extend_from_slice(stdout);
+        entry.extend_from_slice(stderr);
+        fs::write(self.entry_path(key), entry)?;
+        self.evict()
+    }
+
+    /// Output stored for `key`, marking the entry as recently used.
+    pub fn load(&self, key: &str) -> Option<CachedOutput> {
+        let path = self.entry_path(key);
+        let entry = fs::read(&path).ok()?;
+        if entry.len() < 8 {
+            return None;
+        }
+        let stdout_len = u64::from_le_bytes(entry[..8].try_into().ok()?) as usize;
+        let body = &entry[8..];
+        if stdout_len > body.len() {
+            return None;
+        }
+
+        if let Ok(file) = File::options().write(true).open(&path) {
+            let _ = file.set_modified(SystemTime::now());
+        }
+        Some(CachedOutput {
+            stdout: body[..stdout_len].to_vec(),
+            stderr: body[stdout_len..].to_vec(),
+        })
+    }
+
+    /// Removes the least recently used entries until the total fits.
+    fn evict(&self) -> io::Result<()> {
+        let mut entries = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let entry = entry?;
+            let meta = entry.metadata()?;
+            if meta.is_file() {
+                let used = meta.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+                entries.push((used, meta.len(), entry.path()));
+            }
+        }
+
+        let mut total: u64 = entries.iter().map(|(_, len, _)| len).sum();
+        entries.sort();
+        for (_, len, path) in entries {
+            if total <= self.max_bytes {
+                break;
+            }
+            fs::remove_file(path)?;
+            total -= len;
+        }
+        Ok(())
+    }
+
+    fn entry_path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{:016x}", fnv1a(key.as_bytes())))
+    }
+}
+
+/// Stable across builds, unlike `DefaultHasher`, so entries survive upgrades.
+pub(crate) fn fnv1a(bytes: &[u8]) -> u64 {
+    let mut hash = Fnv1a::default();
+    hash.update(bytes);
+    hash.finish()
+}
+
+/// `fnv1a` over bytes that arrive in pieces.
+pub(crate) struct Fnv1a(u64);
+
+impl Default for Fnv1a {
+    fn default() -> Self {
+        Self(0xcbf29ce484222325)
+    }
+}
+
+impl Fnv1a {
+    pub(crate) fn update(&mut self, bytes: &[u8]) {
+        for b in bytes {
+            self.0 = (self.0 ^ *b as u64).wrapping_mul(0x100000001b3);
+        }
+    }
+
+    pub(crate) fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+/// For `io::copy`, to hash a file without reading it into memory.
+impl io::Write for Fnv1a {
+    fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+        self.update(bytes);
+        Ok(bytes.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_store_load_and_evict_lru() {
+        let dir = std::env::temp_dir().join(format!("taskfile-output-{}", std::process::id()));
+        let cache = OutputCache::new(&dir, 40);
+
+        cache.store("a", b"0123456789", b"").unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        cache.store("b", b"0123456789", b"err").unwrap();
+        assert_eq!(
+            cache.load("b"),
+            Some(CachedOutput {
+                stdout: b"0123456789".to_vec(),
+                stderr: b"err".to_vec(),
+            })
+        );
+
+        // `a` is now the least recently used entry and goes first.
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        cache.store("c", b"0123456789", b"").unwrap();
+        assert!(cache.load("a").is_none());
+        assert!(cache.load("b").is_some());
+        assert!(cache.load("c").is_some());
+
+        cache.store("huge", &[0; 64], b"").unwrap();
+        assert!(cache.load("huge").is_none());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,744 @@
+use std::collections::VecDeque;
+use std::process::ExitStatus;
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
+use tokio::process::{Child, Command};
+use tokio::task::JoinHandle;
+use tokio::time::{sleep_until, timeout, Duration, Instant};
+
+/// How long a process group gets to exit after SIGTERM before it is killed.
+const KILL_GRACE: Duration = Duration::from_secs(2);
+
+#[derive(Debug)]
+pub enum Termination {
+    Exited(ExitStatus),
+    Interrupted,
+    /// Killed because it produced no output for the given period.
+    Stalled(Duration),
+    /// Killed because it was still running when `Options::timeout` expired.
+    TimedOut,
+}
+
+/// How a command is supervised while it runs.
+#[derive(Default)]
+pub struct Options<'a> {
+    pub no_output_timeout: Option<Duration>,
+    /// Kill the command once it has run this long, output or not.
+    pub timeout: Option<Duration>,
+    /// Echo output as it arrives instead of only buffering it.
+    pub stream: bool,
+    /// Called with the elapsed run time each time the command has been
+    /// silent for the given interval.
+    pub heartbeat: Option<(Duration, &'a (dyn Fn(Duration) + Sync))>,
+    /// Keeps the last lines of output here as they arrive, for a preview
+    /// while the command runs.
+    pub tail: Option<Arc<Mutex<Tail>>>,
+    /// Leave the command in the terminal's foreground process group so it
+    /// can read from the TTY, and let it handle Ctrl-C itself. Only
+    /// `timeout` applies; there is no output to watch.
+    pub interactive: bool,
+}
+
+/// The last few lines a running command printed, from stdout and stderr
+/// interleaved as they arrived. Each stream's unfinished line is kept
+/// apart until its newline, so a partial write to one is not spliced into
+/// a line of the other.
+#[derive(Debug)]
+pub struct Tail {
+    max: usize,
+    lines: VecDeque<String>,
+    /// The unfinished line of stdout and of stderr.
+    partial: [Vec<u8>; 2],
+}
+
+impl Tail {
+    pub fn new(max: usize) -> Self {
+        Self {
+            max,
+            lines: VecDeque::new(),
+            partial: [Vec::new(), Vec::new()],
+        }
+    }
+
+    fn push(&mut self, stream: Stream, bytes: &[u8]) {
+        let partial = &mut self.partial[stream as usize];
+        for &byte in bytes {
+            match byte {
+                b'\n' => {
+                    let line = String::from_utf8_lossy(partial).into_owned();
+                    partial.clear();
+                    self.lines.push_back(line);
+                    if self.lines.len() > self.max {
+                        self.lines.pop_front();
+                    }
+                }
+                // Progress output redraws its line; keep only the latest.
+                b'\r' => partial.clear(),
+                _ => partial.push(byte),
+            }
+        }
+    }
+
+    /// The kept lines, oldest first, ending with the unfinished ones if any.
+    pub fn lines(&self) -> Vec<String> {
+        let mut lines: Vec<String> = self.lines.iter().cloned().collect();
+        for partial in self.partial.iter().filter(|partial| !partial.is_empty()) {
+            lines.push(String::from_utf8_lossy(partial).into_owned());
+        }
+        let skip = lines.len().saturating_sub(self.max);
+        lines.split_off(skip)
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Stream {
+    Stdout,
+    Stderr,
+}
+
+#[derive(Debug)]
+pub struct Completed {
+    pub termination: Termination,
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+}
+
+/// A spawned command with what it takes to stop everything it starts (e.g.
+/// `npm run dev` spawning node): its own process group on Unix, a job object
+/// on Windows.
+#[derive(Debug)]
+pub struct Tree {
+    pub child: Child,
+    /// `None` where the child could not be put in a job, as in a job that
+    /// does not allow it; `kill_tree` falls back to `taskkill` then.
+    #[cfg(windows)]
+    job: Option<job::Job>,
+}
+
+/// Puts the spawned command in its own process group so the whole tree it
+/// starts can be signalled at once.
+pub fn isolate(cmd: &mut Command) {
+    #[cfg(unix)]
+    cmd.process_group(0);
+
+    #[cfg(windows)]
+    {
+        const CREATE_NEW_PROCESS_GROUP: u32 = 0x0000_0200;
+        cmd.creation_flags(CREATE_NEW_PROCESS_GROUP);
+    }
+}
+
+/// Spawns `cmd` isolated, as a `Tree`. On Windows the child is assigned to
+/// a job that kills whatever is left in it once the runner lets go of it.
+pub fn spawn(cmd: &mut Command) -> std::io::Result<Tree> {
+    isolate(cmd);
+    #[cfg(windows)]
+    {
+        const CREATE_NEW_PROCESS_GROUP: u32 = 0x0000_0200;
+        job::spawn(cmd, CREATE_NEW_PROCESS_GROUP)
+    }
+    #[cfg(not(windows))]
+    Ok(Tree {
+        child: cmd.spawn()?,
+    })
+}
+
+/// Starts the spawned command with `mask` as its umask, so the files it
+/// creates leave those permission bits off.
+#[cfg(unix)]
+pub fn set_umask(cmd: &mut Command, mask: u32) {
+    // SAFETY: the closure runs in the child between fork and exec, and
+    // umask(2) is async-signal-safe.
+    unsafe {
+        cmd.pre_exec(move || {
+            libc::umask(mask as libc::mode_t);
+            Ok(())
+        });
+    }
+}
+
+/// Spawns `cmd` in its own process group and waits for it, killing the whole
+/// tree if the user presses Ctrl-C or a watchdog in `options` trips.
+pub async fn run(mut cmd: Command, options: Options<'_>) -> std::io::Result<Completed> {
+    if options.interactive {
+        return run_interactive(cmd, options.timeout).await;
+    }
+    let mut tree = spawn(&mut cmd)?;
+    let child = &mut tree.child;
+    let started = Instant::now();
+
+    let last_output = Arc::new(Mutex::new(started));
+    let stdout = child.stdout.take().map(|r| {
+        collect(
+            r,
+            last_output.clone(),
+            Stream::Stdout,
+            options.stream,
+            options.tail.clone(),
+        )
+    });
+    let stderr = child.stderr.take().map(|r| {
+        collect(
+            r,
+            last_output.clone(),
+            Stream::Stderr,
+            options.stream,
+            options.tail.clone(),
+        )
+    });
+
+    let termination = tokio::select! {
+        status = child.wait() => Termination::Exited(status?),
+        _ = tokio::signal::ctrl_c() => {
+            kill_tree(&mut tree).await;
+            Termination::Interrupted
+        }
+        limit = idle(&last_output, options.no_output_timeout) => {
+            kill_tree(&mut tree).await;
+            Termination::Stalled(limit)
+        }
+        _ = deadline(started, options.timeout) => {
+            kill_tree(&mut tree).await;
+            Termination::TimedOut
+        }
+        _ = heartbeat(&last_output, started, options.heartbeat) => unreachable!(),
+    };
+
+    Ok(Completed {
+        termination,
+        stdout: join(stdout).await,
+        stderr: join(stderr).await,
+    })
+}
+
+/// Runs `cmd` attached to the terminal. On Unix it gets a process group of
+/// its own that takes over the terminal, as a shell's foreground job does,
+/// so a timeout can kill everything it started. Ctrl-C reaches the command
+/// directly from the terminal, so the runner only keeps itself alive
+/// meanwhile; without a terminal to hand over, Ctrl-C stops the command.
+async fn run_interactive(mut cmd: Command, limit: Option<Duration>) -> std::io::Result<Completed> {
+    #[cfg(unix)]
+    let (mut tree, terminal) = {
+        isolate(&mut cmd);
+        let terminal = terminal::Foreground::hand_over(&mut cmd);
+        let child = cmd.spawn()?;
+        (Tree { child }, terminal)
+    };
+    #[cfg(unix)]
+    let handed_over = terminal.is_some();
+    // A new process group would not get Ctrl-C from the console.
+    #[cfg(windows)]
+    let (mut tree, handed_over) = (job::spawn(&mut cmd, 0)?, true);
+    let started = Instant::now();
+    let termination = loop {
+        tokio::select! {
+            status = tree.child.wait() => break Termination::Exited(status?),
+            _ = tokio::signal::ctrl_c() => {
+                if !handed_over {
+                    kill_tree(&mut tree).await;
+                    break Termination::Interrupted;
+                }
+            }
+            _ = deadline(started, limit) => {
+                kill_tree(&mut tree).await;
+                break Termination::TimedOut;
+            }
+        }
+    };
+    #[cfg(unix)]
+    drop(terminal);
+    Ok(Completed {
+        termination,
+        stdout: Vec::new(),
+        stderr: Vec::new(),
+    })
+}
+
+/// Terminates the child and every process in its group, escalating to a
+/// hard kill once the grace period expires.
+#[cfg(unix)]
+pub async fn kill_tree(tree: &mut Tree) {
+    let child = &mut tree.child;
+    if let Some(pid) = child.id() {
+        let group = -(pid as libc::pid_t);
+        unsafe {
+            libc::kill(group, libc::SIGTERM);
+        }
+        let _ = timeout(KILL_GRACE, child.wait()).await;
+        unsafe {
+            libc::kill(group, libc::SIGKILL);
+        }
+    }
+    let _ = child.wait().await;
+}
+
+/// Terminates the child and its descendants through its job, which holds
+/// every process it started, even ones whose parent already exited.
+/// Without a job, `taskkill /T` walks the process tree instead.
+#[cfg(windows)]
+pub async fn kill_tree(tree: &mut Tree) {
+    let child = &mut tree.child;
+    if let Some(job) = &tree.job {
+        job.terminate();
+    } else if let Some(pid) = child.id() {
+        let _ = timeout(
+            KILL_GRACE,
+            Command::new("taskkill")
+                .args(["/T", "/F", "/PID", &pid.to_string()])
+                .output(),
+        )
+        .await;
+    }
+    let _ = child.kill().await;
+}
+
+#[cfg(unix)]
+mod terminal {
+    use tokio::process::Command;
+
+    /// The terminal handed to a command's process group, given back to the
+    /// runner's on drop.
+    pub struct Foreground {
+        runner: libc::pid_t,
+    }
+
+    impl Foreground {
+        /// When the runner's group owns the terminal on stdin, makes the
+        /// isolated `cmd` take it over as it starts, before it can read.
+        pub fn hand_over(cmd: &mut Command) -> Option<Self> {
+            // SAFETY: these only query the process group and stdin.
+            let runner = unsafe { libc::getpgrp() };
+            let owned = unsafe {
+                libc::isatty(libc::STDIN_FILENO) == 1
+                    && libc::tcgetpgrp(libc::STDIN_FILENO) == runner
+            };
+            if !owned {
+                return None;
+            }
+            // SAFETY: the closure runs in the child between fork and exec,
+            // after it has its own group, and only makes async-signal-safe
+            // calls.
+            unsafe {
+                cmd.pre_exec(|| {
+                    without_sigttou(|| {
+                        libc::tcsetpgrp(libc::STDIN_FILENO, libc::getpid());
+                    });
+                    Ok(())
+                });
+            }
+            Some(Foreground { runner })
+        }
+    }
+
+    impl Drop for Foreground {
+        fn drop(&mut self) {
+            // SAFETY: the runner's group still exists, as the runner does.
+            unsafe {
+                without_sigttou(|| {
+                    libc::tcsetpgrp(libc::STDIN_FILENO, self.runner);
+                });
+            }
+        }
+    }
+
+    /// Runs `set` with SIGTTOU blocked on this thread. A process outside
+    /// the terminal's foreground group that sets it is stopped otherwise.
+    unsafe fn without_sigttou(set: impl FnOnce()) {
+        let mut block: libc::sigset_t = std::mem::zeroed();
+        let mut previous: libc::sigset_t = std::mem::zeroed();
+        libc::sigemptyset(&mut block);
+        libc::sigaddset(&mut block, libc::SIGTTOU);
+        libc::pthread_sigmask(libc::SIG_BLOCK, &block, &mut previous);
+        set();
+        libc::pthread_sigmask(libc::SIG_SETMASK, &previous, std::ptr::null_mut());
+    }
+}
+
+#[cfg(windows)]
+mod job {
+    use super::Tree;
+    use std::io;
+    use tokio::process::{Child, Command};
+    use windows_sys::Win32::Foundation::{CloseHandle, HANDLE, INVALID_HANDLE_VALUE};
+    use windows_sys::Win32::System::Diagnostics::ToolHelp::{
+        CreateToolhelp32Snapshot, Thread32First, Thread32Next, TH32CS_SNAPTHREAD, THREADENTRY32,
+    };
+    use windows_sys::Win32::System::JobObjects::{
+        AssignProcessToJobObject, CreateJobObjectW, JobObjectExtendedLimitInformation,
+        SetInformationJobObject, TerminateJobObject, JOBOBJECT_EXTENDED_LIMIT_INFORMATION,
+        JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE,
+    };
+    use windows_sys::Win32::System::Threading::{
+        OpenThread, ResumeThread, CREATE_SUSPENDED, THREAD_SUSPEND_RESUME,
+    };
+
+    /// Spawns `cmd` with `flags`, suspended, and lets it run only once it is
+    /// in its job, so nothing it starts can escape the job. A child that
+    /// cannot be put in one still runs, without a job.
+    pub fn spawn(cmd: &mut Command, flags: u32) -> io::Result<Tree> {
+        cmd.creation_flags(flags | CREATE_SUSPENDED);
+        let mut child = cmd.spawn()?;
+        let job = Job::assign(&child)
+            .inspect_err(|e| tracing::debug!("Failed to create a job object: {}", e))
+            .ok();
+        if let Err(e) = resume(&child) {
+            let _ = child.start_kill();
+            return Err(e);
+        }
+        Ok(Tree { child, job })
+    }
+
+    /// Resumes the main thread of a child spawned with `CREATE_SUSPENDED`,
+    /// its only thread until then.
+    fn resume(child: &Child) -> io::Result<()> {
+        let pid = child
+            .id()
+            .ok_or_else(|| io::Error::other("the process has already exited"))?;
+        // SAFETY: the snapshot and thread handles are checked before use and
+        // closed once, and `entry` is sized as `Thread32First` expects.
+        unsafe {
+            let snapshot = CreateToolhelp32Snapshot(TH32CS_SNAPTHREAD, 0);
+            if snapshot == INVALID_HANDLE_VALUE {
+                return Err(io::Error::last_os_error());
+            }
+            let mut entry: THREADENTRY32 = std::mem::zeroed();
+            entry.dwSize = std::mem::size_of::<THREADENTRY32>() as u32;
+            let mut resumed = Err(io::Error::other("the process has no thread to resume"));
+            let mut more = Thread32First(snapshot, &mut entry) != 0;
+            while more {
+                if entry.th32OwnerProcessID == pid {
+                    let thread = OpenThread(THREAD_SUSPEND_RESUME, 0, entry.th32ThreadID);
+                    resumed = if thread.is_null() || ResumeThread(thread) == u32::MAX {
+                        Err(io::Error::last_os_error())
+                    } else {
+                        Ok(())
+                    };
+                    if !thread.is_null() {
+                        CloseHandle(thread);
+                    }
+                    break;
+                }
+                more = Thread32Next(snapshot, &mut entry) != 0;
+            }
+            CloseHandle(snapshot);
+            resumed
+        }
+    }
+
+    /// A job object with `JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE`: closing its
+    /// handle, on drop, kills every process still in it.
+    #[derive(Debug)]
+    pub struct Job(HANDLE);
+
+    // SAFETY: a job handle can be used and closed from any thread.
+    unsafe impl Send for Job {}
+    unsafe impl Sync for Job {}
+
+    impl Job {
+        pub fn assign(child: &Child) -> io::Result<Self> {
+            let process = child
+                .raw_handle()
+                .ok_or_else(|| io::Error::other("the process has already exited"))?;
+            // SAFETY: the handles are valid for the calls, and `info` is the
+            // structure `JobObjectExtendedLimitInformation` expects.
+            unsafe {
+                let handle = CreateJobObjectW(std::ptr::null(), std::ptr::null());
+                if handle.is_null() {
+                    return Err(io::Error::last_os_error());
+                }
+                let job = Job(handle);
+                let mut info: JOBOBJECT_EXTENDED_LIMIT_INFORMATION = std::mem::zeroed();
+                info.BasicLimitInformation.LimitFlags = JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE;
+                let set = SetInformationJobObject(
+                    job.0,
+                    JobObjectExtendedLimitInformation,
+                    &info as *const _ as *const _,
+                    std::mem::size_of_val(&info) as u32,
+                );
+                if set == 0 || AssignProcessToJobObject(job.0, process as HANDLE) == 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                Ok(job)
+            }
+        }
+
+        pub fn terminate(&self) {
+            // SAFETY: the handle stays open until drop.
+            unsafe {
+                TerminateJobObject(self.0, 1);
+            }
+        }
+    }
+
+    impl Drop for Job {
+        fn drop(&mut self) {
+            // SAFETY: the handle was opened by `assign` and is closed once.
+            unsafe {
+                CloseHandle(self.0);
+            }
+        }
+    }
+}
+
+/// Resolves once no output has been seen for `limit`; never resolves when
+/// there is no limit.
+async fn idle(last_output: &Mutex<Instant>, limit: Option<Duration>) -> Duration {
+    let Some(limit) = limit else {
+        return std::future::pending().await;
+    };
+    loop {
+        let deadline = *last_output.lock().unwrap() + limit;
+        if Instant::now() >= deadline {
+            return limit;
+        }
+        sleep_until(deadline).await;
+    }
+}
+
+/// Resolves once `limit` has passed since `started`; never resolves when
+/// there is no limit.
+async fn deadline(started: Instant, limit: Option<Duration>) {
+    match limit {
+        Some(limit) => sleep_until(started + limit).await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Invokes the heartbeat callback whenever the command has been silent for
+/// a full interval since its last output or the previous beat. Never resolves.
+async fn heartbeat(
+    last_output: &Mutex<Instant>,
+    started: Instant,
+    heartbeat: Option<(Duration, &(dyn Fn(Duration) + Sync))>,
+) {
+    let Some((interval, beat)) = heartbeat else {
+        return std::future::pending().await;
+    };
+    let mut last_beat = started;
+    loop {
+        let deadline = (*last_output.lock().unwrap()).max(last_beat) + interval;
+        if Instant::now() >= deadline {
+            beat(started.elapsed());
+            last_beat = Instant::now();
+            continue;
+        }
+        sleep_until(deadline).await;
+    }
+}
+
+fn collect<R>(
+    mut reader: R,
+    last_output: Arc<Mutex<Instant>>,
+    stream: Stream,
+    echo: bool,
+    tail: Option<Arc<Mutex<Tail>>>,
+) -> JoinHandle<Vec<u8>>
+where
+    R: AsyncRead + Unpin + Send + 'static,
+{
+    tokio::spawn(async move {
+        let mut buf = Vec::new();
+        let mut chunk = [0u8; 8192];
+        while let Ok(n) = reader.read(&mut chunk).await {
+            if n == 0 {
+                break;
+            }
+            // Flush each chunk so echoed output lands before whatever the
+            // runner prints once the command exits.
+            match stream {
+                _ if !echo => {}
+                Stream::Stdout => {
+                    let mut out = tokio::io::stdout();
+                    let _ = out.write_all(&chunk[..n]).await;
+                    let _ = out.flush().await;
+                }
+                Stream::Stderr => {
+                    let mut err = tokio::io::stderr();
+                    let _ = err.write_all(&chunk[..n]).await;
+                    let _ = err.flush().await;
+                }
+            }
+            if let Some(tail) = &tail {
+                tail.lock().unwrap().push(stream, &chunk[..n]);
+            }
+            buf.extend_from_slice(&chunk[..n]);
+            *last_output.lock().unwrap() = Instant::now();
+        }
+        buf
+    })
+}
+
+async fn join(handle: Option<JoinHandle<Vec<u8>>>) -> Vec<u8> {
+    match handle {
+        Some(handle) => handle.await.unwrap_or_default(),
+        None => Vec::new(),
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::process::Stdio;
+
+    #[tokio::test]
+    async fn test_run_captures_output() {
+        let mut cmd = Command::new("echo");
+        cmd.arg("hi").stdout(Stdio::piped()).stderr(Stdio::piped());
+
+        let completed = run(cmd, Options::default()).await.unwrap();
+        assert!(matches!(completed.termination, Termination::Exited(s) if s.success()));
+        assert_eq!(completed.stdout, b"hi\n");
+    }
+
+    #[test]
+    fn test_tail_keeps_last_lines() {
+        let mut tail = Tail::new(2);
+        tail.push(Stream::Stdout, b"one\ntwo\nthr");
+        tail.push(Stream::Stdout, b"ee\n50%\r100%");
+        assert_eq!(tail.lines(), ["three", "100%"]);
+        tail.push(Stream::Stdout, b"\n");
+        assert_eq!(tail.lines(), ["three", "100%"]);
+
+        // A partial stdout line does not run into a stderr one.
+        let mut tail = Tail::new(3);
+        tail.push(Stream::Stdout, b"compil");
+        tail.push(Stream::Stderr, b"warning: unused\n");
+        tail.push(Stream::Stdout, b"ing\n");
+        assert_eq!(tail.lines(), ["warning: unused", "compiling"]);
+    }
+
+    #[tokio::test]
+    async fn test_run_flags_stalled_command() {
+        let mut cmd = Command::new("sleep");
+        cmd.arg("30");
+        let options = Options {
+            no_output_timeout: Some(Duration::from_millis(100)),
+            ..Default::default()
+        };
+
+        let completed = run(cmd, options).await.unwrap();
+        assert!(matches!(completed.termination, Termination::Stalled(_)));
+    }
+
+    #[tokio::test]
+    async fn test_run_times_out_chatty_command() {
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", "while true; do echo tick; sleep 0.02; done"])
+            .stdout(Stdio::piped());
+        let options = Options {
+            no_output_timeout: Some(Duration::from_secs(5)),
+            timeout: Some(Duration::from_millis(150)),
+            ..Default::default()
+        };
+
+        let completed = run(cmd, options).await.unwrap();
+        assert!(matches!(completed.termination, Termination::TimedOut));
+        assert!(!completed.stdout.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_run_emits_heartbeats_while_silent() {
+        let beats = std::sync::atomic::AtomicUsize::new(0);
+        let beat = |_elapsed: Duration| {
+            beats.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        };
+        let mut cmd = Command::new("sleep");
+        cmd.arg("0.35");
+        let options = Options {
+            heartbeat: Some((Duration::from_millis(100), &beat)),
+            ..Default::default()
+        };
+
+        run(cmd, options).await.unwrap();
+        assert!(beats.load(std::sync::atomic::Ordering::SeqCst) >= 2);
+    }
+
+    #[tokio::test]
+    async fn test_run_interactive_times_out() {
+        let mut cmd = Command::new("sleep");
+        cmd.arg("30");
+        let options = Options {
+            timeout: Some(Duration::from_millis(100)),
+            interactive: true,
+            ..Default::default()
+        };
+
+        let completed = run(cmd, options).await.unwrap();
+        assert!(matches!(completed.termination, Termination::TimedOut));
+    }
+
+    #[tokio::test]
+    async fn test_run_interactive_timeout_kills_the_tree() {
+        let dir = std::env::temp_dir().join(format!("taskfile-interactive-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let pid_file = dir.join("pid");
+        let mut cmd = Command::new("sh");
+        cmd.args([
+            "-c",
+            &format!("sleep 100 & echo $! > {}; wait", pid_file.display()),
+        ]);
+        let options = Options {
+            timeout: Some(Duration::from_millis(300)),
+            interactive: true,
+            ..Default::default()
+        };
+
+        let completed = run(cmd, options).await.unwrap();
+        assert!(matches!(completed.termination, Termination::TimedOut));
+        let grandchild: libc::pid_t = std::fs::read_to_string(&pid_file)
+            .unwrap()
+            .trim()
+            .parse()
+            .unwrap();
+        let gone = Instant::now() + Duration::from_secs(5);
+        while running(grandchild) && Instant::now() < gone {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert!(
+            !running(grandchild),
+            "pid {} outlived the timeout",
+            grandchild
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// Whether `pid` is still running; a zombie waiting to be reaped by
+    /// init does not count.
+    fn running(pid: libc::pid_t) -> bool {
+        let stat = std::fs::read_to_string(format!("/proc/{}/stat", pid)).unwrap_or_default();
+        let zombie = stat
+            .rsplit_once(") ")
+            .is_some_and(|(_, rest)| rest.starts_with('Z'));
+        // SAFETY: signal 0 only checks that the process exists.
+        unsafe { libc::kill(pid, 0) == 0 && !zombie }
+    }
+
+    #[tokio::test]
+    async fn test_kill_tree_reaps_group() {
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", "sleep 100 & echo $!; wait"])
+            .stdout(Stdio::piped());
+        let mut tree = spawn(&mut cmd).unwrap();
+        let mut stdout = tree.child.stdout.take().unwrap();
+        let mut line = Vec::new();
+        while !line.ends_with(b"\n") {
+            let mut byte = [0u8];
+            stdout.read_exact(&mut byte).await.unwrap();
+            line.push(byte[0]);
+        }
+        let grandchild: libc::pid_t = String::from_utf8(line).unwrap().trim().parse().unwrap();
+        assert!(running(grandchild));
+
+        kill_tree(&mut tree).await;
+        assert!(tree.child.try_wait().unwrap().is_some());
+        let gone = Instant::now() + Duration::from_secs(5);
+        while running(grandchild) && Instant::now() < gone {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert!(
+            !running(grandchild),
+            "pid {} outlived kill_tree",
+            grandchild
+        );
+    }
+}
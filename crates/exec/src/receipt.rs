@@ -0,0 +1,109 @@
+use crate::output_cache::fnv1a;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Provenance record of one run of a task with `receipt = true`, written to
+/// `.taskfile/receipts/<run id>-<task>.json`.
+#[derive(Debug, Serialize)]
+pub struct Receipt {
+    pub task: String,
+    pub run_id: String,
+    /// The command as echoed, with secrets masked.
+    pub command: String,
+    /// Unix time the task started, in seconds.
+    pub started_at: u64,
+    pub duration_ms: u64,
+    /// `None` when the task was interrupted or killed for stalling.
+    pub exit_code: Option<i32>,
+    /// Fingerprint of the variables the runner passed in; see `env_hash`.
+    pub env_hash: String,
+    /// `--version` output of each tool in `requires`.
+    pub tools: BTreeMap<String, String>,
+}
+
+impl Receipt {
+    pub fn write(&self, project_dir: &Path) -> io::Result<PathBuf> {
+        let dir = project_dir.join(".taskfile").join("receipts");
+        fs::create_dir_all(&dir)?;
+        let path = dir.join(format!("{}-{}.json", self.run_id, file_stem(&self.task)));
+        let json = crate::json::to_string_pretty(self)?;
+        fs::write(&path, json + "\n")?;
+        Ok(path)
+    }
+}
+
+pub fn duration_ms(duration: Duration) -> u64 {
+    duration.as_millis().try_into().unwrap_or(u64::MAX)
+}
+
+/// A stable hash of `vars` that changes whenever a key or value does, so two
+/// receipts can be compared without storing the (possibly secret) values.
+pub fn env_hash<'a>(vars: impl IntoIterator<Item = (&'a String, &'a String)>) -> String {
+    let sorted: BTreeMap<&String, &String> = vars.into_iter().collect();
+    let mut bytes = Vec::new();
+    for (key, value) in sorted {
+        bytes.extend_from_slice(key.as_bytes());
+        bytes.push(b'=');
+        bytes.extend_from_slice(value.as_bytes());
+        bytes.push(0);
+    }
+    format!("{:016x}", fnv1a(&bytes))
+}
+
+/// Variables as passed to the task, without the per-invocation run ID that
+/// would make every hash differ.
+pub fn hashed_vars<'a>(
+    vars: &'a HashMap<String, String>,
+    run_id_var: &'a str,
+) -> impl Iterator<Item = (&'a String, &'a String)> {
+    vars.iter()
+        .filter(move |(key, _)| key.as_str() != run_id_var)
+}
+
+/// `name` with characters that are awkward in file names (`:` from include
+/// namespaces, path separators) replaced.
+fn file_stem(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_env_hash_ignores_order_and_run_id() {
+        let a = HashMap::from([
+            ("A".to_string(), "1".to_string()),
+            ("B".to_string(), "2".to_string()),
+            ("RUN".to_string(), "x".to_string()),
+        ]);
+        let b = HashMap::from([
+            ("B".to_string(), "2".to_string()),
+            ("A".to_string(), "1".to_string()),
+            ("RUN".to_string(), "y".to_string()),
+        ]);
+        assert_eq!(
+            env_hash(hashed_vars(&a, "RUN")),
+            env_hash(hashed_vars(&b, "RUN"))
+        );
+
+        let c = HashMap::from([("A".to_string(), "12".to_string())]);
+        assert_ne!(
+            env_hash(hashed_vars(&a, "RUN")),
+            env_hash(hashed_vars(&c, "RUN"))
+        );
+        assert_eq!(file_stem("ns:deploy/prod"), "ns_deploy_prod");
+    }
+}
@@ -0,0 +1,163 @@
+use crate::flags::{FlagSpec, FlagType};
+use std::fs;
+use std::ops::Range;
+use std::path::Path;
+
+/// Manifests bumped when a release task lists no `version_files`, if present.
+pub const DEFAULT_VERSION_FILES: &[&str] = &["Cargo.toml", "package.json"];
+
+/// Tag created when a release task sets no `tag`.
+pub const DEFAULT_TAG: &str = "v{{ release.version }}";
+
+/// The `--bump` flag every release task accepts unless it declares its own.
+pub fn bump_flag() -> FlagSpec {
+    FlagSpec {
+        kind: FlagType::String,
+        default: Some(toml::Value::String("patch".to_string())),
+        required: false,
+        help: Some("major, minor, patch or an explicit version".to_string()),
+    }
+}
+
+/// The `MAJOR.MINOR.PATCH` of a version such as `1.2.3` or `1.2.3-rc.1`;
+/// prerelease and build suffixes are ignored.
+pub fn parse_version(version: &str) -> Option<[u64; 3]> {
+    let core = version.split(['-', '+']).next()?;
+    let parts: Vec<u64> = core
+        .split('.')
+        .map(|part| part.parse().ok())
+        .collect::<Option<_>>()?;
+    parts.try_into().ok()
+}
+
+/// Applies `--bump`: `major`, `minor`, `patch`, or an explicit version.
+pub fn bump(current: &str, bump: &str) -> Result<String, String> {
+    if parse_version(bump).is_some() {
+        return Ok(bump.to_string());
+    }
+    let [major, minor, patch] = parse_version(current)
+        .ok_or_else(|| format!("'{}' is not a MAJOR.MINOR.PATCH version", current))?;
+    match bump {
+        "major" => Ok(format!("{}.0.0", major + 1)),
+        "minor" => Ok(format!("{}.{}.0", major, minor + 1)),
+        "patch" => Ok(format!("{}.{}.{}", major, minor, patch + 1)),
+        other => Err(format!(
+            "invalid bump '{}' (expected major, minor, patch or a version like 1.2.3)",
+            other
+        )),
+    }
+}
+
+/// The version declared in a `Cargo.toml` or `package.json`.
+pub fn read_version(path: &Path) -> Result<String, String> {
+    let contents = read(path)?;
+    let range = locate(path, &contents)?;
+    Ok(contents[range].to_string())
+}
+
+/// Rewrites the version in place, leaving the rest of the file untouched.
+pub fn write_version(path: &Path, version: &str) -> Result<(), String> {
+    let mut contents = read(path)?;
+    let range = locate(path, &contents)?;
+    contents.replace_range(range, version);
+    fs::write(path, contents).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+fn read(path: &Path) -> Result<String, String> {
+    fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))
+}
+
+/// Byte range of the version string's value.
+fn locate(path: &Path, contents: &str) -> Result<Range<usize>, String> {
+    let is_json = path.extension().is_some_and(|ext| ext == "json");
+    let found = if is_json {
+        locate_json(contents)
+    } else {
+        locate_toml(contents)
+    };
+    found.ok_or_else(|| format!("No version found in {}", path.display()))
+}
+
+/// `version = "..."` in `[package]` or `[workspace.package]`.
+fn locate_toml(contents: &str) -> Option<Range<usize>> {
+    let mut in_package = false;
+    let mut offset = 0;
+    for line in contents.split_inclusive('\n') {
+        let trimmed = line.trim();
+        if trimmed.starts_with('[') {
+            in_package = trimmed == "[package]" || trimmed == "[workspace.package]";
+        } else if in_package {
+            if let Some(rest) = trimmed.strip_prefix("version") {
+                if rest.trim_start().starts_with('=') {
+                    return quoted_value(line, offset);
+                }
+            }
+        }
+        offset += line.len();
+    }
+    None
+}
+
+/// The first `"version": "..."` key, which `npm` keeps at the top level.
+fn locate_json(contents: &str) -> Option<Range<usize>> {
+    let key = contents.find("\"version\"")?;
+    let after_key = key + "\"version\"".len();
+    let colon = after_key + contents[after_key..].find(':')?;
+    quoted_value(&contents[colon + 1..], colon + 1)
+}
+
+/// Range of the first double-quoted string in the line after `=` or `:`,
+/// offset by `offset`.
+fn quoted_value(text: &str, offset: usize) -> Option<Range<usize>> {
+    let start = text.find('"')? + 1;
+    let end = start + text[start..].find('"')?;
+    Some(offset + start..offset + end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bump() {
+        assert_eq!(bump("1.2.3", "patch").unwrap(), "1.2.4");
+        assert_eq!(bump("1.2.3", "minor").unwrap(), "1.3.0");
+        assert_eq!(bump("1.2.3-beta.1", "major").unwrap(), "2.0.0");
+        assert_eq!(bump("1.2.3", "4.0.0-rc.1").unwrap(), "4.0.0-rc.1");
+        assert!(bump("1.2.3", "huge").is_err());
+        assert!(bump("1.2", "patch").is_err());
+    }
+
+    #[test]
+    fn test_rewrite_manifests() {
+        let dir = std::env::temp_dir().join(format!("taskfile-release-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let cargo = dir.join("Cargo.toml");
+        fs::write(
+            &cargo,
+            "[package]\nname = \"app\"\nversion = \"0.6.0\" # keep\n\n[dependencies]\nserde = { version = \"1.0\" }\n",
+        )
+        .unwrap();
+        assert_eq!(read_version(&cargo).unwrap(), "0.6.0");
+        write_version(&cargo, "0.7.0").unwrap();
+        assert_eq!(
+            fs::read_to_string(&cargo).unwrap(),
+            "[package]\nname = \"app\"\nversion = \"0.7.0\" # keep\n\n[dependencies]\nserde = { version = \"1.0\" }\n"
+        );
+
+        let package = dir.join("package.json");
+        fs::write(
+            &package,
+            "{\n  \"name\": \"app\",\n  \"version\": \"1.0.0\"\n}\n",
+        )
+        .unwrap();
+        write_version(&package, "1.0.1").unwrap();
+        assert_eq!(read_version(&package).unwrap(), "1.0.1");
+
+        fs::write(&cargo, "[dependencies]\nserde = \"1\"\n").unwrap();
+        assert!(read_version(&cargo).is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,58 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Crockford's base32 alphabet, as used by ULIDs.
+const ALPHABET: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+
+/// Generates a ULID: 48 bits of millisecond timestamp followed by 80 random
+/// bits, so IDs sort by creation time.
+pub fn generate() -> String {
+    let millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0);
+    encode(millis, random_u128())
+}
+
+fn encode(millis: u64, random: u128) -> String {
+    let mask = (1u128 << 80) - 1;
+    let value = ((millis as u128 & 0xFFFF_FFFF_FFFF) << 80) | (random & mask);
+
+    (0..26)
+        .rev()
+        .map(|i| ALPHABET[((value >> (i * 5)) & 0x1F) as usize] as char)
+        .collect()
+}
+
+/// `RandomState` is seeded from the OS per process, which is plenty for an
+/// identifier that only has to be unique across invocations.
+fn random_u128() -> u128 {
+    let state = RandomState::new();
+    let half = |salt: u64| {
+        let mut hasher = state.build_hasher();
+        hasher.write_u64(salt);
+        hasher.write_u32(std::process::id());
+        hasher.finish() as u128
+    };
+    (half(0) << 64) | half(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generate_shape() {
+        let id = generate();
+        assert_eq!(id.len(), 26);
+        assert!(id.bytes().all(|b| ALPHABET.contains(&b)));
+        assert_ne!(generate(), id);
+    }
+
+    #[test]
+    fn test_encode_sorts_by_time() {
+        assert!(encode(1, u128::MAX) < encode(2, 0));
+        assert_eq!(encode(0, 0), "00000000000000000000000000");
+    }
+}
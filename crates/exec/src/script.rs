@@ -0,0 +1,58 @@
+/// `word` quoted for a POSIX shell. Where the value of one of `secrets`
+/// (name, value) occurs, the word refers to the variable instead, so a
+/// script written from it does not contain the value.
+pub fn quote(word: &str, secrets: &[(String, String)]) -> String {
+    let mut quoted = String::new();
+    let mut rest = word;
+    loop {
+        let next = secrets
+            .iter()
+            .filter(|(_, value)| !value.is_empty())
+            .filter_map(|(name, value)| rest.find(value.as_str()).map(|at| (at, name, value)))
+            .min_by_key(|(at, _, value)| (*at, std::cmp::Reverse(value.len())));
+        let Some((at, name, value)) = next else {
+            break;
+        };
+        if at > 0 {
+            quoted.push_str(&quote_literal(&rest[..at]));
+        }
+        quoted.push_str(&format!("\"${{{}}}\"", name));
+        rest = &rest[at + value.len()..];
+    }
+    if !rest.is_empty() || quoted.is_empty() {
+        quoted.push_str(&quote_literal(rest));
+    }
+    quoted
+}
+
+fn quote_literal(text: &str) -> String {
+    let safe = |c: char| c.is_ascii_alphanumeric() || "_@%+=:,./-".contains(c);
+    if !text.is_empty() && text.chars().all(safe) {
+        text.to_string()
+    } else {
+        format!("'{}'", text.replace('\'', r"'\''"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quote() {
+        let secrets = [
+            ("TOKEN".to_string(), "s3cr3t".to_string()),
+            ("EMPTY".to_string(), String::new()),
+        ];
+        assert_eq!(quote("cargo", &secrets), "cargo");
+        assert_eq!(quote("", &secrets), "''");
+        assert_eq!(quote("it's here", &secrets), r"'it'\''s here'");
+        assert_eq!(quote("$HOME", &secrets), "'$HOME'");
+        assert_eq!(
+            quote("--token=s3cr3t now", &secrets),
+            "--token=\"${TOKEN}\"' now'"
+        );
+        assert_eq!(quote("s3cr3t", &secrets), "\"${TOKEN}\"");
+        assert_eq!(quote("x: s3cr3t", &secrets), "'x: '\"${TOKEN}\"");
+    }
+}
@@ -0,0 +1,112 @@
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A task's `script`, written to a temporary file for its interpreter and
+/// removed when this is dropped.
+#[derive(Debug)]
+pub struct ScriptFile {
+    path: PathBuf,
+}
+
+impl ScriptFile {
+    /// Writes `contents` under the system's temporary directory, named with
+    /// the extension `program` expects. The file is always a new one,
+    /// readable only by its owner: in a shared `/tmp` another user could
+    /// otherwise plant a file or symlink at the name, and have an elevated
+    /// task run their script or overwrite what the link points at.
+    pub fn write(contents: &str, program: &str) -> io::Result<Self> {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.subsec_nanos())
+            .unwrap_or(0);
+        let mut attempts = 0;
+        loop {
+            let path = env::temp_dir().join(format!(
+                "taskfile-script-{}-{}-{:08x}{}",
+                std::process::id(),
+                NEXT.fetch_add(1, Ordering::Relaxed),
+                nanos,
+                extension(program)
+            ));
+            let mut options = OpenOptions::new();
+            options.write(true).create_new(true);
+            #[cfg(unix)]
+            std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+            match options.open(&path) {
+                Ok(mut file) => {
+                    let script = Self { path };
+                    file.write_all(contents.as_bytes())?;
+                    return Ok(script);
+                }
+                // Someone else holds the name; take the next one.
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists && attempts < 100 => {
+                    attempts += 1;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for ScriptFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// The file extension for scripts run by `program`, for interpreters that
+/// go by it (PowerShell and `cmd` refuse files without theirs); empty when
+/// it does not matter or is not known.
+pub fn extension(program: &str) -> &'static str {
+    let name = Path::new(program)
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_ascii_lowercase())
+        .unwrap_or_default();
+    match name.as_str() {
+        name if name.starts_with("python") => ".py",
+        "node" | "bun" => ".js",
+        "deno" => ".ts",
+        "ruby" => ".rb",
+        "perl" => ".pl",
+        "sh" | "bash" | "zsh" | "dash" => ".sh",
+        "pwsh" | "powershell" => ".ps1",
+        "cmd" => ".cmd",
+        "nu" => ".nu",
+        _ => "",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_script_file() {
+        assert_eq!(extension("python3.12"), ".py");
+        assert_eq!(extension("/usr/bin/node"), ".js");
+        assert_eq!(extension("pwsh.exe"), ".ps1");
+        assert_eq!(extension("awk"), "");
+
+        let script = ScriptFile::write("print('hi')\n", "python3").unwrap();
+        let path = script.path().to_path_buf();
+        assert_eq!(path.extension().unwrap(), "py");
+        assert_eq!(fs::read_to_string(&path).unwrap(), "print('hi')\n");
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+        drop(script);
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,171 @@
+use crate::shell::ShellKind;
+use colored::*;
+use sha2::{Digest, Sha256};
+use std::path::Path;
+
+/// `task self check`: whether the binary at `exe` is the `version` release,
+/// whether `task update` could replace it, and whether commands can be
+/// spawned. Prints one line per check and returns false if any failed; a
+/// checksum that cannot be fetched, or may not be under `--offline` or
+/// without the `http` feature, only warns.
+pub async fn run(exe: &Path, version: &str, offline: bool) -> bool {
+    let asset = format!("task-{}-{}", std::env::consts::OS, std::env::consts::ARCH);
+    let expected = if offline {
+        Err("--offline".to_string())
+    } else {
+        release_checksum(version, &asset).await
+    };
+    let binary = check_binary(exe, version, &asset, expected);
+    let install_dir = check_install_dir(exe.parent().unwrap_or(Path::new(".")));
+    let spawn = check_spawn().await;
+    binary && install_dir && spawn
+}
+
+#[cfg(feature = "http")]
+async fn release_checksum(version: &str, asset: &str) -> Result<String, String> {
+    crate::self_update::release_checksum(version, asset).await
+}
+
+#[cfg(not(feature = "http"))]
+async fn release_checksum(_: &str, _: &str) -> Result<String, String> {
+    Err("this build does not include the http feature".to_string())
+}
+
+/// Compares the sha256 of `exe` with the one the release lists for `asset`.
+fn check_binary(exe: &Path, version: &str, asset: &str, expected: Result<String, String>) -> bool {
+    match (std::fs::read(exe), expected) {
+        (Err(e), _) => {
+            eprintln!("{} Cannot read {}: {}", "✗".red(), exe.display(), e);
+            false
+        }
+        (_, Err(e)) => {
+            println!("{} Binary checksum not verified: {}", "⚠".yellow(), e);
+            true
+        }
+        (Ok(binary), Ok(expected)) => {
+            let actual = format!("{:x}", Sha256::digest(&binary));
+            if actual == expected {
+                println!(
+                    "{} Binary matches the v{} release ({})",
+                    "✓".green(),
+                    version,
+                    asset
+                );
+                true
+            } else {
+                eprintln!(
+                    "{} Binary {} does not match the v{} release ({}): expected sha256 {}, got {}. Reinstall it, unless you built it yourself",
+                    "✗".red(),
+                    exe.display(),
+                    version,
+                    asset,
+                    expected,
+                    actual
+                );
+                false
+            }
+        }
+    }
+}
+
+/// Whether a file can be created next to the binary, as `task update` needs.
+fn check_install_dir(install_dir: &Path) -> bool {
+    let probe = install_dir.join(format!(".task-self-check-{}", std::process::id()));
+    match std::fs::write(&probe, b"") {
+        Ok(()) => {
+            let _ = std::fs::remove_file(&probe);
+            println!(
+                "{} Install directory {} is writable, so `task update` can replace the binary",
+                "✓".green(),
+                install_dir.display()
+            );
+            true
+        }
+        Err(e) => {
+            eprintln!(
+                "{} Install directory {} is not writable ({}); `task update` will fail without more permissions",
+                "✗".red(),
+                install_dir.display(),
+                e
+            );
+            false
+        }
+    }
+}
+
+/// Runs `echo taskfile` through the platform's shell.
+async fn check_spawn() -> bool {
+    let kind = ShellKind::platform();
+    let spawned = tokio::process::Command::new(kind.binary())
+        .args([kind.command_flag(), "echo taskfile"])
+        .stdin(std::process::Stdio::null())
+        .output()
+        .await;
+    match spawned {
+        Ok(output)
+            if output.status.success()
+                && String::from_utf8_lossy(&output.stdout).trim() == "taskfile" =>
+        {
+            println!("{} Spawning processes works ({})", "✓".green(), kind);
+            true
+        }
+        Ok(output) => {
+            eprintln!(
+                "{} {} ran, but `echo taskfile` exited with {} and printed {:?}",
+                "✗".red(),
+                kind,
+                output.status,
+                String::from_utf8_lossy(&output.stdout).trim()
+            );
+            false
+        }
+        Err(e) => {
+            eprintln!("{} Cannot spawn {}: {}", "✗".red(), kind, e);
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[tokio::test]
+    async fn test_self_check_steps() {
+        let dir = std::env::temp_dir().join(format!("taskfile-self-check-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let exe = dir.join("task");
+        fs::write(&exe, b"binary").unwrap();
+        let sha = format!("{:x}", Sha256::digest(b"binary"));
+
+        assert!(check_binary(&exe, "1.0.0", "task-linux-x86_64", Ok(sha)));
+        assert!(!check_binary(
+            &exe,
+            "1.0.0",
+            "task-linux-x86_64",
+            Ok("0".repeat(64))
+        ));
+        // A checksum that could not be fetched only warns.
+        assert!(check_binary(
+            &exe,
+            "1.0.0",
+            "task-linux-x86_64",
+            Err("--offline".to_string())
+        ));
+        assert!(!check_binary(
+            &dir.join("missing"),
+            "1.0.0",
+            "task-linux-x86_64",
+            Err("--offline".to_string())
+        ));
+
+        assert!(check_install_dir(&dir));
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+        assert!(!check_install_dir(&dir.join("missing")));
+
+        assert!(check_spawn().await);
+        assert!(run(&exe, "1.0.0", true).await);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
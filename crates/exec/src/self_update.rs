@@ -0,0 +1,161 @@
+/// The project's releases on GitHub, newest first.
+pub const RELEASES_URL: &str =
+    "https://api.github.com/repos/lassejlv/taskfile/releases?per_page=100";
+
+/// GitHub's list of the project's releases, fetched with curl.
+pub async fn fetch_releases() -> Result<serde_json::Value, String> {
+    let output = tokio::process::Command::new("curl")
+        .args(["-fsSL", "--max-time", "20", RELEASES_URL])
+        .output()
+        .await
+        .map_err(|e| format!("Cannot run curl: {}", e))?;
+    if !output.status.success() {
+        return Err("Failed to download the release list".to_string());
+    }
+    serde_json::from_slice(&output.stdout).map_err(|e| format!("Unexpected release list: {}", e))
+}
+
+/// The tag of the release to install from GitHub's release list, which is
+/// newest first: the one for `version` (with or without a leading `v`),
+/// else the newest on `channel`. `stable` skips prereleases; `nightly`
+/// includes them. Drafts are never picked.
+pub fn select_release(
+    releases: &serde_json::Value,
+    channel: &str,
+    version: Option<&str>,
+) -> Result<String, String> {
+    let published: Vec<(&str, bool)> = releases
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter(|release| !release["draft"].as_bool().unwrap_or(false))
+        .filter_map(|release| {
+            let tag = release["tag_name"].as_str()?;
+            Some((tag, release["prerelease"].as_bool().unwrap_or(false)))
+        })
+        .collect();
+
+    if let Some(version) = version {
+        let wanted = version.trim_start_matches('v');
+        return published
+            .iter()
+            .find(|(tag, _)| tag.trim_start_matches('v') == wanted)
+            .map(|(tag, _)| tag.to_string())
+            .ok_or_else(|| {
+                let recent: Vec<&str> = published.iter().take(5).map(|(tag, _)| *tag).collect();
+                format!(
+                    "No release {} (most recent: {})",
+                    version,
+                    if recent.is_empty() {
+                        "none".to_string()
+                    } else {
+                        recent.join(", ")
+                    }
+                )
+            });
+    }
+    published
+        .iter()
+        .find(|(_, prerelease)| channel == "nightly" || !prerelease)
+        .map(|(tag, _)| tag.to_string())
+        .ok_or_else(|| format!("No release on the {} channel", channel))
+}
+
+/// The sha256 the `version` release's `checksums.txt` lists for `asset`,
+/// fetched with curl like `task update` does.
+pub async fn release_checksum(version: &str, asset: &str) -> Result<String, String> {
+    let url = format!(
+        "https://github.com/lassejlv/taskfile/releases/download/v{}/checksums.txt",
+        version
+    );
+    let output = tokio::process::Command::new("curl")
+        .args(["-fsSL", &url])
+        .output()
+        .await
+        .map_err(|e| format!("cannot run curl: {}", e))?;
+    if !output.status.success() {
+        return Err(format!("could not download {}", url));
+    }
+    parse_checksum(&String::from_utf8_lossy(&output.stdout), asset)
+        .ok_or_else(|| format!("the v{} release lists no checksum for {}", version, asset))
+}
+
+/// Finds `asset` in `sha256sum` output: `<hash>  <file>` lines, with a `*`
+/// before the name for files hashed in binary mode. A line whose hash is not
+/// 64 hex digits does not count.
+pub fn parse_checksum(manifest: &str, asset: &str) -> Option<String> {
+    manifest.lines().find_map(|line| {
+        let (hash, file) = line.trim().split_once(char::is_whitespace)?;
+        let valid = hash.len() == 64 && hash.chars().all(|c| c.is_ascii_hexdigit());
+        (valid && file.trim_start().trim_start_matches('*') == asset).then(|| hash.to_lowercase())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_select_release() {
+        let releases = json!([
+            { "tag_name": "v1.4.0", "draft": true, "prerelease": false },
+            { "tag_name": "v1.4.0-rc.1", "draft": false, "prerelease": true },
+            { "tag_name": "v1.3.2", "draft": false, "prerelease": false },
+            { "tag_name": "v1.3.1", "prerelease": false },
+        ]);
+        let select = |channel, version| select_release(&releases, channel, version);
+
+        assert_eq!(select("stable", None).unwrap(), "v1.3.2");
+        assert_eq!(select("nightly", None).unwrap(), "v1.4.0-rc.1");
+        assert_eq!(select("stable", Some("1.3.1")).unwrap(), "v1.3.1");
+        assert_eq!(select("stable", Some("v1.3.1")).unwrap(), "v1.3.1");
+        // A pinned version is found whatever the channel.
+        assert_eq!(select("stable", Some("1.4.0-rc.1")).unwrap(), "v1.4.0-rc.1");
+
+        let err = select("stable", Some("9.9.9")).unwrap_err();
+        assert_eq!(
+            err,
+            "No release 9.9.9 (most recent: v1.4.0-rc.1, v1.3.2, v1.3.1)"
+        );
+        let err = select("stable", Some("1.4.0")).unwrap_err();
+        assert!(err.starts_with("No release 1.4.0 "), "{}", err);
+
+        let prereleases = json!([{ "tag_name": "v2.0.0-rc.1", "prerelease": true }]);
+        assert_eq!(
+            select_release(&prereleases, "stable", None).unwrap_err(),
+            "No release on the stable channel"
+        );
+        assert_eq!(
+            select_release(&json!([]), "stable", Some("1.0.0")).unwrap_err(),
+            "No release 1.0.0 (most recent: none)"
+        );
+    }
+
+    #[test]
+    fn test_parse_checksum() {
+        let a = "a".repeat(64);
+        let b = "B".repeat(64);
+        let manifest = format!(
+            "{a}  task-linux-x86_64.tar.gz\n{a}  task-linux-x86_64\n{b} *task-windows-x86_64.exe\n"
+        );
+        assert_eq!(
+            parse_checksum(&manifest, "task-linux-x86_64"),
+            Some(a.clone())
+        );
+        assert_eq!(
+            parse_checksum(&manifest, "task-windows-x86_64.exe"),
+            Some("b".repeat(64))
+        );
+        assert_eq!(parse_checksum(&manifest, "task-macos-aarch64"), None);
+        assert_eq!(parse_checksum(&manifest, "task-linux"), None);
+
+        let bad = format!(
+            "{}  task-linux-x86_64\nxyz  task-linux-x86_64\n",
+            "g".repeat(64)
+        );
+        assert_eq!(parse_checksum(&bad, "task-linux-x86_64"), None);
+        assert_eq!(parse_checksum(&format!("{a}\n"), "task-linux-x86_64"), None);
+        assert_eq!(parse_checksum("", "task-linux-x86_64"), None);
+    }
+}
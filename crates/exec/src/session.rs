@@ -0,0 +1,192 @@
+use crate::process::{Completed, Termination};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::process::ExitStatus;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// A run written by `--record` and played back by `--replay`: every
+/// command the tasks ran, in the order they finished.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Session {
+    /// Used again by the replay, so it prints the same run ID.
+    #[serde(default)]
+    pub run_id: String,
+    pub steps: Vec<Step>,
+}
+
+/// One command of a task, or one of its `run_if`/`skip_if` checks.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Step {
+    pub task: String,
+    /// Which command of the task: empty for its only one, then as in the
+    /// runner's output, e.g. ` (2/3)` or ` (post)`, or the check's field.
+    #[serde(default)]
+    pub step: String,
+    /// The command as it was echoed, with secrets masked.
+    pub cmd: String,
+    pub duration_ms: u64,
+    pub outcome: Outcome,
+    #[serde(default)]
+    pub stdout: String,
+    #[serde(default)]
+    pub stderr: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+    /// Exited with this code, `-1` for a signal.
+    Exited(i32),
+    Interrupted,
+    TimedOut,
+    /// Killed after this many milliseconds without output.
+    Stalled(u64),
+}
+
+impl Outcome {
+    pub fn of(termination: &Termination) -> Self {
+        match termination {
+            Termination::Exited(status) => Outcome::Exited(status.code().unwrap_or(-1)),
+            Termination::Interrupted => Outcome::Interrupted,
+            Termination::TimedOut => Outcome::TimedOut,
+            Termination::Stalled(limit) => Outcome::Stalled(limit.as_millis() as u64),
+        }
+    }
+
+    fn termination(self) -> Termination {
+        match self {
+            Outcome::Exited(code) => Termination::Exited(exit_status(code)),
+            Outcome::Interrupted => Termination::Interrupted,
+            Outcome::TimedOut => Termination::TimedOut,
+            Outcome::Stalled(ms) => Termination::Stalled(Duration::from_millis(ms)),
+        }
+    }
+}
+
+impl Step {
+    /// What running the command produced, as if it had just run.
+    pub fn completed(&self) -> Completed {
+        Completed {
+            termination: self.outcome.termination(),
+            stdout: self.stdout.clone().into_bytes(),
+            stderr: self.stderr.clone().into_bytes(),
+        }
+    }
+}
+
+pub fn load(path: &Path) -> Result<Session, String> {
+    let contents = fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    crate::json::from_str(&contents).map_err(|e| match e.kind() {
+        io::ErrorKind::Unsupported => format!("Cannot replay {}: {}", path.display(), e),
+        _ => format!("{} is not a recorded session: {}", path.display(), e),
+    })
+}
+
+pub fn save(path: &Path, session: &Session) -> io::Result<()> {
+    let json = crate::json::to_string_pretty(session)?;
+    fs::write(path, format!("{}\n", json))
+}
+
+/// A session being played back. Each task's steps come back in the order
+/// they were recorded, however the tasks interleave, and the time they took
+/// stands in for the clock, so the durations the runner reports are the
+/// recorded ones.
+#[derive(Debug)]
+pub struct Replay {
+    steps: Mutex<HashMap<(String, String), VecDeque<Step>>>,
+    start: Instant,
+    elapsed: Mutex<Duration>,
+}
+
+impl Replay {
+    pub fn new(session: Session) -> Self {
+        let mut steps: HashMap<(String, String), VecDeque<Step>> = HashMap::new();
+        for step in session.steps {
+            steps
+                .entry((step.task.clone(), step.step.clone()))
+                .or_default()
+                .push_back(step);
+        }
+        Self {
+            steps: Mutex::new(steps),
+            start: Instant::now(),
+            elapsed: Mutex::new(Duration::ZERO),
+        }
+    }
+
+    /// The next recorded run of `step` of `task`, moving the clock on by
+    /// the time it took.
+    pub fn next(&self, task: &str, step: &str) -> Option<Step> {
+        let next = self
+            .steps
+            .lock()
+            .unwrap()
+            .get_mut(&(task.to_string(), step.to_string()))?
+            .pop_front()?;
+        *self.elapsed.lock().unwrap() += Duration::from_millis(next.duration_ms);
+        Some(next)
+    }
+
+    pub fn now(&self) -> Instant {
+        self.start + *self.elapsed.lock().unwrap()
+    }
+}
+
+#[cfg(unix)]
+fn exit_status(code: i32) -> ExitStatus {
+    use std::os::unix::process::ExitStatusExt;
+    ExitStatus::from_raw((code & 0xff) << 8)
+}
+
+#[cfg(windows)]
+fn exit_status(code: i32) -> ExitStatus {
+    use std::os::windows::process::ExitStatusExt;
+    ExitStatus::from_raw(code as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_replay() {
+        let step = |task: &str, label: &str, duration_ms, code| Step {
+            task: task.to_string(),
+            step: label.to_string(),
+            cmd: "cargo test".to_string(),
+            duration_ms,
+            outcome: Outcome::Exited(code),
+            stdout: "ok\n".to_string(),
+            stderr: String::new(),
+        };
+        let session = Session {
+            run_id: "01J0000000000000000000000".to_string(),
+            steps: vec![
+                step("test", " (1/2)", 1500, 0),
+                step("lint", "", 200, 0),
+                step("test", " (1/2)", 700, 3),
+            ],
+        };
+        let json = serde_json::to_string(&session).unwrap();
+        assert!(json.contains("\"outcome\":{\"exited\":0}"));
+        assert_eq!(serde_json::from_str::<Session>(&json).unwrap(), session);
+
+        let replay = Replay::new(session);
+        let start = replay.now();
+        assert_eq!(replay.next("test", " (1/2)").unwrap().duration_ms, 1500);
+        assert!(replay.next("test", " (2/2)").is_none());
+        let again = replay.next("test", " (1/2)").unwrap();
+        assert!(matches!(
+            again.completed().termination,
+            Termination::Exited(status) if status.code() == Some(3)
+        ));
+        assert_eq!(again.completed().stdout, b"ok\n");
+        assert!(replay.next("test", " (1/2)").is_none());
+        assert_eq!(replay.now() - start, Duration::from_millis(2200));
+    }
+}
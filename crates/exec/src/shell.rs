@@ -0,0 +1,179 @@
+use std::env;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+pub use taskfile_model::shell::*;
+
+#[derive(Debug, Clone)]
+pub struct Shell {
+    pub kind: ShellKind,
+    pub path: PathBuf,
+}
+
+impl fmt::Display for Shell {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({})", self.kind, self.path.display())
+    }
+}
+
+/// Returns every known shell found on `PATH`, in platform preference order.
+pub fn detect_shells() -> Vec<Shell> {
+    CANDIDATES
+        .iter()
+        .filter_map(|&kind| find_executable(kind.binary(), None).map(|path| Shell { kind, path }))
+        .collect()
+}
+
+/// Picks the shell tasks should assume: the user's `$SHELL` when it is one we
+/// know, otherwise the first available candidate for this platform.
+pub fn default_shell() -> Option<Shell> {
+    if let Ok(user_shell) = env::var("SHELL") {
+        let path = PathBuf::from(&user_shell);
+        let kind = path
+            .file_name()
+            .and_then(|name| ShellKind::from_binary(&name.to_string_lossy()));
+        if let Some(kind) = kind {
+            if path.is_file() {
+                return Some(Shell { kind, path });
+            }
+        }
+    }
+
+    detect_shells().into_iter().next()
+}
+
+/// Resolves `name` against `path_var` (or the process `PATH`), the same way
+/// the OS would when spawning it.
+pub fn find_executable(name: &str, path_var: Option<&str>) -> Option<PathBuf> {
+    let candidate = Path::new(name);
+    if candidate.components().count() > 1 {
+        return is_executable(candidate).then(|| candidate.to_path_buf());
+    }
+
+    let path_var = match path_var {
+        Some(path) => path.to_string(),
+        None => env::var("PATH").ok()?,
+    };
+
+    env::split_paths(&path_var).find_map(|dir| {
+        executable_names(name)
+            .into_iter()
+            .map(|file| dir.join(file))
+            .find(|full| is_executable(full))
+    })
+}
+
+/// The first line `bin --version` prints, for a bare program name found
+/// on `path_var`. Paths such as `./scripts/build.sh`, and programs inside
+/// `project` (say `node_modules/.bin`), are not run just to ask, since they
+/// could be any script; they are reported as `not queried`.
+pub fn tool_version(bin: &str, path_var: Option<&str>, project: &Path) -> String {
+    if Path::new(bin).components().count() > 1 {
+        return "not queried".to_string();
+    }
+    let Some(path) = find_executable(bin, path_var) else {
+        return "not found".to_string();
+    };
+    let inside = |path: &Path| {
+        path.starts_with(project)
+            || matches!(
+                (path.canonicalize(), project.canonicalize()),
+                (Ok(path), Ok(project)) if path.starts_with(&project)
+            )
+    };
+    if inside(&path) {
+        return "not queried".to_string();
+    }
+
+    let Ok(output) = Command::new(&path)
+        .arg("--version")
+        .stdin(Stdio::null())
+        .output()
+    else {
+        return "unknown".to_string();
+    };
+    // Some tools (e.g. older javas) print their version on stderr.
+    let text = if output.stdout.is_empty() {
+        output.stderr
+    } else {
+        output.stdout
+    };
+    String::from_utf8_lossy(&text)
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty())
+        .filter(|_| output.status.success())
+        .unwrap_or("unknown")
+        .to_string()
+}
+
+#[cfg(windows)]
+fn executable_names(name: &str) -> Vec<String> {
+    if Path::new(name).extension().is_some() {
+        return vec![name.to_string()];
+    }
+    let exts = env::var("PATHEXT").unwrap_or_else(|_| ".EXE;.CMD;.BAT;.COM".to_string());
+    exts.split(';')
+        .filter(|ext| !ext.is_empty())
+        .map(|ext| format!("{}{}", name, ext.to_lowercase()))
+        .collect()
+}
+
+#[cfg(not(windows))]
+fn executable_names(name: &str) -> Vec<String> {
+    vec![name.to_string()]
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    path.metadata()
+        .map(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn test_find_executable() {
+        assert!(find_executable("sh", None).is_some());
+        assert!(find_executable("definitely-not-a-real-binary", None).is_none());
+        assert!(find_executable("sh", Some("")).is_none());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_tool_version_only_asks_tools_outside_the_project() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir =
+            std::env::temp_dir().join(format!("taskfile-tool-version-{}", std::process::id()));
+        let bin = dir.join("bin");
+        std::fs::create_dir_all(&bin).unwrap();
+        let marker = dir.join("ran");
+        let script = bin.join("build");
+        std::fs::write(&script, format!("#!/bin/sh\ntouch {}\n", marker.display())).unwrap();
+        std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let path = bin.to_string_lossy().into_owned();
+        assert_eq!(tool_version("build", Some(&path), &dir), "not queried");
+        assert_eq!(
+            tool_version("./bin/build", Some(&path), &dir),
+            "not queried"
+        );
+        assert_eq!(tool_version("missing-tool", Some(&path), &dir), "not found");
+        assert!(!marker.exists());
+        assert_ne!(tool_version("sh", None, &dir), "not queried");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
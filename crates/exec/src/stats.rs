@@ -0,0 +1,286 @@
+use crate::history::{self, Entry};
+use crate::{format_age, format_duration};
+use colored::*;
+use std::time::Duration;
+
+const USAGE: &str = "Usage: task stats [--days N] [--sort runs|failures|slowest|total] | task stats --flaky [--days N]";
+
+/// What `task stats` was asked for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Query {
+    /// Only runs that started in the last `days` days count.
+    pub days: u64,
+    pub sort: Sort,
+    /// The tasks whose outcome changed on identical inputs instead.
+    pub flaky: bool,
+}
+
+/// The column `task stats` orders its table by, largest first. Ties keep
+/// the order of `history::summarize`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sort {
+    Runs,
+    Failures,
+    Slowest,
+    Total,
+}
+
+impl Query {
+    /// `[--days N] [--sort runs|failures|slowest|total]`, or `--flaky
+    /// [--days N]`. The default is the last 30 days by runs.
+    pub fn parse(args: &[String]) -> Result<Self, String> {
+        let mut query = Query {
+            days: 30,
+            sort: Sort::Runs,
+            flaky: false,
+        };
+        let mut sorted = false;
+        let mut rest = args.iter();
+        while let Some(arg) = rest.next() {
+            match arg.as_str() {
+                "--days" => {
+                    query.days = rest
+                        .next()
+                        .and_then(|n| n.parse().ok())
+                        .filter(|&n| n > 0)
+                        .ok_or("--days expects a positive number of days")?;
+                }
+                "--sort" => {
+                    query.sort = match rest.next().map(String::as_str) {
+                        Some("runs") => Sort::Runs,
+                        Some("failures") => Sort::Failures,
+                        Some("slowest") => Sort::Slowest,
+                        Some("total") => Sort::Total,
+                        Some(other) => {
+                            return Err(format!(
+                                "Unknown sort '{}'; expected runs, failures, slowest or total",
+                                other
+                            ))
+                        }
+                        None => return Err(USAGE.to_string()),
+                    };
+                    sorted = true;
+                }
+                "--flaky" => query.flaky = true,
+                _ => return Err(USAGE.to_string()),
+            }
+        }
+        if sorted && query.flaky {
+            return Err("--sort does not apply to --flaky".to_string());
+        }
+        Ok(query)
+    }
+}
+
+/// What `task stats` prints for `entries` at `now` (Unix seconds): a
+/// summary line and a table of each task's runs, or with `flaky` the
+/// tasks that both passed and failed on identical inputs.
+pub fn report(entries: &[Entry], query: &Query, now: u64) -> String {
+    let since = now.saturating_sub(query.days * 24 * 60 * 60);
+    let plural = if query.days == 1 { "" } else { "s" };
+    if query.flaky {
+        return flaky_report(entries, since, now, query.days, plural);
+    }
+
+    let mut stats = history::summarize(entries, since);
+    match query.sort {
+        Sort::Runs => {}
+        Sort::Failures => stats.sort_by(|a, b| b.failure_rate().total_cmp(&a.failure_rate())),
+        Sort::Slowest => stats.sort_by_key(|s| std::cmp::Reverse(s.slowest)),
+        Sort::Total => stats.sort_by_key(|s| std::cmp::Reverse(s.total)),
+    }
+    if stats.is_empty() {
+        return format!(
+            "No task runs recorded in the last {} day{}.\n",
+            query.days, plural
+        );
+    }
+
+    let runs: usize = stats.iter().map(|s| s.runs).sum();
+    let failures: usize = stats.iter().map(|s| s.failures).sum();
+    let total: Duration = stats.iter().map(|s| s.total).sum();
+    let rows: Vec<[String; 6]> = stats
+        .iter()
+        .map(|s| {
+            [
+                s.task.clone(),
+                s.runs.to_string(),
+                format!("{:.0}%", s.failure_rate() * 100.0),
+                format_duration(s.mean()),
+                format_duration(s.slowest),
+                format_duration(s.total),
+            ]
+        })
+        .collect();
+    format!(
+        "{} runs in the last {} day{} ({} failed), {} in total\n\n{}",
+        runs,
+        query.days,
+        plural,
+        failures,
+        format_duration(total),
+        table(
+            ["Task", "Runs", "Failed", "Mean", "Slowest", "Total"],
+            &rows
+        )
+    )
+}
+
+fn flaky_report(entries: &[Entry], since: u64, now: u64, days: u64, plural: &str) -> String {
+    let flaky = history::flaky(entries, since);
+    if flaky.is_empty() {
+        return format!("No flaky tasks in the last {} day{}.\n", days, plural);
+    }
+    let rows: Vec<[String; 5]> = flaky
+        .iter()
+        .map(|f| {
+            [
+                f.task.clone(),
+                f.runs.to_string(),
+                f.failures.to_string(),
+                f.flips.to_string(),
+                format_age(now.saturating_sub(f.last_seen)),
+            ]
+        })
+        .collect();
+    format!(
+        "{} task{} passed and failed with identical inputs in the last {} day{}\n\n{}",
+        flaky.len(),
+        if flaky.len() == 1 { "" } else { "s" },
+        days,
+        plural,
+        table(["Task", "Runs", "Failed", "Flips", "Last run"], &rows)
+    )
+}
+
+/// `rows` under a bold header, the first column left-aligned and the rest
+/// right-aligned, one line each.
+fn table<const N: usize>(headers: [&str; N], rows: &[[String; N]]) -> String {
+    let widths: Vec<usize> = (0..N)
+        .map(|i| {
+            rows.iter()
+                .map(|row| row[i].chars().count())
+                .chain([headers[i].len()])
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+    let line = |cells: [&str; N]| {
+        cells
+            .iter()
+            .zip(&widths)
+            .enumerate()
+            .map(|(i, (cell, &width))| {
+                if i == 0 {
+                    format!("{:<width$}", cell)
+                } else {
+                    format!("{:>width$}", cell)
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("  ")
+    };
+    let mut table = format!("{}\n", line(headers).bold());
+    for row in rows {
+        table.push_str(&line(row.each_ref().map(String::as_str)));
+        table.push('\n');
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_query() {
+        assert_eq!(
+            Query::parse(&[]).unwrap(),
+            Query {
+                days: 30,
+                sort: Sort::Runs,
+                flaky: false
+            }
+        );
+        let query = Query::parse(&args(&["--days", "7", "--sort", "slowest"])).unwrap();
+        assert_eq!((query.days, query.sort), (7, Sort::Slowest));
+        assert!(
+            Query::parse(&args(&["--flaky", "--days", "1"]))
+                .unwrap()
+                .flaky
+        );
+
+        for (bad, error) in [
+            (&["--days", "0"][..], "--days expects"),
+            (&["--sort", "name"], "Unknown sort 'name'"),
+            (&["--sort"], "Usage:"),
+            (&["--flaky", "--sort", "runs"], "does not apply to --flaky"),
+            (&["--verbose"], "Usage:"),
+        ] {
+            let err = Query::parse(&args(bad)).unwrap_err();
+            assert!(err.contains(error), "{:?}: {}", bad, err);
+        }
+    }
+
+    #[cfg(feature = "records")]
+    #[test]
+    fn test_report_from_history_file() {
+        let dir = std::env::temp_dir().join(format!("taskfile-stats-{}", std::process::id()));
+        let day = 24 * 60 * 60;
+        let now = 100 * day;
+        for (task, started_at, duration_ms, success, fingerprint) in [
+            ("build", now - 2 * day, 2000, true, "a"),
+            ("test", now - day, 500, false, "a"),
+            ("test", now - day, 1500, true, "a"),
+            ("test", now - 60, 1000, true, "a"),
+            // Outside the window of the last 7 days.
+            ("lint", now - 10 * day, 100, false, "a"),
+        ] {
+            let entry = Entry {
+                task: task.to_string(),
+                run_id: "run".to_string(),
+                started_at,
+                duration_ms,
+                success,
+                fingerprint: fingerprint.to_string(),
+            };
+            history::append(&dir, &entry).unwrap();
+        }
+        let entries = history::load(&dir).unwrap();
+        let lines = |query| {
+            report(&entries, &query, now)
+                .lines()
+                .skip(3)
+                .map(|line| line.split_whitespace().collect::<Vec<_>>().join(" "))
+                .collect::<Vec<_>>()
+        };
+
+        let week = Query::parse(&args(&["--days", "7"])).unwrap();
+        assert!(report(&entries, &week, now)
+            .starts_with("4 runs in the last 7 days (1 failed), 5s in total\n\n"));
+        assert_eq!(
+            lines(week),
+            ["test 3 33% 1s 1.5s 3s", "build 1 0% 2s 2s 2s"]
+        );
+        let slowest = Query::parse(&args(&["--days", "7", "--sort", "slowest"])).unwrap();
+        assert_eq!(lines(slowest)[0], "build 1 0% 2s 2s 2s");
+        let all = Query::parse(&args(&["--sort", "failures"])).unwrap();
+        assert_eq!(lines(all)[0], "lint 1 100% 100ms 100ms 100ms");
+
+        let flaky = Query::parse(&args(&["--flaky"])).unwrap();
+        assert!(report(&entries, &flaky, now)
+            .starts_with("1 task passed and failed with identical inputs in the last 30 days\n"));
+        assert_eq!(lines(flaky), ["test 3 1 1 1m ago"]);
+
+        let day_only = Query::parse(&args(&["--days", "1", "--flaky"])).unwrap();
+        assert_eq!(
+            report(&[], &day_only, now),
+            "No flaky tasks in the last 1 day.\n"
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
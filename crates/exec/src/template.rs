@@ -0,0 +1,48 @@
+use std::collections::HashMap;
+
+/// Replaces `{{ key }}` placeholders whose key is in `values`. Unknown keys
+/// are left untouched so later passes (like env substitution) can handle
+/// them.
+pub fn render(input: &str, values: &HashMap<String, String>) -> String {
+    if values.is_empty() {
+        return input.to_string();
+    }
+    render_with(input, |key| values.get(key).cloned())
+}
+
+/// Like `render`, resolving each key through `lookup`.
+pub fn render_with<F>(input: &str, lookup: F) -> String
+where
+    F: Fn(&str) -> Option<String>,
+{
+    let mut result = String::with_capacity(input.len());
+    let mut rest = input;
+    while let Some(open) = rest.find("{{") {
+        let Some(close) = rest[open + 2..].find("}}") else {
+            break;
+        };
+        let key = rest[open + 2..open + 2 + close].trim();
+        result.push_str(&rest[..open]);
+        match lookup(key) {
+            Some(value) => result.push_str(&value),
+            None => result.push_str(&rest[open..open + 2 + close + 2]),
+        }
+        rest = &rest[open + 2 + close + 2..];
+    }
+    result.push_str(rest);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_known_keys_only() {
+        let values = HashMap::from([("flags.force".to_string(), "true".to_string())]);
+        assert_eq!(
+            render("deploy {{ flags.force }} {{HOME}} {{", &values),
+            "deploy true {{HOME}} {{"
+        );
+    }
+}
@@ -0,0 +1,102 @@
+use crate::release;
+use crate::user_config;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// How long a completed check counts as current.
+pub const INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// What the last check for a newer release found, kept in the config
+/// directory between runs.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct State {
+    /// When the check ran, in Unix seconds; 0 for never.
+    pub checked: u64,
+    /// The newest stable release tag it saw, e.g. `v1.3.0`.
+    pub latest: Option<String>,
+}
+
+impl State {
+    /// `update-check` in `user_config::dir()`.
+    pub fn path() -> Option<PathBuf> {
+        user_config::dir().map(|dir| dir.join("update-check"))
+    }
+
+    /// Reads `path`; a missing or unreadable file reads as never checked.
+    pub fn read(path: &Path) -> Self {
+        let contents = fs::read_to_string(path).unwrap_or_default();
+        let mut state = Self::default();
+        for line in contents.lines() {
+            match line.split_once('=') {
+                Some(("checked", value)) => state.checked = value.trim().parse().unwrap_or(0),
+                Some(("latest", value)) if !value.trim().is_empty() => {
+                    state.latest = Some(value.trim().to_string())
+                }
+                _ => {}
+            }
+        }
+        state
+    }
+
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(
+            path,
+            format!(
+                "checked={}\nlatest={}\n",
+                self.checked,
+                self.latest.as_deref().unwrap_or("")
+            ),
+        )
+    }
+
+    /// Whether the last check is older than `INTERVAL` at `now`.
+    pub fn is_due(&self, now: u64) -> bool {
+        now.saturating_sub(self.checked) >= INTERVAL.as_secs()
+    }
+
+    /// The release to hint at, when the check found one newer than `current`.
+    pub fn newer_than(&self, current: &str) -> Option<&str> {
+        let version = |tag: &str| release::parse_version(tag.trim_start_matches('v'));
+        self.latest
+            .as_deref()
+            .filter(|tag| version(tag) > version(current))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_update_check_state() {
+        let dir =
+            std::env::temp_dir().join(format!("taskfile-update-check-{}", std::process::id()));
+        let path = dir.join("update-check");
+        let never = State::read(&path);
+        assert_eq!(never, State::default());
+        assert!(never.is_due(1_000_000));
+
+        let state = State {
+            checked: 1_000_000,
+            latest: Some("v1.3.0".to_string()),
+        };
+        state.write(&path).unwrap();
+        assert_eq!(State::read(&path), state);
+        assert!(!state.is_due(1_000_000 + 60));
+        assert!(state.is_due(1_000_000 + INTERVAL.as_secs()));
+
+        assert_eq!(state.newer_than("1.2.9"), Some("v1.3.0"));
+        assert_eq!(state.newer_than("1.3.0"), None);
+        assert_eq!(state.newer_than("2.0.0"), None);
+        assert_eq!(State::default().newer_than("1.0.0"), None);
+
+        fs::write(&path, "checked=soon\nlatest=\n").unwrap();
+        assert_eq!(State::read(&path), State::default());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
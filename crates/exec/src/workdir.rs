@@ -0,0 +1,107 @@
+use crate::git::Worktree;
+use crate::glob;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A scratch directory under the system temp dir, removed on drop.
+#[derive(Debug)]
+pub struct TempWorkdir {
+    path: PathBuf,
+}
+
+impl TempWorkdir {
+    /// Creates a fresh directory whose name includes `label`.
+    pub fn create(label: &str) -> io::Result<Self> {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.subsec_nanos())
+            .unwrap_or(0);
+        let label: String = label
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+            .collect();
+        let path = std::env::temp_dir().join(format!(
+            "taskfile-{}-{}-{:08x}",
+            label,
+            std::process::id(),
+            nanos
+        ));
+        fs::create_dir_all(&path)?;
+        Ok(Self { path })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for TempWorkdir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
+
+/// Where a task runs instead of the project directory.
+#[derive(Debug)]
+pub enum Workdir {
+    /// A copy of the task's `sources` (`isolate_workdir`).
+    Copy(TempWorkdir),
+    /// A checkout of the task's `git_ref`.
+    Worktree(Worktree),
+}
+
+impl Workdir {
+    pub fn path(&self) -> PathBuf {
+        match self {
+            Workdir::Copy(dir) => dir.path().to_path_buf(),
+            Workdir::Worktree(worktree) => worktree.path(),
+        }
+    }
+}
+
+/// Copies the files under `from` matching `patterns` to the same relative
+/// paths under `to`, returning how many were copied.
+pub fn copy_matching(from: &Path, to: &Path, patterns: &[String]) -> io::Result<usize> {
+    let files = glob::expand(from, patterns)?;
+    for file in &files {
+        let target = to.join(file);
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::copy(from.join(file), target)?;
+    }
+    Ok(files.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_copy_into_and_out_of_workdir() {
+        let project = TempWorkdir::create("project").unwrap();
+        fs::create_dir_all(project.path().join("src")).unwrap();
+        fs::write(project.path().join("src/main.c"), "int main;").unwrap();
+        fs::write(project.path().join("notes.txt"), "skip me").unwrap();
+
+        let scratch = TempWorkdir::create("scratch").unwrap();
+        let copied = copy_matching(project.path(), scratch.path(), &["src".to_string()]).unwrap();
+        assert_eq!(copied, 1);
+        assert!(scratch.path().join("src/main.c").exists());
+        assert!(!scratch.path().join("notes.txt").exists());
+
+        fs::create_dir_all(scratch.path().join("out")).unwrap();
+        fs::write(scratch.path().join("out/app"), "binary").unwrap();
+        copy_matching(scratch.path(), project.path(), &["out/*".to_string()]).unwrap();
+        assert_eq!(
+            fs::read_to_string(project.path().join("out/app")).unwrap(),
+            "binary"
+        );
+
+        let path = scratch.path().to_path_buf();
+        drop(scratch);
+        assert!(!path.exists());
+    }
+}
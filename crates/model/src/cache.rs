@@ -0,0 +1,94 @@
+use serde::{Deserialize, Deserializer};
+
+/// Size cap used when the Taskfile sets no `[cache] max_output_size`.
+pub const DEFAULT_MAX_OUTPUT_SIZE: u64 = 50 * 1024 * 1024;
+
+/// The `[cache]` table of a Taskfile.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct CacheConfig {
+    /// Total disk space replayable output may use, e.g. `"100MB"`.
+    #[serde(default, deserialize_with = "deserialize_size_opt")]
+    pub max_output_size: Option<u64>,
+}
+
+/// How a task with `sources` decides they changed since its last success.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CacheMethod {
+    /// Hash the files' contents, so touching a file changes nothing.
+    #[default]
+    Checksum,
+    /// Compare paths, sizes and modification times only. Cheap for large
+    /// asset trees, but a touched file counts as changed.
+    Timestamp,
+}
+
+impl CacheMethod {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            CacheMethod::Checksum => "checksum",
+            CacheMethod::Timestamp => "timestamp",
+        }
+    }
+}
+
+/// Parses sizes such as `512KB`, `100MB` or `1GB` (binary multiples). A bare
+/// number is taken as bytes.
+pub fn parse_size(input: &str) -> Result<u64, String> {
+    let input = input.trim();
+    let digits = input
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(input.len());
+    let value: u64 = input[..digits]
+        .parse()
+        .map_err(|_| format!("invalid size '{}'", input))?;
+    let multiplier = match input[digits..].trim().to_ascii_uppercase().as_str() {
+        "" | "B" => 1,
+        "KB" | "K" => 1024,
+        "MB" | "M" => 1024 * 1024,
+        "GB" | "G" => 1024 * 1024 * 1024,
+        unit => {
+            return Err(format!(
+                "invalid size unit '{}' in '{}' (expected B, KB, MB or GB)",
+                unit, input
+            ))
+        }
+    };
+    Ok(value * multiplier)
+}
+
+fn deserialize_size_opt<'de, D>(deserializer: D) -> Result<Option<u64>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match Option::<String>::deserialize(deserializer)? {
+        Some(raw) => parse_size(&raw).map(Some).map_err(serde::de::Error::custom),
+        None => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TaskFile;
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("512").unwrap(), 512);
+        assert_eq!(parse_size("2KB").unwrap(), 2048);
+        assert_eq!(parse_size("100MB").unwrap(), 100 * 1024 * 1024);
+        assert!(parse_size("5TB").is_err());
+        assert!(parse_size("MB").is_err());
+    }
+
+    #[test]
+    fn test_parse_method() {
+        let taskfile = TaskFile::parse(
+            "[tasks.assets]\ncmd = \"x\"\nmethod = \"timestamp\"\n[tasks.build]\ncmd = \"x\"\n",
+        )
+        .unwrap();
+        assert_eq!(taskfile.tasks["assets"].method, CacheMethod::Timestamp);
+        assert_eq!(taskfile.tasks["build"].method, CacheMethod::Checksum);
+        assert!(TaskFile::parse("[tasks.a]\ncmd = \"x\"\nmethod = \"mtime\"\n").is_err());
+    }
+}
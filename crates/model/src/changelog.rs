@@ -0,0 +1,195 @@
+use serde::Deserialize;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Changelog a task writes to when its `changelog` sets no `file`.
+pub const DEFAULT_FILE: &str = "CHANGELOG.md";
+
+/// A task's `changelog` table: after a successful run, an entry is added
+/// under `section` of the `Unreleased` heading, in Keep a Changelog style.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ChangelogConfig {
+    /// e.g. `"Added"`, `"Fixed"`, `"Changed"`.
+    pub section: String,
+    /// The entry, e.g. `"{{ flags.note }}"`. When it is unset or renders
+    /// empty, the runner asks for one on the terminal.
+    pub message: Option<String>,
+    /// Question shown when asking for the message.
+    pub prompt: Option<String>,
+    /// Relative to the Taskfile; defaults to `CHANGELOG.md`.
+    pub file: Option<String>,
+}
+
+impl ChangelogConfig {
+    pub fn file(&self) -> &str {
+        self.file.as_deref().unwrap_or(DEFAULT_FILE)
+    }
+
+    pub fn prompt(&self) -> String {
+        match &self.prompt {
+            Some(prompt) => prompt.clone(),
+            None => format!("Changelog entry ({})", self.section),
+        }
+    }
+}
+
+fn is_release_heading(line: &str) -> bool {
+    line.starts_with("## ")
+}
+
+fn is_unreleased(line: &str) -> bool {
+    line.strip_prefix("## ").is_some_and(|title| {
+        title
+            .trim()
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .eq_ignore_ascii_case("unreleased")
+    })
+}
+
+/// Index of the `## [Unreleased]` heading, inserting one before the first
+/// release (or at the end) when there is none.
+fn unreleased(lines: &mut Vec<String>) -> usize {
+    if let Some(index) = lines.iter().position(|line| is_unreleased(line)) {
+        return index;
+    }
+    if lines.is_empty() {
+        lines.extend(["# Changelog".to_string(), String::new()]);
+    }
+    let index = match lines.iter().position(|line| is_release_heading(line)) {
+        Some(index) => index,
+        None => {
+            if lines.last().is_some_and(|line| !line.trim().is_empty()) {
+                lines.push(String::new());
+            }
+            lines.len()
+        }
+    };
+    lines.splice(index..index, ["## [Unreleased]".to_string(), String::new()]);
+    index
+}
+
+/// `text` with `- message` added under `### section` of the unreleased
+/// changes, creating the heading and section as needed.
+pub fn add_entry(text: &str, section: &str, message: &str) -> String {
+    let mut lines: Vec<String> = text.lines().map(String::from).collect();
+    let start = unreleased(&mut lines);
+    let end = (start + 1..lines.len())
+        .find(|&i| is_release_heading(&lines[i]))
+        .unwrap_or(lines.len());
+    let entry = format!("- {}", message);
+    let heading = format!("### {}", section);
+
+    match (start + 1..end).find(|&i| lines[i].trim().eq_ignore_ascii_case(&heading)) {
+        Some(found) => {
+            let section_end = (found + 1..end)
+                .find(|&i| lines[i].starts_with('#'))
+                .unwrap_or(end);
+            let last = (found..section_end)
+                .rev()
+                .find(|&i| !lines[i].trim().is_empty())
+                .unwrap_or(found);
+            if last == found {
+                lines.splice(last + 1..last + 1, [String::new(), entry]);
+            } else {
+                lines.insert(last + 1, entry);
+            }
+        }
+        None => {
+            let mut at = end;
+            while at > start + 1 && lines[at - 1].trim().is_empty() {
+                at -= 1;
+            }
+            lines.splice(at..at, [String::new(), heading, String::new(), entry]);
+            if lines
+                .get(at + 4)
+                .is_some_and(|line| !line.trim().is_empty())
+            {
+                lines.insert(at + 4, String::new());
+            }
+        }
+    }
+    finish(lines)
+}
+
+/// `text` with the unreleased changes moved under a `## [version] - date`
+/// heading, leaving an empty `Unreleased` above it. `None` when the
+/// changelog has no `Unreleased` heading.
+pub fn release(text: &str, version: &str, date: &str) -> Option<String> {
+    let mut lines: Vec<String> = text.lines().map(String::from).collect();
+    let index = lines.iter().position(|line| is_unreleased(line))?;
+    lines.splice(
+        index + 1..index + 1,
+        [String::new(), format!("## [{}] - {}", version, date)],
+    );
+    Some(finish(lines))
+}
+
+fn finish(mut lines: Vec<String>) -> String {
+    while lines.last().is_some_and(|line| line.trim().is_empty()) {
+        lines.pop();
+    }
+    lines.join("\n") + "\n"
+}
+
+/// Today's UTC date as `YYYY-MM-DD`.
+pub fn today() -> String {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let (year, month, day) = civil_from_days((secs / 86_400) as i64);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// Converts days since 1970-01-01 to a proleptic Gregorian date (Howard
+/// Hinnant's `civil_from_days`).
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_add_entry_and_release() {
+        let text = add_entry("", "Fixed", "Crash on empty Taskfile");
+        assert_eq!(
+            text,
+            "# Changelog\n\n## [Unreleased]\n\n### Fixed\n\n- Crash on empty Taskfile\n"
+        );
+
+        let text = add_entry(&text, "Fixed", "Typo in help");
+        let text = add_entry(&text, "Added", "`task tools`");
+        assert_eq!(
+            text,
+            "# Changelog\n\n## [Unreleased]\n\n### Fixed\n\n- Crash on empty Taskfile\n- Typo in help\n\n### Added\n\n- `task tools`\n"
+        );
+
+        let released = release(&text, "0.7.0", "2026-10-14").unwrap();
+        assert!(released.starts_with(
+            "# Changelog\n\n## [Unreleased]\n\n## [0.7.0] - 2026-10-14\n\n### Fixed\n"
+        ));
+
+        let next = add_entry(&released, "Fixed", "Another one");
+        assert!(next.contains("## [Unreleased]\n\n### Fixed\n\n- Another one\n\n## [0.7.0]"));
+        assert_eq!(release("# Notes\n", "1.0.0", "2026-01-01"), None);
+    }
+
+    #[test]
+    fn test_civil_from_days() {
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(civil_from_days(20_740), (2026, 10, 14));
+        assert_eq!(civil_from_days(11_016), (2000, 2, 29));
+    }
+}
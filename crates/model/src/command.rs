@@ -0,0 +1,246 @@
+use std::fmt;
+
+/// Why a command line could not be split into words.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SplitError {
+    UnterminatedSingleQuote,
+    UnterminatedDoubleQuote,
+    TrailingBackslash,
+}
+
+impl fmt::Display for SplitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            SplitError::UnterminatedSingleQuote => "unterminated single quote",
+            SplitError::UnterminatedDoubleQuote => "unterminated double quote",
+            SplitError::TrailingBackslash => "trailing backslash",
+        })
+    }
+}
+
+impl std::error::Error for SplitError {}
+
+/// Splits a command without a shell into its program and arguments, the
+/// way `sh` would split words: whitespace separates them, `'...'` is taken
+/// literally, `"..."` allows `\"`, `\\`, `\$` and `` \` `` escapes, and a
+/// backslash outside quotes escapes the next character. Nothing is expanded;
+/// pipes, globs and `$VAR` need `shell = true`.
+pub fn split(cmd: &str) -> Result<Vec<String>, SplitError> {
+    let mut words = Vec::new();
+    let mut chars = cmd.chars();
+    // `Some` once the current word has started, which may be as an empty
+    // quoted string.
+    let mut word: Option<String> = None;
+
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => words.extend(word.take()),
+            '\'' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => word.push(c),
+                        None => return Err(SplitError::UnterminatedSingleQuote),
+                    }
+                }
+            }
+            '"' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c @ ('"' | '\\' | '$' | '`')) => word.push(c),
+                            // A backslash-newline inside double quotes is a
+                            // line continuation.
+                            Some('\n') => {}
+                            Some(c) => {
+                                word.push('\\');
+                                word.push(c);
+                            }
+                            None => return Err(SplitError::UnterminatedDoubleQuote),
+                        },
+                        Some(c) => word.push(c),
+                        None => return Err(SplitError::UnterminatedDoubleQuote),
+                    }
+                }
+            }
+            '\\' => match chars.next() {
+                Some('\n') => {}
+                Some(c) => word.get_or_insert_with(String::new).push(c),
+                None => return Err(SplitError::TrailingBackslash),
+            },
+            c => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    words.extend(word);
+    Ok(words)
+}
+
+/// Whether `cmd` spans several lines, as a TOML multi-line string can,
+/// which makes it a shell script rather than one command line. A trailing
+/// newline alone does not count.
+pub fn is_script(cmd: &str) -> bool {
+    cmd.trim().contains('\n')
+}
+
+/// The program a command starts: its first word, unquoted. Unlike `split`
+/// this never fails, so it also works on shell scripts whose later words
+/// `split` cannot parse.
+pub fn program(cmd: &str) -> Option<String> {
+    let trimmed = cmd.trim_start();
+    let end = first_word_end(trimmed);
+    match split(&trimmed[..end]) {
+        Ok(words) => words.into_iter().next(),
+        Err(_) => trimmed[..end]
+            .split_whitespace()
+            .next()
+            .map(|word| word.trim_matches(['\'', '"']).to_string()),
+    }
+}
+
+/// Byte offset where the first word of `cmd` ends: the first whitespace
+/// outside quotes, or the end of the string.
+fn first_word_end(cmd: &str) -> usize {
+    let mut quote = None;
+    let mut escaped = false;
+    for (i, c) in cmd.char_indices() {
+        if escaped {
+            escaped = false;
+            continue;
+        }
+        match (quote, c) {
+            (Some('\''), '\'') | (Some('"'), '"') => quote = None,
+            (Some('"') | None, '\\') => escaped = true,
+            (None, '\'' | '"') => quote = Some(c),
+            (None, c) if c.is_whitespace() => return i,
+            _ => {}
+        }
+    }
+    cmd.len()
+}
+
+/// `word` written so that `split` reads it back unchanged: as-is when it is
+/// made of characters that need no quoting, otherwise single-quoted.
+pub fn quote(word: &str) -> String {
+    let plain = !word.is_empty()
+        && word
+            .chars()
+            .all(|c| c.is_alphanumeric() || "-_./=:,+@%^".contains(c));
+    if plain {
+        word.to_string()
+    } else {
+        format!("'{}'", word.replace('\'', r"'\''"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Characters the generated cases are built from: separators, every
+    /// quoting character, substitution syntax and multi-byte text.
+    const ALPHABET: &[char] = &[
+        'a', 'Z', '0', ' ', '\t', '\n', '\'', '"', '\\', '$', '{', '}', '`', '=', '-', '*', 'é',
+        '日', '🚀', '\u{301}', '\u{a0}',
+    ];
+
+    /// Small deterministic generator so failures reproduce.
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self, bound: usize) -> usize {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            (self.0 % bound as u64) as usize
+        }
+
+        fn text(&mut self, max_len: usize) -> String {
+            let len = self.next(max_len + 1);
+            (0..len)
+                .map(|_| ALPHABET[self.next(ALPHABET.len())])
+                .collect()
+        }
+    }
+
+    #[test]
+    fn test_split() {
+        let ok = |cmd: &str| split(cmd).unwrap();
+        assert_eq!(ok("echo 'Hello, World!'"), vec!["echo", "Hello, World!"]);
+        assert_eq!(
+            ok(r#"grep -e "a \"b\" \$c \d" x"#),
+            vec!["grep", "-e", r#"a "b" $c \d"#, "x"]
+        );
+        assert_eq!(ok(r"touch my\ file ''"), vec!["touch", "my file", ""]);
+        assert_eq!(ok("  a\t b\n"), vec!["a", "b"]);
+        assert_eq!(ok("a'b'\"c\"d"), vec!["abcd"]);
+        assert_eq!(
+            ok("echo héllo 日本 🚀"),
+            vec!["echo", "héllo", "日本", "🚀"]
+        );
+        assert_eq!(ok("echo \\\nnext"), vec!["echo", "next"]);
+        assert!(ok("").is_empty());
+
+        assert_eq!(
+            split("echo 'oops"),
+            Err(SplitError::UnterminatedSingleQuote)
+        );
+        assert_eq!(
+            split("echo \"oops\\\""),
+            Err(SplitError::UnterminatedDoubleQuote)
+        );
+        assert_eq!(split("echo oops\\"), Err(SplitError::TrailingBackslash));
+    }
+
+    #[test]
+    fn test_program() {
+        assert_eq!(program("  cargo build").as_deref(), Some("cargo"));
+        assert_eq!(program("'my tool' --flag").as_deref(), Some("my tool"));
+        assert_eq!(program("grep 'unbalanced | wc").as_deref(), Some("grep"));
+        assert_eq!(program("   "), None);
+    }
+
+    #[test]
+    fn test_is_script() {
+        assert!(!is_script("cargo build\n"));
+        assert!(!is_script("\n  cargo build  \n"));
+        assert!(is_script("set -u\ncargo build\n"));
+        assert!(is_script("docker run \\\n  --rm app"));
+    }
+
+    #[test]
+    fn test_quote_round_trips() {
+        let mut rng = Rng(0x9e3779b97f4a7c15);
+        for _ in 0..2000 {
+            let words: Vec<String> = (0..rng.next(5)).map(|_| rng.text(8)).collect();
+            let line = words.iter().map(|w| quote(w)).collect::<Vec<_>>().join(" ");
+            assert_eq!(split(&line).as_ref(), Ok(&words), "line: {:?}", line);
+            if let Some(first) = words.first() {
+                assert_eq!(program(&line).as_ref(), Some(first), "line: {:?}", line);
+            }
+        }
+    }
+
+    #[test]
+    fn test_split_arbitrary_input() {
+        let mut rng = Rng(0x2545f4914f6cdd1d);
+        for _ in 0..5000 {
+            let cmd = rng.text(16);
+            // Never panics, and whatever parses survives quoting again.
+            if let Ok(words) = split(&cmd) {
+                let line = words.iter().map(|w| quote(w)).collect::<Vec<_>>().join(" ");
+                assert_eq!(split(&line), Ok(words), "input: {:?}", cmd);
+                assert_eq!(
+                    program(&cmd),
+                    split(&line).unwrap().into_iter().next(),
+                    "input: {:?}",
+                    cmd
+                );
+            } else {
+                program(&cmd);
+            }
+        }
+    }
+}
@@ -0,0 +1,165 @@
+use crate::duration;
+use serde::Deserialize;
+use std::time::Duration;
+
+/// Compose file used when a task's `compose` sets no `file`.
+pub const DEFAULT_FILE: &str = "docker-compose.yml";
+
+/// How long `up` waits for services to become healthy by default.
+pub const DEFAULT_WAIT_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// A task's `compose` table, wrapping `docker compose`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ComposeConfig {
+    /// Relative to the Taskfile; defaults to `docker-compose.yml`.
+    pub file: Option<String>,
+    /// The service to act on; all services in the file when unset.
+    pub service: Option<String>,
+    #[serde(default)]
+    pub action: ComposeAction,
+    /// How long `up` waits for the services to report healthy (or running,
+    /// when they have no healthcheck), e.g. `"2m"`.
+    #[serde(default, deserialize_with = "duration::deserialize_opt")]
+    pub wait_timeout: Option<Duration>,
+    /// Stop what `up` started once the whole run finishes, pass or fail.
+    #[serde(default)]
+    pub teardown: bool,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ComposeAction {
+    #[default]
+    Up,
+    Down,
+    Stop,
+    Restart,
+}
+
+impl ComposeConfig {
+    pub fn file(&self) -> &str {
+        self.file.as_deref().unwrap_or(DEFAULT_FILE)
+    }
+
+    pub fn wait_timeout(&self) -> Duration {
+        self.wait_timeout.unwrap_or(DEFAULT_WAIT_TIMEOUT)
+    }
+
+    /// Arguments after `docker compose` for `action`.
+    pub fn args(&self, action: ComposeAction) -> Vec<String> {
+        let mut args = vec!["-f".to_string(), self.file().to_string()];
+        match action {
+            ComposeAction::Up => args.extend(["up".to_string(), "-d".to_string()]),
+            ComposeAction::Down => args.push("down".to_string()),
+            ComposeAction::Stop => args.push("stop".to_string()),
+            ComposeAction::Restart => args.push("restart".to_string()),
+        }
+        // `down` always acts on the whole project.
+        if action != ComposeAction::Down {
+            args.extend(self.service.clone());
+        }
+        args
+    }
+
+    /// Arguments listing the services' state as JSON, for `parse_status`.
+    pub fn ps_args(&self) -> Vec<String> {
+        let mut args = vec![
+            "-f".to_string(),
+            self.file().to_string(),
+            "ps".to_string(),
+            "--all".to_string(),
+            "--format".to_string(),
+            "json".to_string(),
+        ];
+        args.extend(self.service.clone());
+        args
+    }
+
+    /// What undoes `up` when `teardown` is set: stopping the one service, or
+    /// taking the whole project down.
+    pub fn teardown_action(&self) -> ComposeAction {
+        match self.service {
+            Some(_) => ComposeAction::Stop,
+            None => ComposeAction::Down,
+        }
+    }
+}
+
+/// Where the services stand while `up` waits for them.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Status {
+    Ready,
+    Starting,
+    /// A service exited or its healthcheck failed; the message names it.
+    Failed(String),
+}
+
+/// Reads `docker compose ps --format json`, which newer releases print as
+/// one object per line and older ones as a single array.
+pub fn parse_status(output: &str) -> Status {
+    let trimmed = output.trim();
+    let services: Vec<serde_json::Value> = if trimmed.starts_with('[') {
+        serde_json::from_str(trimmed).unwrap_or_default()
+    } else {
+        trimmed
+            .lines()
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect()
+    };
+    if services.is_empty() {
+        return Status::Starting;
+    }
+
+    let mut status = Status::Ready;
+    for service in &services {
+        let field = |name: &str| {
+            service
+                .get(name)
+                .and_then(|v| v.as_str())
+                .unwrap_or_default()
+                .to_ascii_lowercase()
+        };
+        let name = service
+            .get("Service")
+            .or_else(|| service.get("Name"))
+            .and_then(|v| v.as_str())
+            .unwrap_or("service");
+        match (field("State").as_str(), field("Health").as_str()) {
+            ("exited" | "dead", _) => return Status::Failed(format!("{} exited", name)),
+            (_, "unhealthy") => return Status::Failed(format!("{} is unhealthy", name)),
+            ("running", "healthy" | "") => {}
+            _ => status = Status::Starting,
+        }
+    }
+    status
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_args_and_status() {
+        let config: ComposeConfig = toml::from_str(r#"service = "db""#).unwrap();
+        assert_eq!(
+            config.args(ComposeAction::Up),
+            vec!["-f", "docker-compose.yml", "up", "-d", "db"]
+        );
+        assert_eq!(config.teardown_action(), ComposeAction::Stop);
+        assert!(toml::from_str::<ComposeConfig>(r#"action = "launch""#).is_err());
+
+        let lines = r#"{"Service":"db","State":"running","Health":"starting"}
+{"Service":"cache","State":"running","Health":""}"#;
+        assert_eq!(parse_status(lines), Status::Starting);
+        assert_eq!(
+            parse_status(r#"[{"Service":"db","State":"running","Health":"healthy"}]"#),
+            Status::Ready
+        );
+        assert_eq!(
+            parse_status(r#"{"Service":"db","State":"exited","Health":""}"#),
+            Status::Failed("db exited".to_string())
+        );
+        assert_eq!(parse_status(""), Status::Starting);
+    }
+}
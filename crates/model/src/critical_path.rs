@@ -0,0 +1,100 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// The chain of tasks ending at `root` with the largest total duration: the
+/// shortest wall-clock time the run could take with unlimited parallelism.
+/// `durations` holds the tasks that ran; dependencies missing from it (for
+/// example skipped ones) count as zero but are still followed.
+pub fn critical_path<F>(
+    root: &str,
+    durations: &HashMap<String, Duration>,
+    deps: F,
+) -> Vec<(String, Duration)>
+where
+    F: Fn(&str) -> Vec<String>,
+{
+    let mut finish: HashMap<String, (Duration, Option<String>)> = HashMap::new();
+    longest(root, durations, &deps, &mut finish, &mut Vec::new());
+
+    let mut path = Vec::new();
+    let mut current = Some(root.to_string());
+    while let Some(name) = current {
+        if let Some(duration) = durations.get(&name) {
+            path.push((name.clone(), *duration));
+        }
+        current = finish.get(&name).and_then(|(_, next)| next.clone());
+    }
+    path.reverse();
+    path
+}
+
+/// Length of the longest chain ending at `name`, memoized in `finish`
+/// together with the dependency that chain goes through.
+fn longest<F>(
+    name: &str,
+    durations: &HashMap<String, Duration>,
+    deps: &F,
+    finish: &mut HashMap<String, (Duration, Option<String>)>,
+    stack: &mut Vec<String>,
+) -> Duration
+where
+    F: Fn(&str) -> Vec<String>,
+{
+    if let Some((total, _)) = finish.get(name) {
+        return *total;
+    }
+    // Cycles are rejected before anything runs; just stop following them.
+    if stack.iter().any(|n| n == name) {
+        return Duration::ZERO;
+    }
+
+    stack.push(name.to_string());
+    let mut best: Option<(Duration, String)> = None;
+    for dep in deps(name) {
+        let total = longest(&dep, durations, deps, finish, stack);
+        if best.as_ref().is_none_or(|(b, _)| total > *b) {
+            best = Some((total, dep));
+        }
+    }
+    stack.pop();
+
+    let own = durations.get(name).copied().unwrap_or_default();
+    let (upstream, via) = match best {
+        Some((total, dep)) => (total, Some(dep)),
+        None => (Duration::ZERO, None),
+    };
+    finish.insert(name.to_string(), (own + upstream, via));
+    own + upstream
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_critical_path_follows_slowest_branch() {
+        let graph: HashMap<String, Vec<String>> = HashMap::from([
+            (
+                "deploy".to_string(),
+                vec!["build".to_string(), "lint".to_string()],
+            ),
+            ("build".to_string(), vec!["fetch".to_string()]),
+            ("lint".to_string(), vec!["fetch".to_string()]),
+            ("fetch".to_string(), vec![]),
+        ]);
+        let secs = |s| Duration::from_secs(s);
+        let durations = HashMap::from([
+            ("deploy".to_string(), secs(1)),
+            ("build".to_string(), secs(5)),
+            ("lint".to_string(), secs(2)),
+            ("fetch".to_string(), secs(3)),
+        ]);
+
+        let path = critical_path("deploy", &durations, |name| {
+            graph.get(name).cloned().unwrap_or_default()
+        });
+        let names: Vec<&str> = path.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, vec!["fetch", "build", "deploy"]);
+        assert_eq!(path.iter().map(|(_, d)| *d).sum::<Duration>(), secs(9));
+    }
+}
@@ -0,0 +1,348 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum FlagType {
+    #[default]
+    Bool,
+    String,
+    Int,
+}
+
+impl fmt::Display for FlagType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            FlagType::Bool => "bool",
+            FlagType::String => "string",
+            FlagType::Int => "int",
+        })
+    }
+}
+
+/// One entry of a task's `[tasks.<name>.flags]` table.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct FlagSpec {
+    #[serde(rename = "type", default)]
+    pub kind: FlagType,
+    pub default: Option<toml::Value>,
+    #[serde(default)]
+    pub required: bool,
+    pub help: Option<String>,
+}
+
+/// One entry of a task's `[tasks.<name>.params]` table: either the default
+/// value, or a table whose `default` can be left out to make the parameter
+/// required.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum ParamSpec {
+    Default(String),
+    Table {
+        default: Option<String>,
+        help: Option<String>,
+    },
+}
+
+impl ParamSpec {
+    pub fn default_value(&self) -> Option<&str> {
+        match self {
+            ParamSpec::Default(value) => Some(value),
+            ParamSpec::Table { default, .. } => default.as_deref(),
+        }
+    }
+
+    pub fn help(&self) -> Option<&str> {
+        match self {
+            ParamSpec::Default(_) => None,
+            ParamSpec::Table { help, .. } => help.as_deref(),
+        }
+    }
+
+    pub fn usage(&self, name: &str) -> String {
+        match self.default_value() {
+            Some(default) => format!("{}={}", name, default),
+            None => format!("{}=<value>", name),
+        }
+    }
+}
+
+impl FlagSpec {
+    /// Usage fragment such as `--force` or `--region <string>`.
+    pub fn usage(&self, name: &str) -> String {
+        match self.kind {
+            FlagType::Bool => format!("--{}", name),
+            kind => format!("--{} <{}>", name, kind),
+        }
+    }
+}
+
+/// Usage line for a task, e.g. `task deploy --replicas <int> [--force]
+/// [region=eu-west-1]`. Required flags come first, the rest are bracketed,
+/// and params follow the flags.
+pub fn usage(
+    task_name: &str,
+    specs: &HashMap<String, FlagSpec>,
+    params: &HashMap<String, ParamSpec>,
+) -> String {
+    let mut names: Vec<&String> = specs.keys().collect();
+    names.sort_by_key(|name| (!specs[*name].required, *name));
+
+    let mut line = format!("task {}", task_name);
+    for name in names {
+        let spec = &specs[name];
+        if spec.required {
+            line.push_str(&format!(" {}", spec.usage(name)));
+        } else {
+            line.push_str(&format!(" [{}]", spec.usage(name)));
+        }
+    }
+
+    let mut names: Vec<&String> = params.keys().collect();
+    names.sort_by_key(|name| (params[*name].default_value().is_some(), *name));
+    for name in names {
+        let spec = &params[name];
+        match spec.default_value() {
+            Some(_) => line.push_str(&format!(" [{}]", spec.usage(name))),
+            None => line.push_str(&format!(" {}", spec.usage(name))),
+        }
+    }
+    line
+}
+
+/// Parses `--name`, `--no-name`, `--name value` and `--name=value` against
+/// `specs`, returning template values keyed `flags.<name>`. Every declared
+/// flag gets a value; bools default to `false` and others to empty.
+pub fn parse(
+    specs: &HashMap<String, FlagSpec>,
+    args: &[String],
+) -> Result<HashMap<String, String>, String> {
+    parse_with_params(specs, &HashMap::new(), args)
+}
+
+/// Like `parse`, also accepting `name=value` for the task's `params`, which
+/// become template values keyed by the bare name.
+pub fn parse_with_params(
+    specs: &HashMap<String, FlagSpec>,
+    params: &HashMap<String, ParamSpec>,
+    args: &[String],
+) -> Result<HashMap<String, String>, String> {
+    let mut values: HashMap<String, String> = HashMap::new();
+    let mut given: HashMap<&str, &str> = HashMap::new();
+    let mut iter = args.iter();
+
+    while let Some(arg) = iter.next() {
+        let Some(flag) = arg.strip_prefix("--") else {
+            match arg.split_once('=') {
+                Some((name, value)) if params.contains_key(name) => {
+                    given.insert(name, value);
+                    continue;
+                }
+                Some((name, _)) if !name.is_empty() => {
+                    return Err(format!("unknown param '{}'{}", name, known_params(params)));
+                }
+                _ => {
+                    return Err(format!(
+                        "unexpected argument '{}' (pass arguments for the command after --)",
+                        arg
+                    ))
+                }
+            }
+        };
+        let (name, inline) = match flag.split_once('=') {
+            Some((name, value)) => (name, Some(value.to_string())),
+            None => (flag, None),
+        };
+
+        let (name, spec, negated) = match specs.get(name) {
+            Some(spec) => (name, spec, false),
+            None => match name
+                .strip_prefix("no-")
+                .and_then(|n| specs.get(n).map(|s| (n, s)))
+            {
+                Some((name, spec)) if spec.kind == FlagType::Bool => (name, spec, true),
+                _ => return Err(format!("unknown flag '--{}'{}", name, known_flags(specs))),
+            },
+        };
+
+        let value = match spec.kind {
+            FlagType::Bool => match inline.as_deref() {
+                None => (!negated).to_string(),
+                Some("true") | Some("false") if !negated => inline.clone().unwrap(),
+                Some(other) => {
+                    return Err(format!("flag '--{}' is a bool, got '{}'", name, other));
+                }
+            },
+            kind => {
+                let value = match inline {
+                    Some(value) => value,
+                    None => iter
+                        .next()
+                        .cloned()
+                        .ok_or_else(|| format!("flag '--{}' expects a {} value", name, kind))?,
+                };
+                if kind == FlagType::Int && value.parse::<i64>().is_err() {
+                    return Err(format!("flag '--{}' expects an int, got '{}'", name, value));
+                }
+                value
+            }
+        };
+        values.insert(name.to_string(), value);
+    }
+
+    let mut missing = Vec::new();
+    let mut resolved = HashMap::new();
+    for (name, spec) in specs {
+        let value = match values.remove(name) {
+            Some(value) => value,
+            None if spec.required => {
+                missing.push(format!("--{}", name));
+                continue;
+            }
+            None => match &spec.default {
+                Some(toml::Value::String(s)) => s.clone(),
+                Some(other) => other.to_string(),
+                None if spec.kind == FlagType::Bool => "false".to_string(),
+                None => String::new(),
+            },
+        };
+        resolved.insert(format!("flags.{}", name), value);
+    }
+
+    if !missing.is_empty() {
+        missing.sort();
+        return Err(format!("missing required flags: {}", missing.join(", ")));
+    }
+
+    for (name, spec) in params {
+        match given.get(name.as_str()).copied().or(spec.default_value()) {
+            Some(value) => {
+                resolved.insert(name.clone(), value.to_string());
+            }
+            None => missing.push(format!("{}=<value>", name)),
+        }
+    }
+    if !missing.is_empty() {
+        missing.sort();
+        return Err(format!("missing required params: {}", missing.join(", ")));
+    }
+    Ok(resolved)
+}
+
+fn known_params(params: &HashMap<String, ParamSpec>) -> String {
+    if params.is_empty() {
+        return " (this task declares no params)".to_string();
+    }
+    let mut names: Vec<&String> = params.keys().collect();
+    names.sort();
+    let names: Vec<&str> = names.into_iter().map(String::as_str).collect();
+    format!(" (expected one of: {})", names.join(", "))
+}
+
+fn known_flags(specs: &HashMap<String, FlagSpec>) -> String {
+    if specs.is_empty() {
+        return " (this task declares no flags)".to_string();
+    }
+    let mut names: Vec<String> = specs.iter().map(|(n, s)| s.usage(n)).collect();
+    names.sort();
+    format!(" (expected one of: {})", names.join(", "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn specs() -> HashMap<String, FlagSpec> {
+        toml::from_str(
+            r#"
+force = { type = "bool" }
+region = { type = "string", default = "eu-west-1" }
+replicas = { type = "int", required = true }
+"#,
+        )
+        .unwrap()
+    }
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_flags() {
+        let values = parse(&specs(), &args(&["--force", "--replicas=3"])).unwrap();
+        assert_eq!(values["flags.force"], "true");
+        assert_eq!(values["flags.region"], "eu-west-1");
+        assert_eq!(values["flags.replicas"], "3");
+
+        let values = parse(
+            &specs(),
+            &args(&["--no-force", "--replicas", "1", "--region", "us"]),
+        )
+        .unwrap();
+        assert_eq!(values["flags.force"], "false");
+        assert_eq!(values["flags.region"], "us");
+    }
+
+    #[test]
+    fn test_usage() {
+        assert_eq!(
+            usage("deploy", &specs(), &HashMap::new()),
+            "task deploy --replicas <int> [--force] [--region <string>]"
+        );
+        assert_eq!(
+            usage("tag", &HashMap::new(), &params()),
+            "task tag version=<value> [region=eu-west-1]"
+        );
+    }
+
+    fn params() -> HashMap<String, ParamSpec> {
+        toml::from_str(
+            r#"
+region = "eu-west-1"
+version = { help = "Version to tag" }
+"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_parse_params() {
+        let values = parse_with_params(
+            &specs(),
+            &params(),
+            &args(&["version=1.2", "--replicas", "2", "region=us-east-1"]),
+        )
+        .unwrap();
+        assert_eq!(values["version"], "1.2");
+        assert_eq!(values["region"], "us-east-1");
+        assert_eq!(values["flags.replicas"], "2");
+
+        let values =
+            parse_with_params(&HashMap::new(), &params(), &args(&["version=a=b"])).unwrap();
+        assert_eq!(values["version"], "a=b");
+        assert_eq!(values["region"], "eu-west-1");
+
+        assert!(parse_with_params(&HashMap::new(), &params(), &args(&[]))
+            .unwrap_err()
+            .contains("missing required params: version=<value>"));
+        assert!(
+            parse_with_params(&HashMap::new(), &params(), &args(&["zone=a", "version=1"]))
+                .unwrap_err()
+                .contains("unknown param 'zone' (expected one of: region, version)")
+        );
+    }
+
+    #[test]
+    fn test_parse_flag_errors() {
+        assert!(parse(&specs(), &args(&[]))
+            .unwrap_err()
+            .contains("--replicas"));
+        assert!(parse(&specs(), &args(&["--replicas=x"]))
+            .unwrap_err()
+            .contains("expects an int"));
+        assert!(parse(&specs(), &args(&["--replicas=1", "--bogus"]))
+            .unwrap_err()
+            .contains("unknown flag '--bogus'"));
+    }
+}
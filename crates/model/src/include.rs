@@ -0,0 +1,523 @@
+use crate::{Task, TaskFile};
+use env_parser::EnvConfig;
+use indexmap::IndexMap;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Deepest include chain accepted before assuming a runaway configuration.
+pub const MAX_INCLUDE_DEPTH: usize = 16;
+
+/// One `include` entry: either a bare path or a table.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum Include {
+    Path(String),
+    Table {
+        file: String,
+        /// Prefix for the included task names (`api:deploy`), which lets the
+        /// same file be included more than once.
+        namespace: Option<String>,
+        /// Variables overriding the included file's own `[env]`.
+        #[serde(default)]
+        vars: HashMap<String, String>,
+    },
+}
+
+impl Include {
+    pub fn file(&self) -> &str {
+        match self {
+            Include::Path(file) => file,
+            Include::Table { file, .. } => file,
+        }
+    }
+
+    pub fn namespace(&self) -> Option<&str> {
+        match self {
+            Include::Path(_) => None,
+            Include::Table { namespace, .. } => namespace.as_deref(),
+        }
+    }
+
+    pub fn vars(&self) -> Option<&HashMap<String, String>> {
+        match self {
+            Include::Path(_) => None,
+            Include::Table { vars, .. } => Some(vars),
+        }
+    }
+}
+
+/// `include` accepts a single entry or a list of them.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum Includes {
+    One(Include),
+    Many(Vec<Include>),
+}
+
+impl Default for Includes {
+    fn default() -> Self {
+        Includes::Many(Vec::new())
+    }
+}
+
+impl Includes {
+    pub fn iter(&self) -> std::slice::Iter<'_, Include> {
+        match self {
+            Includes::One(include) => std::slice::from_ref(include).iter(),
+            Includes::Many(includes) => includes.iter(),
+        }
+    }
+}
+
+/// The `[env]` of one file in the include tree. Its variables apply to that
+/// file's tasks, layered over its parent's; scope 0 is the root Taskfile.
+#[derive(Debug, Clone)]
+pub struct EnvScope {
+    pub env: Option<EnvConfig>,
+    /// The Taskfile this scope belongs to.
+    pub file: PathBuf,
+    pub dir: PathBuf,
+    pub parent: Option<usize>,
+    /// `vars` from the include entry, applied on top of `env`.
+    pub overrides: HashMap<String, String>,
+}
+
+/// Env scopes of the include tree plus the scope of every included task.
+/// Tasks missing from `task_scopes` belong to the root scope.
+#[derive(Debug, Default)]
+pub struct Resolved {
+    pub scopes: Vec<EnvScope>,
+    pub task_scopes: HashMap<String, usize>,
+    /// Included tasks replaced by a task of the same name in the including
+    /// file.
+    pub overridden: Vec<Override>,
+}
+
+/// Where a task is defined. `line` is 1-based, and missing when the file
+/// could not be read again to find it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Location {
+    pub file: PathBuf,
+    pub line: Option<usize>,
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "{}:{}", self.file.display(), line),
+            None => write!(f, "{}", self.file.display()),
+        }
+    }
+}
+
+/// A task of the including file taking precedence over an included one.
+#[derive(Debug, Clone)]
+pub struct Override {
+    pub task: String,
+    pub kept: Location,
+    pub ignored: Location,
+}
+
+/// A task on its way into the merged Taskfile.
+struct Entry {
+    task: Task,
+    scope: usize,
+    /// The name as written in its own file, before any namespace prefix.
+    local_name: String,
+}
+
+impl Resolved {
+    /// The single root scope of a Taskfile without includes.
+    pub fn root(taskfile: &TaskFile, dir: &Path) -> Self {
+        Self {
+            scopes: vec![EnvScope {
+                env: taskfile.env.clone(),
+                file: PathBuf::new(),
+                dir: dir.to_path_buf(),
+                parent: None,
+                overrides: HashMap::new(),
+            }],
+            task_scopes: HashMap::new(),
+            overridden: Vec::new(),
+        }
+    }
+
+    fn location(&self, entry: &Entry) -> Location {
+        let file = &self.scopes[entry.scope].file;
+        Location {
+            file: file.clone(),
+            line: task_line(file, &entry.local_name),
+        }
+    }
+}
+
+/// Loads every file `taskfile` includes, recursively, and merges their
+/// tasks into it. Tasks defined by the including file win over included ones
+/// (recorded in `Resolved::overridden`); the same name coming from two
+/// included files is an error, since neither one is the obvious winner.
+pub fn resolve(
+    taskfile: &mut TaskFile,
+    path: &Path,
+) -> Result<Resolved, Box<dyn std::error::Error>> {
+    let mut resolved = Resolved::root(taskfile, path.parent().unwrap_or(Path::new("")));
+    resolved.scopes[0].file = path.to_path_buf();
+    let mut chain = vec![canonical(path)];
+    let tasks = collect(taskfile, path, &mut chain, 0, &mut resolved)?;
+
+    for (name, entry) in tasks {
+        if entry.scope != 0 {
+            resolved.task_scopes.insert(name.clone(), entry.scope);
+        }
+        taskfile.tasks.insert(name, entry.task);
+    }
+    Ok(resolved)
+}
+
+/// Takes `taskfile`'s tasks and those of everything it includes, tagged with
+/// the env scope each one came from.
+fn collect(
+    taskfile: &mut TaskFile,
+    path: &Path,
+    chain: &mut Vec<PathBuf>,
+    scope: usize,
+    resolved: &mut Resolved,
+) -> Result<IndexMap<String, Entry>, Box<dyn std::error::Error>> {
+    let base = path.parent().unwrap_or(Path::new(""));
+    let includes: Vec<Include> = taskfile.include.iter().cloned().collect();
+    let mut tasks: IndexMap<String, Entry> = std::mem::take(&mut taskfile.tasks)
+        .into_iter()
+        .map(|(name, task)| {
+            let entry = Entry {
+                task,
+                scope,
+                local_name: name.clone(),
+            };
+            (name, entry)
+        })
+        .collect();
+
+    for include in includes {
+        let include_path = base.join(include.file());
+        let key = canonical(&include_path);
+
+        if chain.contains(&key) {
+            return Err(format!(
+                "Include cycle detected: {} -> {}",
+                render_chain(chain),
+                include_path.display()
+            )
+            .into());
+        }
+        if chain.len() > MAX_INCLUDE_DEPTH {
+            return Err(format!(
+                "Include depth limit ({}) exceeded: {} -> {}",
+                MAX_INCLUDE_DEPTH,
+                render_chain(chain),
+                include_path.display()
+            )
+            .into());
+        }
+
+        let contents = fs::read_to_string(&include_path).map_err(|e| {
+            format!(
+                "Failed to read included file {} (from {}): {}",
+                include_path.display(),
+                path.display(),
+                e
+            )
+        })?;
+        let mut included: TaskFile = toml::from_str(&contents).map_err(|e| {
+            format!(
+                "Failed to parse included file {}: {}",
+                include_path.display(),
+                e
+            )
+        })?;
+
+        let included_scope = resolved.scopes.len();
+        resolved.scopes.push(EnvScope {
+            env: included.env.clone(),
+            file: include_path.clone(),
+            dir: include_path.parent().unwrap_or(Path::new("")).to_path_buf(),
+            parent: Some(scope),
+            overrides: include.vars().cloned().unwrap_or_default(),
+        });
+
+        chain.push(key);
+        let mut nested = collect(
+            &mut included,
+            &include_path,
+            chain,
+            included_scope,
+            resolved,
+        )?;
+        chain.pop();
+
+        if let Some(namespace) = include.namespace() {
+            nested = namespaced(nested, namespace);
+        }
+
+        for (name, entry) in nested {
+            match tasks.get(&name) {
+                None => {
+                    tasks.insert(name, entry);
+                }
+                Some(existing) if existing.scope == scope => {
+                    let kept = resolved.location(existing);
+                    let ignored = resolved.location(&entry);
+                    resolved.overridden.push(Override {
+                        task: name,
+                        kept,
+                        ignored,
+                    });
+                }
+                Some(existing) => {
+                    return Err(format!(
+                        "Task '{}' is defined in both {} and {}; give one of the includes a namespace, or define '{}' in {} to choose",
+                        name,
+                        resolved.location(existing),
+                        resolved.location(&entry),
+                        name,
+                        path.display()
+                    )
+                    .into());
+                }
+            }
+        }
+    }
+
+    Ok(tasks)
+}
+
+/// Prefixes every task name with `namespace:` and rewrites dependencies on
+/// sibling tasks to match; dependencies on outside tasks are left alone.
+fn namespaced(tasks: IndexMap<String, Entry>, namespace: &str) -> IndexMap<String, Entry> {
+    let prefix = |name: &str| format!("{}:{}", namespace, name);
+    let local: Vec<String> = tasks.keys().cloned().collect();
+
+    tasks
+        .into_iter()
+        .map(|(name, mut entry)| {
+            if let Some(deps) = &mut entry.task.depends_on {
+                for dep in deps.iter_mut() {
+                    if local.contains(&dep.task) {
+                        dep.task = prefix(&dep.task);
+                    }
+                }
+            }
+            (prefix(&name), entry)
+        })
+        .collect()
+}
+
+/// Line of `[tasks.<name>]`, or of `<name> = ...` under `[tasks]`, in `file`.
+fn task_line(file: &Path, name: &str) -> Option<usize> {
+    let contents = fs::read_to_string(file).ok()?;
+    let document = toml_edit::ImDocument::parse(contents.as_str()).ok()?;
+    let (key, _) = document
+        .get("tasks")?
+        .as_table_like()?
+        .get_key_value(name)?;
+    let start = key.span()?.start;
+    Some(contents[..start].matches('\n').count() + 1)
+}
+
+fn canonical(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
+
+fn render_chain(chain: &[PathBuf]) -> String {
+    chain
+        .iter()
+        .map(|p| p.display().to_string())
+        .collect::<Vec<_>>()
+        .join(" -> ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("taskfile-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn load(path: &Path) -> Result<TaskFile, Box<dyn std::error::Error>> {
+        load_resolved(path).map(|(taskfile, _)| taskfile)
+    }
+
+    fn load_resolved(path: &Path) -> Result<(TaskFile, Resolved), Box<dyn std::error::Error>> {
+        let mut taskfile: TaskFile = toml::from_str(&fs::read_to_string(path)?)?;
+        let resolved = resolve(&mut taskfile, path)?;
+        Ok((taskfile, resolved))
+    }
+
+    #[test]
+    fn test_include_merges_tasks() {
+        let dir = scratch_dir("include-merge");
+        fs::write(
+            dir.join("Taskfile.toml"),
+            "include = \"ci.toml\"\n[tasks.build]\ncmd = \"echo local\"\n[tasks.audit]\ncmd = \"echo\"\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("ci.toml"),
+            "[tasks.lint]\ncmd = \"echo lint\"\n[tasks.build]\ncmd = \"echo included\"\n",
+        )
+        .unwrap();
+
+        let (taskfile, resolved) = load_resolved(&dir.join("Taskfile.toml")).unwrap();
+        assert_eq!(taskfile.tasks["build"].cmd, "echo local");
+        // Declaration order, with included tasks after the including file's.
+        let names: Vec<&str> = taskfile.tasks.keys().map(String::as_str).collect();
+        assert_eq!(names, ["build", "audit", "lint"]);
+
+        let [shadowed] = resolved.overridden.as_slice() else {
+            panic!("{:?}", resolved.overridden);
+        };
+        assert_eq!(shadowed.task, "build");
+        assert_eq!(shadowed.kept.line, Some(2));
+        assert!(shadowed.ignored.file.ends_with("ci.toml"));
+        assert_eq!(shadowed.ignored.line, Some(3));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_include_rejects_task_defined_by_two_includes() {
+        let dir = scratch_dir("include-duplicate");
+        fs::write(
+            dir.join("Taskfile.toml"),
+            "include = [\"a.toml\", \"b.toml\"]\n[tasks]\n",
+        )
+        .unwrap();
+        fs::write(dir.join("a.toml"), "[tasks.lint]\ncmd = \"echo a\"\n").unwrap();
+        fs::write(
+            dir.join("b.toml"),
+            "[tasks]\ntest = { cmd = \"echo\" }\nlint = { cmd = \"echo b\" }\n",
+        )
+        .unwrap();
+
+        let err = load(&dir.join("Taskfile.toml")).unwrap_err().to_string();
+        assert!(
+            err.contains("Task 'lint' is defined in both")
+                && err.contains("a.toml:1")
+                && err.contains("b.toml:3"),
+            "{}",
+            err
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_include_records_env_scopes() {
+        let dir = scratch_dir("include-scopes");
+        fs::write(
+            dir.join("Taskfile.toml"),
+            "include = \"sub/deploy.toml\"\n[tasks.build]\ncmd = \"echo\"\n",
+        )
+        .unwrap();
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        fs::write(
+            dir.join("sub/deploy.toml"),
+            "[env]\nvars = { REGION = \"eu\" }\n[tasks.deploy]\ncmd = \"echo\"\n",
+        )
+        .unwrap();
+
+        let (_, resolved) = load_resolved(&dir.join("Taskfile.toml")).unwrap();
+        assert_eq!(resolved.scopes.len(), 2);
+        assert_eq!(resolved.task_scopes.get("deploy"), Some(&1));
+        assert!(!resolved.task_scopes.contains_key("build"));
+        assert_eq!(resolved.scopes[1].parent, Some(0));
+        assert!(resolved.scopes[1].dir.ends_with("sub"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_include_same_file_twice_with_namespaces() {
+        let dir = scratch_dir("include-namespace");
+        fs::write(
+            dir.join("Taskfile.toml"),
+            r#"
+include = [
+    { file = "service.toml", namespace = "api", vars = { SERVICE = "api" } },
+    { file = "service.toml", namespace = "web", vars = { SERVICE = "web" } },
+]
+[tasks.setup]
+cmd = "echo setup"
+"#,
+        )
+        .unwrap();
+        fs::write(
+            dir.join("service.toml"),
+            r#"
+[tasks.build]
+cmd = "echo build $SERVICE"
+[tasks.deploy]
+cmd = "echo deploy $SERVICE"
+depends_on = ["build", "setup"]
+"#,
+        )
+        .unwrap();
+
+        let (taskfile, resolved) = load_resolved(&dir.join("Taskfile.toml")).unwrap();
+        let deploy = &taskfile.tasks["web:deploy"];
+        assert_eq!(
+            deploy.depends_on,
+            Some(vec!["web:build".into(), "setup".into()])
+        );
+        assert!(taskfile.tasks.contains_key("api:build"));
+
+        let api_scope = resolved.task_scopes["api:deploy"];
+        let web_scope = resolved.task_scopes["web:deploy"];
+        assert_eq!(resolved.scopes[api_scope].overrides["SERVICE"], "api");
+        assert_eq!(resolved.scopes[web_scope].overrides["SERVICE"], "web");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_include_cycle_names_chain() {
+        let dir = scratch_dir("include-cycle");
+        fs::write(dir.join("a.toml"), "include = [\"b.toml\"]\n[tasks]\n").unwrap();
+        fs::write(
+            dir.join("b.toml"),
+            "include = [{ file = \"a.toml\" }]\n[tasks]\n",
+        )
+        .unwrap();
+
+        let err = load(&dir.join("a.toml")).unwrap_err().to_string();
+        assert!(err.contains("Include cycle detected"), "{}", err);
+        assert!(
+            err.contains("a.toml -> ") && err.contains("b.toml"),
+            "{}",
+            err
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_include_depth_limit() {
+        let dir = scratch_dir("include-depth");
+        for i in 0..=MAX_INCLUDE_DEPTH + 1 {
+            fs::write(
+                dir.join(format!("{}.toml", i)),
+                format!("include = \"{}.toml\"\n[tasks]\n", i + 1),
+            )
+            .unwrap();
+        }
+
+        let err = load(&dir.join("0.toml")).unwrap_err().to_string();
+        assert!(err.contains("Include depth limit"), "{}", err);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
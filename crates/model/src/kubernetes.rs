@@ -0,0 +1,125 @@
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+/// How long a finished Job (and its pod's logs) is kept before the cluster
+/// deletes it.
+const TTL_AFTER_FINISHED_SECS: u64 = 600;
+
+/// A task's `kubernetes` table: run the command as a Kubernetes Job instead
+/// of on this machine. Experimental.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct KubernetesConfig {
+    /// Container image with everything the command needs, e.g.
+    /// `"rust:1.80"`.
+    pub image: String,
+    /// Defaults to the current `kubectl` context's namespace.
+    pub namespace: Option<String>,
+    /// CPU request and limit, e.g. `"2"` or `"500m"`.
+    pub cpu: Option<String>,
+    /// Memory request and limit, e.g. `"4Gi"`.
+    pub memory: Option<String>,
+}
+
+impl KubernetesConfig {
+    /// `kubectl` arguments selecting the namespace, if one is set.
+    pub fn namespace_args(&self) -> Vec<String> {
+        match &self.namespace {
+            Some(namespace) => vec!["--namespace".to_string(), namespace.clone()],
+            None => Vec::new(),
+        }
+    }
+
+    /// The Job running `script` through `sh -c` once, without retries.
+    pub fn job_manifest(
+        &self,
+        name: &str,
+        task_name: &str,
+        script: &str,
+        env: &[(&str, &str)],
+    ) -> Value {
+        let mut resources = serde_json::Map::new();
+        for (key, value) in [("cpu", &self.cpu), ("memory", &self.memory)] {
+            if let Some(value) = value {
+                resources.insert(key.to_string(), json!(value));
+            }
+        }
+        let env: Vec<Value> = env
+            .iter()
+            .map(|(name, value)| json!({ "name": name, "value": value }))
+            .collect();
+
+        json!({
+            "apiVersion": "batch/v1",
+            "kind": "Job",
+            "metadata": {
+                "name": name,
+                "labels": { "app.kubernetes.io/managed-by": "taskfile" },
+                "annotations": { "taskfile/task": task_name },
+            },
+            "spec": {
+                "backoffLimit": 0,
+                "ttlSecondsAfterFinished": TTL_AFTER_FINISHED_SECS,
+                "template": {
+                    "spec": {
+                        "restartPolicy": "Never",
+                        "containers": [{
+                            "name": "task",
+                            "image": self.image,
+                            "command": ["sh", "-c", script],
+                            "env": env,
+                            "resources": { "requests": resources, "limits": resources },
+                        }],
+                    },
+                },
+            },
+        })
+    }
+}
+
+/// A valid, unique Job name for one run of `task_name`: lowercase, `-`
+/// separated and at most 63 characters.
+pub fn job_name(task_name: &str, run_id: &str) -> String {
+    let mut task: String = task_name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_lowercase()
+            } else {
+                '-'
+            }
+        })
+        .collect();
+    task.truncate(40);
+    // The tail of a run ID is its random part.
+    let suffix = run_id[run_id.len().saturating_sub(10)..].to_ascii_lowercase();
+    format!("task-{}-{}", task.trim_matches('-'), suffix)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_job_manifest() {
+        let config: KubernetesConfig = toml::from_str(
+            r#"
+image = "rust:1.80"
+namespace = "ci"
+memory = "4Gi"
+"#,
+        )
+        .unwrap();
+        let name = job_name("ns:Build_All", "01M4WFAHET1VRR40A786JC0XK6");
+        assert_eq!(name, "task-ns-build-all-a786jc0xk6");
+
+        let job = config.job_manifest(&name, "ns:Build_All", "cargo build", &[("A", "1")]);
+        let container = &job["spec"]["template"]["spec"]["containers"][0];
+        assert_eq!(container["image"], "rust:1.80");
+        assert_eq!(container["command"], json!(["sh", "-c", "cargo build"]));
+        assert_eq!(container["env"][0], json!({ "name": "A", "value": "1" }));
+        assert_eq!(container["resources"]["limits"], json!({ "memory": "4Gi" }));
+        assert_eq!(job["spec"]["backoffLimit"], 0);
+        assert_eq!(config.namespace_args(), vec!["--namespace", "ci"]);
+    }
+}
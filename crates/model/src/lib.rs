@@ -0,0 +1,761 @@
+//! The Taskfile format: the types a `Taskfile.toml` deserializes into and
+//! the pure helpers that go with them, without the runtime that executes
+//! tasks. Editors, formatters and other tools can depend on this crate alone.
+//!
+//! Nothing here spawns processes, so the crate builds for `wasm32-wasip1`
+//! as well; reading Taskfiles and includes only needs a filesystem.
+
+pub mod cache;
+pub mod changelog;
+pub mod command;
+pub mod compose;
+pub mod critical_path;
+pub mod duration;
+pub mod flags;
+pub mod include;
+pub mod kubernetes;
+pub mod locale;
+pub mod mode;
+pub mod pipeline;
+pub mod platform;
+pub mod remote;
+pub mod sandbox;
+pub mod schedule;
+pub mod settings;
+pub mod shell;
+pub mod task_test;
+pub mod ui;
+pub mod user_config;
+
+use cache::{CacheConfig, CacheMethod};
+use changelog::ChangelogConfig;
+use compose::ComposeConfig;
+use env_parser::EnvConfig;
+use flags::{FlagSpec, ParamSpec};
+use include::{Includes, Resolved};
+use indexmap::IndexMap;
+use kubernetes::KubernetesConfig;
+use locale::Desc;
+use pipeline::Pipeline;
+use remote::RemoteBuilder;
+use serde::Deserialize;
+use settings::Settings;
+use shell::{Requirement, ShellKind, ShellSetting};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+use task_test::TaskTest;
+use ui::UiConfig;
+
+/// What a second invocation does when the project lock is already held.
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LockMode {
+    /// Exit immediately with an error naming the holder.
+    Fail,
+    /// Block until the holder finishes.
+    Wait,
+}
+
+/// How many times a task runs when several tasks in one invocation depend
+/// on it.
+#[derive(Debug, Default, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum RunPolicy {
+    /// At most once per invocation.
+    #[default]
+    Once,
+    /// Every time a task that depends on it runs.
+    Always,
+}
+
+/// An entry of `depends_on`: a task name, or a table such as
+/// `{ task = "lint", optional = true }`.
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+#[serde(from = "DependencySpec")]
+pub struct Dependency {
+    pub task: String,
+    /// A failure only prints a warning; the dependent task still runs.
+    pub optional: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum DependencySpec {
+    Name(String),
+    Table {
+        task: String,
+        #[serde(default)]
+        optional: bool,
+    },
+}
+
+impl From<DependencySpec> for Dependency {
+    fn from(spec: DependencySpec) -> Self {
+        match spec {
+            DependencySpec::Name(task) => Dependency {
+                task,
+                optional: false,
+            },
+            DependencySpec::Table { task, optional } => Dependency { task, optional },
+        }
+    }
+}
+
+impl From<&str> for Dependency {
+    fn from(task: &str) -> Self {
+        Dependency {
+            task: task.to_string(),
+            optional: false,
+        }
+    }
+}
+
+impl std::fmt::Display for Dependency {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.optional {
+            write!(f, "{} (optional)", self.task)
+        } else {
+            f.write_str(&self.task)
+        }
+    }
+}
+
+/// The `[hooks]` table: commands run around every task, or around the whole
+/// run, without editing each task.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Hooks {
+    /// Before each task that runs, with its name in `$TASKFILE_TASK`.
+    #[serde(default)]
+    pub before_each: Vec<String>,
+    /// After each task that ran, also with `$TASKFILE_STATUS` (`success` or
+    /// `failed`) and `$TASKFILE_DURATION_MS`.
+    #[serde(default)]
+    pub after_each: Vec<String>,
+    /// Once, before the first task of the run.
+    #[serde(default)]
+    pub before_all: Vec<String>,
+    /// Once, after the run, with `$TASKFILE_STATUS` and
+    /// `$TASKFILE_DURATION_MS` for the run as a whole.
+    #[serde(default)]
+    pub after_all: Vec<String>,
+}
+
+impl Hooks {
+    pub fn is_empty(&self) -> bool {
+        self.before_each.is_empty()
+            && self.after_each.is_empty()
+            && self.before_all.is_empty()
+            && self.after_all.is_empty()
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct TaskFile {
+    /// In the order they are declared, includes after the tasks of the
+    /// including file.
+    pub tasks: IndexMap<String, Task>,
+    pub env: Option<EnvConfig>,
+    /// Opt-in `.taskfile/lock` guarding against overlapping invocations.
+    pub lock: Option<LockMode>,
+    /// Other Taskfiles whose tasks are merged into this one.
+    #[serde(default)]
+    pub include: Includes,
+    /// Limits for what the runner keeps under `.taskfile/cache`.
+    pub cache: Option<CacheConfig>,
+    /// Spinner, colors and borders; see `ui::Theme`.
+    pub ui: Option<UiConfig>,
+    /// Ring the terminal bell when a run that took at least this long
+    /// finishes, e.g. `"30s"`. `--bell` overrides it.
+    #[serde(default, deserialize_with = "duration::deserialize_opt")]
+    pub bell: Option<Duration>,
+    /// Default for tasks that do not set `shell` themselves.
+    pub shell: Option<ShellSetting>,
+    /// Project-wide behavior; see `settings::Settings`.
+    #[serde(default)]
+    pub settings: Settings,
+    #[serde(default)]
+    pub hooks: Hooks,
+    /// Where tasks with `remote_build = true` run.
+    pub remote_builder: Option<RemoteBuilder>,
+    /// Reusable values such as a binary name or version, available as
+    /// `{{name}}` in `cmd`, `desc` and `cwd`. Params and flags of the same
+    /// name win.
+    #[serde(default)]
+    pub vars: HashMap<String, String>,
+    /// Named sequences of stages, run with `task pipeline <name>`; see
+    /// `pipeline::Pipeline`. Included Taskfiles' pipelines are ignored.
+    #[serde(default)]
+    pub pipelines: IndexMap<String, Pipeline>,
+    /// Checks run by `task test-tasks`; see `task_test::TaskTest`. Included
+    /// Taskfiles' tests are ignored.
+    #[serde(default)]
+    pub tests: IndexMap<String, TaskTest>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct Task {
+    /// `"release"` bumps the version, commits and tags before the commands
+    /// run; see `release`.
+    #[serde(rename = "type", default)]
+    pub kind: TaskKind,
+    /// The command, or a table of per-platform variants; see
+    /// `platform::Cmd`. A command of several lines is a script for the
+    /// task's shell, or the platform's, instead of words to split.
+    #[serde(default)]
+    pub cmd: platform::Cmd,
+    /// Commands run in order, stopping at the first failure; use instead of
+    /// `cmd` rather than chaining with `&&`.
+    #[serde(default)]
+    pub cmds: Vec<String>,
+    /// Alternatives tried in order when the program `cmd` runs is not
+    /// installed, e.g. `["cat README.md"]` behind `bat README.md`.
+    #[serde(default)]
+    pub cmd_fallbacks: Vec<String>,
+    /// A multi-line program written into the Taskfile, used instead of
+    /// `cmd`. It is saved to a temporary file, which `interpreter` runs
+    /// with the task's arguments after it and which is removed afterwards.
+    pub script: Option<String>,
+    /// What runs `script`, e.g. `"python3"`, `"node"` or `"bash -eu"`;
+    /// defaults to the task's shell.
+    pub interpreter: Option<String>,
+    /// Commands run before `cmd`; if one fails, the task fails without
+    /// running `cmd`.
+    #[serde(default)]
+    pub pre: Vec<String>,
+    /// Commands run after `cmd` has exited, successful or not, with its exit
+    /// code in `$TASKFILE_EXIT_CODE`. A failing hook fails a task that had
+    /// succeeded.
+    #[serde(default)]
+    pub post: Vec<String>,
+    /// Cleanup commands, e.g. `["docker compose down"]`, run in order after
+    /// the task's commands whether they succeeded, failed or were
+    /// interrupted. Their own failures are reported but do not change the
+    /// task's result.
+    #[serde(default)]
+    pub finally: Vec<String>,
+    /// One-line summary: a string, or a table of translations such as
+    /// `desc.de`, chosen by `--lang` or `TASKFILE_LANG`.
+    pub desc: Option<Desc>,
+    /// Long-form markdown help shown by `describe` and `help`; `desc` stays
+    /// the one-line summary used in `list`.
+    pub docs: Option<String>,
+    /// Labels such as `"ci"` or `"db"` for `task list --tag` and
+    /// `task run --tag`.
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Tasks that run first; see `Dependency`.
+    pub depends_on: Option<Vec<Dependency>>,
+    /// Run the `depends_on` tasks at the same time instead of one after
+    /// another; unset follows `[settings] depends_on_parallel`.
+    pub depends_on_parallel: Option<bool>,
+    /// Kill the task if it prints nothing for this long, e.g. `"5m"`.
+    #[serde(default, deserialize_with = "duration::deserialize_opt")]
+    pub no_output_timeout: Option<Duration>,
+    /// Show this many of the latest output lines under the spinner while
+    /// the task runs; they are cleared once it finishes.
+    pub max_output_lines: Option<usize>,
+    /// Attach the task to the terminal (stdin, stdout and stderr) for
+    /// programs such as `psql` or `npm login`. No spinner, and its output is
+    /// not captured.
+    #[serde(default)]
+    pub interactive: bool,
+    /// Run the task's commands as root, through `sudo` (a UAC prompt on
+    /// Windows), asking for the password once before they start. `run_if`,
+    /// `skip_if` and `status` checks still run as the user.
+    #[serde(default)]
+    pub elevate: bool,
+    /// When run as a dependency, start the task and carry on without
+    /// waiting for it, e.g. for a database the tests need. It is stopped
+    /// once the run is over, also after a failure or Ctrl-C. Its output goes
+    /// to `.taskfile/background/<task>.log`.
+    #[serde(default)]
+    pub background: bool,
+    /// A y/N question asked before a run that includes the task starts,
+    /// e.g. `"This drops the production DB. Continue?"`. `--yes` answers it;
+    /// without a terminal to ask on, the run fails instead.
+    pub confirm: Option<String>,
+    /// `"always"` runs the task again each time something depends on it,
+    /// instead of once per invocation.
+    #[serde(default)]
+    pub run: RunPolicy,
+    /// Kill the task if it is still running after this long, e.g. `"30s"`.
+    /// With `cmds`, the limit covers all of them together.
+    #[serde(default, deserialize_with = "duration::deserialize_opt")]
+    pub timeout: Option<Duration>,
+    /// Safe to re-run, so `--auto-retry-transient` may retry it.
+    #[serde(default)]
+    pub idempotent: bool,
+    /// Rough run time, e.g. `"2m"`, used to start long tasks first when
+    /// several are ready at once.
+    #[serde(default, deserialize_with = "duration::deserialize_opt")]
+    pub estimated_duration: Option<Duration>,
+    /// Flags accepted on the command line (`task deploy --force`), available
+    /// to `cmd` as `{{flags.<name>}}`.
+    #[serde(default)]
+    pub flags: HashMap<String, FlagSpec>,
+    /// Named parameters given as `task deploy region=us-east-1`, available
+    /// as `{{region}}`. A parameter without a default is required.
+    #[serde(default)]
+    pub params: HashMap<String, ParamSpec>,
+    /// Exit codes that count as success; defaults to `[0]`.
+    pub success_codes: Option<Vec<i32>>,
+    /// Operating systems the task applies to, e.g. `["linux", "macos"]`;
+    /// elsewhere it is skipped along with its dependencies. Empty means all.
+    #[serde(default, deserialize_with = "platform::deserialize_platforms")]
+    pub platforms: Vec<String>,
+    /// Run the task only when this shell command exits 0, e.g.
+    /// `"test -f Cargo.lock"`. Otherwise it is skipped, which counts as
+    /// success for its dependents.
+    pub run_if: Option<String>,
+    /// Skip the task when this shell command exits 0; the opposite of
+    /// `run_if`.
+    pub skip_if: Option<String>,
+    /// Commands that, when all exit 0, mean the task is up to date and can
+    /// be skipped, e.g. `["test -f dist/bundle.js"]`. They run in the
+    /// task's directory. With `sources`, those have to be unchanged too.
+    #[serde(default)]
+    pub status: Vec<String>,
+    /// Report a failure as a warning and carry on with the run, as for
+    /// cleanup like `rm -rf dist`.
+    #[serde(default)]
+    pub ignore_errors: bool,
+    /// Exit codes reported as "skipped" rather than failed, e.g. a linter
+    /// exiting 1 when there is nothing to check.
+    #[serde(default)]
+    pub skip_codes: Vec<i32>,
+    /// Makes the task cacheable: when this renders to the same value as on
+    /// the last successful run, the task is skipped and its output replayed.
+    /// Accepts `{{flags.*}}`, `{{VAR}}` and `$VAR`, e.g. `"{{GIT_SHA}}-{{flags.profile}}"`.
+    pub cache_key: Option<String>,
+    /// Keep the task's trimmed stdout as this variable, e.g. `"GIT_SHA"`,
+    /// for the tasks that run after it in the same invocation: `$GIT_SHA`
+    /// or `{{GIT_SHA}}` in their commands and in their environment.
+    pub capture: Option<String>,
+    /// `false` starts the command from an empty environment instead of the
+    /// runner's; only `env_import` and the Taskfile's own `[env]` reach it.
+    pub env_inherit: Option<bool>,
+    /// `false` lets a command of several lines go on past a failing line,
+    /// instead of starting it with `set -e` or its shell's equivalent.
+    pub errexit: Option<bool>,
+    /// Process environment variables passed through when `env_inherit` is
+    /// `false`, e.g. `["PATH", "HOME"]`.
+    #[serde(default)]
+    pub env_import: Vec<String>,
+    /// Environment variables the task needs, e.g. `["AWS_PROFILE"]`,
+    /// including ones only the program itself reads. A run that includes
+    /// the task fails before anything starts if any is unset or empty.
+    #[serde(default)]
+    pub requires_env: Vec<String>,
+    /// What each environment variable the task reads is for, e.g.
+    /// `AWS_REGION = "Target region"`, for `task describe` and
+    /// `task validate`.
+    #[serde(default)]
+    pub env_docs: IndexMap<String, String>,
+    /// Programs the task needs on `PATH`, checked before it runs. Entries
+    /// can carry an `install` command for `task bootstrap`.
+    #[serde(default)]
+    pub requires: Vec<Requirement>,
+    /// Write a provenance receipt under `.taskfile/receipts` after each run.
+    #[serde(default)]
+    pub receipt: bool,
+    /// Files the task reads, as globs relative to the Taskfile (`src/**/*.rs`,
+    /// or a directory name for everything in it).
+    #[serde(default)]
+    pub sources: Vec<String>,
+    /// Files the task produces, as globs like `sources`.
+    #[serde(default)]
+    pub outputs: Vec<String>,
+    /// Permissions set on the files matching `outputs` once the task
+    /// succeeds, in octal like `"600"`. Unix only.
+    #[serde(default, deserialize_with = "mode::deserialize_opt")]
+    pub output_mode: Option<u32>,
+    /// Permission bits the task's processes leave off the files they create,
+    /// in octal like `"077"`. Unix only.
+    #[serde(default, deserialize_with = "mode::deserialize_umask")]
+    pub umask: Option<u32>,
+    /// How `sources` are compared with the last successful run: by
+    /// contents (`"checksum"`, the default) or by modification time
+    /// (`"timestamp"`).
+    #[serde(default)]
+    pub method: CacheMethod,
+    /// Run in a temporary copy of `sources` and copy `outputs` back on
+    /// success, so the task cannot clobber files other tasks are using.
+    #[serde(default)]
+    pub isolate_workdir: bool,
+    /// Directory the commands run in, relative to the Taskfile (or to the
+    /// temporary workdir, when there is one). Accepts `{{var}}`.
+    pub cwd: Option<String>,
+    /// The task is meant to reach outside the project, e.g. to install into
+    /// `~/.local/bin`: no warning for absolute paths in its commands or a
+    /// `cwd` outside the Taskfile's directory.
+    #[serde(default)]
+    pub allow_outside: bool,
+    /// Run in a temporary worktree of this ref (e.g. a release tag); flags
+    /// can be used, as in `"v{{ flags.tag }}"`. `outputs` are copied back.
+    pub git_ref: Option<String>,
+    /// Refuse to run while tracked files have uncommitted changes.
+    #[serde(default)]
+    pub require_clean_git: bool,
+    /// Run the command through a shell so pipes, `&&`, globs and quoting
+    /// work, instead of splitting it on whitespace: `true` for `sh -c`
+    /// (`cmd /C` on Windows), or a shell by name such as `"bash"` or `"pwsh"`.
+    pub shell: Option<ShellSetting>,
+    /// Manifests a release task bumps; defaults to whichever of `Cargo.toml`
+    /// and `package.json` exist. The first one holds the current version.
+    #[serde(default)]
+    pub version_files: Vec<String>,
+    /// Tag a release task creates, defaulting to `"v{{ release.version }}"`.
+    pub tag: Option<String>,
+    /// Add an entry to the changelog after each successful run.
+    pub changelog: Option<ChangelogConfig>,
+    /// Run `docker compose` for a service before the commands, waiting for
+    /// it to become healthy on `up`.
+    pub compose: Option<ComposeConfig>,
+    /// Run the commands as a Kubernetes Job in this image instead of
+    /// locally (experimental).
+    pub kubernetes: Option<KubernetesConfig>,
+    /// Sync `sources` to the `[remote_builder]`, run the commands there and
+    /// sync `outputs` back.
+    #[serde(default)]
+    pub remote_build: bool,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TaskKind {
+    #[default]
+    Command,
+    Release,
+}
+
+/// How a task's exit code is reported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitOutcome {
+    Success,
+    Skipped,
+    Failed,
+}
+
+impl Task {
+    /// The commands to run in order, each with the alternatives tried when
+    /// its program is not installed. `cmd_fallbacks` only applies to `cmd`.
+    pub fn steps(&self) -> Vec<Vec<&String>> {
+        if self.cmds.is_empty() {
+            vec![self
+                .cmd
+                .current()
+                .into_iter()
+                .chain(&self.cmd_fallbacks)
+                .collect()]
+        } else {
+            self.cmds.iter().map(|cmd| vec![cmd]).collect()
+        }
+    }
+
+    /// The program and arguments `script` runs with, its path still to be
+    /// added: `interpreter`, else the shell the task runs `cmd` through,
+    /// else the platform's. `default` is the Taskfile's top-level `shell`.
+    pub fn interpreter(
+        &self,
+        default: Option<ShellSetting>,
+    ) -> Result<Vec<String>, command::SplitError> {
+        if let Some(interpreter) = &self.interpreter {
+            return command::split(interpreter);
+        }
+        Ok(
+            match self.shell_kind(default).unwrap_or_else(ShellKind::platform) {
+                ShellKind::Cmd => vec!["cmd".to_string(), "/C".to_string()],
+                kind => vec![kind.binary().to_string()],
+            },
+        )
+    }
+
+    /// The names of the tasks in `depends_on`, in order.
+    pub fn dependency_names(&self) -> impl Iterator<Item = &String> {
+        self.depends_on.iter().flatten().map(|dep| &dep.task)
+    }
+
+    /// The variables in `env_docs`, then those in `requires_env` without
+    /// docs.
+    pub fn documented_env(&self) -> impl Iterator<Item = &String> {
+        self.env_docs.keys().chain(
+            self.requires_env
+                .iter()
+                .filter(|key| !self.env_docs.contains_key(*key)),
+        )
+    }
+
+    /// The shell the command runs through, if any; `default` is the
+    /// Taskfile's top-level `shell`.
+    pub fn shell_kind(&self, default: Option<ShellSetting>) -> Option<ShellKind> {
+        self.shell.or(default).and_then(ShellSetting::kind)
+    }
+
+    /// Whether `platforms` includes this OS and `cmd` has a variant for it.
+    pub fn runs_here(&self) -> bool {
+        self.not_here().is_none()
+    }
+
+    /// Why the task is skipped on this OS, as the note shown for it:
+    /// `only for macos`, or `no command for linux` when `cmd` is a table
+    /// without a variant that applies.
+    pub fn not_here(&self) -> Option<String> {
+        if !self.platforms.is_empty() && !self.platforms.iter().any(|p| platform::is_current(p)) {
+            return Some(format!("only for {}", self.platforms.join(", ")));
+        }
+        self.cmd
+            .current()
+            .is_none()
+            .then(|| format!("no command for {}", std::env::consts::OS))
+    }
+
+    pub fn inherits_env(&self) -> bool {
+        self.env_inherit.unwrap_or(true)
+    }
+
+    pub fn errexit(&self) -> bool {
+        self.errexit.unwrap_or(true)
+    }
+
+    /// Maps an exit code to an outcome using `success_codes` and
+    /// `skip_codes`. `None` (killed by a signal) is always a failure.
+    pub fn exit_outcome(&self, code: Option<i32>) -> ExitOutcome {
+        let Some(code) = code else {
+            return ExitOutcome::Failed;
+        };
+        let success = match &self.success_codes {
+            Some(codes) => codes.contains(&code),
+            None => code == 0,
+        };
+        if success {
+            ExitOutcome::Success
+        } else if self.skip_codes.contains(&code) {
+            ExitOutcome::Skipped
+        } else {
+            ExitOutcome::Failed
+        }
+    }
+}
+
+impl TaskFile {
+    pub fn parse(contents: &str) -> Result<Self, toml::de::Error> {
+        toml::from_str(contents)
+    }
+
+    /// The shell for tasks that do not choose one: top-level `shell`, or
+    /// `[settings] shell`.
+    pub fn default_shell(&self) -> Option<ShellSetting> {
+        self.shell.or(self.settings.shell)
+    }
+
+    /// Reads the Taskfile at `path` and merges in everything it includes.
+    pub fn load(path: &Path) -> Result<(Self, Resolved), Box<dyn std::error::Error>> {
+        let contents = fs::read_to_string(path)?;
+        let mut taskfile = Self::parse(&contents)?;
+        if taskfile.shell.is_some() && taskfile.settings.shell.is_some() {
+            return Err(format!(
+                "{}: set the default shell either at the top level or in [settings], not both",
+                path.display()
+            )
+            .into());
+        }
+        let resolved = include::resolve(&mut taskfile, path)?;
+        Ok((taskfile, resolved))
+    }
+
+    /// `task_name` and every task it depends on, directly or transitively.
+    pub fn dependency_closure(&self, task_name: &str) -> HashSet<String> {
+        let mut seen = HashSet::new();
+        let mut pending = vec![task_name.to_string()];
+        while let Some(name) = pending.pop() {
+            if !seen.insert(name.clone()) {
+                continue;
+            }
+            if let Some(task) = self.tasks.get(&name) {
+                pending.extend(task.dependency_names().cloned());
+            }
+        }
+        seen
+    }
+
+    /// A chain of dependencies leading from `root` back to a task already on
+    /// it, e.g. `["a", "b", "a"]`, if there is one. Unknown tasks count as
+    /// having no dependencies.
+    pub fn dependency_cycle(&self, root: &str) -> Option<Vec<String>> {
+        fn visit(
+            taskfile: &TaskFile,
+            name: &str,
+            path: &mut Vec<String>,
+            done: &mut HashSet<String>,
+        ) -> Option<Vec<String>> {
+            if let Some(start) = path.iter().position(|n| n == name) {
+                let mut cycle = path[start..].to_vec();
+                cycle.push(name.to_string());
+                return Some(cycle);
+            }
+            if done.contains(name) {
+                return None;
+            }
+            path.push(name.to_string());
+            let deps = taskfile.tasks.get(name).into_iter();
+            for dep in deps.flat_map(Task::dependency_names) {
+                if let Some(cycle) = visit(taskfile, dep, path, done) {
+                    return Some(cycle);
+                }
+            }
+            path.pop();
+            done.insert(name.to_string());
+            None
+        }
+        visit(self, root, &mut Vec::new(), &mut HashSet::new())
+    }
+
+    /// The tasks in `root`'s dependency graph that have to run again when
+    /// `changed` did: those tasks themselves and everything in the graph
+    /// that depends on one of them, directly or transitively.
+    pub fn affected(&self, root: &str, changed: &HashSet<String>) -> HashSet<String> {
+        let graph = self.dependency_closure(root);
+        let mut affected: HashSet<String> = changed
+            .iter()
+            .filter(|name| graph.contains(*name))
+            .cloned()
+            .collect();
+        loop {
+            let more: Vec<String> = graph
+                .iter()
+                .filter(|name| !affected.contains(*name))
+                .filter(|name| {
+                    self.tasks
+                        .get(*name)
+                        .is_some_and(|t| t.dependency_names().any(|dep| affected.contains(dep)))
+                })
+                .cloned()
+                .collect();
+            if more.is_empty() {
+                return affected;
+            }
+            affected.extend(more);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exit_outcome() {
+        let task = Task {
+            success_codes: Some(vec![0, 2]),
+            skip_codes: vec![1],
+            ..Default::default()
+        };
+        assert_eq!(task.exit_outcome(Some(2)), ExitOutcome::Success);
+        assert_eq!(task.exit_outcome(Some(1)), ExitOutcome::Skipped);
+        assert_eq!(task.exit_outcome(Some(3)), ExitOutcome::Failed);
+        assert_eq!(task.exit_outcome(None), ExitOutcome::Failed);
+        assert_eq!(Task::default().exit_outcome(Some(0)), ExitOutcome::Success);
+    }
+
+    #[test]
+    fn test_affected() {
+        let taskfile = TaskFile::parse(
+            "[tasks.gen]\ncmd = \"gen\"\n[tasks.lib]\ncmd = \"lib\"\ndepends_on = [\"gen\"]\n[tasks.docs]\ncmd = \"docs\"\n[tasks.app]\ncmd = \"app\"\ndepends_on = [\"lib\", \"docs\"]\n[tasks.other]\ncmd = \"other\"\ndepends_on = [\"gen\"]\n",
+        )
+        .unwrap();
+        let set = |names: &[&str]| -> HashSet<String> {
+            names.iter().map(|name| name.to_string()).collect()
+        };
+
+        assert_eq!(
+            taskfile.affected("app", &set(&["gen"])),
+            set(&["gen", "lib", "app"])
+        );
+        assert_eq!(
+            taskfile.affected("app", &set(&["docs"])),
+            set(&["docs", "app"])
+        );
+        assert_eq!(taskfile.affected("lib", &set(&["docs"])), set(&[]));
+    }
+
+    #[test]
+    fn test_parse_dependencies() {
+        let taskfile = TaskFile::parse(
+            "[tasks.build]\ncmd = \"build\"\ndepends_on = [\"fmt\", { task = \"lint\", optional = true }, { task = \"gen\" }]\n",
+        )
+        .unwrap();
+        let build = &taskfile.tasks["build"];
+        assert_eq!(
+            build.depends_on.as_deref().unwrap(),
+            [
+                Dependency::from("fmt"),
+                Dependency {
+                    task: "lint".to_string(),
+                    optional: true
+                },
+                Dependency::from("gen"),
+            ]
+        );
+        assert_eq!(
+            build.dependency_names().collect::<Vec<_>>(),
+            ["fmt", "lint", "gen"]
+        );
+        assert_eq!(
+            build.depends_on.as_ref().unwrap()[1].to_string(),
+            "lint (optional)"
+        );
+        assert!(TaskFile::parse(
+            "[tasks.build]\ncmd = \"b\"\ndepends_on = [{ name = \"lint\" }]\n"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_dependency_cycle() {
+        let taskfile = TaskFile::parse(
+            "[tasks.a]\ncmd = \"a\"\ndepends_on = [\"b\", \"c\"]\n[tasks.b]\ncmd = \"b\"\ndepends_on = [\"c\"]\n[tasks.c]\ncmd = \"c\"\n[tasks.x]\ncmd = \"x\"\ndepends_on = [\"a\", \"y\"]\n[tasks.y]\ncmd = \"y\"\ndepends_on = [\"z\"]\n[tasks.z]\ncmd = \"z\"\ndepends_on = [\"y\"]\n",
+        )
+        .unwrap();
+        assert_eq!(taskfile.dependency_cycle("a"), None);
+        assert_eq!(taskfile.dependency_cycle("x").unwrap(), ["y", "z", "y"]);
+    }
+
+    #[test]
+    fn test_interpreter() {
+        let taskfile = TaskFile::parse(
+            "[tasks.report]\ninterpreter = \"python3 -u\"\nscript = \"\"\"\nimport sys\nprint(sys.argv[1:])\n\"\"\"\n[tasks.setup]\nshell = \"bash\"\nscript = \"echo hi\"\n[tasks.plain]\nscript = \"echo hi\"\n",
+        )
+        .unwrap();
+        let report = &taskfile.tasks["report"];
+        assert_eq!(
+            report.script.as_deref(),
+            Some("import sys\nprint(sys.argv[1:])\n")
+        );
+        assert_eq!(report.interpreter(None).unwrap(), ["python3", "-u"]);
+        assert_eq!(taskfile.tasks["setup"].interpreter(None).unwrap(), ["bash"]);
+        let plain = &taskfile.tasks["plain"];
+        assert_eq!(
+            plain
+                .interpreter(Some(ShellSetting::Named(ShellKind::Zsh)))
+                .unwrap(),
+            ["zsh"]
+        );
+        let platform = if cfg!(windows) { "cmd" } else { "sh" };
+        assert_eq!(plain.interpreter(None).unwrap()[0], platform);
+    }
+
+    #[test]
+    fn test_documented_env() {
+        let taskfile = TaskFile::parse(
+            "[tasks.deploy]\ncmd = \"deploy\"\nrequires_env = [\"AWS_PROFILE\", \"DATABASE_URL\"]\n[tasks.deploy.env_docs]\nAWS_REGION = \"Target region\"\nAWS_PROFILE = \"Credentials profile\"\n",
+        )
+        .unwrap();
+        let task = &taskfile.tasks["deploy"];
+        assert_eq!(task.env_docs["AWS_REGION"], "Target region");
+        let documented: Vec<&String> = task.documented_env().collect();
+        assert_eq!(documented, ["AWS_REGION", "AWS_PROFILE", "DATABASE_URL"]);
+    }
+}
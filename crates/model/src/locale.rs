@@ -0,0 +1,75 @@
+use indexmap::IndexMap;
+use serde::Deserialize;
+use std::env;
+
+/// Environment variable choosing the language of task descriptions, e.g.
+/// `de`. `--lang` takes precedence.
+pub const LANG_VAR: &str = "TASKFILE_LANG";
+
+/// A task's `desc`: one string for everyone, or one per language
+/// (`desc.en`, `desc.de`, ...).
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum Desc {
+    Plain(String),
+    Localized(IndexMap<String, String>),
+}
+
+impl From<&str> for Desc {
+    fn from(desc: &str) -> Self {
+        Desc::Plain(desc.to_string())
+    }
+}
+
+impl Desc {
+    /// The description for `lang`. A regional tag such as `de-AT` or `de_AT`
+    /// falls back to `de`, then to the `default` entry, then to `en`, then
+    /// to whichever language is listed first.
+    pub fn get(&self, lang: Option<&str>) -> &str {
+        let texts = match self {
+            Desc::Plain(desc) => return desc,
+            Desc::Localized(texts) => texts,
+        };
+        let lang = lang.map(str::trim).filter(|lang| !lang.is_empty());
+        let base = lang.and_then(|lang| lang.split(['-', '_']).next());
+        [lang, base, Some("default"), Some("en")]
+            .into_iter()
+            .flatten()
+            .find_map(|key| texts.get(key))
+            .or_else(|| texts.values().next())
+            .map(String::as_str)
+            .unwrap_or("")
+    }
+}
+
+/// The language picked by `--lang`, or else by `TASKFILE_LANG`.
+pub fn selected(flag: Option<&str>) -> Option<String> {
+    flag.map(str::to_string)
+        .or_else(|| env::var(LANG_VAR).ok())
+        .filter(|lang| !lang.trim().is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_desc_language_fallback() {
+        let desc: Desc = toml::from_str::<IndexMap<String, Desc>>(
+            "desc = { de = \"Bauen\", en = \"Build\", fr = \"Construire\" }",
+        )
+        .unwrap()
+        .swap_remove("desc")
+        .unwrap();
+
+        assert_eq!(desc.get(Some("de")), "Bauen");
+        assert_eq!(desc.get(Some("de-AT")), "Bauen");
+        assert_eq!(desc.get(Some("fr_CA")), "Construire");
+        assert_eq!(desc.get(Some("ja")), "Build");
+        assert_eq!(desc.get(None), "Build");
+        assert_eq!(Desc::from("Build").get(Some("de")), "Build");
+
+        let desc = Desc::Localized(IndexMap::from([("de".to_string(), "Bauen".to_string())]));
+        assert_eq!(desc.get(Some("ja")), "Bauen");
+    }
+}
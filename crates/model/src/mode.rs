@@ -0,0 +1,80 @@
+use serde::{Deserialize, Deserializer};
+
+/// Parses Unix permission bits written in octal, as for `chmod` or
+/// `umask`: `"600"`, `"0640"` or `"0o755"`. Setuid, setgid and sticky bits
+/// are allowed, nothing above them.
+pub fn parse_mode(input: &str) -> Result<u32, String> {
+    let input = input.trim();
+    let digits = input.strip_prefix("0o").unwrap_or(input);
+    if digits.is_empty() || !digits.chars().all(|c| ('0'..='7').contains(&c)) {
+        return Err(format!(
+            "invalid mode '{}' (expected octal digits, e.g. \"640\")",
+            input
+        ));
+    }
+    match u32::from_str_radix(digits, 8) {
+        Ok(mode) if mode <= 0o7777 => Ok(mode),
+        _ => Err(format!("mode '{}' is larger than 7777", input)),
+    }
+}
+
+/// Like `parse_mode`, for a `umask`, which only covers the permission bits.
+pub fn parse_umask(input: &str) -> Result<u32, String> {
+    let mask = parse_mode(input)?;
+    if mask > 0o777 {
+        return Err(format!("umask '{}' is larger than 777", input.trim()));
+    }
+    Ok(mask)
+}
+
+/// `#[serde(deserialize_with)]` for an optional `parse_mode` string.
+pub fn deserialize_opt<'de, D>(deserializer: D) -> Result<Option<u32>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match Option::<String>::deserialize(deserializer)? {
+        Some(raw) => parse_mode(&raw).map(Some).map_err(serde::de::Error::custom),
+        None => Ok(None),
+    }
+}
+
+/// `#[serde(deserialize_with)]` for an optional `parse_umask` string.
+pub fn deserialize_umask<'de, D>(deserializer: D) -> Result<Option<u32>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match Option::<String>::deserialize(deserializer)? {
+        Some(raw) => parse_umask(&raw)
+            .map(Some)
+            .map_err(serde::de::Error::custom),
+        None => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TaskFile;
+
+    #[test]
+    fn test_parse_mode() {
+        assert_eq!(parse_mode("600"), Ok(0o600));
+        assert_eq!(parse_mode("0640"), Ok(0o640));
+        assert_eq!(parse_mode("0o4755"), Ok(0o4755));
+        assert!(parse_mode("800").is_err());
+        assert!(parse_mode("rw-").is_err());
+        assert!(parse_mode("").is_err());
+        assert!(parse_mode("17777").is_err());
+        assert_eq!(parse_umask("027"), Ok(0o027));
+        assert!(parse_umask("1022").is_err());
+
+        let taskfile = TaskFile::parse(
+            "[tasks.certs]\ncmd = \"gen-certs\"\numask = \"077\"\noutputs = [\"certs/*.key\"]\noutput_mode = \"0400\"\n",
+        )
+        .unwrap();
+        let certs = &taskfile.tasks["certs"];
+        assert_eq!(certs.umask, Some(0o077));
+        assert_eq!(certs.output_mode, Some(0o400));
+        assert!(TaskFile::parse("[tasks.a]\ncmd = \"a\"\numask = \"0o2000\"\n").is_err());
+    }
+}
@@ -0,0 +1,111 @@
+use crate::locale::Desc;
+use serde::Deserialize;
+use std::collections::HashSet;
+
+/// A `[pipelines.<name>]` entry, run with `task pipeline <name>`: stages
+/// one after another, the tasks within a stage concurrently, e.g.
+/// `stages = [["lint", "typecheck"], ["test"], ["build"]]`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Pipeline {
+    pub desc: Option<Desc>,
+    pub stages: Vec<Stage>,
+}
+
+/// A stage: a list of task names, or a table such as
+/// `{ tasks = ["deploy"], approval = true }`.
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+#[serde(from = "StageSpec")]
+pub struct Stage {
+    pub tasks: Vec<String>,
+    /// Ask before the stage starts, unless `--approve` is passed.
+    pub approval: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StageSpec {
+    Tasks(Vec<String>),
+    Table(StageTable),
+}
+
+/// A stage's table form. `deny_unknown_fields` does not reach an enum's
+/// variants, so it gets a struct of its own to catch typos like `aproval`.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct StageTable {
+    tasks: Vec<String>,
+    #[serde(default)]
+    approval: bool,
+}
+
+impl From<StageSpec> for Stage {
+    fn from(spec: StageSpec) -> Self {
+        match spec {
+            StageSpec::Tasks(tasks) => Stage {
+                tasks,
+                approval: false,
+            },
+            StageSpec::Table(StageTable { tasks, approval }) => Stage { tasks, approval },
+        }
+    }
+}
+
+impl From<Vec<String>> for Stage {
+    fn from(tasks: Vec<String>) -> Self {
+        Stage {
+            tasks,
+            approval: false,
+        }
+    }
+}
+
+impl Pipeline {
+    /// Every task the stages name, in order, each once.
+    pub fn tasks(&self) -> Vec<&String> {
+        let mut seen = HashSet::new();
+        self.stages
+            .iter()
+            .flat_map(|stage| &stage.tasks)
+            .filter(|name| seen.insert(*name))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TaskFile;
+
+    #[test]
+    fn test_parse_pipeline() {
+        let taskfile = TaskFile::parse(
+            "[pipelines.ci]\ndesc = \"What CI runs\"\nstages = [[\"lint\", \"typecheck\"], [\"test\"], [\"lint\", \"build\"]]\n[tasks]\n",
+        )
+        .unwrap();
+        let ci = &taskfile.pipelines["ci"];
+        assert_eq!(ci.desc.as_ref().unwrap().get(None), "What CI runs");
+        assert_eq!(ci.stages.len(), 3);
+        assert_eq!(ci.tasks(), ["lint", "typecheck", "test", "build"]);
+        assert!(!ci.stages[0].approval);
+
+        let taskfile = TaskFile::parse(
+            "[pipelines.release]\nstages = [[\"test\"], { tasks = [\"deploy\"], approval = true }]\n[tasks]\n",
+        )
+        .unwrap();
+        let stages = &taskfile.pipelines["release"].stages;
+        assert_eq!(stages[0], Stage::from(vec!["test".to_string()]));
+        assert_eq!(
+            stages[1],
+            Stage {
+                tasks: vec!["deploy".to_string()],
+                approval: true
+            }
+        );
+        assert!(TaskFile::parse("[pipelines.ci]\nsteps = []\n[tasks]\n").is_err());
+        assert!(TaskFile::parse(
+            "[pipelines.ci]\nstages = [{ tasks = [\"deploy\"], aproval = true }]\n[tasks]\n"
+        )
+        .is_err());
+    }
+}
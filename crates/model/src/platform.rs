@@ -0,0 +1,171 @@
+use serde::{Deserialize, Deserializer};
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt;
+
+/// Names `platforms` and `cmd.<platform>` accept: `std::env::consts::OS`
+/// values, plus `unix` for any of the Unix-likes.
+const KNOWN: &[&str] = &[
+    "linux", "macos", "windows", "unix", "freebsd", "netbsd", "openbsd", "android", "ios",
+];
+
+/// Whether `name` covers the OS this runner was built for.
+pub fn is_current(name: &str) -> bool {
+    name == env::consts::OS || (name == "unix" && cfg!(unix))
+}
+
+/// `task.cmd`, either a plain command or a table of per-platform variants
+/// such as `cmd = { default = "rm -rf dist", windows = "rmdir /s /q dist" }`.
+/// The table is kept as written, so one Taskfile reads the same everywhere,
+/// and the runner picks the variant for the OS it runs on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Cmd {
+    Plain(String),
+    Variants(BTreeMap<String, String>),
+}
+
+impl Default for Cmd {
+    fn default() -> Self {
+        Cmd::Plain(String::new())
+    }
+}
+
+impl Cmd {
+    /// The command for this OS: its own entry first, then `unix`, then
+    /// `default`. `None` when a table has none of them.
+    pub fn current(&self) -> Option<&String> {
+        self.pick(env::consts::OS, cfg!(unix))
+    }
+
+    fn pick(&self, os: &str, unix: bool) -> Option<&String> {
+        match self {
+            Cmd::Plain(cmd) => Some(cmd),
+            Cmd::Variants(variants) => variants
+                .get(os)
+                .or_else(|| unix.then(|| variants.get("unix")).flatten())
+                .or_else(|| variants.get("default")),
+        }
+    }
+
+    /// Whether no command is set at all, neither plain nor for any platform.
+    pub fn is_empty(&self) -> bool {
+        match self {
+            Cmd::Plain(cmd) => cmd.is_empty(),
+            Cmd::Variants(variants) => variants.is_empty(),
+        }
+    }
+}
+
+impl From<String> for Cmd {
+    fn from(cmd: String) -> Self {
+        Cmd::Plain(cmd)
+    }
+}
+
+impl From<&str> for Cmd {
+    fn from(cmd: &str) -> Self {
+        Cmd::Plain(cmd.to_string())
+    }
+}
+
+impl PartialEq<&str> for Cmd {
+    fn eq(&self, other: &&str) -> bool {
+        matches!(self, Cmd::Plain(cmd) if cmd == other)
+    }
+}
+
+/// A plain command as written; a table as its variants, `windows: ...`,
+/// separated by ` | `.
+impl fmt::Display for Cmd {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Cmd::Plain(cmd) => f.write_str(cmd),
+            Cmd::Variants(variants) => {
+                let shown: Vec<String> = variants
+                    .iter()
+                    .map(|(platform, cmd)| format!("{}: {}", platform, cmd))
+                    .collect();
+                f.write_str(&shown.join(" | "))
+            }
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Cmd {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Raw {
+            Plain(String),
+            Variants(BTreeMap<String, String>),
+        }
+
+        let variants = match Raw::deserialize(deserializer)? {
+            Raw::Plain(cmd) => return Ok(Cmd::Plain(cmd)),
+            Raw::Variants(variants) => variants,
+        };
+        if let Some(unknown) = variants
+            .keys()
+            .find(|key| *key != "default" && !KNOWN.contains(&key.as_str()))
+        {
+            return Err(serde::de::Error::custom(format!(
+                "unknown platform '{}' in cmd (expected default or one of: {})",
+                unknown,
+                KNOWN.join(", ")
+            )));
+        }
+        Ok(Cmd::Variants(variants))
+    }
+}
+
+/// `task.platforms`, rejecting names that would never match.
+pub fn deserialize_platforms<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let platforms = Vec::<String>::deserialize(deserializer)?;
+    if let Some(unknown) = platforms.iter().find(|p| !KNOWN.contains(&p.as_str())) {
+        return Err(serde::de::Error::custom(format!(
+            "unknown platform '{}' (expected one of: {})",
+            unknown,
+            KNOWN.join(", ")
+        )));
+    }
+    Ok(platforms)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pick_variant() {
+        let cmd: Cmd = toml::from_str::<BTreeMap<String, Cmd>>(
+            r#"cmd = { default = "rm -rf dist", windows = "rmdir /s /q dist", unix = "rm -r dist" }"#,
+        )
+        .unwrap()
+        .remove("cmd")
+        .unwrap();
+        let pick = |os, unix| cmd.pick(os, unix).map(String::as_str);
+        assert_eq!(pick("windows", false), Some("rmdir /s /q dist"));
+        assert_eq!(pick("macos", true), Some("rm -r dist"));
+        assert_eq!(pick("wasi", false), Some("rm -rf dist"));
+        assert_eq!(
+            cmd.to_string(),
+            "default: rm -rf dist | unix: rm -r dist | windows: rmdir /s /q dist"
+        );
+
+        let windows_only = Cmd::Variants(BTreeMap::from([(
+            "windows".to_string(),
+            "rmdir /s /q dist".to_string(),
+        )]));
+        assert_eq!(windows_only.pick("linux", true), None);
+        assert!(!windows_only.is_empty());
+        assert_eq!(Cmd::from("make").pick("linux", true).unwrap(), "make");
+        assert!(Cmd::default().is_empty());
+        assert!(is_current(env::consts::OS));
+    }
+}
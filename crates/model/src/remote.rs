@@ -0,0 +1,95 @@
+use serde::Deserialize;
+
+/// The Taskfile's `[remote_builder]` table: the machine tasks with
+/// `remote_build = true` run on, reached over SSH.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RemoteBuilder {
+    /// SSH destination, e.g. `"ci@builder.internal"`; `~/.ssh/config`
+    /// aliases work.
+    pub host: String,
+    /// Bastion to jump through (`ssh -J`).
+    pub jump: Option<String>,
+    /// Directory on the builder that sources are synced to, relative to the
+    /// remote home unless absolute. Defaults to `taskfile/<project dir>`.
+    pub dir: Option<String>,
+}
+
+impl RemoteBuilder {
+    /// Options that go before the destination in every `ssh` call.
+    pub fn ssh_options(&self) -> Vec<String> {
+        let mut args = vec!["-o".to_string(), "BatchMode=yes".to_string()];
+        if let Some(jump) = &self.jump {
+            args.extend(["-J".to_string(), jump.clone()]);
+        }
+        args
+    }
+
+    /// The remote shell `rsync -e` uses, with the same options as `ssh`.
+    pub fn rsync_shell(&self) -> String {
+        std::iter::once("ssh".to_string())
+            .chain(self.ssh_options())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    pub fn dir(&self, project: &str) -> String {
+        match &self.dir {
+            Some(dir) => dir.trim_end_matches('/').to_string(),
+            None => format!("taskfile/{}", project),
+        }
+    }
+}
+
+/// `rsync` filter arguments that pull back only the files matching
+/// `outputs`, using the same globs as the rest of the Taskfile: a bare
+/// directory covers everything inside it.
+pub fn output_filters(outputs: &[String]) -> Vec<String> {
+    let mut args = vec!["--include=*/".to_string()];
+    for pattern in outputs {
+        let pattern = pattern.trim_start_matches("./").trim_end_matches('/');
+        args.push(format!("--include=/{}", pattern));
+        args.push(format!("--include=/{}/**", pattern));
+    }
+    args.extend(["--exclude=*".to_string(), "--prune-empty-dirs".to_string()]);
+    args
+}
+
+/// `text` quoted for a POSIX shell on the remote side.
+pub fn shell_quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', r"'\''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builder_args() {
+        let builder: RemoteBuilder = toml::from_str(
+            r#"
+host = "builder"
+jump = "bastion.example.com"
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            builder.rsync_shell(),
+            "ssh -o BatchMode=yes -J bastion.example.com"
+        );
+        assert_eq!(builder.dir("app"), "taskfile/app");
+        assert_eq!(
+            output_filters(&["target/release/app".to_string(), "dist/".to_string()]),
+            vec![
+                "--include=*/",
+                "--include=/target/release/app",
+                "--include=/target/release/app/**",
+                "--include=/dist",
+                "--include=/dist/**",
+                "--exclude=*",
+                "--prune-empty-dirs",
+            ]
+        );
+        assert_eq!(shell_quote("it's"), r"'it'\''s'");
+    }
+}
@@ -0,0 +1,160 @@
+use std::env;
+use std::path::{Component, Path, PathBuf};
+
+/// Where programs live; a command may run one from here
+/// (`/usr/bin/env python3`), but its arguments are checked like any other
+/// path, so `rm -rf /etc/nginx` still counts.
+const PROGRAM_DIRS: &[&str] = &["/bin", "/lib", "/lib64", "/nix", "/opt", "/sbin", "/usr"];
+
+/// Temporary directories besides the system's own.
+const TEMP_DIRS: &[&str] = &["/tmp", "/var/tmp"];
+
+/// Devices that are safe to name anywhere: `2>/dev/null`, `/dev/stdin`.
+const DEVICES: &[&str] = &[
+    "/dev/null",
+    "/dev/zero",
+    "/dev/random",
+    "/dev/urandom",
+    "/dev/tty",
+    "/dev/stdin",
+    "/dev/stdout",
+    "/dev/stderr",
+];
+
+/// Words after which the next one is still a program: `sudo /usr/sbin/nginx`.
+const PREFIXES: &[&str] = &[
+    "!", "if", "then", "else", "elif", "do", "while", "until", "command", "env", "exec", "nohup",
+    "sudo", "time",
+];
+
+/// The absolute paths `cmd` names outside `root`, as a guess from its words:
+/// anything starting with `/`, `~`, `$HOME` or a drive letter, leaving out
+/// programs run from system directories, harmless devices and temporary
+/// directories. Each path comes back once, as written.
+pub fn outside_paths(cmd: &str, root: &Path) -> Vec<String> {
+    let temp = env::temp_dir();
+    let mut paths = Vec::new();
+    for (program, word) in words(cmd) {
+        // `--out=/srv/app` or `DEST=~/bin`.
+        let word = word.split_once('=').map_or(word, |(_, value)| value);
+        if !is_absolute(word) || paths.iter().any(|path| path == word) {
+            continue;
+        }
+        let path = normalize(Path::new(word));
+        if path.starts_with(normalize(root))
+            || path.starts_with(&temp)
+            || TEMP_DIRS.iter().any(|dir| path.starts_with(dir))
+            || DEVICES.iter().any(|device| path == Path::new(device))
+            || path.starts_with("/dev/fd")
+            || (program && PROGRAM_DIRS.iter().any(|dir| path.starts_with(dir)))
+        {
+            continue;
+        }
+        paths.push(word.to_string());
+    }
+    paths
+}
+
+/// The words of `cmd`, each with whether it names the program of a command:
+/// the first word, and the first after `;`, `|`, `&&`, a newline and the
+/// like.
+fn words(cmd: &str) -> Vec<(bool, &str)> {
+    let mut words = Vec::new();
+    let mut program = true;
+    let mut start = 0;
+    for (i, c) in cmd.char_indices().chain([(cmd.len(), '\n')]) {
+        let ends_command = ";|&()\n".contains(c);
+        if !(ends_command || c.is_whitespace() || "'\"`<>".contains(c)) {
+            continue;
+        }
+        let word = &cmd[start..i];
+        start = i + c.len_utf8();
+        if !word.is_empty() {
+            words.push((program, word));
+            // `FOO=1 /usr/bin/make` runs a program after the assignment.
+            program = program && (PREFIXES.contains(&word) || word.contains('='));
+        }
+        if ends_command {
+            program = true;
+        }
+    }
+    words
+}
+
+/// Whether `dir`, with `..` resolved, lies outside `root`.
+pub fn escapes(dir: &Path, root: &Path) -> bool {
+    !normalize(dir).starts_with(normalize(root))
+}
+
+fn is_absolute(word: &str) -> bool {
+    let home = |rest: &str| rest.is_empty() || rest.starts_with('/');
+    let bytes = word.as_bytes();
+    word.starts_with('/')
+        || word.strip_prefix('~').is_some_and(home)
+        || word.strip_prefix("$HOME").is_some_and(home)
+        || word.strip_prefix("${HOME}").is_some_and(home)
+        || (bytes.len() > 2
+            && bytes[0].is_ascii_alphabetic()
+            && bytes[1] == b':'
+            && matches!(bytes[2], b'\\' | b'/'))
+}
+
+/// `path` with `.` and `..` resolved, without touching the filesystem.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn test_outside_paths() {
+        let root = Path::new("/home/dev/project");
+        assert_eq!(
+            outside_paths(
+                "rm -rf /var/www/app ~/.cache \"$HOME/bin\" --out=/srv/app /var/www/app",
+                root
+            ),
+            ["/var/www/app", "~/.cache", "$HOME/bin", "/srv/app"]
+        );
+        assert!(outside_paths(
+            "cp /home/dev/project/a.txt /tmp/ 2>/dev/null; /usr/bin/env ls ~user s/a/b/ ./x ../y",
+            root
+        )
+        .is_empty());
+        assert!(outside_paths(
+            "if /usr/bin/test -f x; then\n  FOO=1 /opt/tool/bin/run < /dev/stdin | sudo /usr/sbin/nginx -t\nfi",
+            root
+        )
+        .is_empty());
+        assert_eq!(
+            outside_paths("rm -rf /etc/nginx && cp app /usr/local/bin/app", root),
+            ["/etc/nginx", "/usr/local/bin/app"]
+        );
+        assert_eq!(
+            outside_paths("cat /dev/sda /proc/1/environ", root),
+            ["/dev/sda", "/proc/1/environ"]
+        );
+        assert_eq!(
+            outside_paths("cat /home/dev/project/../other/secret", root),
+            ["/home/dev/project/../other/secret"]
+        );
+        assert_eq!(outside_paths("rm -rf /", root), ["/"]);
+
+        assert!(!escapes(&root.join("crates/cli"), root));
+        assert!(escapes(&root.join("../other"), root));
+        assert!(escapes(Path::new("/srv"), root));
+    }
+}
@@ -0,0 +1,28 @@
+use std::time::Duration;
+
+/// Orders tasks that are ready to run longest-first by their estimated
+/// duration, which shortens the total run when they execute in parallel.
+/// Tasks without an estimate go last; ties keep their original order.
+pub fn longest_first<T, F>(ready: &mut [T], estimate: F)
+where
+    F: Fn(&T) -> Option<Duration>,
+{
+    ready.sort_by_key(|task| std::cmp::Reverse(estimate(task)));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_longest_first() {
+        let mut ready = vec!["lint", "test", "docs", "build"];
+        longest_first(&mut ready, |name| match *name {
+            "test" => Some(Duration::from_secs(120)),
+            "build" => Some(Duration::from_secs(300)),
+            "lint" => Some(Duration::from_secs(5)),
+            _ => None,
+        });
+        assert_eq!(ready, vec!["build", "test", "lint", "docs"]);
+    }
+}
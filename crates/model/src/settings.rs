@@ -0,0 +1,73 @@
+use crate::shell::ShellSetting;
+use crate::user_config::Output;
+use serde::Deserialize;
+use std::num::NonZeroUsize;
+
+/// The `[settings]` table: behavior the Taskfile's author pins for everyone
+/// who runs it. Command-line flags still win; each user's `[defaults]` only
+/// applies where a setting is left unset. Included Taskfiles' settings are
+/// ignored.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Settings {
+    /// Default for tasks that do not set `shell` themselves; the same as the
+    /// top-level `shell`, which cannot be set as well.
+    pub shell: Option<ShellSetting>,
+    /// `"plain"` or `"spinner"`, unless `--plain` is passed.
+    pub output: Option<Output>,
+    /// Behave as if `--strict` were always passed.
+    #[serde(default)]
+    pub strict_env: bool,
+    /// Run every task's dependencies at the same time, unless the task sets
+    /// `depends_on_parallel = false`.
+    #[serde(default)]
+    pub depends_on_parallel: bool,
+    /// How many tasks may run at once, unless `--jobs` is passed.
+    pub jobs: Option<NonZeroUsize>,
+    #[serde(default)]
+    pub dotenv: Dotenv,
+    /// `false` keeps runs out of `.taskfile/history.jsonl`, leaving `task
+    /// stats` nothing to show.
+    pub history: Option<bool>,
+}
+
+/// What happens when a file listed in `[env] files` does not exist.
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Dotenv {
+    /// Skip it, e.g. for a `.env.local` only some developers have.
+    #[default]
+    Optional,
+    /// Fail before any task runs.
+    Required,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shell::ShellKind;
+    use crate::TaskFile;
+
+    #[test]
+    fn test_parse_settings() {
+        let taskfile = TaskFile::parse(
+            "[settings]\nshell = \"bash\"\noutput = \"plain\"\nstrict_env = true\ndotenv = \"required\"\ndepends_on_parallel = true\njobs = 4\nhistory = false\n[tasks]\n",
+        )
+        .unwrap();
+        let settings = &taskfile.settings;
+        assert_eq!(settings.shell, Some(ShellSetting::Named(ShellKind::Bash)));
+        assert_eq!(settings.output, Some(Output::Plain));
+        assert!(settings.strict_env);
+        assert!(settings.depends_on_parallel);
+        assert_eq!(settings.jobs, NonZeroUsize::new(4));
+        assert_eq!(settings.dotenv, Dotenv::Required);
+        assert_eq!(settings.history, Some(false));
+        assert_eq!(taskfile.default_shell(), settings.shell);
+
+        let taskfile = TaskFile::parse("shell = true\n[tasks]\n").unwrap();
+        assert_eq!(taskfile.default_shell(), Some(ShellSetting::Enabled(true)));
+        assert_eq!(taskfile.settings.dotenv, Dotenv::Optional);
+        assert!(TaskFile::parse("[settings]\nstrict = true\n[tasks]\n").is_err());
+        assert!(TaskFile::parse("[settings]\njobs = 0\n[tasks]\n").is_err());
+    }
+}
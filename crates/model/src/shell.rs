@@ -0,0 +1,203 @@
+use serde::{Deserialize, Deserializer};
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShellKind {
+    Bash,
+    Zsh,
+    Sh,
+    Pwsh,
+    PowerShell,
+    Cmd,
+    Nu,
+}
+
+/// All kinds, for parsing `shell = "..."`.
+const ALL: &[ShellKind] = &[
+    ShellKind::Bash,
+    ShellKind::Zsh,
+    ShellKind::Sh,
+    ShellKind::Pwsh,
+    ShellKind::PowerShell,
+    ShellKind::Cmd,
+    ShellKind::Nu,
+];
+
+impl ShellKind {
+    pub fn binary(self) -> &'static str {
+        match self {
+            ShellKind::Bash => "bash",
+            ShellKind::Zsh => "zsh",
+            ShellKind::Sh => "sh",
+            ShellKind::Pwsh => "pwsh",
+            ShellKind::PowerShell => "powershell",
+            ShellKind::Cmd => "cmd",
+            ShellKind::Nu => "nu",
+        }
+    }
+
+    /// The flag that makes the shell run a command string.
+    pub fn command_flag(self) -> &'static str {
+        match self {
+            ShellKind::Bash | ShellKind::Zsh | ShellKind::Sh | ShellKind::Nu => "-c",
+            ShellKind::Pwsh | ShellKind::PowerShell => "-Command",
+            ShellKind::Cmd => "/C",
+        }
+    }
+
+    /// The line that makes a script in this shell stop at its first failing
+    /// command. `cmd` has none, and `nu` stops there anyway.
+    pub fn errexit(self) -> Option<&'static str> {
+        match self {
+            ShellKind::Bash | ShellKind::Zsh | ShellKind::Sh => Some("set -e"),
+            ShellKind::Pwsh | ShellKind::PowerShell => Some("$ErrorActionPreference = 'Stop'"),
+            ShellKind::Cmd | ShellKind::Nu => None,
+        }
+    }
+
+    /// The shell `shell = true` runs commands with: `sh` on Unix, `cmd` on
+    /// Windows.
+    pub fn platform() -> Self {
+        if cfg!(windows) {
+            ShellKind::Cmd
+        } else {
+            ShellKind::Sh
+        }
+    }
+
+    pub fn is_powershell(self) -> bool {
+        matches!(self, ShellKind::Pwsh | ShellKind::PowerShell)
+    }
+
+    pub fn from_binary(name: &str) -> Option<Self> {
+        let name = name.strip_suffix(".exe").unwrap_or(name);
+        CANDIDATES
+            .iter()
+            .copied()
+            .find(|kind| kind.binary() == name)
+    }
+}
+
+/// Shells `detect_shells` looks for, in platform preference order.
+#[cfg(windows)]
+pub const CANDIDATES: &[ShellKind] = &[
+    ShellKind::Pwsh,
+    ShellKind::PowerShell,
+    ShellKind::Cmd,
+    ShellKind::Bash,
+];
+
+/// Shells `detect_shells` looks for, in platform preference order.
+#[cfg(not(windows))]
+pub const CANDIDATES: &[ShellKind] = &[
+    ShellKind::Bash,
+    ShellKind::Zsh,
+    ShellKind::Sh,
+    ShellKind::Pwsh,
+];
+
+impl fmt::Display for ShellKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.binary())
+    }
+}
+
+/// A task's `shell`: `true` for the platform shell, or a shell by name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShellSetting {
+    Enabled(bool),
+    Named(ShellKind),
+}
+
+impl ShellSetting {
+    pub fn kind(self) -> Option<ShellKind> {
+        match self {
+            ShellSetting::Enabled(true) => Some(ShellKind::platform()),
+            ShellSetting::Enabled(false) => None,
+            ShellSetting::Named(kind) => Some(kind),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for ShellSetting {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Raw {
+            Enabled(bool),
+            Named(String),
+        }
+
+        match Raw::deserialize(deserializer)? {
+            Raw::Enabled(enabled) => Ok(ShellSetting::Enabled(enabled)),
+            Raw::Named(name) => ALL
+                .iter()
+                .copied()
+                .find(|kind| kind.binary() == name)
+                .map(ShellSetting::Named)
+                .ok_or_else(|| {
+                    let known: Vec<&str> = ALL.iter().map(|kind| kind.binary()).collect();
+                    serde::de::Error::custom(format!(
+                        "unknown shell '{}' (expected true, false or one of: {})",
+                        name,
+                        known.join(", ")
+                    ))
+                }),
+        }
+    }
+}
+
+/// One entry of a task's `requires`: a program name, or a table that also
+/// gives the command `task bootstrap` runs to install it, as in
+/// `{ bin = "golangci-lint", install = "brew install golangci-lint" }`.
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum Requirement {
+    Bin(String),
+    Table {
+        bin: String,
+        install: Option<String>,
+    },
+}
+
+impl Requirement {
+    pub fn bin(&self) -> &str {
+        match self {
+            Requirement::Bin(bin) | Requirement::Table { bin, .. } => bin,
+        }
+    }
+
+    pub fn install(&self) -> Option<&str> {
+        match self {
+            Requirement::Bin(_) => None,
+            Requirement::Table { install, .. } => install.as_deref(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_from_binary() {
+        assert_eq!(ShellKind::from_binary("bash"), Some(ShellKind::Bash));
+        assert_eq!(ShellKind::from_binary("fish"), None);
+    }
+
+    #[test]
+    fn test_shell_setting() {
+        let parse = |value: &str| toml::from_str::<HashMap<String, ShellSetting>>(value);
+        assert_eq!(
+            parse("shell = \"bash\"").unwrap()["shell"],
+            ShellSetting::Named(ShellKind::Bash)
+        );
+        assert_eq!(
+            parse("shell = true").unwrap()["shell"].kind(),
+            Some(ShellKind::platform())
+        );
+        let err = parse("shell = \"fish\"").unwrap_err().to_string();
+        assert!(err.contains("unknown shell 'fish'"), "{}", err);
+    }
+}
@@ -0,0 +1,61 @@
+use indexmap::IndexMap;
+use serde::Deserialize;
+
+/// A `[tests.<name>]` entry, run by `task test-tasks`: the task runs in a
+/// copy of a fixture directory, and its exit code and output are compared
+/// with what is expected.
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct TaskTest {
+    pub task: String,
+    /// Flags and arguments as on the command line, e.g.
+    /// `["--target", "prod", "--", "-v"]`.
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// Directory, relative to the Taskfile, copied to where the task runs.
+    /// Without one it runs in a directory holding only the Taskfile.
+    pub fixture: Option<String>,
+    #[serde(default)]
+    pub exit_code: i32,
+    /// Where the output is kept, relative to the Taskfile; defaults to
+    /// `tests/snapshots/<name>.snap`.
+    pub snapshot: Option<String>,
+    /// Set for the run, on top of the environment `task test-tasks` has.
+    #[serde(default)]
+    pub env: IndexMap<String, String>,
+}
+
+impl TaskTest {
+    pub fn snapshot_path(&self, name: &str) -> String {
+        self.snapshot
+            .clone()
+            .unwrap_or_else(|| format!("tests/snapshots/{}.snap", name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::TaskFile;
+
+    #[test]
+    fn test_parse_task_test() {
+        let taskfile = TaskFile::parse(
+            "[tests.build-fails]\ntask = \"build\"\nargs = [\"--release\"]\nfixture = \"tests/broken\"\nexit_code = 1\nenv = { RUST_LOG = \"warn\" }\n[tests.lint]\ntask = \"lint\"\nsnapshot = \"snap/lint.txt\"\n[tasks]\n",
+        )
+        .unwrap();
+        let build = &taskfile.tests["build-fails"];
+        assert_eq!(build.task, "build");
+        assert_eq!(build.args, ["--release"]);
+        assert_eq!(build.fixture.as_deref(), Some("tests/broken"));
+        assert_eq!(build.exit_code, 1);
+        assert_eq!(build.env["RUST_LOG"], "warn");
+        assert_eq!(
+            build.snapshot_path("build-fails"),
+            "tests/snapshots/build-fails.snap"
+        );
+        let lint = &taskfile.tests["lint"];
+        assert_eq!(lint.exit_code, 0);
+        assert_eq!(lint.snapshot_path("lint"), "snap/lint.txt");
+        assert!(TaskFile::parse("[tests.x]\ntask = \"a\"\nexpect = 0\n[tasks]\n").is_err());
+    }
+}
@@ -0,0 +1,272 @@
+use colored::{Color, ColoredString, Colorize};
+use serde::{Deserialize, Deserializer};
+use std::env;
+
+/// Environment variable selecting a theme preset for this terminal. When set
+/// it replaces the Taskfile's `[ui]` section entirely, so a user whose font
+/// lacks the glyphs can always fall back to `ascii`.
+pub const THEME_VAR: &str = "TASKFILE_THEME";
+
+/// The `[ui]` table of a Taskfile. Unset fields come from the preset.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct UiConfig {
+    pub theme: Option<Preset>,
+    pub spinner: Option<Spinner>,
+    #[serde(default, deserialize_with = "deserialize_color_opt")]
+    pub success_color: Option<Color>,
+    #[serde(default, deserialize_with = "deserialize_color_opt")]
+    pub failure_color: Option<Color>,
+    pub borders: Option<Borders>,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Preset {
+    #[default]
+    Unicode,
+    Ascii,
+}
+
+impl Preset {
+    fn parse(name: &str) -> Result<Self, String> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "unicode" | "default" => Ok(Preset::Unicode),
+            "ascii" => Ok(Preset::Ascii),
+            other => Err(format!(
+                "unknown theme '{}' (expected unicode or ascii)",
+                other
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Spinner {
+    Dots,
+    Line,
+    Arc,
+}
+
+impl Spinner {
+    pub fn frames(self) -> &'static [&'static str] {
+        match self {
+            Spinner::Dots => &["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"],
+            Spinner::Line => &["-", "\\", "|", "/"],
+            Spinner::Arc => &["◜", "◠", "◝", "◞", "◡", "◟"],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Borders {
+    Unicode,
+    Ascii,
+}
+
+/// Box-drawing characters for `list`. Corners and joints are indexed
+/// left, middle, right.
+pub struct BorderChars {
+    pub horizontal: &'static str,
+    pub vertical: &'static str,
+    pub top: [&'static str; 3],
+    pub separator: [&'static str; 3],
+    pub bottom: [&'static str; 3],
+}
+
+impl Borders {
+    pub fn chars(self) -> BorderChars {
+        match self {
+            Borders::Unicode => BorderChars {
+                horizontal: "─",
+                vertical: "│",
+                top: ["┌", "┬", "┐"],
+                separator: ["├", "┼", "┤"],
+                bottom: ["└", "┴", "┘"],
+            },
+            Borders::Ascii => BorderChars {
+                horizontal: "-",
+                vertical: "|",
+                top: ["+", "+", "+"],
+                separator: ["+", "+", "+"],
+                bottom: ["+", "+", "+"],
+            },
+        }
+    }
+}
+
+/// Status markers printed in front of run output.
+#[derive(Debug, Clone, Copy)]
+pub struct Glyphs {
+    pub success: &'static str,
+    pub failure: &'static str,
+    pub warning: &'static str,
+    pub running: &'static str,
+    pub skipped: &'static str,
+    /// A task that exited with one of its `skip_codes`.
+    pub skip_code: &'static str,
+    pub retry: &'static str,
+    pub rule: &'static str,
+}
+
+const UNICODE_GLYPHS: Glyphs = Glyphs {
+    success: "✓",
+    failure: "✗",
+    warning: "⚠",
+    running: "▶",
+    skipped: "⤼",
+    skip_code: "⊘",
+    retry: "↻",
+    rule: "──",
+};
+
+const ASCII_GLYPHS: Glyphs = Glyphs {
+    success: "+",
+    failure: "x",
+    warning: "!",
+    running: ">",
+    skipped: "-",
+    skip_code: "o",
+    retry: "~",
+    rule: "--",
+};
+
+/// Resolved look of the runner's output.
+#[derive(Debug, Clone, Copy)]
+pub struct Theme {
+    pub spinner: Spinner,
+    pub success_color: Color,
+    pub failure_color: Color,
+    pub borders: Borders,
+    pub glyphs: Glyphs,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::preset(Preset::Unicode)
+    }
+}
+
+impl Theme {
+    pub fn preset(preset: Preset) -> Self {
+        match preset {
+            Preset::Unicode => Self {
+                spinner: Spinner::Dots,
+                success_color: Color::Green,
+                failure_color: Color::Red,
+                borders: Borders::Unicode,
+                glyphs: UNICODE_GLYPHS,
+            },
+            Preset::Ascii => Self {
+                spinner: Spinner::Line,
+                success_color: Color::Green,
+                failure_color: Color::Red,
+                borders: Borders::Ascii,
+                glyphs: ASCII_GLYPHS,
+            },
+        }
+    }
+
+    /// `$TASKFILE_THEME` when set, otherwise `config` over its preset.
+    pub fn resolve(config: Option<&UiConfig>) -> Result<Self, String> {
+        if let Ok(name) = env::var(THEME_VAR) {
+            return Preset::parse(&name)
+                .map(Self::preset)
+                .map_err(|e| format!("{}: {}", THEME_VAR, e));
+        }
+
+        let Some(config) = config else {
+            return Ok(Self::default());
+        };
+        let mut theme = Self::preset(config.theme.unwrap_or_default());
+        if let Some(spinner) = config.spinner {
+            theme.spinner = spinner;
+        }
+        if let Some(color) = config.success_color {
+            theme.success_color = color;
+        }
+        if let Some(color) = config.failure_color {
+            theme.failure_color = color;
+        }
+        if let Some(borders) = config.borders {
+            theme.borders = borders;
+        }
+        Ok(theme)
+    }
+
+    pub fn success(&self) -> ColoredString {
+        self.glyphs.success.color(self.success_color)
+    }
+
+    pub fn failure(&self) -> ColoredString {
+        self.glyphs.failure.color(self.failure_color)
+    }
+
+    pub fn warning(&self) -> ColoredString {
+        self.glyphs.warning.yellow()
+    }
+
+    pub fn skipped(&self) -> ColoredString {
+        self.glyphs.skipped.yellow()
+    }
+
+    pub fn skip_code(&self) -> ColoredString {
+        self.glyphs.skip_code.yellow()
+    }
+
+    pub fn retry(&self) -> ColoredString {
+        self.glyphs.retry.yellow()
+    }
+
+    /// `text` in the success color, e.g. a duration.
+    pub fn good(&self, text: &str) -> ColoredString {
+        text.color(self.success_color)
+    }
+
+    /// `text` in the failure color.
+    pub fn bad(&self, text: &str) -> ColoredString {
+        text.color(self.failure_color)
+    }
+}
+
+fn deserialize_color_opt<'de, D>(deserializer: D) -> Result<Option<Color>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match Option::<String>::deserialize(deserializer)? {
+        Some(name) => name.parse().map(Some).map_err(|()| {
+            serde::de::Error::custom(format!(
+                "unknown color '{}' (expected e.g. green, red, bright blue)",
+                name
+            ))
+        }),
+        None => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config_overrides_preset() {
+        let config: UiConfig = toml::from_str(
+            r#"
+            theme = "ascii"
+            success_color = "bright blue"
+            borders = "unicode"
+            "#,
+        )
+        .unwrap();
+        let theme = Theme::resolve(Some(&config)).unwrap();
+        assert_eq!(theme.spinner, Spinner::Line);
+        assert_eq!(theme.success_color, Color::BrightBlue);
+        assert_eq!(theme.failure_color, Color::Red);
+        assert_eq!(theme.borders, Borders::Unicode);
+        assert_eq!(theme.glyphs.success, "+");
+
+        assert!(toml::from_str::<UiConfig>("success_color = \"teal\"").is_err());
+    }
+}
@@ -0,0 +1,175 @@
+use crate::command;
+use crate::duration;
+use crate::ui::Spinner;
+use indexmap::IndexMap;
+use serde::Deserialize;
+use std::env;
+use std::fs;
+use std::num::NonZeroUsize;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Environment variable pointing at a config file to use instead of the
+/// default location.
+pub const CONFIG_VAR: &str = "TASKFILE_CONFIG";
+
+/// Settings that belong to the user rather than to a project, read from
+/// `~/.config/taskfile/config.toml`.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct UserConfig {
+    /// Short names for longer argument lists, e.g.
+    /// `d = "docker:up --profile dev"`.
+    #[serde(default)]
+    pub aliases: IndexMap<String, String>,
+    /// What the user would otherwise pass on every invocation.
+    #[serde(default)]
+    pub defaults: Defaults,
+}
+
+/// The `[defaults]` table. Command-line flags override each of these, and
+/// so do the Taskfile's own choices (`[settings]`, `[ui] spinner`, `bell`).
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Defaults {
+    /// Unset picks plain output under CI or without a terminal.
+    pub output: Option<Output>,
+    pub color: Option<ColorChoice>,
+    pub spinner: Option<Spinner>,
+    /// File names looked for in the current directory, first match wins.
+    /// Empty means `Taskfile.toml`.
+    #[serde(default)]
+    pub taskfiles: Vec<String>,
+    /// Like `--bell=<duration>`.
+    #[serde(default, deserialize_with = "duration::deserialize_opt")]
+    pub bell: Option<Duration>,
+    /// Like `--jobs`.
+    pub jobs: Option<NonZeroUsize>,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Output {
+    Plain,
+    Spinner,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorChoice {
+    /// Color when writing to a terminal and `NO_COLOR` is unset.
+    Auto,
+    Always,
+    Never,
+}
+
+impl UserConfig {
+    /// Reads the user config; a missing file is an empty config.
+    pub fn load() -> Result<Self, String> {
+        let Some(path) = path() else {
+            return Ok(Self::default());
+        };
+        match fs::read_to_string(&path) {
+            Ok(contents) => toml::from_str(&contents)
+                .map_err(|e| format!("Failed to parse {}: {}", path.display(), e)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(format!("Failed to read {}: {}", path.display(), e)),
+        }
+    }
+
+    /// Replaces the first positional argument with its alias, if it has one.
+    /// `takes_value` lists the options whose value is a separate argument,
+    /// so that value is not mistaken for the alias. Aliases do not expand
+    /// further, and nothing after `--` is touched.
+    pub fn expand(&self, args: &[String], takes_value: &[&str]) -> Result<Vec<String>, String> {
+        let mut i = 0;
+        while let Some(arg) = args.get(i) {
+            if arg == "--" {
+                break;
+            }
+            if !arg.starts_with('-') {
+                let Some(alias) = self.aliases.get(arg) else {
+                    break;
+                };
+                let words = command::split(alias)
+                    .map_err(|e| format!("Cannot parse alias '{}': {}", arg, e))?;
+                let mut expanded = args[..i].to_vec();
+                expanded.extend(words);
+                expanded.extend_from_slice(&args[i + 1..]);
+                return Ok(expanded);
+            }
+            i += if takes_value.contains(&arg.as_str()) {
+                2
+            } else {
+                1
+            };
+        }
+        Ok(args.to_vec())
+    }
+}
+
+/// `$TASKFILE_CONFIG`, else `config.toml` in `dir()`.
+pub fn path() -> Option<PathBuf> {
+    match env::var_os(CONFIG_VAR).filter(|value| !value.is_empty()) {
+        Some(path) => Some(PathBuf::from(path)),
+        None => dir().map(|dir| dir.join("config.toml")),
+    }
+}
+
+/// `$XDG_CONFIG_HOME/taskfile` or `~/.config/taskfile`
+/// (`%APPDATA%\taskfile` on Windows), also home to state such as the update
+/// check.
+pub fn dir() -> Option<PathBuf> {
+    let var = |name| env::var_os(name).filter(|value| !value.is_empty());
+    let base = if cfg!(windows) {
+        var("APPDATA").map(PathBuf::from)
+    } else {
+        var("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| var("HOME").map(|home| PathBuf::from(home).join(".config")))
+    };
+    base.map(|base| base.join("taskfile"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expand_alias() {
+        let config: UserConfig = toml::from_str(
+            "[aliases]\nd = \"docker:up --profile dev\"\nt = \"test -- --nocapture\"\n",
+        )
+        .unwrap();
+        let args =
+            |line: &str| -> Vec<String> { line.split_whitespace().map(String::from).collect() };
+        let expand = |line: &str| config.expand(&args(line), &["-e", "--from"]).unwrap();
+
+        assert_eq!(expand("d"), args("docker:up --profile dev"));
+        assert_eq!(
+            expand("--plain d x=1"),
+            args("--plain docker:up --profile dev x=1")
+        );
+        assert_eq!(expand("-e d d"), args("-e d docker:up --profile dev"));
+        assert_eq!(expand("t"), args("test -- --nocapture"));
+        assert_eq!(expand("build d"), args("build d"));
+        assert_eq!(expand("-- d"), args("-- d"));
+        assert!(toml::from_str::<UserConfig>("alias = {}").is_err());
+    }
+
+    #[test]
+    fn test_parse_defaults() {
+        let config: UserConfig = toml::from_str(
+            "[defaults]\noutput = \"plain\"\ncolor = \"never\"\nspinner = \"arc\"\ntaskfiles = [\"tasks.toml\"]\nbell = \"1m\"\njobs = 8\n",
+        )
+        .unwrap();
+        let defaults = config.defaults;
+        assert_eq!(defaults.output, Some(Output::Plain));
+        assert_eq!(defaults.color, Some(ColorChoice::Never));
+        assert_eq!(defaults.spinner, Some(Spinner::Arc));
+        assert_eq!(defaults.taskfiles, ["tasks.toml"]);
+        assert_eq!(defaults.bell, Some(Duration::from_secs(60)));
+        assert_eq!(defaults.jobs, NonZeroUsize::new(8));
+        assert!(toml::from_str::<UserConfig>("[defaults]\njobs = 0\n").is_err());
+    }
+}
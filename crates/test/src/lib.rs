@@ -0,0 +1,425 @@
+//! Snapshot tests for Taskfiles. A `[tests.<name>]` entry names a task, the
+//! arguments to give it and a fixture directory; the harness runs the `task`
+//! binary on a copy of the fixture, checks its exit code, and compares its
+//! output with a snapshot file, which it writes the first time.
+//!
+//! `task test-tasks` is built on this crate, and a project's own
+//! `cargo test` can drive it the same way:
+//!
+//! ```no_run
+//! use taskfile_test::{Harness, Outcome};
+//!
+//! let harness = Harness::new("task", "Taskfile.toml");
+//! for (name, test) in taskfile_test::load("Taskfile.toml".as_ref()).unwrap() {
+//!     let outcome = harness.run(&name, &test).unwrap();
+//!     assert!(!matches!(outcome, Outcome::Failed(_)), "{}: {:?}", name, outcome);
+//! }
+//! ```
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use taskfile_model::user_config::CONFIG_VAR;
+use taskfile_model::TaskFile;
+
+pub use taskfile_model::task_test::TaskTest;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    Passed,
+    /// There was no snapshot yet, or the harness updates them; the output
+    /// was written to this file.
+    Written(PathBuf),
+    /// What did not match.
+    Failed(String),
+}
+
+/// Runs tests with a `task` binary, in scratch directories under the
+/// system's temporary directory.
+#[derive(Debug, Clone)]
+pub struct Harness {
+    binary: PathBuf,
+    taskfile: PathBuf,
+    update: bool,
+}
+
+impl Harness {
+    /// `binary` is the `task` executable to test with, and `taskfile` the
+    /// Taskfile the tests belong to; fixtures and snapshots are relative to
+    /// its directory.
+    pub fn new(binary: impl Into<PathBuf>, taskfile: impl Into<PathBuf>) -> Self {
+        Self {
+            binary: binary.into(),
+            taskfile: taskfile.into(),
+            update: false,
+        }
+    }
+
+    /// Write every snapshot from this run instead of comparing with it.
+    pub fn update(mut self, update: bool) -> Self {
+        self.update = update;
+        self
+    }
+
+    fn project_dir(&self) -> &Path {
+        self.taskfile.parent().unwrap_or(Path::new(""))
+    }
+
+    /// Runs the test `name`. An error means it could not run at all, say
+    /// because its fixture is missing.
+    pub fn run(&self, name: &str, test: &TaskTest) -> Result<Outcome, String> {
+        let dir = scratch_dir()
+            .map_err(|e| format!("Failed to create a directory for test '{}': {}", name, e))?;
+        let outcome = self.run_in(&dir, name, test);
+        let _ = fs::remove_dir_all(&dir);
+        outcome
+    }
+
+    fn run_in(&self, dir: &Path, name: &str, test: &TaskTest) -> Result<Outcome, String> {
+        let project = self.project_dir();
+        if let Some(fixture) = &test.fixture {
+            let fixture = project.join(fixture);
+            if !fixture.is_dir() {
+                return Err(format!("Fixture {} is not a directory", fixture.display()));
+            }
+            copy_dir(&fixture, dir)
+                .map_err(|e| format!("Failed to copy {}: {}", fixture.display(), e))?;
+        }
+        let taskfile = dir.join("Taskfile.toml");
+        if !taskfile.exists() {
+            fs::copy(&self.taskfile, &taskfile)
+                .map_err(|e| format!("Failed to copy {}: {}", self.taskfile.display(), e))?;
+        }
+
+        // The user's config could alias the task away or pick other output,
+        // so point at one that does not exist. `run` keeps a task named like
+        // a built-in command, say `list`, from running that command instead.
+        let output = Command::new(&self.binary)
+            .args(["--plain", "run"])
+            .arg(&test.task)
+            .args(&test.args)
+            .current_dir(dir)
+            .env("NO_COLOR", "1")
+            .env("TASKFILE_NO_UPDATE_CHECK", "1")
+            .env(CONFIG_VAR, dir.join(".taskfile-test-config.toml"))
+            .envs(&test.env)
+            .stdin(Stdio::null())
+            .output()
+            .map_err(|e| format!("Failed to run {}: {}", self.binary.display(), e))?;
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let run_id = run_id(&stdout).or_else(|| run_id(&stderr));
+        let actual = snapshot(
+            &normalize(&stdout, dir, run_id),
+            &normalize(&stderr, dir, run_id),
+        );
+
+        let code = output.status.code();
+        if code != Some(test.exit_code) {
+            let got = code.map_or("a signal".to_string(), |code| code.to_string());
+            return Ok(Outcome::Failed(format!(
+                "expected exit code {}, got {}; the output was:\n{}",
+                test.exit_code,
+                got,
+                indent(&actual)
+            )));
+        }
+        let path = project.join(test.snapshot_path(name));
+        match fs::read_to_string(&path) {
+            Ok(expected) if !self.update => Ok(if expected == actual {
+                Outcome::Passed
+            } else {
+                Outcome::Failed(format!(
+                    "output differs from {}:\n{}",
+                    path.display(),
+                    diff(&expected, &actual)
+                ))
+            }),
+            Err(e) if e.kind() != io::ErrorKind::NotFound => {
+                Err(format!("Failed to read {}: {}", path.display(), e))
+            }
+            _ => {
+                if let Some(parent) = path.parent() {
+                    fs::create_dir_all(parent)
+                        .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+                }
+                fs::write(&path, actual)
+                    .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+                Ok(Outcome::Written(path))
+            }
+        }
+    }
+}
+
+/// The `[tests]` of the Taskfile at `path`, in order. Included Taskfiles are
+/// not read; their tests are ignored anyway.
+pub fn load(path: &Path) -> Result<Vec<(String, TaskTest)>, String> {
+    let contents = fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let taskfile = TaskFile::parse(&contents)
+        .map_err(|e| format!("Failed to parse {}: {}", path.display(), e))?;
+    Ok(taskfile.tests.into_iter().collect())
+}
+
+/// What a snapshot file holds: both streams, each under a header.
+pub fn snapshot(stdout: &str, stderr: &str) -> String {
+    let section = |text: &str| match text {
+        "" => String::new(),
+        text if text.ends_with('\n') => text.to_string(),
+        text => format!("{}\n", text),
+    };
+    format!(
+        "--- stdout\n{}--- stderr\n{}",
+        section(stdout),
+        section(stderr)
+    )
+}
+
+/// `output` with what changes from run to run replaced: the scratch
+/// directory by `[dir]`, the run ID by `[run-id]` and durations such as
+/// `1m 5s` or `40ms` by `[duration]`.
+pub fn normalize(output: &str, dir: &Path, run_id: Option<&str>) -> String {
+    let mut output = output.to_string();
+    let canonical = dir.canonicalize().unwrap_or_else(|_| dir.to_path_buf());
+    for dir in [canonical.as_path(), dir] {
+        output = output.replace(&*dir.to_string_lossy(), "[dir]");
+    }
+    if let Some(run_id) = run_id.filter(|id| !id.is_empty()) {
+        output = output.replace(run_id, "[run-id]");
+    }
+    mask_durations(&output)
+}
+
+fn run_id(output: &str) -> Option<&str> {
+    output
+        .lines()
+        .find_map(|line| line.strip_prefix("Run ID: "))
+        .map(str::trim)
+}
+
+fn mask_durations(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut masked = String::new();
+    let mut copied = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        let word_start = i == 0 || !(bytes[i - 1].is_ascii_alphanumeric() || bytes[i - 1] == b'.');
+        if word_start && bytes[i].is_ascii_digit() {
+            if let Some(len) = duration_len(&bytes[i..]) {
+                masked.push_str(&text[copied..i]);
+                masked.push_str("[duration]");
+                i += len;
+                copied = i;
+                continue;
+            }
+        }
+        i += 1;
+    }
+    masked.push_str(&text[copied..]);
+    masked
+}
+
+/// Length of the duration at the start of `text`, in the forms the runner
+/// prints: `40ms`, `2.3s`, `5s`, `1m 5s` or `1m 5s 20ms`.
+fn duration_len(text: &[u8]) -> Option<usize> {
+    let digits = |from: usize| {
+        text[from..]
+            .iter()
+            .take_while(|b| b.is_ascii_digit())
+            .count()
+    };
+    let mut len = None;
+    let mut i = 0;
+    loop {
+        let mut end = i + digits(i);
+        if end == i {
+            break;
+        }
+        if text.get(end) == Some(&b'.') {
+            let fraction = digits(end + 1);
+            if fraction == 0 {
+                break;
+            }
+            end += 1 + fraction;
+        }
+        let minutes = if text[end..].starts_with(b"ms") {
+            end += 2;
+            false
+        } else if let Some(unit @ (b's' | b'm')) = text.get(end) {
+            end += 1;
+            *unit == b'm'
+        } else {
+            break;
+        };
+        if text.get(end).is_some_and(|b| b.is_ascii_alphanumeric()) {
+            break;
+        }
+        // Minutes alone are not a duration the runner prints.
+        if !minutes {
+            len = Some(end);
+        }
+        if text.get(end) != Some(&b' ') {
+            break;
+        }
+        i = end + 1;
+    }
+    len
+}
+
+/// The lines of `expected` and `actual`, marked `-` where only the snapshot
+/// has them and `+` where only the new output does.
+pub fn diff(expected: &str, actual: &str) -> String {
+    let old: Vec<&str> = expected.lines().collect();
+    let new: Vec<&str> = actual.lines().collect();
+    // common[i][j]: the longest common subsequence of old[i..] and new[j..].
+    let mut common = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            common[i][j] = if old[i] == new[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+    let mut lines = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            lines.push(format!("  {}", old[i]));
+            i += 1;
+            j += 1;
+        } else if i < old.len() && (j == new.len() || common[i + 1][j] >= common[i][j + 1]) {
+            lines.push(format!("- {}", old[i]));
+            i += 1;
+        } else {
+            lines.push(format!("+ {}", new[j]));
+            j += 1;
+        }
+    }
+    lines.join("\n")
+}
+
+fn indent(text: &str) -> String {
+    text.lines()
+        .map(|line| format!("  {}", line))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn scratch_dir() -> io::Result<PathBuf> {
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+    let dir = std::env::temp_dir().join(format!(
+        "taskfile-test-{}-{}",
+        std::process::id(),
+        NEXT.fetch_add(1, Ordering::Relaxed)
+    ));
+    if dir.exists() {
+        fs::remove_dir_all(&dir)?;
+    }
+    fs::create_dir_all(&dir)?;
+    Ok(dir)
+}
+
+fn copy_dir(from: &Path, to: &Path) -> io::Result<()> {
+    fs::create_dir_all(to)?;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let target = to.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_dir(&entry.path(), &target)?;
+        } else {
+            fs::copy(entry.path(), target)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize() {
+        let dir = Path::new("/tmp/taskfile-test-1-0");
+        let output = "Run ID: 01J9Z\n▶ Running task 'ls': ls /tmp/taskfile-test-1-0/src\n✓ Task 'ls' completed successfully in 12ms\n✓ Task 'e2e' completed successfully in 1m 5s 300ms\nslept 2.5s, 3 files, v1.2s, 40mb, 5m\n";
+        assert_eq!(
+            normalize(output, dir, run_id(output)),
+            "Run ID: [run-id]\n▶ Running task 'ls': ls [dir]/src\n✓ Task 'ls' completed successfully in [duration]\n✓ Task 'e2e' completed successfully in [duration]\nslept [duration], 3 files, v1.2s, 40mb, 5m\n"
+        );
+        assert_eq!(normalize("run 01J9Z\n", dir, None), "run 01J9Z\n");
+    }
+
+    #[test]
+    fn test_snapshot_and_diff() {
+        assert_eq!(snapshot("hi", ""), "--- stdout\nhi\n--- stderr\n");
+        assert_eq!(diff("a\nb\nc\n", "a\nc\nd\n"), "  a\n- b\n  c\n+ d");
+        assert_eq!(diff("a\n", "a\n"), "  a");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_run() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let project = std::env::temp_dir().join(format!("taskfile-harness-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&project);
+        fs::create_dir_all(project.join("fixtures/basic")).unwrap();
+        fs::write(project.join("fixtures/basic/input.txt"), "data\n").unwrap();
+        fs::write(
+            project.join("Taskfile.toml"),
+            "[tasks.build]\ncmd = \"true\"\n",
+        )
+        .unwrap();
+
+        // Stands in for `task`: prints what it was given and where it ran.
+        let binary = project.join("fake-task");
+        fs::write(
+            &binary,
+            "#!/bin/sh\necho \"Run ID: 01FAKE$$\"\necho \"args: $*\"\necho \"in $PWD:\" $(ls)\necho \"took 15ms\" >&2\nexit ${FAKE_EXIT:-0}\n",
+        )
+        .unwrap();
+        fs::set_permissions(&binary, fs::Permissions::from_mode(0o755)).unwrap();
+
+        let mut test = TaskTest {
+            task: "build".to_string(),
+            args: vec!["--release".to_string()],
+            fixture: Some("fixtures/basic".to_string()),
+            exit_code: 0,
+            snapshot: None,
+            env: Default::default(),
+        };
+        let harness = Harness::new(&binary, project.join("Taskfile.toml"));
+        let snap = project.join("tests/snapshots/build.snap");
+        assert_eq!(
+            harness.run("build", &test).unwrap(),
+            Outcome::Written(snap.clone())
+        );
+        assert_eq!(
+            fs::read_to_string(&snap).unwrap(),
+            "--- stdout\nRun ID: [run-id]\nargs: --plain run build --release\nin [dir]: Taskfile.toml input.txt\n--- stderr\ntook [duration]\n"
+        );
+        assert_eq!(harness.run("build", &test).unwrap(), Outcome::Passed);
+
+        test.args.clear();
+        let Outcome::Failed(reason) = harness.run("build", &test).unwrap() else {
+            panic!("changed output should fail");
+        };
+        assert!(reason.contains("- args: --plain run build --release\n+ args: --plain run build"));
+
+        test.env.insert("FAKE_EXIT".to_string(), "2".to_string());
+        let Outcome::Failed(reason) = harness.run("build", &test).unwrap() else {
+            panic!("a wrong exit code should fail");
+        };
+        assert!(reason.starts_with("expected exit code 0, got 2"));
+        test.exit_code = 2;
+        let harness = harness.update(true);
+        assert_eq!(harness.run("build", &test).unwrap(), Outcome::Written(snap));
+
+        test.fixture = Some("fixtures/missing".to_string());
+        assert!(harness.run("build", &test).is_err());
+        fs::remove_dir_all(&project).unwrap();
+    }
+}
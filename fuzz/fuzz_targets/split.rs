@@ -0,0 +1,13 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use taskfile_model::command::{program, quote, split};
+
+fuzz_target!(|cmd: &str| {
+    let first = program(cmd);
+    if let Ok(words) = split(cmd) {
+        let line = words.iter().map(|w| quote(w)).collect::<Vec<_>>().join(" ");
+        assert_eq!(split(&line).as_ref(), Ok(&words));
+        assert_eq!(first, words.into_iter().next());
+    }
+});
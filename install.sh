@@ -8,6 +8,7 @@ YELLOW='\033[1;33m'
 NC='\033[0m'
 
 INSTALL_DIR="$HOME/.local/bin"
+VERSION=""
 
 REPO="taskfile"
 OWNER="lassejlv"
@@ -73,15 +74,19 @@ install_task_runner() {
     print_info "Detected platform: $platform"
 
 
-    print_info "Fetching latest version..."
-    version=$(get_latest_version)
+    if [ -n "$VERSION" ]; then
+        version="$VERSION"
+    else
+        print_info "Fetching latest version..."
+        version=$(get_latest_version)
+    fi
 
     if [ -z "$version" ]; then
         print_error "Failed to get latest version"
         exit 1
     fi
 
-    print_info "Latest version: $version"
+    print_info "Version: $version"
 
 
     filename="task-${platform}.tar.gz"
@@ -134,6 +139,10 @@ while [[ $# -gt 0 ]]; do
             INSTALL_DIR="$2"
             shift 2
             ;;
+        --version)
+            VERSION="$2"
+            shift 2
+            ;;
         --help|-h)
             echo "Task Runner Installation Script"
             echo ""
@@ -141,6 +150,7 @@ while [[ $# -gt 0 ]]; do
             echo ""
             echo "Options:"
             echo "  --install-dir DIR    Install directory (default: $HOME/.local/bin)"
+            echo "  --version TAG        Release to install, e.g. v1.3.2 (default: latest)"
             echo "  --help, -h          Show this help message"
             exit 0
             ;;